@@ -4,6 +4,8 @@ use std::path::{Path, PathBuf};
 
 use serde_json::Value;
 
+use crate::metadata::write_atomically;
+
 pub const ALIAS_FILE_NAME: &str = "alias.json";
 
 pub type AliasMap = HashMap<String, Vec<String>>;
@@ -13,6 +15,29 @@ pub type AliasGroups = Vec<Vec<String>>;
 pub struct AliasWarning {
     pub path: PathBuf,
     pub message: String,
+    /// Set when the file is readable but one of its groups joins two others.
+    pub conflict: Option<AliasConflict>,
+}
+
+/// An incoming group whose terms already belong to more than one existing
+/// group, so adding it would merge unrelated concepts. `term` is the first
+/// incoming term found in `existing_group`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasConflict {
+    pub term: String,
+    pub existing_group: Vec<String>,
+    pub incoming_group: Vec<String>,
+}
+
+impl AliasConflict {
+    pub fn message(&self) -> String {
+        format!(
+            "`{}` already aliases [{}]; adding [{}] merges it with another group",
+            self.term,
+            self.existing_group.join(", "),
+            self.incoming_group.join(", ")
+        )
+    }
 }
 
 pub fn alias_path_for_root(root: &Path) -> PathBuf {
@@ -118,11 +143,72 @@ pub fn alias_map_from_groups(groups: &AliasGroups) -> AliasMap {
     out
 }
 
+/// The groups behind `map`, e.g. one merged from several roots by
+/// [`load_alias_map_from_roots`], in the normalized alias.json form.
+pub fn alias_groups_from_map(map: &AliasMap) -> AliasGroups {
+    normalize_alias_groups(
+        map.iter()
+            .map(|(term, aliases)| std::iter::once(term).chain(aliases).cloned().collect())
+            .collect(),
+    )
+}
+
 pub fn load_alias_groups_from_path(path: &Path) -> Result<AliasGroups, String> {
+    load_raw_alias_groups(path).map(normalize_alias_groups)
+}
+
+fn load_raw_alias_groups(path: &Path) -> Result<AliasGroups, String> {
+    parse_alias_groups(&read_alias_json(path)?)
+}
+
+/// The groups of a root's alias.json in the order and spelling they are
+/// written in, without merging, for callers that treat a group's first term
+/// as special. Only blank and repeated terms are dropped.
+pub fn load_alias_groups_as_written(root: &Path) -> Result<AliasGroups, String> {
+    let path = alias_path_for_root(root);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    parse_alias_groups_as_written(&read_alias_json(&path)?)
+}
+
+fn read_alias_json(path: &Path) -> Result<Value, String> {
     let bytes = fs::read(path).map_err(|err| format!("failed to read alias file: {err}"))?;
-    let value: Value = serde_json::from_slice(&bytes)
-        .map_err(|err| format!("failed to parse alias json: {err}"))?;
-    parse_alias_groups(&value).map(normalize_alias_groups)
+    serde_json::from_slice(&bytes).map_err(|err| format!("failed to parse alias json: {err}"))
+}
+
+/// Conflicts from merging `incoming` into already-normalized `groups`: one
+/// per existing group it touches, but only when it touches two or more.
+pub fn find_alias_conflicts(groups: &AliasGroups, incoming: &[String]) -> Vec<AliasConflict> {
+    let incoming = normalize_search_terms(incoming.to_vec());
+    let touched = groups
+        .iter()
+        .filter_map(|group| {
+            let term = incoming.iter().find(|term| group.contains(term))?;
+            Some(AliasConflict {
+                term: term.clone(),
+                existing_group: group.clone(),
+                incoming_group: incoming.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+    if touched.len() < 2 {
+        return Vec::new();
+    }
+    touched
+}
+
+/// Replays the groups of an alias file in order and reports each one that
+/// bridges groups listed before it.
+fn alias_file_conflicts(raw: AliasGroups) -> Vec<AliasConflict> {
+    let mut seen = AliasGroups::new();
+    let mut conflicts = Vec::new();
+    for group in raw {
+        conflicts.extend(find_alias_conflicts(&seen, &group));
+        seen.push(group);
+        seen = normalize_alias_groups(seen);
+    }
+    conflicts
 }
 
 pub fn load_alias_groups_from_root(root: &Path) -> Result<AliasGroups, String> {
@@ -142,8 +228,7 @@ pub fn save_alias_groups_to_path(path: &Path, groups: &AliasGroups) -> Result<()
         fs::create_dir_all(parent)
             .map_err(|err| format!("failed to create alias directory: {err}"))?;
     }
-    fs::write(path, bytes).map_err(|err| format!("failed to write alias file: {err}"))?;
-    Ok(())
+    write_atomically(path, &bytes).map_err(|err| format!("failed to write alias file: {err}"))
 }
 
 pub fn save_alias_groups_to_root(root: &Path, groups: &AliasGroups) -> Result<(), String> {
@@ -151,15 +236,22 @@ pub fn save_alias_groups_to_root(root: &Path, groups: &AliasGroups) -> Result<()
     save_alias_groups_to_path(&path, groups)
 }
 
-pub fn merge_alias_terms(groups: &mut AliasGroups, terms: Vec<String>) -> bool {
+/// Merges `terms` into one group, returning whether anything changed and the
+/// conflicts the merge ran into. The merge happens either way; callers that
+/// care should check the conflicts before saving.
+pub fn merge_alias_terms(
+    groups: &mut AliasGroups,
+    terms: Vec<String>,
+) -> (bool, Vec<AliasConflict>) {
     let mut current = normalize_alias_groups(std::mem::take(groups));
     let before = current.clone();
 
     let incoming = normalize_search_terms(terms);
     if incoming.len() < 2 {
         *groups = current;
-        return false;
+        return (false, Vec::new());
     }
+    let conflicts = find_alias_conflicts(&current, &incoming);
 
     let incoming_set: HashSet<String> = incoming.iter().cloned().collect();
     let mut merged_terms = incoming;
@@ -174,7 +266,21 @@ pub fn merge_alias_terms(groups: &mut AliasGroups, terms: Vec<String>) -> bool {
     kept.push(merged_terms);
 
     *groups = normalize_alias_groups(kept);
-    *groups != before
+    (*groups != before, conflicts)
+}
+
+/// [`merge_alias_terms`] for each group of `incoming` in turn, collecting the
+/// conflicts of all of them.
+pub fn import_alias_groups(
+    groups: &mut AliasGroups,
+    incoming: AliasGroups,
+) -> (bool, Vec<AliasConflict>) {
+    let before = normalize_alias_groups(groups.clone());
+    let mut conflicts = Vec::new();
+    for group in incoming {
+        conflicts.extend(merge_alias_terms(groups, group).1);
+    }
+    (*groups != before, conflicts)
 }
 
 pub fn remove_alias_terms(groups: &mut AliasGroups, terms: Vec<String>) -> bool {
@@ -196,6 +302,68 @@ pub fn remove_alias_terms(groups: &mut AliasGroups, terms: Vec<String>) -> bool
     *groups != before
 }
 
+/// Renames `from` to `to` wherever it appears. A group where `to` already
+/// exists simply loses `from` (and disappears if that leaves one term), and a
+/// `to` from another group merges the two groups.
+pub fn rename_alias_term(groups: &mut AliasGroups, from: &str, to: &str) -> bool {
+    let mut current = normalize_alias_groups(std::mem::take(groups));
+    let before = current.clone();
+
+    let (Some(from), Some(to)) = (normalize_search_term(from), normalize_search_term(to)) else {
+        *groups = current;
+        return false;
+    };
+
+    for term in current.iter_mut().flatten() {
+        if *term == from {
+            *term = to.clone();
+        }
+    }
+
+    *groups = normalize_alias_groups(current);
+    *groups != before
+}
+
+/// Rewrites each tag that belongs to an alias group as the group's first
+/// term, spelled as in the group, dropping the duplicates that creates.
+/// Members match case-insensitively, so pass groups as written (see
+/// [`load_alias_groups_as_written`]) rather than normalized ones, whose
+/// first term is merely the alphabetically first. Tags outside every group
+/// keep their spelling, and so does a tag found in two groups with different
+/// first terms, since there is no telling which one it means.
+pub fn canonicalize_tags(tags: &[String], groups: &AliasGroups) -> Vec<String> {
+    // Normalized member -> (normalized first term, first term as written).
+    let mut canonical = HashMap::<String, Option<(String, String)>>::new();
+    for group in groups {
+        let Some(first) = group
+            .iter()
+            .find_map(|term| Some((normalize_search_term(term)?, term.trim().to_string())))
+        else {
+            continue;
+        };
+        for term in group.iter().filter_map(|term| normalize_search_term(term)) {
+            let entry = canonical.entry(term).or_insert_with(|| Some(first.clone()));
+            if entry.as_ref().map(|(key, _)| key) != Some(&first.0) {
+                *entry = None;
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut seen = HashSet::new();
+    for tag in tags {
+        let key = normalize_search_term(tag).unwrap_or_default();
+        let tag = match canonical.get(&key) {
+            Some(Some((first_key, first))) if *first_key != key => first.clone(),
+            _ => tag.clone(),
+        };
+        if seen.insert(normalize_search_term(&tag).unwrap_or_default()) {
+            out.push(tag);
+        }
+    }
+    out
+}
+
 pub fn load_alias_map_from_roots(roots: &[PathBuf]) -> (AliasMap, Vec<AliasWarning>) {
     let mut all_aliases = AliasMap::new();
     let mut warnings = Vec::new();
@@ -206,14 +374,22 @@ pub fn load_alias_map_from_roots(roots: &[PathBuf]) -> (AliasMap, Vec<AliasWarni
             continue;
         }
 
-        match load_alias_groups_from_path(&path) {
+        match load_raw_alias_groups(&path) {
             Ok(groups) => {
+                for conflict in alias_file_conflicts(groups.clone()) {
+                    warnings.push(AliasWarning {
+                        path: path.clone(),
+                        message: conflict.message(),
+                        conflict: Some(conflict),
+                    });
+                }
                 let parsed = alias_map_from_groups(&groups);
                 merge_alias_map(&mut all_aliases, parsed);
             }
             Err(err) => warnings.push(AliasWarning {
                 path: path.clone(),
                 message: err,
+                conflict: None,
             }),
         }
     }
@@ -259,7 +435,14 @@ pub fn expand_search_terms_with_aliases(terms: Vec<String>, alias_map: &AliasMap
     out
 }
 
-fn parse_alias_groups(value: &Value) -> Result<AliasGroups, String> {
+pub(crate) fn parse_alias_groups(value: &Value) -> Result<AliasGroups, String> {
+    Ok(parse_alias_groups_as_written(value)?
+        .into_iter()
+        .map(normalize_search_terms)
+        .collect())
+}
+
+fn parse_alias_groups_as_written(value: &Value) -> Result<AliasGroups, String> {
     let groups = value
         .as_array()
         .ok_or_else(|| "root value must be an array".to_string())?;
@@ -278,13 +461,14 @@ fn parse_alias_group(value: &Value) -> Result<Vec<String>, String> {
         .as_array()
         .ok_or_else(|| "group must be an array".to_string())?;
     let mut out = Vec::new();
+    let mut seen = HashSet::new();
     for item in arr {
         let raw = item
             .as_str()
             .ok_or_else(|| "group contains non-string value".to_string())?;
         if let Some(term) = normalize_search_term(raw) {
-            if !out.contains(&term) {
-                out.push(term);
+            if seen.insert(term) {
+                out.push(raw.trim().to_string());
             }
         }
     }
@@ -307,9 +491,11 @@ mod tests {
     use serde_json::json;
 
     use super::{
-        alias_map_from_groups, expand_search_terms_with_aliases, merge_alias_terms,
-        normalize_alias_groups, normalize_search_terms, parse_alias_groups, remove_alias_terms,
-        AliasMap,
+        alias_file_conflicts, alias_groups_from_map, alias_map_from_groups, alias_path_for_root,
+        canonicalize_tags, expand_search_terms_with_aliases, import_alias_groups,
+        load_alias_groups_as_written, merge_alias_map, merge_alias_terms, normalize_alias_groups,
+        normalize_search_terms, parse_alias_groups, remove_alias_terms, rename_alias_term,
+        save_alias_groups_to_path, AliasConflict, AliasMap,
     };
 
     #[test]
@@ -360,10 +546,26 @@ mod tests {
             vec!["a".to_string(), "b".to_string()],
             vec!["x".to_string(), "y".to_string()],
         ];
-        assert!(merge_alias_terms(
+        let (changed, conflicts) = merge_alias_terms(
             &mut groups,
-            vec!["b".to_string(), "x".to_string(), "z".to_string()]
-        ));
+            vec!["b".to_string(), "x".to_string(), "z".to_string()],
+        );
+        assert!(changed);
+        assert_eq!(
+            conflicts,
+            vec![
+                AliasConflict {
+                    term: "b".to_string(),
+                    existing_group: vec!["a".to_string(), "b".to_string()],
+                    incoming_group: vec!["b".to_string(), "x".to_string(), "z".to_string()],
+                },
+                AliasConflict {
+                    term: "x".to_string(),
+                    existing_group: vec!["x".to_string(), "y".to_string()],
+                    incoming_group: vec!["b".to_string(), "x".to_string(), "z".to_string()],
+                },
+            ]
+        );
         assert_eq!(
             groups,
             vec![vec![
@@ -376,6 +578,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn merge_alias_terms_extending_one_group_is_not_a_conflict() {
+        let mut groups = vec![vec!["a".to_string(), "b".to_string()]];
+        let (changed, conflicts) =
+            merge_alias_terms(&mut groups, vec!["B".to_string(), "c".to_string()]);
+        assert!(changed);
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            groups,
+            vec![vec!["a".to_string(), "b".to_string(), "c".to_string()]]
+        );
+    }
+
+    #[test]
+    fn alias_files_report_groups_that_bridge_earlier_groups() {
+        let raw = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["b".to_string(), "c".to_string()],
+            vec!["x".to_string(), "y".to_string()],
+            vec!["c".to_string(), "y".to_string()],
+        ];
+        let conflicts = alias_file_conflicts(raw);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].term, "c");
+        assert_eq!(conflicts[0].existing_group, vec!["a", "b", "c"]);
+        assert_eq!(conflicts[1].term, "y");
+    }
+
     #[test]
     fn remove_alias_terms_drops_small_groups() {
         let mut groups = vec![vec!["a".to_string(), "b".to_string(), "c".to_string()]];
@@ -386,6 +616,40 @@ mod tests {
         assert!(groups.is_empty());
     }
 
+    #[test]
+    fn rename_alias_term_replaces_the_term_in_place() {
+        let mut groups = vec![vec!["cat".to_string(), "nekko".to_string()]];
+        assert!(rename_alias_term(&mut groups, "Nekko", "neko"));
+        assert_eq!(groups, vec![vec!["cat".to_string(), "neko".to_string()]]);
+    }
+
+    #[test]
+    fn rename_alias_term_to_an_existing_term_dedups_the_group() {
+        let mut groups = vec![
+            vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            vec!["x".to_string(), "y".to_string()],
+        ];
+        assert!(rename_alias_term(&mut groups, "a", "b"));
+        assert_eq!(
+            groups,
+            vec![
+                vec!["b".to_string(), "c".to_string()],
+                vec!["x".to_string(), "y".to_string()],
+            ]
+        );
+
+        assert!(rename_alias_term(&mut groups, "x", "y"));
+        assert_eq!(groups, vec![vec!["b".to_string(), "c".to_string()]]);
+    }
+
+    #[test]
+    fn rename_alias_term_ignores_unknown_terms() {
+        let mut groups = vec![vec!["a".to_string(), "b".to_string()]];
+        assert!(!rename_alias_term(&mut groups, "missing", "z"));
+        assert!(!rename_alias_term(&mut groups, "a", " "));
+        assert_eq!(groups, vec![vec!["a".to_string(), "b".to_string()]]);
+    }
+
     #[test]
     fn search_terms_expand_with_aliases_bidirectionally() {
         let mut alias = AliasMap::new();
@@ -409,4 +673,130 @@ mod tests {
         ]);
         assert_eq!(terms, vec!["yurucamp".to_string()]);
     }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn canonicalize_tags_maps_members_to_the_first_term() {
+        let groups = vec![tags(&["yurucamp", "ゆるキャン", "摇曳露营"])];
+        assert_eq!(
+            canonicalize_tags(&tags(&["ゆるキャン", "tent", "Yurucamp"]), &groups),
+            tags(&["yurucamp", "tent"])
+        );
+        assert_eq!(
+            canonicalize_tags(&tags(&["Yurucamp", "摇曳露营"]), &groups),
+            tags(&["Yurucamp"])
+        );
+        assert_eq!(
+            canonicalize_tags(&tags(&["tent"]), &groups),
+            tags(&["tent"])
+        );
+    }
+
+    #[test]
+    fn canonicalize_tags_keeps_the_written_first_term() {
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-alias-written-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            alias_path_for_root(&root),
+            r#"[["Yuru Camp", "ゆるキャン", " yuru camp ", "a_yurucamp"]]"#,
+        )
+        .unwrap();
+
+        let groups = load_alias_groups_as_written(&root).unwrap();
+        assert_eq!(
+            groups,
+            vec![tags(&["Yuru Camp", "ゆるキャン", "a_yurucamp"])]
+        );
+        assert_eq!(
+            canonicalize_tags(&tags(&["a_yurucamp", "tent", "ゆるキャン"]), &groups),
+            tags(&["Yuru Camp", "tent"])
+        );
+        assert_eq!(
+            canonicalize_tags(&tags(&["yuru camp"]), &groups),
+            tags(&["yuru camp"])
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn canonicalize_tags_leaves_tags_in_two_groups_alone() {
+        let groups = vec![tags(&["cat", "neko"]), tags(&["kitty", "neko"])];
+        assert_eq!(
+            canonicalize_tags(&tags(&["neko", "kitty"]), &groups),
+            tags(&["neko", "kitty"])
+        );
+        let same_first = vec![tags(&["cat", "neko"]), tags(&["cat", "neko", "gato"])];
+        assert_eq!(
+            canonicalize_tags(&tags(&["gato", "neko"]), &same_first),
+            tags(&["cat"])
+        );
+    }
+
+    #[test]
+    fn import_alias_groups_merges_each_group() {
+        let mut groups = vec![tags(&["a", "b"]), tags(&["x", "y"])];
+        let (changed, conflicts) =
+            import_alias_groups(&mut groups, vec![tags(&["B", "c"]), tags(&["p", "q"])]);
+        assert!(changed);
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            groups,
+            vec![tags(&["a", "b", "c"]), tags(&["p", "q"]), tags(&["x", "y"])]
+        );
+
+        let (changed, conflicts) = import_alias_groups(&mut groups, vec![tags(&["q", "p"])]);
+        assert!(!changed);
+        assert!(conflicts.is_empty());
+
+        let (changed, conflicts) = import_alias_groups(&mut groups, vec![tags(&["c", "y"])]);
+        assert!(changed);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(
+            groups,
+            vec![tags(&["a", "b", "c", "x", "y"]), tags(&["p", "q"])]
+        );
+    }
+
+    #[test]
+    fn saving_aliases_replaces_the_whole_file() {
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-alias-save-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("alias.json");
+        std::fs::write(&path, "x".repeat(4096)).unwrap();
+
+        save_alias_groups_to_path(&path, &vec![tags(&["cat", "neko"])]).unwrap();
+        let saved: Vec<Vec<String>> =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved, vec![tags(&["cat", "neko"])]);
+        assert!(!dir.join("alias.json.tmp").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn alias_groups_from_map_round_trips_merged_groups() {
+        let mut map = alias_map_from_groups(&vec![tags(&["cat", "neko"])]);
+        merge_alias_map(
+            &mut map,
+            alias_map_from_groups(&vec![tags(&["Neko", "gato"])]),
+        );
+        let groups = alias_groups_from_map(&map);
+        assert_eq!(groups, vec![tags(&["cat", "gato", "neko"])]);
+        assert_eq!(
+            alias_groups_from_map(&alias_map_from_groups(&groups)),
+            groups
+        );
+    }
 }
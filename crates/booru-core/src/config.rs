@@ -1,22 +1,204 @@
+use std::fs;
 use std::path::{Path, PathBuf};
 
+use serde::{Deserialize, Serialize};
+use xdg::BaseDirectories;
+
+use crate::error::BooruError;
+
+pub const CONFIG_FILE_NAME: &str = "booru.toml";
+/// Name looked up under `~/.config/lightbooru` after [`CONFIG_FILE_NAME`].
+pub const USER_CONFIG_FILE_NAME: &str = "config.toml";
+/// Extensions treated as videos unless `video_extensions` says otherwise.
+pub const DEFAULT_VIDEO_EXTENSIONS: &[&str] = &["mp4", "webm", "mkv", "mov"];
+
 #[derive(Clone, Debug)]
 pub struct BooruConfig {
     pub roots: Vec<PathBuf>,
+    /// Show sensitive images unless a frontend is told otherwise.
+    pub default_sensitive: bool,
+    pub exclude_globs: Vec<String>,
+    /// Files and directories skipped while scanning, compared by their
+    /// canonical paths.
+    pub exclude_paths: Vec<PathBuf>,
+    pub follow_symlinks: bool,
+    /// `None` walks the roots without a depth limit.
+    pub max_depth: Option<usize>,
+    /// Extensions, without the dot and compared ignoring case, of files
+    /// thumbnailed and hashed through ffmpeg instead of an image decoder.
+    pub video_extensions: Vec<String>,
+}
+
+/// The contents of `booru.toml`. Every key is optional.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    pub roots: Vec<PathBuf>,
+    pub default_sensitive: bool,
+    #[serde(alias = "exclude_patterns")]
+    pub exclude_globs: Vec<String>,
+    pub exclude_paths: Vec<PathBuf>,
+    pub follow_symlinks: bool,
+    /// 0 means unlimited.
+    pub max_depth: usize,
+    pub video_extensions: Vec<String>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            default_sensitive: false,
+            exclude_globs: Vec::new(),
+            exclude_paths: Vec::new(),
+            follow_symlinks: false,
+            max_depth: 0,
+            video_extensions: default_video_extensions(),
+        }
+    }
 }
 
 impl BooruConfig {
     pub fn default() -> Self {
         let root = default_root();
-        Self { roots: vec![root] }
+        Self {
+            roots: vec![root],
+            default_sensitive: false,
+            exclude_globs: Vec::new(),
+            exclude_paths: Vec::new(),
+            follow_symlinks: false,
+            max_depth: None,
+            video_extensions: default_video_extensions(),
+        }
     }
 
     pub fn with_roots(roots: Vec<PathBuf>) -> Self {
         let expanded = roots.into_iter().map(|p| expand_tilde(&p)).collect();
-        Self { roots: expanded }
+        Self {
+            roots: expanded,
+            default_sensitive: false,
+            exclude_globs: Vec::new(),
+            exclude_paths: Vec::new(),
+            follow_symlinks: false,
+            max_depth: None,
+            video_extensions: default_video_extensions(),
+        }
+    }
+
+    /// Relative roots resolve against the file's directory. A file without
+    /// roots falls back to [`default_root`].
+    pub fn from_file(path: &Path) -> Result<Self, BooruError> {
+        let data = fs::read_to_string(path).map_err(|source| BooruError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let file: ConfigFile = toml::from_str(&data).map_err(|source| BooruError::Toml {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        let roots = if file.roots.is_empty() {
+            vec![default_root()]
+        } else {
+            file.roots
+                .iter()
+                .map(|root| base_dir.join(expand_tilde(root)))
+                .collect()
+        };
+        let mut config = Self {
+            roots,
+            default_sensitive: file.default_sensitive,
+            exclude_globs: file.exclude_globs,
+            exclude_paths: file
+                .exclude_paths
+                .iter()
+                .map(|path| base_dir.join(expand_tilde(path)))
+                .collect(),
+            follow_symlinks: file.follow_symlinks,
+            max_depth: None,
+            video_extensions: file.video_extensions,
+        };
+        config.set_max_depth(file.max_depth);
+        Ok(config)
+    }
+
+    /// Same as [`BooruConfig::from_file`], under its earlier name.
+    #[deprecated(note = "use `BooruConfig::from_file`")]
+    pub fn load_from_file(path: &Path) -> Result<Self, BooruError> {
+        Self::from_file(path)
+    }
+
+    /// Sets [`BooruConfig::max_depth`], treating 0 as unlimited like the
+    /// config file and `--max-depth` do.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = (max_depth > 0).then_some(max_depth);
+    }
+
+    /// Layers the frontends' `--exclude`, `--exclude-path` and `--max-depth`
+    /// flags over the loaded config: excludes add to the file's, and a depth
+    /// replaces it.
+    pub fn apply_scan_overrides(
+        &mut self,
+        exclude_globs: Vec<String>,
+        exclude_paths: Vec<PathBuf>,
+        max_depth: Option<usize>,
+    ) {
+        self.exclude_globs.extend(exclude_globs);
+        self.exclude_paths.extend(exclude_paths);
+        if let Some(max_depth) = max_depth {
+            self.set_max_depth(max_depth);
+        }
+    }
+
+    /// `./booru.toml`, then `booru.toml` or `config.toml` in
+    /// `$XDG_CONFIG_HOME/lightbooru`, then `~/.config/lightbooru/config.toml`.
+    pub fn discover_file() -> Option<PathBuf> {
+        let local = PathBuf::from(CONFIG_FILE_NAME);
+        if local.is_file() {
+            return Some(local);
+        }
+        if let Ok(xdg) = BaseDirectories::with_prefix("lightbooru") {
+            for name in [CONFIG_FILE_NAME, USER_CONFIG_FILE_NAME] {
+                if let Some(path) = xdg.find_config_file(name) {
+                    return Some(path);
+                }
+            }
+        }
+        let fallback = dirs::home_dir()?
+            .join(".config")
+            .join("lightbooru")
+            .join(USER_CONFIG_FILE_NAME);
+        fallback.is_file().then_some(fallback)
+    }
+
+    /// Loads the discovered config file, or [`BooruConfig::default`] when
+    /// there is none.
+    pub fn load_default() -> Result<Self, BooruError> {
+        match Self::discover_file() {
+            Some(path) => Self::from_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Layers `--base` arguments over the discovered config file. Any
+    /// `--base` replaces the file's roots instead of adding to them.
+    pub fn resolve(cli_bases: Vec<PathBuf>) -> Result<Self, BooruError> {
+        let mut config = Self::load_default()?;
+        if !cli_bases.is_empty() {
+            config.roots = Self::with_roots(cli_bases).roots;
+        }
+        Ok(config)
     }
 }
 
+pub fn default_video_extensions() -> Vec<String> {
+    DEFAULT_VIDEO_EXTENSIONS
+        .iter()
+        .map(|ext| ext.to_string())
+        .collect()
+}
+
 pub fn default_root() -> PathBuf {
     if let Some(home) = dirs::home_dir() {
         return home.join("Pictures").join("gallery-dl");
@@ -34,3 +216,86 @@ pub fn expand_tilde(path: &Path) -> PathBuf {
     }
     path.to_path_buf()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::{BooruConfig, ConfigFile};
+
+    #[test]
+    fn config_file_round_trips_through_toml() {
+        let file = ConfigFile {
+            roots: vec![PathBuf::from("gallery-dl"), PathBuf::from("/srv/art")],
+            default_sensitive: true,
+            exclude_globs: vec!["*/thumbnails/*".to_string()],
+            exclude_paths: vec![PathBuf::from("private")],
+            follow_symlinks: true,
+            max_depth: 3,
+            video_extensions: vec!["mp4".to_string(), "gifv".to_string()],
+        };
+        let text = toml::to_string(&file).unwrap();
+        assert_eq!(toml::from_str::<ConfigFile>(&text).unwrap(), file);
+        assert_eq!(
+            toml::from_str::<ConfigFile>("").unwrap(),
+            ConfigFile::default()
+        );
+    }
+
+    #[test]
+    fn from_file_resolves_relative_roots_against_file_dir() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-config-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("booru.toml");
+        std::fs::write(
+            &path,
+            "roots = [\"pics\", \"/abs\"]\ndefault_sensitive = true\n",
+        )
+        .unwrap();
+
+        let config = BooruConfig::from_file(&path).unwrap();
+        assert_eq!(config.roots, vec![dir.join("pics"), PathBuf::from("/abs")]);
+        assert!(config.default_sensitive);
+        assert!(config.exclude_globs.is_empty());
+        assert!(!config.follow_symlinks);
+        assert_eq!(config.max_depth, None);
+        assert_eq!(config.video_extensions, ["mp4", "webm", "mkv", "mov"]);
+
+        std::fs::write(
+            &path,
+            "follow_symlinks = true\nmax_depth = 2\nexclude_patterns = [\"*.gif\"]\n\
+             exclude_paths = [\"pics/private\"]\n\
+             video_extensions = [\"mp4\", \"ogv\"]\n",
+        )
+        .unwrap();
+        let config = BooruConfig::from_file(&path).unwrap();
+        assert_eq!(config.exclude_paths, vec![dir.join("pics/private")]);
+        assert!(config.follow_symlinks);
+        assert_eq!(config.max_depth, Some(2));
+        assert_eq!(config.exclude_globs, vec!["*.gif".to_string()]);
+        assert_eq!(config.video_extensions, ["mp4", "ogv"]);
+
+        let mut overridden = config.clone();
+        overridden.apply_scan_overrides(
+            vec!["*.tmp".to_string()],
+            vec![PathBuf::from("/srv/private")],
+            Some(0),
+        );
+        assert_eq!(overridden.exclude_globs, vec!["*.gif", "*.tmp"]);
+        assert_eq!(
+            overridden.exclude_paths,
+            vec![dir.join("pics/private"), PathBuf::from("/srv/private")]
+        );
+        assert_eq!(overridden.max_depth, None);
+
+        std::fs::write(&path, "roots = 3").unwrap();
+        assert!(BooruConfig::from_file(&path).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
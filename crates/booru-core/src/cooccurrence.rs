@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::scan::ImageItem;
+
+/// How many items carry each pair of tags together. Keys are ordered so the
+/// lexically smaller tag comes first. Tags on fewer than `min_support` items
+/// are left out entirely, which keeps one-off tags from producing a pair with
+/// everything they were seen next to.
+pub fn compute_tag_cooccurrence(
+    items: &[ImageItem],
+    min_support: usize,
+) -> HashMap<(String, String), usize> {
+    let item_tags = items
+        .iter()
+        .map(|item| {
+            let mut tags = item.merged_tags();
+            tags.sort();
+            tags.dedup();
+            tags
+        })
+        .collect::<Vec<_>>();
+
+    let mut support = HashMap::<&str, usize>::new();
+    for tag in item_tags.iter().flatten() {
+        *support.entry(tag.as_str()).or_default() += 1;
+    }
+
+    let mut pairs = HashMap::new();
+    for tags in &item_tags {
+        let kept = tags
+            .iter()
+            .filter(|tag| support[tag.as_str()] >= min_support)
+            .collect::<Vec<_>>();
+        for (idx, first) in kept.iter().enumerate() {
+            for second in &kept[idx + 1..] {
+                *pairs
+                    .entry(((*first).clone(), (*second).clone()))
+                    .or_default() += 1;
+            }
+        }
+    }
+    pairs
+}
+
+/// Up to `limit` tags seen together with any of `tags`, most often first and
+/// ties by name. Counts are summed over `tags`; the given tags themselves are
+/// never suggested.
+pub fn related_tags(
+    cooccurrence: &HashMap<(String, String), usize>,
+    tags: &[String],
+    limit: usize,
+) -> Vec<(String, usize)> {
+    let mut counts = HashMap::<&str, usize>::new();
+    for ((first, second), count) in cooccurrence {
+        let other = if tags.contains(first) {
+            second
+        } else if tags.contains(second) {
+            first
+        } else {
+            continue;
+        };
+        if !tags.contains(other) {
+            *counts.entry(other.as_str()).or_default() += count;
+        }
+    }
+    let mut related = counts
+        .into_iter()
+        .map(|(tag, count)| (tag.to_string(), count))
+        .collect::<Vec<_>>();
+    related.sort_by(|(lhs_tag, lhs_count), (rhs_tag, rhs_count)| {
+        rhs_count.cmp(lhs_count).then_with(|| lhs_tag.cmp(rhs_tag))
+    });
+    related.truncate(limit);
+    related
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use super::{compute_tag_cooccurrence, related_tags};
+    use crate::metadata::BooruEdits;
+    use crate::scan::ImageItem;
+
+    fn item_with_tags(tags: &[&str]) -> ImageItem {
+        ImageItem::new(
+            PathBuf::new(),
+            PathBuf::new(),
+            PathBuf::new(),
+            json!({ "tags": tags }),
+            BooruEdits::default(),
+        )
+    }
+
+    #[test]
+    fn counts_pairs_of_tags_with_enough_support() {
+        let items = [
+            item_with_tags(&["sky", "sea", "cloud"]),
+            item_with_tags(&["sky", "sea"]),
+            item_with_tags(&["sky", "cloud", "sky"]),
+            item_with_tags(&["sea", "boat"]),
+        ];
+
+        let pairs = compute_tag_cooccurrence(&items, 2);
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(pairs[&("sea".to_string(), "sky".to_string())], 2);
+        assert_eq!(pairs[&("cloud".to_string(), "sky".to_string())], 2);
+        assert_eq!(pairs[&("cloud".to_string(), "sea".to_string())], 1);
+
+        let pairs = compute_tag_cooccurrence(&items, 1);
+        assert_eq!(pairs[&("boat".to_string(), "sea".to_string())], 1);
+    }
+
+    #[test]
+    fn related_tags_sum_over_the_given_tags() {
+        let items = [
+            item_with_tags(&["sky", "sea", "cloud"]),
+            item_with_tags(&["sky", "sea"]),
+            item_with_tags(&["sky", "cloud"]),
+            item_with_tags(&["sea", "boat"]),
+            item_with_tags(&["sea", "boat"]),
+        ];
+        let pairs = compute_tag_cooccurrence(&items, 2);
+
+        assert_eq!(
+            related_tags(&pairs, &["sky".to_string()], 10),
+            vec![("cloud".to_string(), 2), ("sea".to_string(), 2)]
+        );
+        assert_eq!(
+            related_tags(&pairs, &["sky".to_string(), "sea".to_string()], 1),
+            vec![("cloud".to_string(), 3)]
+        );
+        assert!(related_tags(&pairs, &["unknown".to_string()], 10).is_empty());
+    }
+}
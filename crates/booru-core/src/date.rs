@@ -0,0 +1,82 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+/// Parses the date formats found in gallery-dl metadata: unix seconds or
+/// milliseconds, RFC 3339, Twitter-style dates, and naive local date-times.
+/// A bare `YYYY-MM-DD` is read as local midnight.
+pub fn parse_date(raw: &str) -> Option<DateTime<Local>> {
+    let raw = raw.trim();
+    if let Ok(ts) = raw.parse::<i64>() {
+        return parse_unix_timestamp(ts);
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(dt.with_timezone(&Local));
+    }
+
+    if let Ok(dt) = DateTime::parse_from_str(raw, "%a %b %d %H:%M:%S %z %Y") {
+        return Some(dt.with_timezone(&Local));
+    }
+
+    for fmt in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y/%m/%d %H:%M:%S",
+        "%Y/%m/%d %H:%M",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(raw, fmt) {
+            if let Some(local_dt) = localize_naive_datetime(naive) {
+                return Some(local_dt);
+            }
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        return localize_naive_datetime(date.and_hms_opt(0, 0, 0)?);
+    }
+
+    None
+}
+
+fn parse_unix_timestamp(ts: i64) -> Option<DateTime<Local>> {
+    let (seconds, nanos) = if ts.abs() >= 1_000_000_000_000 {
+        let seconds = ts.div_euclid(1000);
+        let millis = ts.rem_euclid(1000) as u32;
+        (seconds, millis * 1_000_000)
+    } else {
+        (ts, 0)
+    };
+
+    let utc = Utc.timestamp_opt(seconds, nanos).single()?;
+    Some(utc.with_timezone(&Local))
+}
+
+fn localize_naive_datetime(naive: NaiveDateTime) -> Option<DateTime<Local>> {
+    let local = Local.from_local_datetime(&naive);
+    local
+        .single()
+        .or_else(|| local.earliest())
+        .or_else(|| local.latest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_date;
+
+    #[test]
+    fn parse_date_accepts_seconds_and_milliseconds() {
+        let seconds = parse_date("1768034678").unwrap();
+        let millis = parse_date("1768034678500").unwrap();
+        assert_eq!(seconds.timestamp(), 1768034678);
+        assert_eq!(millis.timestamp_millis(), 1768034678500);
+    }
+
+    #[test]
+    fn parse_date_orders_mixed_formats() {
+        let rfc = parse_date("2024-01-02T03:04:05Z").unwrap();
+        let twitter = parse_date("Wed Jan 03 03:04:05 +0000 2024").unwrap();
+        assert!(rfc < twitter);
+        assert!(parse_date("2024/01/02 03:04").is_some());
+        assert!(parse_date("2024-01-02").unwrap() < parse_date("2024-01-02 00:00:01").unwrap());
+        assert!(parse_date("yesterday").is_none());
+    }
+}
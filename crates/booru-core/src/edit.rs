@@ -1,3 +1,4 @@
+use std::fs;
 use std::path::Path;
 
 use crate::error::BooruError;
@@ -8,12 +9,143 @@ pub fn apply_update_to_image(
     image_path: &Path,
     update: EditUpdate,
 ) -> Result<BooruEdits, BooruError> {
+    apply_update(image_path, update, false).map(|(_, edits)| edits)
+}
+
+/// Like [`apply_update_to_image`], keeping the previous edits file as
+/// `<name>.booru.json.bak`.
+pub fn apply_update_to_image_with_backup(
+    image_path: &Path,
+    update: EditUpdate,
+) -> Result<BooruEdits, BooruError> {
+    apply_update(image_path, update, true).map(|(_, edits)| edits)
+}
+
+/// Like [`apply_update_to_image`], also returning the edits file as it was
+/// before, `None` if there was none, so a frontend can offer undo through
+/// [`restore_edits_for_image`].
+pub fn apply_update_to_image_with_previous(
+    image_path: &Path,
+    update: EditUpdate,
+) -> Result<(Option<BooruEdits>, BooruEdits), BooruError> {
+    apply_update(image_path, update, false)
+}
+
+/// Puts back the edits file reported by
+/// [`apply_update_to_image_with_previous`]: writes `previous`, or deletes
+/// the file when the image had none.
+pub fn restore_edits_for_image(
+    image_path: &Path,
+    previous: Option<&BooruEdits>,
+) -> Result<(), BooruError> {
     let booru_path = booru_path_for_image(image_path);
-    let mut edits = match BooruEdits::load(&booru_path)? {
-        Some(existing) => existing,
-        None => BooruEdits::default(),
-    };
+    match previous {
+        Some(edits) => edits.save(&booru_path),
+        None => match fs::remove_file(&booru_path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(BooruError::Io {
+                path: booru_path,
+                source: err,
+            }),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// The edits [`apply_update_to_image`] would save, without writing them.
+pub fn apply_update_to_image_dry_run(
+    image_path: &Path,
+    update: EditUpdate,
+) -> Result<BooruEdits, BooruError> {
+    load_and_apply(image_path, update).map(|(_, edits)| edits)
+}
+
+fn load_and_apply(
+    image_path: &Path,
+    update: EditUpdate,
+) -> Result<(Option<BooruEdits>, BooruEdits), BooruError> {
+    update.validate()?;
+    let previous = BooruEdits::load(&booru_path_for_image(image_path))?;
+    let mut edits = previous.clone().unwrap_or_default();
     edits.apply_update(update);
-    edits.save(&booru_path)?;
-    Ok(edits)
+    Ok((previous, edits))
+}
+
+fn apply_update(
+    image_path: &Path,
+    update: EditUpdate,
+    backup: bool,
+) -> Result<(Option<BooruEdits>, BooruEdits), BooruError> {
+    let (previous, edits) = load_and_apply(image_path, update)?;
+    let booru_path = booru_path_for_image(image_path);
+    if backup {
+        edits.save_with_backup(&booru_path)?;
+    } else {
+        edits.save(&booru_path)?;
+    }
+    Ok((previous, edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn previous_snapshot_holds_pre_edit_tags() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-undo-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image_path = dir.join("a.jpg");
+        let booru_path = booru_path_for_image(&image_path);
+
+        // Undoing the first edit removes the file it created.
+        let (previous, _) = apply_update_to_image_with_previous(
+            &image_path,
+            EditUpdate {
+                notes: Some("draft".to_string()),
+                ..EditUpdate::default()
+            },
+        )
+        .unwrap();
+        assert!(previous.is_none());
+        assert!(booru_path.exists());
+        restore_edits_for_image(&image_path, previous.as_ref()).unwrap();
+        assert!(!booru_path.exists());
+
+        apply_update_to_image(
+            &image_path,
+            EditUpdate {
+                set_tags: Some(vec!["sky".to_string(), "cloud".to_string()]),
+                ..EditUpdate::default()
+            },
+        )
+        .unwrap();
+        let (previous, edits) = apply_update_to_image_with_previous(
+            &image_path,
+            EditUpdate {
+                clear_tags: true,
+                sensitive: Some(true),
+                ..EditUpdate::default()
+            },
+        )
+        .unwrap();
+        let previous = previous.unwrap();
+        assert_eq!(
+            previous.tags.set,
+            Some(vec!["sky".to_string(), "cloud".to_string()])
+        );
+        assert_eq!(previous.sensitive, None);
+        assert_eq!(edits.tags.set, Some(Vec::new()));
+
+        restore_edits_for_image(&image_path, Some(&previous)).unwrap();
+        let restored = BooruEdits::load(&booru_path).unwrap().unwrap();
+        assert_eq!(restored.tags.set, previous.tags.set);
+        assert_eq!(restored.sensitive, None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
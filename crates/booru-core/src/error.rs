@@ -20,12 +20,51 @@ pub enum BooruError {
         #[source]
         source: image::ImageError,
     },
+    #[error("config parse error on {path}: {source}")]
+    Toml {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("invalid exclude glob `{pattern}`: {source}")]
+    Glob {
+        pattern: String,
+        #[source]
+        source: globset::Error,
+    },
+    #[error("video error on {path}: {message}")]
+    Video { path: PathBuf, message: String },
+    #[error("cannot decode {path}: {message}")]
+    UnsupportedImage { path: PathBuf, message: String },
+    #[error("EXIF error on {path}: {message}")]
+    Exif { path: PathBuf, message: String },
     #[error("database error on {path}: {source}")]
     Database {
         path: PathBuf,
         #[source]
         source: rusqlite::Error,
     },
+    #[error("file watch error: {source}")]
+    Watch {
+        #[source]
+        source: notify::Error,
+    },
     #[error("cache error: {message}")]
     Cache { message: String },
+    #[error("invalid edit: {0}")]
+    Edit(#[from] EditError),
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    #[error("cannot clear tags and set tags in the same update")]
+    ClearAndSetTags,
+    #[error("tag `{0}` is both added and removed")]
+    TagAddedAndRemoved(String),
+    #[error("cannot clear notes and set notes in the same update")]
+    ClearAndSetNotes,
+    #[error("`{0}` is a built-in edit field and cannot be set as an extra field")]
+    ReservedField(String),
+    #[error("field `{0}` is both set and removed")]
+    FieldSetAndRemoved(String),
 }
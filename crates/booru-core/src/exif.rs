@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use ::exif::{Context, DateTime, Exif, In, Reader, Tag, Value as ExifValue};
+use serde_json::{Map, Value};
+
+use crate::error::BooruError;
+
+/// Containers kamadak-exif can read an EXIF block from.
+pub(crate) const EXIF_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "tif", "tiff", "png", "webp", "heic", "heif", "avif",
+];
+
+/// Windows "Tags" field: UTF-16LE, entries separated by `;`.
+const XP_KEYWORDS: Tag = Tag(Context::Tiff, 0x9c9e);
+
+pub(crate) fn has_exif_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            EXIF_EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Reads the embedded EXIF block of `path` into the same shape as a
+/// gallery-dl sidecar: `date`, `author`, `description` and `tags`. Fields
+/// missing from the image are left out; `sensitive` is never set. Fails if
+/// the image has no EXIF or none of those fields.
+pub fn extract_exif_metadata(path: &Path) -> Result<Value, BooruError> {
+    let exif_error = |message: String| BooruError::Exif {
+        path: path.to_path_buf(),
+        message,
+    };
+    let file = File::open(path).map_err(|source| BooruError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let exif = Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .map_err(|err| exif_error(err.to_string()))?;
+
+    let mut object = Map::new();
+    let date = [Tag::DateTimeOriginal, Tag::DateTime]
+        .into_iter()
+        .find_map(|tag| exif_date(&exif, tag));
+    if let Some(date) = date {
+        object.insert("date".to_string(), Value::String(date));
+    }
+    let author = [Tag::Artist, Tag::Copyright]
+        .into_iter()
+        .find_map(|tag| exif_ascii(&exif, tag));
+    if let Some(author) = author {
+        object.insert("author".to_string(), Value::String(author));
+    }
+    if let Some(description) = exif_ascii(&exif, Tag::ImageDescription) {
+        object.insert("description".to_string(), Value::String(description));
+    }
+    let tags = exif_keywords(&exif);
+    if !tags.is_empty() {
+        object.insert(
+            "tags".to_string(),
+            Value::Array(tags.into_iter().map(Value::String).collect()),
+        );
+    }
+
+    if object.is_empty() {
+        return Err(exif_error("no descriptive EXIF fields".to_string()));
+    }
+    Ok(Value::Object(object))
+}
+
+fn exif_ascii(exif: &Exif, tag: Tag) -> Option<String> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+    let ExifValue::Ascii(parts) = &field.value else {
+        return None;
+    };
+    let text = parts
+        .iter()
+        .map(|part| {
+            String::from_utf8_lossy(part)
+                .trim_matches(['\0', ' '])
+                .to_string()
+        })
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// EXIF dates are `YYYY:MM:DD HH:MM:SS` in camera-local time; rewritten to
+/// the `YYYY-MM-DD HH:MM:SS` form gallery-dl uses.
+fn exif_date(exif: &Exif, tag: Tag) -> Option<String> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+    let ExifValue::Ascii(parts) = &field.value else {
+        return None;
+    };
+    let date = DateTime::from_ascii(parts.first()?).ok()?;
+    Some(format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        date.year, date.month, date.day, date.hour, date.minute, date.second
+    ))
+}
+
+fn exif_keywords(exif: &Exif) -> Vec<String> {
+    let Some(field) = exif.get_field(XP_KEYWORDS, In::PRIMARY) else {
+        return Vec::new();
+    };
+    let ExifValue::Byte(bytes) = &field.value else {
+        return Vec::new();
+    };
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+        .split(';')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/exif.jpg")
+    }
+
+    #[test]
+    fn extracts_fields_from_jpeg_fixture() {
+        let value = extract_exif_metadata(&fixture()).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "date": "2023-07-14 18:30:00",
+                "author": "Alice Example",
+                "description": "Sunset over the bay",
+                "tags": ["sunset", "bay"],
+            })
+        );
+    }
+
+    #[test]
+    fn image_without_exif_is_an_error() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-exif-none-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("plain.png");
+        image::RgbImage::new(2, 2).save(&path).unwrap();
+
+        let err = extract_exif_metadata(&path).unwrap_err();
+        assert!(matches!(err, BooruError::Exif { .. }));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,61 +1,260 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::UNIX_EPOCH;
 
-use imagehash::{average_hash, difference_hash, perceptual_hash};
+use image::DynamicImage;
+use imagehash::{AverageHash, DifferenceHash, PerceptualHash};
 use rayon::prelude::*;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use xdg::BaseDirectories;
 
 use crate::error::BooruError;
+use crate::media::MediaKind;
+use crate::progress::ProgressObserver;
 use crate::scan::ImageItem;
 
-#[derive(Clone, Copy, Debug)]
-pub enum FuzzyHashAlgorithm {
+/// The hash function. Its discriminant is what [`HashCache`] stores.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum HashKind {
     AHash,
     DHash,
     PHash,
 }
 
-#[derive(Clone, Debug)]
+impl HashKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::AHash => "ahash",
+            Self::DHash => "dhash",
+            Self::PHash => "phash",
+        }
+    }
+}
+
+impl FromStr for HashKind {
+    type Err = ParseFuzzyHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ahash" => Ok(Self::AHash),
+            "dhash" => Ok(Self::DHash),
+            "phash" => Ok(Self::PHash),
+            other => Err(ParseFuzzyHashError(format!("unknown algorithm `{other}`"))),
+        }
+    }
+}
+
+/// Sides supported by [`FuzzyHashAlgorithm::with_size`].
+pub const HASH_SIZES: [u8; 3] = [8, 16, 32];
+
+/// A [`HashKind`] and how many cells per side the image is reduced to before
+/// hashing, so a hash has `size * size` bits. Larger sizes see finer detail
+/// and tell apart images that only share their overall layout.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FuzzyHashAlgorithm {
+    pub kind: HashKind,
+    pub size: u8,
+}
+
+impl FuzzyHashAlgorithm {
+    pub const DEFAULT_SIZE: u8 = 8;
+    pub const AHASH: Self = Self::new(HashKind::AHash);
+    pub const DHASH: Self = Self::new(HashKind::DHash);
+    pub const PHASH: Self = Self::new(HashKind::PHash);
+
+    pub const fn new(kind: HashKind) -> Self {
+        Self {
+            kind,
+            size: Self::DEFAULT_SIZE,
+        }
+    }
+
+    /// `None` unless `size` is one of [`HASH_SIZES`].
+    pub fn with_size(self, size: u8) -> Option<Self> {
+        HASH_SIZES.contains(&size).then_some(Self { size, ..self })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.kind.as_str()
+    }
+
+    /// How many bits this algorithm's hashes have.
+    pub fn bit_len(self) -> usize {
+        usize::from(self.size).pow(2)
+    }
+}
+
+impl From<HashKind> for FuzzyHashAlgorithm {
+    fn from(kind: HashKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl fmt::Display for FuzzyHashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses a [`HashKind`] at the default size.
+impl FromStr for FuzzyHashAlgorithm {
+    type Err = ParseFuzzyHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<HashKind>().map(Self::new)
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid fuzzy hash: {0}")]
+pub struct ParseFuzzyHashError(String);
+
+/// Returned by [`FuzzyHash::distance`] for hashes of different algorithms or
+/// lengths, whose bit positions mean different things.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("cannot compare {lhs_algo} hash ({lhs_bits} bits) with {rhs_algo} hash ({rhs_bits} bits)")]
+pub struct FuzzyHashMismatch {
+    pub lhs_algo: FuzzyHashAlgorithm,
+    pub lhs_bits: usize,
+    pub rhs_algo: FuzzyHashAlgorithm,
+    pub rhs_bits: usize,
+}
+
+/// A perceptual hash. Its string form is `<algo>:<bit count>:<hex>`, where the
+/// hex digits encode the bits packed eight per byte, least significant first.
+/// The size of `algo` is recovered from a square bit count when parsing.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct FuzzyHash {
     pub algo: FuzzyHashAlgorithm,
     pub bits: Vec<bool>,
 }
 
+impl fmt::Display for FuzzyHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:", self.algo.as_str(), self.bits.len())?;
+        for byte in pack_bits(&self.bits) {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FuzzyHash {
+    type Err = ParseFuzzyHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, ':');
+        let (Some(algo), Some(len), Some(hex)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(ParseFuzzyHashError(
+                "expected `<algo>:<bits>:<hex>`".to_string(),
+            ));
+        };
+
+        let kind = algo.parse::<HashKind>()?;
+        let len = len
+            .parse::<usize>()
+            .map_err(|err| ParseFuzzyHashError(format!("invalid bit count: {err}")))?;
+        let algo = HASH_SIZES
+            .into_iter()
+            .map(|size| FuzzyHashAlgorithm { kind, size })
+            .find(|algo| algo.bit_len() == len)
+            .unwrap_or(FuzzyHashAlgorithm::new(kind));
+        if hex.len() != len.div_ceil(8) * 2 {
+            return Err(ParseFuzzyHashError(format!(
+                "expected {} hex digits for {len} bits, got {}",
+                len.div_ceil(8) * 2,
+                hex.len()
+            )));
+        }
+
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|idx| {
+                hex.get(idx..idx + 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or_else(|| ParseFuzzyHashError(format!("invalid hex digits in `{hex}`")))
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        Ok(Self {
+            algo,
+            bits: unpack_bits(&bytes, len),
+        })
+    }
+}
+
+impl Serialize for FuzzyHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FuzzyHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl FuzzyHash {
-    pub fn distance(&self, other: &FuzzyHash) -> u32 {
-        let min_len = self.bits.len().min(other.bits.len());
-        let mut diff = 0u32;
-        for idx in 0..min_len {
-            if self.bits[idx] != other.bits[idx] {
-                diff += 1;
-            }
+    /// Hamming distance between two hashes of the same algorithm and length.
+    pub fn distance(&self, other: &FuzzyHash) -> Result<u32, FuzzyHashMismatch> {
+        if self.algo != other.algo || self.bits.len() != other.bits.len() {
+            return Err(FuzzyHashMismatch {
+                lhs_algo: self.algo,
+                lhs_bits: self.bits.len(),
+                rhs_algo: other.algo,
+                rhs_bits: other.bits.len(),
+            });
         }
-        diff + (self.bits.len().max(other.bits.len()) - min_len) as u32
+        let diff = self
+            .bits
+            .iter()
+            .zip(&other.bits)
+            .filter(|(lhs, rhs)| lhs != rhs)
+            .count();
+        Ok(diff as u32)
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DuplicateGroup {
     pub items: Vec<usize>,
+    /// Distance from `items[0]` to each entry of `items`, in the same order.
+    /// Grouping is transitive, so these can exceed the threshold.
+    pub distances: Vec<u32>,
+    /// The largest distance between any two entries of `items`.
+    pub distance: u32,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DuplicateWarning {
     pub path: PathBuf,
     pub message: String,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SimilarMatch {
+    pub item: usize,
+    pub distance: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DuplicateReport {
     pub groups: Vec<DuplicateGroup>,
     pub warnings: Vec<DuplicateWarning>,
 }
 
-pub trait ProgressObserver: Send + Sync {
-    fn inc(&self, delta: u64);
+/// The result of [`find_similar_to`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SimilarReport {
+    /// `(item index, distance)`, closest first.
+    pub matches: Vec<(usize, u32)>,
+    /// Items that failed to hash and so could not be compared.
+    pub warnings: Vec<DuplicateWarning>,
 }
 
 #[derive(Clone, Debug)]
@@ -83,6 +282,34 @@ impl FileFingerprint {
     }
 }
 
+/// `$XDG_CACHE_HOME/lightbooru/hash_cache.sqlite`, with its directory
+/// created. [`crate::IntegrityCache`] keeps its table in the same file.
+pub(crate) fn default_cache_path() -> Result<PathBuf, BooruError> {
+    let base = BaseDirectories::with_prefix("lightbooru").map_err(|err| BooruError::Cache {
+        message: err.to_string(),
+    })?;
+    base.place_cache_file("hash_cache.sqlite")
+        .map_err(|err| BooruError::Cache {
+            message: err.to_string(),
+        })
+}
+
+/// Bump whenever cached rows would mean something different, e.g. when
+/// [`HashKind`]'s numbering changes. A cache stamped with another version is
+/// rebuilt on [`HashCache::open`].
+pub const HASH_CACHE_SCHEMA_VERSION: i64 = 2;
+
+const HASH_CACHE_TABLE: &str = "CREATE TABLE IF NOT EXISTS hash_cache (
+     path TEXT NOT NULL,
+     algo INTEGER NOT NULL,
+     hash_size INTEGER NOT NULL,
+     mtime INTEGER NOT NULL,
+     size INTEGER NOT NULL,
+     bits BLOB NOT NULL,
+     bits_len INTEGER NOT NULL,
+     PRIMARY KEY(path, algo, hash_size)
+ );";
+
 pub struct HashCache {
     conn: Connection,
     path: PathBuf,
@@ -90,15 +317,7 @@ pub struct HashCache {
 
 impl HashCache {
     pub fn open_default() -> Result<Self, BooruError> {
-        let base = BaseDirectories::with_prefix("lightbooru").map_err(|err| BooruError::Cache {
-            message: err.to_string(),
-        })?;
-        let path = base
-            .place_cache_file("hash_cache.sqlite")
-            .map_err(|err| BooruError::Cache {
-                message: err.to_string(),
-            })?;
-        Self::open(&path)
+        Self::open(&default_cache_path()?)
     }
 
     pub fn open(path: &Path) -> Result<Self, BooruError> {
@@ -115,30 +334,58 @@ impl HashCache {
         conn.execute_batch(
             "PRAGMA journal_mode=WAL;
              PRAGMA synchronous=NORMAL;
-             CREATE TABLE IF NOT EXISTS hash_cache (
-                 path TEXT NOT NULL,
-                 algo INTEGER NOT NULL,
-                 mtime INTEGER NOT NULL,
-                 size INTEGER NOT NULL,
-                 bits BLOB NOT NULL,
-                 bits_len INTEGER NOT NULL,
-                 PRIMARY KEY(path, algo)
+             CREATE TABLE IF NOT EXISTS schema_version (
+                 version INTEGER NOT NULL
              );",
         )
         .map_err(|source| BooruError::Database {
             path: path.to_path_buf(),
             source,
         })?;
-        Ok(Self {
+        let mut cache = Self {
             conn,
             path: path.to_path_buf(),
-        })
+        };
+        cache.migrate()?;
+        Ok(cache)
+    }
+
+    /// Recreates the table, dropping every row, if the cache was written
+    /// under another [`HASH_CACHE_SCHEMA_VERSION`], including caches from
+    /// before the version was recorded, and stamps the current one.
+    fn migrate(&mut self) -> Result<(), BooruError> {
+        let db_err = |source| BooruError::Database {
+            path: self.path.clone(),
+            source,
+        };
+        let tx = self.conn.transaction().map_err(db_err)?;
+        let stored = tx
+            .query_row("SELECT version FROM schema_version", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .optional()
+            .map_err(db_err)?;
+        if stored == Some(HASH_CACHE_SCHEMA_VERSION) {
+            return Ok(());
+        }
+        tx.execute_batch("DROP TABLE IF EXISTS hash_cache; DELETE FROM schema_version;")
+            .map_err(db_err)?;
+        tx.execute_batch(HASH_CACHE_TABLE).map_err(db_err)?;
+        tx.execute(
+            "INSERT INTO schema_version (version) VALUES (?1)",
+            params![HASH_CACHE_SCHEMA_VERSION],
+        )
+        .map_err(db_err)?;
+        tx.commit().map_err(db_err)
     }
 
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    /// Rows are keyed by [`HashKind`] and hash size. A row whose stored bit
+    /// length still does not fit `algo` counts as a miss, so the hash gets
+    /// recomputed and the row overwritten.
     pub fn lookup(
         &self,
         image_path: &Path,
@@ -148,14 +395,19 @@ impl HashCache {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT mtime, size, bits, bits_len FROM hash_cache WHERE path = ?1 AND algo = ?2",
+                "SELECT mtime, size, bits, bits_len FROM hash_cache
+                 WHERE path = ?1 AND algo = ?2 AND hash_size = ?3",
             )
             .map_err(|source| BooruError::Database {
                 path: self.path.clone(),
                 source,
             })?;
         let mut rows = stmt
-            .query(params![image_path.to_string_lossy(), algo as i32])
+            .query(params![
+                image_path.to_string_lossy(),
+                algo.kind as i32,
+                algo.size
+            ])
             .map_err(|source| BooruError::Database {
                 path: self.path.clone(),
                 source,
@@ -181,7 +433,11 @@ impl HashCache {
                     path: self.path.clone(),
                     source,
                 })?;
-                let bits = unpack_bits(&bits, bits_len as usize);
+                let bits_len = usize::try_from(bits_len).unwrap_or(usize::MAX);
+                if bits_len != algo.bit_len() || bits.len() != bits_len.div_ceil(8) {
+                    return Ok(None);
+                }
+                let bits = unpack_bits(&bits, bits_len);
                 return Ok(Some(FuzzyHash { algo, bits }));
             }
         }
@@ -195,19 +451,25 @@ impl HashCache {
         fingerprint: &FileFingerprint,
         hash: &FuzzyHash,
     ) -> Result<(), BooruError> {
+        if hash.algo != algo {
+            return Err(BooruError::Cache {
+                message: format!("refusing to store a {} hash as {algo}", hash.algo),
+            });
+        }
         let bits = pack_bits(&hash.bits);
         self.conn
             .execute(
-                "INSERT INTO hash_cache (path, algo, mtime, size, bits, bits_len)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-                 ON CONFLICT(path, algo) DO UPDATE SET
+                "INSERT INTO hash_cache (path, algo, hash_size, mtime, size, bits, bits_len)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(path, algo, hash_size) DO UPDATE SET
                      mtime = excluded.mtime,
                      size = excluded.size,
                      bits = excluded.bits,
                      bits_len = excluded.bits_len",
                 params![
                     image_path.to_string_lossy(),
-                    algo as i32,
+                    algo.kind as i32,
+                    algo.size,
                     fingerprint.mtime,
                     fingerprint.size,
                     bits,
@@ -220,6 +482,74 @@ impl HashCache {
             })?;
         Ok(())
     }
+
+    /// Deletes every row whose path is not in `existing_paths` and returns
+    /// how many rows were removed. Paths compare as stored by
+    /// [`HashCache::store`], i.e. `Path::to_string_lossy`.
+    ///
+    /// This drops rows for any path missing from the set, not just deleted
+    /// files: when several libraries share one cache file, pruning with one
+    /// library's paths wipes the others' hashes.
+    pub fn prune(&mut self, existing_paths: &HashSet<String>) -> Result<usize, BooruError> {
+        let db_err = |source| BooruError::Database {
+            path: self.path.clone(),
+            source,
+        };
+        let tx = self.conn.transaction().map_err(db_err)?;
+        let stale = {
+            let mut stmt = tx
+                .prepare("SELECT DISTINCT path FROM hash_cache")
+                .map_err(db_err)?;
+            let paths = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(db_err)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(db_err)?;
+            paths
+                .into_iter()
+                .filter(|path| !existing_paths.contains(path))
+                .collect::<Vec<_>>()
+        };
+        let mut removed = 0;
+        {
+            let mut stmt = tx
+                .prepare("DELETE FROM hash_cache WHERE path = ?1")
+                .map_err(db_err)?;
+            for path in &stale {
+                removed += stmt.execute(params![path]).map_err(db_err)?;
+            }
+        }
+        tx.commit().map_err(db_err)?;
+        Ok(removed)
+    }
+
+    /// Deletes every row and returns how many there were.
+    pub fn force_clear(&mut self) -> Result<usize, BooruError> {
+        self.conn
+            .execute("DELETE FROM hash_cache", [])
+            .map_err(|source| BooruError::Database {
+                path: self.path.clone(),
+                source,
+            })
+    }
+
+    /// [`HashCache::prune`]s rows for images not among `items`, then runs
+    /// SQLite's `VACUUM` so the file actually shrinks. Like `prune`, it
+    /// drops the rows of any other library sharing the cache file.
+    pub fn vacuum(&mut self, items: &[ImageItem]) -> Result<usize, BooruError> {
+        let live = items
+            .iter()
+            .map(|item| item.image_path.to_string_lossy().into_owned())
+            .collect::<HashSet<_>>();
+        let removed = self.prune(&live)?;
+        self.conn
+            .execute_batch("VACUUM")
+            .map_err(|source| BooruError::Database {
+                path: self.path.clone(),
+                source,
+            })?;
+        Ok(removed)
+    }
 }
 
 pub struct HashComputation {
@@ -227,17 +557,54 @@ pub struct HashComputation {
     pub warnings: Vec<DuplicateWarning>,
 }
 
-pub fn compute_fuzzy_hash(path: &Path, algo: FuzzyHashAlgorithm) -> Result<FuzzyHash, BooruError> {
+/// Videos, as told by `kind`, are hashed by their first frame when built
+/// with the `ffmpeg` feature.
+#[cfg_attr(not(feature = "ffmpeg"), allow(unused_variables))]
+pub fn compute_fuzzy_hash(
+    path: &Path,
+    kind: MediaKind,
+    algo: FuzzyHashAlgorithm,
+) -> Result<FuzzyHash, BooruError> {
+    #[cfg(feature = "ffmpeg")]
+    if kind == MediaKind::Video {
+        let frame = crate::media::extract_video_frame(path)?;
+        return hash_video_frame(path, &frame, algo);
+    }
     let image = image::open(path).map_err(|source| BooruError::Image {
         path: path.to_path_buf(),
         source,
     })?;
-    let bits = match algo {
-        FuzzyHashAlgorithm::AHash => average_hash(&image).bits,
-        FuzzyHashAlgorithm::DHash => difference_hash(&image).bits,
-        FuzzyHashAlgorithm::PHash => perceptual_hash(&image).bits,
+    Ok(hash_image(&image, algo))
+}
+
+#[cfg(feature = "ffmpeg")]
+fn hash_video_frame(
+    path: &Path,
+    frame: &[u8],
+    algo: FuzzyHashAlgorithm,
+) -> Result<FuzzyHash, BooruError> {
+    let image = image::load_from_memory(frame).map_err(|source| BooruError::Image {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(hash_image(&image, algo))
+}
+
+fn hash_image(image: &DynamicImage, algo: FuzzyHashAlgorithm) -> FuzzyHash {
+    let size = usize::from(algo.size);
+    let bits = match algo.kind {
+        HashKind::AHash => AverageHash::new().with_size(size, size).hash(image).bits,
+        HashKind::DHash => DifferenceHash::new().with_size(size, size).hash(image).bits,
+        // The DCT keeps the lowest frequencies of an image four times as large.
+        HashKind::PHash => {
+            PerceptualHash::new()
+                .with_image_size(size * 4, size * 4)
+                .with_hash_size(size, size)
+                .hash(image)
+                .bits
+        }
     };
-    Ok(FuzzyHash { algo, bits })
+    FuzzyHash { algo, bits }
 }
 
 pub fn compute_hashes_with_cache(
@@ -248,9 +615,17 @@ pub fn compute_hashes_with_cache(
 ) -> HashComputation {
     let mut warnings = Vec::new();
     let mut hashes = Vec::new();
-    let mut pending: Vec<(usize, PathBuf, Option<FileFingerprint>)> = Vec::new();
+    let mut pending: Vec<(usize, PathBuf, MediaKind, Option<FileFingerprint>)> = Vec::new();
 
     for (idx, item) in items.iter().enumerate() {
+        // Without ffmpeg videos cannot be decoded; they are left out of
+        // duplicate groups.
+        if cfg!(not(feature = "ffmpeg")) && item.media_kind() == MediaKind::Video {
+            if let Some(observer) = progress {
+                observer.inc(1);
+            }
+            continue;
+        }
         let fingerprint = if cache.is_some() {
             FileFingerprint::from_path(&item.image_path)
                 .map_err(|err| {
@@ -281,7 +656,7 @@ pub fn compute_hashes_with_cache(
                 }),
             }
         }
-        pending.push((idx, item.image_path.clone(), fingerprint));
+        pending.push((idx, item.image_path.clone(), item.media_kind(), fingerprint));
     }
 
     let observer = progress;
@@ -292,8 +667,8 @@ pub fn compute_hashes_with_cache(
         PathBuf,
     )> = pending
         .par_iter()
-        .map(|(idx, path, fingerprint)| {
-            let result = compute_fuzzy_hash(path, algo);
+        .map(|(idx, path, kind, fingerprint)| {
+            let result = compute_fuzzy_hash(path, *kind, algo);
             if let Some(observer) = observer {
                 observer.inc(1);
             }
@@ -326,30 +701,32 @@ pub fn compute_hashes_with_cache(
     HashComputation { hashes, warnings }
 }
 
+/// Only hashes made with `algo` are compared; others are ignored.
 pub fn group_duplicates(
     items: &[ImageItem],
     hashes: &[(usize, FuzzyHash)],
+    algo: FuzzyHashAlgorithm,
     max_distance: u32,
     skip_same_dir: bool,
 ) -> Vec<DuplicateGroup> {
+    let hashes: Vec<&(usize, FuzzyHash)> = hashes
+        .iter()
+        .filter(|(_, hash)| hash.algo == algo)
+        .collect();
+    let index = build_hash_index(hashes.iter().copied());
     let mut uf = UnionFind::new(items.len());
-    let pairs: Vec<(usize, usize)> = (0..hashes.len())
-        .into_par_iter()
-        .flat_map(|i| {
-            let mut local = Vec::new();
-            for j in (i + 1)..hashes.len() {
-                let (idx_i, hash_i) = &hashes[i];
-                let (idx_j, hash_j) = &hashes[j];
-                if skip_same_dir
-                    && same_parent(&items[*idx_i].image_path, &items[*idx_j].image_path)
-                {
-                    continue;
-                }
-                if hash_i.distance(hash_j) <= max_distance {
-                    local.push((*idx_i, *idx_j));
-                }
-            }
-            local
+    let pairs: Vec<(usize, usize)> = hashes
+        .par_iter()
+        .flat_map_iter(|(idx_i, hash_i)| {
+            index
+                .neighbors(hash_i, max_distance)
+                .into_iter()
+                .filter(move |found| found.item > *idx_i)
+                .filter(move |found| {
+                    !skip_same_dir
+                        || !same_parent(&items[*idx_i].image_path, &items[found.item].image_path)
+                })
+                .map(move |found| (*idx_i, found.item))
         })
         .collect();
 
@@ -358,15 +735,39 @@ pub fn group_duplicates(
     }
 
     let mut groups_map: HashMap<usize, Vec<usize>> = HashMap::new();
-    for (idx, _) in hashes {
+    for (idx, _) in &hashes {
         let root = uf.find(*idx);
         groups_map.entry(root).or_default().push(*idx);
     }
 
+    let hash_by_item: HashMap<usize, &FuzzyHash> =
+        hashes.iter().map(|(idx, hash)| (*idx, hash)).collect();
     let mut groups: Vec<DuplicateGroup> = groups_map
         .into_values()
         .filter(|items| items.len() > 1)
-        .map(|items| DuplicateGroup { items })
+        .map(|items| {
+            let distance_between = |lhs: usize, rhs: usize| {
+                hash_by_item[&lhs]
+                    .distance(hash_by_item[&rhs])
+                    .expect("grouped hashes share algorithm and length")
+            };
+            let distances = items
+                .iter()
+                .map(|idx| distance_between(items[0], *idx))
+                .collect();
+            let distance = items
+                .iter()
+                .enumerate()
+                .flat_map(|(pos, lhs)| items[pos + 1..].iter().map(|rhs| (*lhs, *rhs)))
+                .map(|(lhs, rhs)| distance_between(lhs, rhs))
+                .max()
+                .unwrap_or(0);
+            DuplicateGroup {
+                items,
+                distances,
+                distance,
+            }
+        })
         .collect();
 
     groups.sort_by_key(|group| group.items.len());
@@ -375,6 +776,183 @@ pub fn group_duplicates(
     groups
 }
 
+/// A BK-tree over hashes, one tree per algorithm and bit length. Queries
+/// only descend into subtrees that can hold a hash within the distance.
+pub struct HashIndex<'a> {
+    hashes: Vec<&'a (usize, FuzzyHash)>,
+    nodes: Vec<BkNode>,
+    roots: HashMap<(FuzzyHashAlgorithm, usize), usize>,
+}
+
+struct BkNode {
+    /// Position in `HashIndex::hashes`.
+    entry: usize,
+    /// `(distance to this node, child node)`, one child per distance.
+    children: Vec<(u32, usize)>,
+}
+
+pub fn build_hash_index<'a>(
+    hashes: impl IntoIterator<Item = &'a (usize, FuzzyHash)>,
+) -> HashIndex<'a> {
+    let hashes = hashes.into_iter().collect::<Vec<_>>();
+    let mut index = HashIndex {
+        nodes: Vec::with_capacity(hashes.len()),
+        roots: HashMap::new(),
+        hashes,
+    };
+    for entry in 0..index.hashes.len() {
+        index.insert(entry);
+    }
+    index
+}
+
+impl HashIndex<'_> {
+    fn insert(&mut self, entry: usize) {
+        let hash = &self.hashes[entry].1;
+        let node = self.nodes.len();
+        self.nodes.push(BkNode {
+            entry,
+            children: Vec::new(),
+        });
+        let key = (hash.algo, hash.bits.len());
+        let Some(mut current) = self.roots.get(&key).copied() else {
+            self.roots.insert(key, node);
+            return;
+        };
+        loop {
+            let distance = self.distance_to_node(current, hash);
+            let next = self.nodes[current]
+                .children
+                .iter()
+                .find(|(child_distance, _)| *child_distance == distance)
+                .map(|(_, child)| *child);
+            match next {
+                Some(child) => current = child,
+                None => {
+                    self.nodes[current].children.push((distance, node));
+                    return;
+                }
+            }
+        }
+    }
+
+    fn distance_to_node(&self, node: usize, hash: &FuzzyHash) -> u32 {
+        self.hashes[self.nodes[node].entry]
+            .1
+            .distance(hash)
+            .expect("a tree only holds hashes of one algorithm and length")
+    }
+
+    /// Every indexed item within `max_distance` of `hash`, including `hash`'s
+    /// own item if it is indexed. Hashes of another algorithm or length never
+    /// match.
+    pub fn neighbors(&self, hash: &FuzzyHash, max_distance: u32) -> Vec<SimilarMatch> {
+        let mut found = Vec::new();
+        let Some(&root) = self.roots.get(&(hash.algo, hash.bits.len())) else {
+            return found;
+        };
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            let distance = self.distance_to_node(node, hash);
+            if distance <= max_distance {
+                found.push(SimilarMatch {
+                    item: self.hashes[self.nodes[node].entry].0,
+                    distance,
+                });
+            }
+            // Triangle inequality: a child at `child_distance` from this node
+            // can only hold matches if it is within `max_distance` of
+            // `distance`.
+            let low = distance.saturating_sub(max_distance);
+            let high = distance.saturating_add(max_distance);
+            stack.extend(
+                self.nodes[node]
+                    .children
+                    .iter()
+                    .filter(|(child_distance, _)| (low..=high).contains(child_distance))
+                    .map(|(_, child)| *child),
+            );
+        }
+        found
+    }
+}
+
+/// Returns the hashed items within `max_distance` of `target`, closest first.
+/// The target item itself and hashes that cannot be compared with its hash
+/// are excluded.
+pub fn similar_to(
+    hashes: &[(usize, FuzzyHash)],
+    target: usize,
+    max_distance: u32,
+) -> Option<Vec<SimilarMatch>> {
+    let (_, target_hash) = hashes.iter().find(|(idx, _)| *idx == target)?;
+    let mut matches: Vec<SimilarMatch> = hashes
+        .iter()
+        .filter(|(idx, _)| *idx != target)
+        .filter_map(|(idx, hash)| {
+            Some(SimilarMatch {
+                item: *idx,
+                distance: target_hash.distance(hash).ok()?,
+            })
+        })
+        .filter(|found| found.distance <= max_distance)
+        .collect();
+    matches.sort_by_key(|found| (found.distance, found.item));
+    Some(matches)
+}
+
+/// The items within `threshold` of the image at `path`, closest first and at
+/// most `limit` of them, as `(item index, distance)`. `path` need not be in
+/// `items`; if it is, that item is left out. Items that fail to hash are
+/// reported as warnings, but failing to hash `path` is an error.
+pub fn find_similar_to(
+    path: &Path,
+    kind: MediaKind,
+    items: &[ImageItem],
+    algo: FuzzyHashAlgorithm,
+    threshold: u32,
+    limit: usize,
+    mut cache: Option<&mut HashCache>,
+) -> Result<SimilarReport, BooruError> {
+    let target = hash_with_cache(path, kind, algo, cache.as_deref_mut())?;
+    let mut computation = compute_hashes_with_cache(items, algo, cache, None);
+    // `path` takes the index past the last item, so no item is mistaken
+    // for it.
+    computation.hashes.push((items.len(), target));
+    let matches = similar_to(&computation.hashes, items.len(), threshold)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|found| items[found.item].image_path != path)
+        .take(limit)
+        .map(|found| (found.item, found.distance))
+        .collect();
+    Ok(SimilarReport {
+        matches,
+        warnings: computation.warnings,
+    })
+}
+
+/// Like one step of [`compute_hashes_with_cache`]. The cache is best effort:
+/// a failed lookup or store just means hashing the file.
+fn hash_with_cache(
+    path: &Path,
+    kind: MediaKind,
+    algo: FuzzyHashAlgorithm,
+    cache: Option<&mut HashCache>,
+) -> Result<FuzzyHash, BooruError> {
+    let Some((cache, fingerprint)) =
+        cache.and_then(|cache| Some((cache, FileFingerprint::from_path(path).ok()?)))
+    else {
+        return compute_fuzzy_hash(path, kind, algo);
+    };
+    if let Ok(Some(hash)) = cache.lookup(path, algo, &fingerprint) {
+        return Ok(hash);
+    }
+    let hash = compute_fuzzy_hash(path, kind, algo)?;
+    let _ = cache.store(path, algo, &fingerprint, &hash);
+    Ok(hash)
+}
+
 pub fn find_duplicates_with_cache(
     items: &[ImageItem],
     algo: FuzzyHashAlgorithm,
@@ -384,7 +962,13 @@ pub fn find_duplicates_with_cache(
     progress: Option<&dyn ProgressObserver>,
 ) -> DuplicateReport {
     let computation = compute_hashes_with_cache(items, algo, cache, progress);
-    let groups = group_duplicates(items, &computation.hashes, max_distance, skip_same_dir);
+    let groups = group_duplicates(
+        items,
+        &computation.hashes,
+        algo,
+        max_distance,
+        skip_same_dir,
+    );
     DuplicateReport {
         groups,
         warnings: computation.warnings,
@@ -473,3 +1057,753 @@ impl UnionFind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{
+        build_hash_index, find_similar_to, group_duplicates, hash_image, same_parent, similar_to,
+        DuplicateReport, DuplicateWarning, FileFingerprint, FuzzyHash, FuzzyHashAlgorithm,
+        HashCache, SimilarMatch, UnionFind, HASH_CACHE_SCHEMA_VERSION, HASH_SIZES,
+    };
+    use crate::media::MediaKind;
+    use crate::metadata::BooruEdits;
+    use crate::scan::ImageItem;
+
+    fn make_hash(algo: FuzzyHashAlgorithm, len: usize) -> FuzzyHash {
+        FuzzyHash {
+            algo,
+            bits: (0..len).map(|idx| idx % 3 == 0).collect(),
+        }
+    }
+
+    #[test]
+    fn fuzzy_hash_string_round_trip() {
+        let hash = make_hash(FuzzyHashAlgorithm::DHASH, 64);
+        let text = hash.to_string();
+        assert_eq!(text, "dhash:64:4992244992244992");
+        assert_eq!(text.parse::<FuzzyHash>().unwrap(), hash);
+    }
+
+    #[test]
+    fn fuzzy_hash_string_round_trip_with_odd_bit_length() {
+        let hash = make_hash(FuzzyHashAlgorithm::PHASH, 13);
+        let text = hash.to_string();
+        assert_eq!(text, "phash:13:4912");
+        assert_eq!(text.parse::<FuzzyHash>().unwrap(), hash);
+    }
+
+    #[test]
+    fn fuzzy_hash_serde_round_trip() {
+        let hash = make_hash(FuzzyHashAlgorithm::AHASH, 11);
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, "\"ahash:11:4902\"");
+        let parsed: FuzzyHash = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, hash);
+    }
+
+    #[test]
+    fn fuzzy_hash_string_keeps_the_hash_size() {
+        let algo = FuzzyHashAlgorithm::DHASH.with_size(16).unwrap();
+        let hash = make_hash(algo, algo.bit_len());
+        let parsed = hash.to_string().parse::<FuzzyHash>().unwrap();
+        assert_eq!(parsed.algo.size, 16);
+        assert_eq!(parsed, hash);
+        assert!(FuzzyHashAlgorithm::DHASH.with_size(12).is_none());
+    }
+
+    #[test]
+    fn hash_length_matches_bit_len_for_every_kind_and_size() {
+        let image = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 48, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8])
+        }));
+        for base in [
+            FuzzyHashAlgorithm::AHASH,
+            FuzzyHashAlgorithm::DHASH,
+            FuzzyHashAlgorithm::PHASH,
+        ] {
+            for size in HASH_SIZES {
+                let algo = base.with_size(size).unwrap();
+                assert_eq!(
+                    hash_image(&image, algo).bits.len(),
+                    algo.bit_len(),
+                    "{algo:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hash_cache_keeps_sizes_apart() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-hash-sizes-{unique}"));
+        let cache = HashCache::open(&dir.join("cache.sqlite")).unwrap();
+        let image = PathBuf::from("/lib/a.jpg");
+        let fingerprint = FileFingerprint { mtime: 1, size: 2 };
+        let small = FuzzyHashAlgorithm::DHASH;
+        let large = small.with_size(16).unwrap();
+        cache
+            .store(
+                &image,
+                small,
+                &fingerprint,
+                &make_hash(small, small.bit_len()),
+            )
+            .unwrap();
+        assert!(cache.lookup(&image, large, &fingerprint).unwrap().is_none());
+
+        cache
+            .store(
+                &image,
+                large,
+                &fingerprint,
+                &make_hash(large, large.bit_len()),
+            )
+            .unwrap();
+        let lookup = |algo| cache.lookup(&image, algo, &fingerprint).unwrap().unwrap();
+        assert_eq!(lookup(small).bits.len(), 64);
+        assert_eq!(lookup(large).bits.len(), 256);
+
+        drop(cache);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Images sharing a coarse layout but with different detail inside each
+    /// cell of an 8×8 grid: every 8×8 average hash is the same, while 16×16
+    /// hashes pick up the detail and keep them apart.
+    #[test]
+    fn larger_hash_size_separates_images_with_the_same_layout() {
+        const TILE: u32 = 16;
+        let mut rng = StdRng::seed_from_u64(7);
+        let layout = (0..64).map(|_| rng.gen_bool(0.5)).collect::<Vec<_>>();
+        let images = (0..8)
+            .map(|_| {
+                // Two of the four tiles in each cell are bright.
+                let patterns = (0..64)
+                    .map(|_| {
+                        [0b0011u8, 0b0101, 0b0110, 0b1001, 0b1010, 0b1100][rng.gen_range(0..6)]
+                    })
+                    .collect::<Vec<_>>();
+                image::DynamicImage::ImageLuma8(image::GrayImage::from_fn(
+                    TILE * 16,
+                    TILE * 16,
+                    |x, y| {
+                        let (tile_x, tile_y) = (x / TILE, y / TILE);
+                        let cell = (tile_y / 2 * 8 + tile_x / 2) as usize;
+                        let quadrant = (tile_y % 2) * 2 + tile_x % 2;
+                        let base = if layout[cell] { 166.0 } else { 90.0 };
+                        let detail = if patterns[cell] & (1 << quadrant) != 0 {
+                            76.0
+                        } else {
+                            -76.0
+                        };
+                        image::Luma([(base + detail) as u8])
+                    },
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let close_pairs = |size| {
+            let algo = FuzzyHashAlgorithm::AHASH.with_size(size).unwrap();
+            // The usual threshold of 8 out of 64 bits, scaled to the size.
+            let threshold = algo.bit_len() as u32 / 8;
+            let hashes = images
+                .iter()
+                .map(|image| hash_image(image, algo))
+                .collect::<Vec<_>>();
+            let mut close = 0;
+            for (idx, lhs) in hashes.iter().enumerate() {
+                for rhs in &hashes[idx + 1..] {
+                    if lhs.distance(rhs).unwrap() <= threshold {
+                        close += 1;
+                    }
+                }
+            }
+            close
+        };
+        assert_eq!(close_pairs(8), 28);
+        assert_eq!(close_pairs(16), 0);
+    }
+
+    #[test]
+    fn fuzzy_hash_rejects_malformed_strings() {
+        assert!("dhash:64".parse::<FuzzyHash>().is_err());
+        assert!("xhash:8:ff".parse::<FuzzyHash>().is_err());
+        assert!("dhash:16:ff".parse::<FuzzyHash>().is_err());
+        assert!("dhash:8:zz".parse::<FuzzyHash>().is_err());
+    }
+
+    #[test]
+    fn similar_to_sorts_by_distance_and_skips_target() {
+        let base = make_hash(FuzzyHashAlgorithm::DHASH, 16);
+        let mut near = base.clone();
+        near.bits[1] = !near.bits[1];
+        let mut far = base.clone();
+        for bit in far.bits.iter_mut().take(10) {
+            *bit = !*bit;
+        }
+        let hashes = vec![(0, far), (1, base.clone()), (2, near), (4, base)];
+
+        let matches = similar_to(&hashes, 1, 4).unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                SimilarMatch {
+                    item: 4,
+                    distance: 0
+                },
+                SimilarMatch {
+                    item: 2,
+                    distance: 1
+                },
+            ]
+        );
+        assert!(similar_to(&hashes, 3, 4).is_none());
+    }
+
+    #[test]
+    fn distance_rejects_other_algorithms_and_lengths() {
+        let dhash = make_hash(FuzzyHashAlgorithm::DHASH, 64);
+        let phash = make_hash(FuzzyHashAlgorithm::PHASH, 64);
+        assert_eq!(dhash.distance(&dhash), Ok(0));
+        assert!(dhash.distance(&phash).is_err());
+        assert!(dhash
+            .distance(&make_hash(FuzzyHashAlgorithm::DHASH, 144))
+            .is_err());
+
+        let items = (0..3)
+            .map(|idx| {
+                ImageItem::new(
+                    PathBuf::from(format!("/lib/{idx}/a.jpg")),
+                    PathBuf::new(),
+                    PathBuf::new(),
+                    serde_json::Value::Null,
+                    BooruEdits::default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let hashes = vec![(0, dhash.clone()), (1, phash), (2, dhash)];
+        let groups = group_duplicates(&items, &hashes, FuzzyHashAlgorithm::DHASH, 0, false);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].items.len(), 2);
+        assert_eq!(groups[0].distances, vec![0, 0]);
+        assert!(!groups[0].items.contains(&1));
+    }
+
+    #[test]
+    fn duplicate_report_records_the_widest_pair_and_round_trips() {
+        let items = (0..3)
+            .map(|idx| {
+                ImageItem::new(
+                    PathBuf::from(format!("/lib/{idx}/a.jpg")),
+                    PathBuf::new(),
+                    PathBuf::new(),
+                    serde_json::Value::Null,
+                    BooruEdits::default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // A chain: each hash is three bits from the next, six from the far end.
+        let hashes = (0..3)
+            .map(|idx| {
+                let bits = (0..64).map(|bit| bit < idx * 3).collect();
+                (
+                    idx,
+                    FuzzyHash {
+                        algo: FuzzyHashAlgorithm::DHASH,
+                        bits,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let groups = group_duplicates(&items, &hashes, FuzzyHashAlgorithm::DHASH, 4, false);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].distances, vec![0, 3, 6]);
+        assert_eq!(groups[0].distance, 6);
+
+        let report = DuplicateReport {
+            groups,
+            warnings: vec![DuplicateWarning {
+                path: PathBuf::from("/lib/broken.jpg"),
+                message: "unsupported image".to_string(),
+            }],
+        };
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(
+            serde_json::from_str::<DuplicateReport>(&json).unwrap(),
+            report
+        );
+    }
+
+    /// The all-pairs grouping `group_duplicates` used before the BK-tree.
+    fn brute_force_groups(
+        items: &[ImageItem],
+        hashes: &[(usize, FuzzyHash)],
+        algo: FuzzyHashAlgorithm,
+        max_distance: u32,
+        skip_same_dir: bool,
+    ) -> Vec<Vec<usize>> {
+        let hashes = hashes
+            .iter()
+            .filter(|(_, hash)| hash.algo == algo)
+            .collect::<Vec<_>>();
+        let mut uf = UnionFind::new(items.len());
+        for i in 0..hashes.len() {
+            for j in (i + 1)..hashes.len() {
+                let (idx_i, hash_i) = hashes[i];
+                let (idx_j, hash_j) = hashes[j];
+                if skip_same_dir
+                    && same_parent(&items[*idx_i].image_path, &items[*idx_j].image_path)
+                {
+                    continue;
+                }
+                if matches!(hash_i.distance(hash_j), Ok(distance) if distance <= max_distance) {
+                    uf.union(*idx_i, *idx_j);
+                }
+            }
+        }
+        let mut groups = std::collections::HashMap::<usize, Vec<usize>>::new();
+        for (idx, _) in &hashes {
+            groups.entry(uf.find(*idx)).or_default().push(*idx);
+        }
+        let mut groups = groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect::<Vec<_>>();
+        groups.sort();
+        groups
+    }
+
+    #[test]
+    fn indexed_grouping_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(0x6c62);
+        let mut hashes = Vec::new();
+        for _ in 0..60 {
+            let base = (0..64).map(|_| rng.gen_bool(0.5)).collect::<Vec<_>>();
+            for _ in 0..5 {
+                let mut bits = base.clone();
+                for _ in 0..rng.gen_range(0..8) {
+                    let bit = rng.gen_range(0..bits.len());
+                    bits[bit] = !bits[bit];
+                }
+                let algo = if rng.gen_bool(0.1) {
+                    FuzzyHashAlgorithm::PHASH
+                } else {
+                    FuzzyHashAlgorithm::DHASH
+                };
+                hashes.push((hashes.len(), FuzzyHash { algo, bits }));
+            }
+        }
+        let items = (0..hashes.len())
+            .map(|idx| {
+                ImageItem::new(
+                    PathBuf::from(format!("/lib/{}/{idx}.jpg", idx % 7)),
+                    PathBuf::new(),
+                    PathBuf::new(),
+                    serde_json::Value::Null,
+                    BooruEdits::default(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for max_distance in [0, 3, 8, 20] {
+            for skip_same_dir in [false, true] {
+                let mut indexed = group_duplicates(
+                    &items,
+                    &hashes,
+                    FuzzyHashAlgorithm::DHASH,
+                    max_distance,
+                    skip_same_dir,
+                )
+                .into_iter()
+                .map(|group| group.items)
+                .collect::<Vec<_>>();
+                indexed.sort();
+                assert_eq!(
+                    indexed,
+                    brute_force_groups(
+                        &items,
+                        &hashes,
+                        FuzzyHashAlgorithm::DHASH,
+                        max_distance,
+                        skip_same_dir,
+                    ),
+                    "max_distance={max_distance} skip_same_dir={skip_same_dir}"
+                );
+            }
+        }
+
+        let index = build_hash_index(&hashes);
+        let (_, probe) = &hashes[0];
+        let mut neighbors = index
+            .neighbors(probe, 8)
+            .into_iter()
+            .map(|found| found.item)
+            .collect::<Vec<_>>();
+        neighbors.sort();
+        let expected = hashes
+            .iter()
+            .filter(|(_, hash)| matches!(probe.distance(hash), Ok(distance) if distance <= 8))
+            .map(|(idx, _)| *idx)
+            .collect::<Vec<_>>();
+        assert_eq!(neighbors, expected);
+    }
+
+    #[test]
+    fn hash_cache_misses_on_wrong_algorithm_or_length() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-hash-cache-{unique}"));
+        let cache = HashCache::open(&dir.join("cache.sqlite")).unwrap();
+        let image = PathBuf::from("/lib/a.jpg");
+        let fingerprint = FileFingerprint { mtime: 1, size: 2 };
+        let dhash = make_hash(
+            FuzzyHashAlgorithm::DHASH,
+            FuzzyHashAlgorithm::DHASH.bit_len(),
+        );
+
+        cache
+            .store(&image, FuzzyHashAlgorithm::DHASH, &fingerprint, &dhash)
+            .unwrap();
+        assert_eq!(
+            cache
+                .lookup(&image, FuzzyHashAlgorithm::DHASH, &fingerprint)
+                .unwrap(),
+            Some(dhash.clone())
+        );
+        assert_eq!(
+            cache
+                .lookup(&image, FuzzyHashAlgorithm::PHASH, &fingerprint)
+                .unwrap(),
+            None
+        );
+        assert!(cache
+            .store(&image, FuzzyHashAlgorithm::PHASH, &fingerprint, &dhash)
+            .is_err());
+
+        let truncated = make_hash(FuzzyHashAlgorithm::DHASH, dhash.bits.len() - 4);
+        cache
+            .store(&image, FuzzyHashAlgorithm::DHASH, &fingerprint, &truncated)
+            .unwrap();
+        assert_eq!(
+            cache
+                .lookup(&image, FuzzyHashAlgorithm::DHASH, &fingerprint)
+                .unwrap(),
+            None
+        );
+
+        drop(cache);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hash_cache_prune_removes_rows_for_missing_paths() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-hash-prune-{unique}"));
+        let mut cache = HashCache::open(&dir.join("cache.sqlite")).unwrap();
+        let fingerprint = FileFingerprint { mtime: 1, size: 2 };
+        let hash = make_hash(
+            FuzzyHashAlgorithm::DHASH,
+            FuzzyHashAlgorithm::DHASH.bit_len(),
+        );
+        let kept = PathBuf::from("/lib/kept.jpg");
+        let deleted = PathBuf::from("/lib/deleted.jpg");
+        for image in [&kept, &deleted] {
+            cache
+                .store(image, FuzzyHashAlgorithm::DHASH, &fingerprint, &hash)
+                .unwrap();
+        }
+
+        let existing = HashSet::from([kept.to_string_lossy().into_owned()]);
+        assert_eq!(cache.prune(&existing).unwrap(), 1);
+        assert_eq!(cache.prune(&existing).unwrap(), 0);
+        assert!(cache
+            .lookup(&kept, FuzzyHashAlgorithm::DHASH, &fingerprint)
+            .unwrap()
+            .is_some());
+        assert!(cache
+            .lookup(&deleted, FuzzyHashAlgorithm::DHASH, &fingerprint)
+            .unwrap()
+            .is_none());
+
+        drop(cache);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hash_cache_vacuum_keeps_only_live_items() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-hash-vacuum-{unique}"));
+        let mut cache = HashCache::open(&dir.join("cache.sqlite")).unwrap();
+        let fingerprint = FileFingerprint { mtime: 1, size: 2 };
+        for algo in [FuzzyHashAlgorithm::DHASH, FuzzyHashAlgorithm::AHASH] {
+            let hash = make_hash(algo, algo.bit_len());
+            for idx in 0..4 {
+                let path = PathBuf::from(format!("/lib/{idx}.jpg"));
+                cache.store(&path, algo, &fingerprint, &hash).unwrap();
+            }
+        }
+        let live = ImageItem::new(
+            PathBuf::from("/lib/0.jpg"),
+            PathBuf::new(),
+            PathBuf::new(),
+            serde_json::Value::Null,
+            BooruEdits::default(),
+        );
+
+        assert_eq!(cache.vacuum(std::slice::from_ref(&live)).unwrap(), 6);
+        assert_eq!(cache.vacuum(std::slice::from_ref(&live)).unwrap(), 0);
+        for algo in [FuzzyHashAlgorithm::DHASH, FuzzyHashAlgorithm::AHASH] {
+            assert!(cache
+                .lookup(&live.image_path, algo, &fingerprint)
+                .unwrap()
+                .is_some());
+            assert!(cache
+                .lookup(Path::new("/lib/3.jpg"), algo, &fingerprint)
+                .unwrap()
+                .is_none());
+        }
+
+        drop(cache);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hash_cache_drops_rows_from_another_schema_version() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-hash-schema-{unique}"));
+        let path = dir.join("cache.sqlite");
+        let image = PathBuf::from("/lib/a.jpg");
+        let fingerprint = FileFingerprint { mtime: 1, size: 2 };
+        let algo = FuzzyHashAlgorithm::DHASH;
+        let hash = make_hash(algo, algo.bit_len());
+        let stored_version = || {
+            rusqlite::Connection::open(&path)
+                .unwrap()
+                .query_row("SELECT version FROM schema_version", [], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .unwrap()
+        };
+
+        let cache = HashCache::open(&path).unwrap();
+        cache.store(&image, algo, &fingerprint, &hash).unwrap();
+        drop(cache);
+        assert_eq!(stored_version(), HASH_CACHE_SCHEMA_VERSION);
+        let cache = HashCache::open(&path).unwrap();
+        assert!(cache.lookup(&image, algo, &fingerprint).unwrap().is_some());
+        drop(cache);
+
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute(
+                "UPDATE schema_version SET version = ?1",
+                [HASH_CACHE_SCHEMA_VERSION - 1],
+            )
+            .unwrap();
+        let mut cache = HashCache::open(&path).unwrap();
+        assert!(cache.lookup(&image, algo, &fingerprint).unwrap().is_none());
+        assert_eq!(stored_version(), HASH_CACHE_SCHEMA_VERSION);
+
+        cache.store(&image, algo, &fingerprint, &hash).unwrap();
+        assert_eq!(cache.force_clear().unwrap(), 1);
+        assert_eq!(cache.force_clear().unwrap(), 0);
+
+        drop(cache);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn find_similar_to_finds_a_copy_at_distance_zero() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-similar-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gradient = image::GrayImage::from_fn(64, 64, |x, y| image::Luma([(x * 4 + y) as u8]));
+        let stripes = image::GrayImage::from_fn(64, 64, |_, y| {
+            image::Luma([if y % 16 < 8 { 255 } else { 0 }])
+        });
+        gradient.save(dir.join("a.png")).unwrap();
+        std::fs::copy(dir.join("a.png"), dir.join("copy.png")).unwrap();
+        stripes.save(dir.join("other.png")).unwrap();
+        std::fs::write(dir.join("broken.png"), b"not a png").unwrap();
+        let items = ["a.png", "broken.png", "copy.png", "other.png"]
+            .into_iter()
+            .map(|name| {
+                ImageItem::new(
+                    dir.join(name),
+                    PathBuf::new(),
+                    PathBuf::new(),
+                    serde_json::Value::Null,
+                    BooruEdits::default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut cache = HashCache::open(&dir.join("cache.sqlite")).unwrap();
+        let algo = FuzzyHashAlgorithm::DHASH;
+
+        let similar = |path: &Path, cache: Option<&mut HashCache>| {
+            find_similar_to(path, MediaKind::Image, &items, algo, 8, 10, cache)
+        };
+        let report = similar(&dir.join("a.png"), None).unwrap();
+        assert_eq!(report.matches, vec![(2, 0)]);
+        let warned = report
+            .warnings
+            .iter()
+            .map(|warning| warning.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(warned, vec![dir.join("broken.png")]);
+        assert_eq!(
+            similar(&dir.join("a.png"), Some(&mut cache))
+                .unwrap()
+                .matches,
+            vec![(2, 0)]
+        );
+        // Cached now, including the target.
+        assert_eq!(
+            similar(&dir.join("a.png"), Some(&mut cache))
+                .unwrap()
+                .matches,
+            vec![(2, 0)]
+        );
+        assert_eq!(
+            find_similar_to(
+                &dir.join("a.png"),
+                MediaKind::Image,
+                &items,
+                algo,
+                64,
+                1,
+                None
+            )
+            .unwrap()
+            .matches,
+            vec![(2, 0)]
+        );
+        assert!(similar(&dir.join("broken.png"), None).is_err());
+
+        drop(cache);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(all(unix, feature = "ffmpeg"))]
+    #[test]
+    fn video_hash_matches_its_first_frame() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use super::{compute_fuzzy_hash, hash_video_frame};
+        use crate::media::extract_video_frame_with;
+
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-video-hash-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let frame = dir.join("frame.png");
+        image::RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 16) as u8, 0])
+        })
+        .save(&frame)
+        .unwrap();
+        let video = dir.join("clip.mp4");
+        std::fs::write(&video, b"not really a video").unwrap();
+        // Stands in for ffmpeg by printing the frame whatever it is asked.
+        let ffmpeg = dir.join("ffmpeg");
+        std::fs::write(&ffmpeg, format!("#!/bin/sh\ncat '{}'\n", frame.display())).unwrap();
+        std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let algo = FuzzyHashAlgorithm::DHASH;
+        let bytes = extract_video_frame_with(&ffmpeg, &video).unwrap();
+        assert_eq!(
+            hash_video_frame(&video, &bytes, algo).unwrap(),
+            compute_fuzzy_hash(&frame, MediaKind::Image, algo).unwrap()
+        );
+
+        let failing = dir.join("failing-ffmpeg");
+        std::fs::write(
+            &failing,
+            "#!/bin/sh\necho 'moov atom not found' >&2\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&failing, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let err = extract_video_frame_with(&failing, &video).unwrap_err();
+        assert!(err.to_string().contains("moov atom not found"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(all(unix, feature = "ffmpeg"))]
+    #[test]
+    fn library_hashes_include_videos_under_ffmpeg() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use super::{compute_fuzzy_hash, compute_hashes_with_cache};
+        use crate::media::FFMPEG_ENV_VAR;
+
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-video-library-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let frame = dir.join("frame.png");
+        image::RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, 0, (y * 16) as u8])
+        })
+        .save(&frame)
+        .unwrap();
+        let ffmpeg = dir.join("ffmpeg");
+        std::fs::write(&ffmpeg, format!("#!/bin/sh\ncat '{}'\n", frame.display())).unwrap();
+        std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+        // No other test in this crate runs `extract_video_frame`.
+        std::env::set_var(FFMPEG_ENV_VAR, &ffmpeg);
+
+        let items = ["clip.mp4", "frame.png"]
+            .map(|name| {
+                let path = dir.join(name);
+                if name.ends_with(".mp4") {
+                    std::fs::write(&path, b"not really a video").unwrap();
+                }
+                ImageItem::new(
+                    path.clone(),
+                    path.with_extension("json"),
+                    path.with_extension("booru.json"),
+                    serde_json::Value::Null,
+                    BooruEdits::default(),
+                )
+            })
+            .to_vec();
+        let algo = FuzzyHashAlgorithm::DHASH;
+        let computation = compute_hashes_with_cache(&items, algo, None, None);
+        assert!(computation.warnings.is_empty());
+        let mut hashes = computation.hashes;
+        hashes.sort_by_key(|(idx, _)| *idx);
+        let expected = compute_fuzzy_hash(&frame, MediaKind::Image, algo).unwrap();
+        assert_eq!(hashes, vec![(0, expected.clone()), (1, expected)]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+use crate::error::BooruError;
+use crate::hash::{default_cache_path, FileFingerprint};
+use crate::progress::ProgressObserver;
+use crate::scan::ImageItem;
+
+/// SHA-256 digests of image files, kept in the `integrity_cache` table of
+/// the [`crate::HashCache`] database so a later run can tell whether a file's
+/// content changed.
+pub struct IntegrityCache {
+    conn: Connection,
+    path: PathBuf,
+}
+
+impl IntegrityCache {
+    pub fn open_default() -> Result<Self, BooruError> {
+        Self::open(&default_cache_path()?)
+    }
+
+    pub fn open(path: &Path) -> Result<Self, BooruError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|source| BooruError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        let conn = Connection::open(path).map_err(|source| BooruError::Database {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        conn.execute_batch(
+            "PRAGMA journal_mode=WAL;
+             PRAGMA synchronous=NORMAL;
+             CREATE TABLE IF NOT EXISTS integrity_cache (
+                 path TEXT PRIMARY KEY NOT NULL,
+                 mtime INTEGER NOT NULL,
+                 size INTEGER NOT NULL,
+                 sha256 TEXT NOT NULL
+             );",
+        )
+        .map_err(|source| BooruError::Database {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(Self {
+            conn,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Recorded fingerprints and digests keyed by `Path::to_string_lossy`.
+    fn digests(&self) -> Result<HashMap<String, (FileFingerprint, String)>, BooruError> {
+        let db_err = |source| BooruError::Database {
+            path: self.path.clone(),
+            source,
+        };
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, mtime, size, sha256 FROM integrity_cache")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map([], |row| {
+                let fingerprint = FileFingerprint {
+                    mtime: row.get(1)?,
+                    size: row.get(2)?,
+                };
+                Ok((row.get(0)?, (fingerprint, row.get(3)?)))
+            })
+            .map_err(db_err)?;
+        rows.collect::<Result<_, _>>().map_err(db_err)
+    }
+
+    fn write(
+        &mut self,
+        stores: &[(String, FileFingerprint, String)],
+        removals: &[String],
+    ) -> Result<(), BooruError> {
+        let db_err = |source| BooruError::Database {
+            path: self.path.clone(),
+            source,
+        };
+        let tx = self.conn.transaction().map_err(db_err)?;
+        {
+            let mut insert = tx
+                .prepare(
+                    "INSERT INTO integrity_cache (path, mtime, size, sha256)
+                     VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(path) DO UPDATE SET
+                         mtime = excluded.mtime,
+                         size = excluded.size,
+                         sha256 = excluded.sha256",
+                )
+                .map_err(db_err)?;
+            for (path, fingerprint, digest) in stores {
+                insert
+                    .execute(params![path, fingerprint.mtime, fingerprint.size, digest])
+                    .map_err(db_err)?;
+            }
+            let mut delete = tx
+                .prepare("DELETE FROM integrity_cache WHERE path = ?1")
+                .map_err(db_err)?;
+            for path in removals {
+                delete.execute(params![path]).map_err(db_err)?;
+            }
+        }
+        tx.commit().map_err(db_err)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IntegrityWarning {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Outcome of [`check_integrity`]. Path lists are sorted.
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    /// Content differs from the recorded digest.
+    pub changed: Vec<PathBuf>,
+    /// No digest was recorded yet; one is now.
+    pub new: Vec<PathBuf>,
+    /// A digest is recorded but the file no longer exists.
+    pub missing: Vec<PathBuf>,
+    /// Files whose content matched, or only their size and mtime when the
+    /// check was not `full`.
+    pub unchanged: usize,
+    pub warnings: Vec<IntegrityWarning>,
+}
+
+/// Lowercase hex SHA-256 of the file's bytes.
+pub fn sha256_file(path: &Path) -> Result<String, BooruError> {
+    let io_err = |source| BooruError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut file = File::open(path).map_err(io_err)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(io_err)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Hashes every item and compares against `cache`. New files are always
+/// recorded. Changed and missing files keep being reported until a run with
+/// `repair` records the new digests and forgets the missing files.
+///
+/// Files whose size and mtime match the recorded ones are taken as
+/// unchanged without being read, unless `full` is set; a full run also
+/// catches content that changed behind an unchanged mtime.
+///
+/// Only rows whose file is gone count as missing, so digests recorded for
+/// other roots sharing the cache are left alone.
+pub fn check_integrity(
+    items: &[ImageItem],
+    cache: &mut IntegrityCache,
+    repair: bool,
+    full: bool,
+    progress: Option<&dyn ProgressObserver>,
+) -> Result<IntegrityReport, BooruError> {
+    let recorded = cache.digests()?;
+    let results = items
+        .par_iter()
+        .map(|item| {
+            let path = &item.image_path;
+            let result = FileFingerprint::from_path(path).and_then(|fingerprint| {
+                let unchanged = recorded
+                    .get(path.to_string_lossy().as_ref())
+                    .filter(|(previous, _)| {
+                        !full
+                            && previous.mtime == fingerprint.mtime
+                            && previous.size == fingerprint.size
+                    })
+                    .map(|(_, digest)| digest.clone());
+                let digest = match unchanged {
+                    Some(digest) => digest,
+                    None => sha256_file(path)?,
+                };
+                Ok((fingerprint, digest))
+            });
+            if let Some(observer) = progress {
+                observer.inc(1);
+            }
+            (path, result)
+        })
+        .collect::<Vec<_>>();
+
+    let mut report = IntegrityReport::default();
+    let mut stores = Vec::new();
+    let mut checked = HashSet::new();
+    for (path, result) in results {
+        let key = path.to_string_lossy().into_owned();
+        checked.insert(key.clone());
+        let (fingerprint, digest) = match result {
+            Ok(hashed) => hashed,
+            Err(err) => {
+                report.warnings.push(IntegrityWarning {
+                    path: path.clone(),
+                    message: format!("{err}"),
+                });
+                continue;
+            }
+        };
+        match recorded.get(&key) {
+            Some((_, previous)) if *previous == digest => {
+                report.unchanged += 1;
+                continue;
+            }
+            Some(_) => {
+                report.changed.push(path.clone());
+                if !repair {
+                    continue;
+                }
+            }
+            None => report.new.push(path.clone()),
+        }
+        stores.push((key, fingerprint, digest));
+    }
+
+    let mut removals = Vec::new();
+    for key in recorded.keys() {
+        let path = Path::new(key);
+        if !checked.contains(key) && !path.exists() {
+            report.missing.push(path.to_path_buf());
+            if repair {
+                removals.push(key.clone());
+            }
+        }
+    }
+
+    cache.write(&stores, &removals)?;
+    report.changed.sort();
+    report.new.sort();
+    report.missing.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::metadata::BooruEdits;
+
+    fn item_at(image_path: PathBuf) -> ImageItem {
+        let meta_path = image_path.with_extension("jpg.json");
+        let booru_path = image_path.with_extension("jpg.booru.json");
+        ImageItem::new(
+            image_path,
+            meta_path,
+            booru_path,
+            json!({}),
+            BooruEdits::default(),
+        )
+    }
+
+    #[test]
+    fn sha256_matches_known_digest() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-sha256-{unique}"));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("abc.jpg");
+        fs::write(&path, "abc").unwrap();
+
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_new_changed_and_missing_until_repaired() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-integrity-{unique}"));
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.jpg"), dir.join("b.jpg"));
+        fs::write(&a, "one").unwrap();
+        fs::write(&b, "two").unwrap();
+        let mut cache = IntegrityCache::open(&dir.join("cache.sqlite")).unwrap();
+        let items = vec![item_at(a.clone()), item_at(b.clone())];
+
+        let report = check_integrity(&items, &mut cache, false, true, None).unwrap();
+        assert_eq!(report.new, vec![a.clone(), b.clone()]);
+        assert!(report.changed.is_empty() && report.missing.is_empty());
+
+        fs::write(&a, "uno").unwrap();
+        fs::remove_file(&b).unwrap();
+        let items = vec![item_at(a.clone())];
+        for _ in 0..2 {
+            let report = check_integrity(&items, &mut cache, false, true, None).unwrap();
+            assert_eq!(report.changed, vec![a.clone()]);
+            assert_eq!(report.missing, vec![b.clone()]);
+            assert!(report.new.is_empty());
+        }
+
+        let report = check_integrity(&items, &mut cache, true, true, None).unwrap();
+        assert_eq!(report.changed, vec![a.clone()]);
+        assert_eq!(report.missing, vec![b.clone()]);
+
+        let report = check_integrity(&items, &mut cache, false, true, None).unwrap();
+        assert!(report.changed.is_empty() && report.missing.is_empty());
+        assert_eq!(report.unchanged, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unchanged_fingerprints_skip_hashing_unless_full() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-integrity-skip-{unique}"));
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.jpg");
+        fs::write(&a, "one").unwrap();
+        let mut cache = IntegrityCache::open(&dir.join("cache.sqlite")).unwrap();
+        let items = vec![item_at(a.clone())];
+        check_integrity(&items, &mut cache, false, false, None).unwrap();
+
+        // Stands in for content that changed behind the same size and mtime.
+        cache
+            .conn
+            .execute("UPDATE integrity_cache SET sha256 = 'stale'", [])
+            .unwrap();
+        let report = check_integrity(&items, &mut cache, false, false, None).unwrap();
+        assert_eq!(report.unchanged, 1);
+        let report = check_integrity(&items, &mut cache, false, true, None).unwrap();
+        assert_eq!(report.changed, vec![a.clone()]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
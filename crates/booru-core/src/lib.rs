@@ -1,32 +1,69 @@
 pub mod alias;
 pub mod config;
+pub mod cooccurrence;
+pub mod date;
 pub mod edit;
 pub mod error;
+#[cfg(feature = "exif")]
+pub mod exif;
 pub mod hash;
+pub mod integrity;
+pub mod media;
 pub mod metadata;
 pub mod path;
+pub mod progress;
 pub mod scan;
+pub mod validate;
 
+#[cfg(feature = "exif")]
+pub use crate::exif::extract_exif_metadata;
 pub use alias::{
-    alias_map_from_groups, alias_path_for_root, expand_search_terms_with_aliases,
-    load_alias_groups_from_path, load_alias_groups_from_root, load_alias_map_from_roots,
-    merge_alias_terms, normalize_alias_groups, normalize_search_term, normalize_search_terms,
-    remove_alias_terms, save_alias_groups_to_path, save_alias_groups_to_root, AliasGroups,
-    AliasMap, AliasWarning, ALIAS_FILE_NAME,
-};
-pub use config::BooruConfig;
-pub use edit::apply_update_to_image;
-pub use error::BooruError;
+    alias_groups_from_map, alias_map_from_groups, alias_path_for_root, canonicalize_tags,
+    expand_search_terms_with_aliases, find_alias_conflicts, import_alias_groups,
+    load_alias_groups_as_written, load_alias_groups_from_path, load_alias_groups_from_root,
+    load_alias_map_from_roots, merge_alias_terms, normalize_alias_groups, normalize_search_term,
+    normalize_search_terms, remove_alias_terms, rename_alias_term, save_alias_groups_to_path,
+    save_alias_groups_to_root, AliasConflict, AliasGroups, AliasMap, AliasWarning, ALIAS_FILE_NAME,
+};
+pub use config::{
+    BooruConfig, ConfigFile, CONFIG_FILE_NAME, DEFAULT_VIDEO_EXTENSIONS, USER_CONFIG_FILE_NAME,
+};
+pub use cooccurrence::{compute_tag_cooccurrence, related_tags};
+pub use date::parse_date;
+pub use edit::{
+    apply_update_to_image, apply_update_to_image_dry_run, apply_update_to_image_with_backup,
+    apply_update_to_image_with_previous, restore_edits_for_image,
+};
+pub use error::{BooruError, EditError};
 pub use hash::{
-    compute_hashes_with_cache, find_duplicates, find_duplicates_with_cache, group_duplicates,
-    DuplicateGroup, DuplicateReport, FileFingerprint, FuzzyHashAlgorithm, HashCache,
-    HashComputation, ProgressObserver,
+    build_hash_index, compute_hashes_with_cache, find_duplicates, find_duplicates_with_cache,
+    find_similar_to, group_duplicates, similar_to, DuplicateGroup, DuplicateReport,
+    DuplicateWarning, FileFingerprint, FuzzyHash, FuzzyHashAlgorithm, FuzzyHashMismatch, HashCache,
+    HashComputation, HashIndex, HashKind, ParseFuzzyHashError, SimilarMatch, SimilarReport,
+    HASH_CACHE_SCHEMA_VERSION, HASH_SIZES,
 };
-pub use metadata::{extract_string_field, extract_tags, BooruEdits, EditUpdate, TagEdits};
+pub use integrity::{
+    check_integrity, sha256_file, IntegrityCache, IntegrityReport, IntegrityWarning,
+};
+#[cfg(feature = "heic")]
+pub use media::convert_foreign_image;
+#[cfg(feature = "ffmpeg")]
+pub use media::extract_video_frame;
+pub use media::{average_color, dominant_color, ForeignImageFormat, MediaKind};
+pub use metadata::{extract_string_field, extract_tags, BooruEdits, EditUpdate, Rating, TagEdits};
 pub use path::{
     booru_path_for_image, metadata_path_for_image, normalize_image_path, resolve_image_path,
 };
+pub use progress::ProgressObserver;
 pub use scan::{
-    item_matches_search_terms, scan_roots, ImageItem, Index, Library, ScanReport, ScanWarning,
-    SearchQuery, SearchResult, SearchSort,
+    author_frequency, find_orphan_metadata, item_matches_search_terms, parse_search_term,
+    scan_orphans, scan_roots, scan_roots_excluding, split_excluded_terms, tag_completions,
+    tag_frequency, ImageItem, Index, Library, LibraryStats, LibraryWatcher, RescanReport,
+    ScanReport, ScanWarning, SearchMode, SearchQuery, SearchResult, SearchSort, SearchTerm,
+    SensitiveFilter, TagCompletionIndex, TagPattern, IGNORE_FILE_NAME, RESOLUTION_PROBE_WARN_LIMIT,
+    WATCH_DEBOUNCE,
+};
+pub use validate::{
+    alias_group_is_undersized, date_is_unparseable, edits_are_contradictory, image_is_missing,
+    json_error, validate_library, ValidationIssue,
 };
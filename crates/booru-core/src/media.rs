@@ -0,0 +1,294 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+#[cfg(any(feature = "ffmpeg", feature = "heic"))]
+use std::process::{Command, Stdio};
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+use crate::error::BooruError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Video,
+}
+
+impl MediaKind {
+    /// Classifies by extension only, against a list like
+    /// [`BooruConfig::video_extensions`](crate::BooruConfig::video_extensions);
+    /// the file is not opened.
+    pub fn from_path<S: AsRef<str>>(path: &Path, video_extensions: &[S]) -> Self {
+        let is_video = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                video_extensions
+                    .iter()
+                    .any(|video| ext.eq_ignore_ascii_case(video.as_ref()))
+            });
+        if is_video {
+            Self::Video
+        } else {
+            Self::Image
+        }
+    }
+}
+
+/// Average colour of the image shrunk to 4×4, for a flat placeholder while
+/// the real image loads.
+pub fn dominant_color(path: &Path) -> Result<[u8; 3], BooruError> {
+    let image = image::open(path).map_err(|source| BooruError::Image {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(average_color(&image))
+}
+
+/// [`dominant_color`] of an already decoded image.
+pub fn average_color(image: &DynamicImage) -> [u8; 3] {
+    let small = image.resize_exact(4, 4, FilterType::Triangle).to_rgb8();
+    let mut sums = [0u32; 3];
+    for pixel in small.pixels() {
+        for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+            *sum += u32::from(channel);
+        }
+    }
+    let count = small.width() * small.height();
+    sums.map(|sum| ((sum + count / 2) / count) as u8)
+}
+
+/// Still-image formats that neither gdk-pixbuf nor the `image` crate decode
+/// in their default builds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForeignImageFormat {
+    Heic,
+    Avif,
+    JpegXl,
+}
+
+impl ForeignImageFormat {
+    /// Sniffs the first bytes of the file, so a HEIC saved as `.jpg` is still
+    /// caught. `None` for anything else, including unreadable files.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let mut header = Vec::with_capacity(16);
+        File::open(path)
+            .ok()?
+            .take(16)
+            .read_to_end(&mut header)
+            .ok()?;
+        Self::from_magic(&header)
+    }
+
+    fn from_magic(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0xff, 0x0a]) || header.starts_with(b"\0\0\0\x0cJXL \r\n\x87\n") {
+            return Some(Self::JpegXl);
+        }
+        // ISO base media file: a box size, `ftyp`, then the major brand.
+        if header.get(4..8) != Some(b"ftyp") {
+            return None;
+        }
+        match header.get(8..12)? {
+            b"avif" | b"avis" => Some(Self::Avif),
+            b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" | b"mif1" | b"msf1" => {
+                Some(Self::Heic)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Heic => "HEIC",
+            Self::Avif => "AVIF",
+            Self::JpegXl => "JPEG XL",
+        }
+    }
+}
+
+/// Converts a [`ForeignImageFormat`] file to PNG bytes with ImageMagick,
+/// trying `magick` before the older `convert` name. Only the first frame
+/// or image of the file is kept.
+#[cfg(feature = "heic")]
+pub fn convert_foreign_image(
+    path: &Path,
+    format: ForeignImageFormat,
+) -> Result<Vec<u8>, BooruError> {
+    let error = |message: String| BooruError::UnsupportedImage {
+        path: path.to_path_buf(),
+        message,
+    };
+    let mut input = path.as_os_str().to_owned();
+    input.push("[0]");
+    for program in ["magick", "convert"] {
+        let output = match Command::new(program)
+            .arg(&input)
+            .arg("png:-")
+            .stdin(Stdio::null())
+            .output()
+        {
+            Ok(output) => output,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(error(format!("failed to run {program}: {err}"))),
+        };
+        if !output.status.success() || output.stdout.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(error(if stderr.is_empty() {
+                format!("ImageMagick could not convert this {} image", format.name())
+            } else {
+                stderr
+            }));
+        }
+        return Ok(output.stdout);
+    }
+    Err(error(format!(
+        "{} images need ImageMagick (`magick` or `convert` on PATH)",
+        format.name()
+    )))
+}
+
+/// Decodes the first frame of a video with the `ffmpeg` binary on `PATH`, or
+/// the one named by `LIGHTBOORU_FFMPEG`, and returns it as PNG bytes.
+#[cfg(feature = "ffmpeg")]
+pub fn extract_video_frame(path: &Path) -> Result<Vec<u8>, BooruError> {
+    let ffmpeg = std::env::var_os(FFMPEG_ENV_VAR).unwrap_or_else(|| "ffmpeg".into());
+    extract_video_frame_with(Path::new(&ffmpeg), path)
+}
+
+#[cfg(feature = "ffmpeg")]
+pub(crate) const FFMPEG_ENV_VAR: &str = "LIGHTBOORU_FFMPEG";
+
+#[cfg(feature = "ffmpeg")]
+pub(crate) fn extract_video_frame_with(ffmpeg: &Path, path: &Path) -> Result<Vec<u8>, BooruError> {
+    let output = Command::new(ffmpeg)
+        .args(["-nostdin", "-v", "error", "-i"])
+        .arg(path)
+        .args(["-frames:v", "1", "-f", "image2pipe", "-c:v", "png", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| BooruError::Video {
+            path: path.to_path_buf(),
+            message: format!("failed to run ffmpeg: {err}"),
+        })?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(BooruError::Video {
+            path: path.to_path_buf(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    use super::{average_color, dominant_color, ForeignImageFormat, MediaKind};
+    use crate::config::DEFAULT_VIDEO_EXTENSIONS;
+    use crate::error::BooruError;
+
+    #[test]
+    fn media_kind_follows_extension() {
+        let kind = |path: &str| MediaKind::from_path(Path::new(path), DEFAULT_VIDEO_EXTENSIONS);
+        assert_eq!(kind("a/1.mp4"), MediaKind::Video);
+        assert_eq!(kind("a/1.WebM"), MediaKind::Video);
+        assert_eq!(kind("a/1.gif"), MediaKind::Image);
+        assert_eq!(kind("a/noext"), MediaKind::Image);
+        assert_eq!(kind("a/1.avi"), MediaKind::Image);
+        assert_eq!(
+            MediaKind::from_path(Path::new("a/1.AVI"), &["avi".to_string()]),
+            MediaKind::Video
+        );
+    }
+
+    #[test]
+    fn average_color_of_solid_and_split_images() {
+        let solid = RgbImage::from_pixel(10, 6, Rgb([12, 200, 90]));
+        assert_eq!(
+            average_color(&DynamicImage::ImageRgb8(solid)),
+            [12, 200, 90]
+        );
+
+        let split = RgbImage::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        });
+        assert_eq!(
+            average_color(&DynamicImage::ImageRgb8(split)),
+            [128, 0, 128]
+        );
+    }
+
+    #[test]
+    fn dominant_color_reads_the_file() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-color-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("grey.png");
+        RgbImage::from_pixel(3, 3, Rgb([40, 40, 40]))
+            .save(&path)
+            .unwrap();
+
+        assert_eq!(dominant_color(&path).unwrap(), [40, 40, 40]);
+        assert!(matches!(
+            dominant_color(&dir.join("missing.png")),
+            Err(BooruError::Image { .. })
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn foreign_formats_are_recognised_by_magic_bytes() {
+        let sniff = ForeignImageFormat::from_magic;
+        assert_eq!(
+            sniff(b"\0\0\0\x18ftypheic\0\0\0\0"),
+            Some(ForeignImageFormat::Heic)
+        );
+        assert_eq!(
+            sniff(b"\0\0\0\x1cftypavif\0\0\0\0"),
+            Some(ForeignImageFormat::Avif)
+        );
+        assert_eq!(sniff(&[0xff, 0x0a, 0xfa]), Some(ForeignImageFormat::JpegXl));
+        assert_eq!(
+            sniff(b"\0\0\0\x0cJXL \r\n\x87\n\0\0"),
+            Some(ForeignImageFormat::JpegXl)
+        );
+        assert_eq!(sniff(b"\0\0\0\x18ftypisom\0\0\0\0"), None);
+        assert_eq!(sniff(&[0xff, 0xd8, 0xff, 0xe0]), None);
+        assert_eq!(sniff(b""), None);
+    }
+
+    #[test]
+    fn detection_ignores_the_extension() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-sniff-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let disguised = dir.join("photo.jpg");
+        std::fs::write(&disguised, b"\0\0\0\x18ftypheic\0\0\0\0mif1").unwrap();
+        let named = dir.join("photo.heic");
+        std::fs::write(&named, [0xff, 0xd8, 0xff, 0xe0]).unwrap();
+
+        assert_eq!(
+            ForeignImageFormat::detect(&disguised),
+            Some(ForeignImageFormat::Heic)
+        );
+        assert_eq!(ForeignImageFormat::detect(&named), None);
+        assert_eq!(ForeignImageFormat::detect(&dir.join("missing.heic")), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,14 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use unicode_normalization::UnicodeNormalization;
 
-use crate::error::BooruError;
+use crate::error::{BooruError, EditError};
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(default)]
@@ -15,16 +18,74 @@ pub struct TagEdits {
     pub remove: Vec<String>,
 }
 
+/// Content rating on the danbooru scale. `Sensitive` is danbooru's mild
+/// level and does not count as sensitive; see [`Rating::is_sensitive`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    General,
+    Sensitive,
+    Questionable,
+    Explicit,
+}
+
+impl Rating {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::General => "general",
+            Self::Sensitive => "sensitive",
+            Self::Questionable => "questionable",
+            Self::Explicit => "explicit",
+        }
+    }
+
+    pub fn is_sensitive(self) -> bool {
+        matches!(self, Self::Questionable | Self::Explicit)
+    }
+}
+
+/// Accepts the full names and the single-letter booru codes (`g`/`s`/`q`/`e`).
+impl FromStr for Rating {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "g" | "general" | "safe" => Ok(Self::General),
+            "s" | "sensitive" => Ok(Self::Sensitive),
+            "q" | "questionable" => Ok(Self::Questionable),
+            "e" | "explicit" => Ok(Self::Explicit),
+            other => Err(format!("unknown rating: {other}")),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct BooruEdits {
     pub tags: TagEdits,
     pub notes: Option<String>,
     pub sensitive: Option<bool>,
+    pub rating: Option<Rating>,
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
 }
 
+/// A batch of edits applied by [`BooruEdits::apply_update`].
+///
+/// Tag fields are applied in a fixed order: `clear_tags`, then `set_tags`,
+/// then `add_tags`/`remove_tags` on top of the result. `clear_tags` together
+/// with `set_tags`, or the same tag in both `add_tags` and `remove_tags`, is
+/// ambiguous and rejected by [`EditUpdate::validate`].
+///
+/// `clear_notes` removes the notes field, while `notes: Some("")` keeps it
+/// empty. Passing both is rejected like `clear_tags` with `set_tags`.
+///
+/// Setting `rating` without `sensitive` drops any earlier sensitive override,
+/// so the new rating decides [`crate::ImageItem::merged_sensitive`].
+///
+/// `set_fields` and `remove_fields` write to [`BooruEdits::extra`]. The
+/// built-in field names are refused there, since they would collide once
+/// flattened into the same JSON object.
 #[derive(Clone, Debug, Default)]
 pub struct EditUpdate {
     pub set_tags: Option<Vec<String>>,
@@ -32,7 +93,101 @@ pub struct EditUpdate {
     pub remove_tags: Vec<String>,
     pub clear_tags: bool,
     pub notes: Option<String>,
+    pub clear_notes: bool,
     pub sensitive: Option<bool>,
+    pub rating: Option<Rating>,
+    pub set_fields: Vec<(String, Value)>,
+    pub remove_fields: Vec<String>,
+}
+
+/// Keys of [`BooruEdits`] itself, which extra fields must not shadow.
+const BUILTIN_EDIT_FIELDS: &[&str] = &["tags", "notes", "sensitive", "rating"];
+
+impl EditUpdate {
+    pub fn validate(&self) -> Result<(), EditError> {
+        if self.clear_tags && self.set_tags.is_some() {
+            return Err(EditError::ClearAndSetTags);
+        }
+        if self.clear_notes && self.notes.is_some() {
+            return Err(EditError::ClearAndSetNotes);
+        }
+
+        let remove_set = tag_keys(&normalize_tags(self.remove_tags.clone()));
+        if let Some(tag) = normalize_tags(self.add_tags.clone())
+            .into_iter()
+            .find(|tag| remove_set.contains(&tag_key(tag)))
+        {
+            return Err(EditError::TagAddedAndRemoved(tag));
+        }
+
+        let mut set_keys = self.set_fields.iter().map(|(key, _)| key);
+        if let Some(key) = set_keys
+            .clone()
+            .chain(&self.remove_fields)
+            .find(|key| BUILTIN_EDIT_FIELDS.contains(&key.as_str()))
+        {
+            return Err(EditError::ReservedField(key.clone()));
+        }
+        if let Some(key) = set_keys.find(|key| self.remove_fields.contains(key)) {
+            return Err(EditError::FieldSetAndRemoved(key.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+/// `<path>.<suffix>`, in the same directory so a rename stays on one
+/// filesystem.
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Writes `<path>.tmp` and renames it over `path`, so readers never see a
+/// half-written file.
+pub(crate) fn write_atomically(path: &Path, data: &[u8]) -> Result<(), BooruError> {
+    let tmp_path = sibling_path(path, "tmp");
+    let write = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()
+    };
+    if let Err(source) = write() {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(BooruError::Io {
+            path: tmp_path,
+            source,
+        });
+    }
+    replace_file(&tmp_path, path).map_err(|source| {
+        // After the Windows fallback has removed the old file, the temporary
+        // file holds the only copy left, so keep it and point at it.
+        if cfg!(windows) && !path.exists() {
+            return BooruError::Io {
+                path: tmp_path.clone(),
+                source,
+            };
+        }
+        let _ = fs::remove_file(&tmp_path);
+        BooruError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    })
+}
+
+fn replace_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match fs::rename(from, to) {
+        // Windows refuses to rename over a file that is held open without
+        // delete sharing; retry once the old file is gone.
+        Err(_) if cfg!(windows) && to.exists() => {
+            fs::remove_file(to)?;
+            fs::rename(from, to)
+        }
+        result => result,
+    }
 }
 
 impl BooruEdits {
@@ -64,10 +219,20 @@ impl BooruEdits {
             path: path.to_path_buf(),
             source,
         })?;
-        fs::write(path, data).map_err(|source| BooruError::Io {
-            path: path.to_path_buf(),
-            source,
-        })
+        write_atomically(path, &data)
+    }
+
+    /// Like [`BooruEdits::save`], but first copies an existing file at `path`
+    /// to `<path>.bak`.
+    pub fn save_with_backup(&self, path: &Path) -> Result<(), BooruError> {
+        if path.is_file() {
+            let backup = sibling_path(path, "bak");
+            fs::copy(path, &backup).map_err(|source| BooruError::Io {
+                path: backup,
+                source,
+            })?;
+        }
+        self.save(path)
     }
 
     pub fn apply_update(&mut self, update: EditUpdate) {
@@ -89,33 +254,48 @@ impl BooruEdits {
 
             match &mut self.tags.set {
                 Some(current) => {
-                    let mut set = to_ordered_set(current.clone());
+                    let mut set = tag_keys(current);
                     for tag in add_tags {
-                        if set.insert(tag.clone()) {
+                        if set.insert(tag_key(&tag)) {
                             current.push(tag);
                         }
                     }
                     if !remove_tags.is_empty() {
-                        let remove_set: HashSet<String> = remove_tags.iter().cloned().collect();
-                        current.retain(|tag| !remove_set.contains(tag));
+                        let remove_set = tag_keys(&remove_tags);
+                        current.retain(|tag| !remove_set.contains(&tag_key(tag)));
                     }
                 }
                 None => {
                     self.tags.add = merge_tag_list(self.tags.add.clone(), add_tags);
                     self.tags.remove = merge_tag_list(self.tags.remove.clone(), remove_tags);
-                    let remove_set: HashSet<String> = self.tags.remove.iter().cloned().collect();
-                    self.tags.add.retain(|tag| !remove_set.contains(tag));
+                    let remove_set = tag_keys(&self.tags.remove);
+                    self.tags
+                        .add
+                        .retain(|tag| !remove_set.contains(&tag_key(tag)));
                 }
             }
         }
 
+        if update.clear_notes {
+            self.notes = None;
+        }
         if let Some(notes) = update.notes {
             self.notes = Some(notes);
         }
 
+        if let Some(rating) = update.rating {
+            self.rating = Some(rating);
+            self.sensitive = None;
+        }
+
         if let Some(sensitive) = update.sensitive {
             self.sensitive = Some(sensitive);
         }
+
+        for key in &update.remove_fields {
+            self.extra.remove(key);
+        }
+        self.extra.extend(update.set_fields);
     }
 
     pub fn merged_tags(&self, original_tags: &[String]) -> Vec<String> {
@@ -124,10 +304,11 @@ impl BooruEdits {
         }
 
         let mut tags = normalize_tags(original_tags.to_vec());
-        let remove_set: HashSet<String> = self.tags.remove.iter().cloned().collect();
-        tags.retain(|tag| !remove_set.contains(tag));
+        let remove_set = tag_keys(&self.tags.remove);
+        tags.retain(|tag| !remove_set.contains(&tag_key(tag)));
+        let mut seen = tag_keys(&tags);
         for tag in &self.tags.add {
-            if !tags.contains(tag) {
+            if seen.insert(tag_key(tag)) {
                 tags.push(tag.clone());
             }
         }
@@ -288,20 +469,26 @@ fn parse_bool_string(s: &str) -> Option<bool> {
     }
 }
 
+/// A string with a comma is a delimited list and splits on commas,
+/// semicolons, pipes and whitespace alike, so `"a, b c"` yields three tags.
+/// Otherwise whitespace wins: a space-joined Danbooru `tag_string` keeps
+/// tags such as `;d` or `:|` whole, and only a single word like `"a|b"` is
+/// split on semicolons and pipes. Multi-word tags need underscores
+/// (`blue_sky`) to survive as one tag.
 fn split_tag_string(input: &str) -> Vec<String> {
-    if input.contains(',') {
-        input
-            .split(',')
-            .map(|tag| tag.trim().to_string())
-            .filter(|tag| !tag.is_empty())
-            .collect()
+    let separators: fn(char) -> bool = if input.contains(',') {
+        |c| matches!(c, ',' | ';' | '|') || c.is_whitespace()
+    } else if input.trim().contains(char::is_whitespace) {
+        char::is_whitespace
     } else {
-        input
-            .split_whitespace()
-            .map(|tag| tag.trim().to_string())
-            .filter(|tag| !tag.is_empty())
-            .collect()
-    }
+        |c| matches!(c, ';' | '|')
+    };
+    input
+        .split(separators)
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
 fn push_tag(tag: &str, tags: &mut Vec<String>, seen: &mut HashSet<String>) {
@@ -314,6 +501,8 @@ fn push_tag(tag: &str, tags: &mut Vec<String>, seen: &mut HashSet<String>) {
     }
 }
 
+/// Trims and NFC-normalizes each tag, then drops later duplicates that only
+/// differ in case. The first spelling seen is the one kept.
 fn normalize_tags(tags: Vec<String>) -> Vec<String> {
     let mut normalized = Vec::new();
     let mut seen = HashSet::new();
@@ -322,39 +511,48 @@ fn normalize_tags(tags: Vec<String>) -> Vec<String> {
         if tag.is_empty() {
             continue;
         }
-        if seen.insert(tag.to_string()) {
-            normalized.push(tag.to_string());
+        let tag = tag.nfc().collect::<String>();
+        if seen.insert(tag.to_lowercase()) {
+            normalized.push(tag);
         }
     }
     normalized
 }
 
+/// What two tags must share to count as the same tag.
+fn tag_key(tag: &str) -> String {
+    tag.nfc().collect::<String>().to_lowercase()
+}
+
+fn tag_keys(tags: &[String]) -> HashSet<String> {
+    tags.iter().map(|tag| tag_key(tag)).collect()
+}
+
 fn merge_tag_list(mut current: Vec<String>, incoming: Vec<String>) -> Vec<String> {
-    let mut seen = to_ordered_set(current.clone());
+    let mut seen = tag_keys(&current);
     for tag in incoming {
-        if seen.insert(tag.clone()) {
+        if seen.insert(tag_key(&tag)) {
             current.push(tag);
         }
     }
     current
 }
 
-fn to_ordered_set(tags: Vec<String>) -> HashSet<String> {
-    let mut seen = HashSet::new();
-    for tag in tags {
-        seen.insert(tag);
-    }
-    seen
-}
-
 #[cfg(test)]
 mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
     use serde_json::json;
 
     use super::{
         extract_bool_field, extract_nested_scalar_field, extract_scalar_field,
-        extract_string_field, extract_tags,
+        extract_string_field, extract_tags, split_tag_string, BooruEdits, EditUpdate, Rating,
     };
+    use crate::error::EditError;
+
+    fn tags(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
 
     #[test]
     fn extract_string_field_ignores_empty() {
@@ -402,6 +600,27 @@ mod tests {
         assert_eq!(extract_bool_field(&value, &["nsfw"]), Some(false));
     }
 
+    #[test]
+    fn tag_strings_split_on_mixed_separators() {
+        assert_eq!(split_tag_string("a, b c"), vec!["a", "b", "c"]);
+        assert_eq!(split_tag_string("a|b"), vec!["a", "b"]);
+        assert_eq!(split_tag_string(" x;y_z ,, "), vec!["x", "y_z"]);
+        assert_eq!(split_tag_string(" a;b "), vec!["a", "b"]);
+        assert_eq!(
+            extract_tags(&json!({"tag_string": "blue_sky, cloud|sea"})),
+            vec!["blue_sky", "cloud", "sea"]
+        );
+    }
+
+    #[test]
+    fn space_joined_tag_strings_keep_punctuation_tags() {
+        assert_eq!(split_tag_string("smile ;d :|"), vec!["smile", ";d", ":|"]);
+        assert_eq!(
+            extract_tags(&json!({"tag_string": "|_| ;) 1girl"})),
+            vec!["|_|", ";)", "1girl"]
+        );
+    }
+
     #[test]
     fn extract_tags_reads_twitter_hashtags() {
         let value = json!({
@@ -415,4 +634,244 @@ mod tests {
             vec!["理由もなく再掲していいタグ", "シェリハン"]
         );
     }
+
+    #[test]
+    fn validate_rejects_clear_with_set_tags() {
+        let update = EditUpdate {
+            set_tags: Some(tags(&["a"])),
+            clear_tags: true,
+            ..EditUpdate::default()
+        };
+        assert_eq!(update.validate(), Err(EditError::ClearAndSetTags));
+    }
+
+    #[test]
+    fn validate_rejects_tag_both_added_and_removed() {
+        let update = EditUpdate {
+            add_tags: tags(&["a", " b "]),
+            remove_tags: tags(&["b"]),
+            ..EditUpdate::default()
+        };
+        assert_eq!(
+            update.validate(),
+            Err(EditError::TagAddedAndRemoved("b".to_string()))
+        );
+    }
+
+    #[test]
+    fn clear_with_add_tags_clears_then_adds() {
+        let update = EditUpdate {
+            add_tags: tags(&["c"]),
+            clear_tags: true,
+            ..EditUpdate::default()
+        };
+        assert_eq!(update.validate(), Ok(()));
+
+        let mut edits = BooruEdits::default();
+        edits.apply_update(update);
+        assert_eq!(edits.merged_tags(&tags(&["a", "b"])), tags(&["c"]));
+    }
+
+    #[test]
+    fn set_with_add_and_remove_applies_on_top_of_set() {
+        let update = EditUpdate {
+            set_tags: Some(tags(&["a", "b"])),
+            add_tags: tags(&["c"]),
+            remove_tags: tags(&["a"]),
+            ..EditUpdate::default()
+        };
+        assert_eq!(update.validate(), Ok(()));
+
+        let mut edits = BooruEdits::default();
+        edits.apply_update(update);
+        assert_eq!(edits.merged_tags(&tags(&["x"])), tags(&["b", "c"]));
+    }
+
+    #[test]
+    fn notes_are_kept_set_emptied_or_cleared() {
+        let with_notes = || BooruEdits {
+            notes: Some("todo".to_string()),
+            ..BooruEdits::default()
+        };
+
+        let mut edits = with_notes();
+        edits.apply_update(EditUpdate::default());
+        assert_eq!(edits.notes.as_deref(), Some("todo"));
+
+        let mut edits = with_notes();
+        edits.apply_update(EditUpdate {
+            notes: Some("done".to_string()),
+            ..EditUpdate::default()
+        });
+        assert_eq!(edits.notes.as_deref(), Some("done"));
+
+        let mut edits = with_notes();
+        edits.apply_update(EditUpdate {
+            notes: Some(String::new()),
+            ..EditUpdate::default()
+        });
+        assert_eq!(edits.notes.as_deref(), Some(""));
+
+        let mut edits = with_notes();
+        edits.apply_update(EditUpdate {
+            clear_notes: true,
+            ..EditUpdate::default()
+        });
+        assert_eq!(edits.notes, None);
+        let saved = serde_json::to_value(&edits).unwrap();
+        assert!(saved["notes"].is_null());
+    }
+
+    #[test]
+    fn validate_rejects_clear_with_set_notes() {
+        let update = EditUpdate {
+            notes: Some(String::new()),
+            clear_notes: true,
+            ..EditUpdate::default()
+        };
+        assert_eq!(update.validate(), Err(EditError::ClearAndSetNotes));
+    }
+
+    #[test]
+    fn merged_tags_drop_case_variants_keeping_the_first_spelling() {
+        let original = ["Flower", "sky", "flower", "SKY"].map(String::from);
+        let edits = BooruEdits::default();
+        assert_eq!(edits.merged_tags(&original), vec!["Flower", "sky"]);
+
+        let mut edits = BooruEdits::default();
+        edits.apply_update(EditUpdate {
+            add_tags: vec!["Sea".to_string(), "FLOWER".to_string()],
+            remove_tags: vec!["SKY".to_string()],
+            ..EditUpdate::default()
+        });
+        assert_eq!(edits.merged_tags(&original), vec!["Flower", "Sea"]);
+    }
+
+    #[test]
+    fn nfc_and_nfd_spellings_are_one_tag() {
+        // "が" precomposed, then as "か" plus a combining voiced mark.
+        let nfc = "\u{304c}".to_string();
+        let nfd = "\u{304b}\u{3099}".to_string();
+        let mut edits = BooruEdits::default();
+        edits.apply_update(EditUpdate {
+            set_tags: Some(vec![nfd.clone(), nfc.clone()]),
+            ..EditUpdate::default()
+        });
+        assert_eq!(edits.tags.set, Some(vec![nfc.clone()]));
+        assert_eq!(edits.merged_tags(&[]), vec![nfc]);
+    }
+
+    #[test]
+    fn extra_fields_are_set_overwritten_and_removed() {
+        let mut edits = BooruEdits::default();
+        edits.apply_update(EditUpdate {
+            set_fields: vec![
+                ("collection".to_string(), json!("favorites")),
+                ("score".to_string(), json!(3)),
+            ],
+            ..EditUpdate::default()
+        });
+        let saved = serde_json::to_value(&edits).unwrap();
+        assert_eq!(saved["collection"], json!("favorites"));
+        let mut edits: BooruEdits = serde_json::from_value(saved).unwrap();
+        assert_eq!(edits.extra["score"], json!(3));
+
+        edits.apply_update(EditUpdate {
+            set_fields: vec![("collection".to_string(), json!(["a", "b"]))],
+            remove_fields: vec!["score".to_string()],
+            ..EditUpdate::default()
+        });
+        assert_eq!(edits.extra.len(), 1);
+        assert_eq!(edits.extra["collection"], json!(["a", "b"]));
+
+        edits.apply_update(EditUpdate {
+            remove_fields: vec!["collection".to_string(), "missing".to_string()],
+            ..EditUpdate::default()
+        });
+        assert!(edits.extra.is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_builtin_and_conflicting_fields() {
+        let update = EditUpdate {
+            set_fields: vec![("notes".to_string(), json!("x"))],
+            ..EditUpdate::default()
+        };
+        assert_eq!(
+            update.validate(),
+            Err(EditError::ReservedField("notes".to_string()))
+        );
+        let update = EditUpdate {
+            set_fields: vec![("collection".to_string(), json!("x"))],
+            remove_fields: vec!["collection".to_string()],
+            ..EditUpdate::default()
+        };
+        assert_eq!(
+            update.validate(),
+            Err(EditError::FieldSetAndRemoved("collection".to_string()))
+        );
+    }
+
+    #[test]
+    fn rating_parses_codes_and_names() {
+        assert_eq!("q".parse::<Rating>(), Ok(Rating::Questionable));
+        assert_eq!(" Explicit ".parse::<Rating>(), Ok(Rating::Explicit));
+        assert_eq!("safe".parse::<Rating>(), Ok(Rating::General));
+        assert!("x".parse::<Rating>().is_err());
+        assert!(!Rating::Sensitive.is_sensitive());
+        assert!(Rating::Questionable.is_sensitive());
+    }
+
+    #[test]
+    fn rating_update_replaces_sensitive_override() {
+        let mut edits = BooruEdits {
+            sensitive: Some(false),
+            ..BooruEdits::default()
+        };
+        edits.apply_update(EditUpdate {
+            rating: Some(Rating::Explicit),
+            ..EditUpdate::default()
+        });
+        assert_eq!(edits.rating, Some(Rating::Explicit));
+        assert_eq!(edits.sensitive, None);
+        assert_eq!(
+            serde_json::to_value(&edits).unwrap()["rating"],
+            serde_json::json!("explicit")
+        );
+    }
+
+    #[test]
+    fn failed_save_leaves_previous_file_intact() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-save-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.jpg.booru.json");
+        let mut edits = BooruEdits {
+            notes: Some("first".to_string()),
+            ..BooruEdits::default()
+        };
+        edits.save(&path).unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        assert!(!dir.join("a.jpg.booru.json.tmp").exists());
+
+        // A directory in the way of the temp file makes the write fail.
+        std::fs::create_dir(dir.join("a.jpg.booru.json.tmp")).unwrap();
+        edits.notes = Some("second".to_string());
+        assert!(edits.save(&path).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), saved);
+        std::fs::remove_dir(dir.join("a.jpg.booru.json.tmp")).unwrap();
+
+        edits.save_with_backup(&path).unwrap();
+        assert_eq!(
+            std::fs::read(dir.join("a.jpg.booru.json.bak")).unwrap(),
+            saved
+        );
+        let loaded = BooruEdits::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.notes.as_deref(), Some("second"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,5 @@
+/// Receives progress from long-running library operations, e.g. to drive a
+/// progress bar. Implementations must tolerate calls from worker threads.
+pub trait ProgressObserver: Send + Sync {
+    fn inc(&self, delta: u64);
+}
@@ -1,21 +1,42 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
-
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use chrono::{DateTime, Local};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::event::RemoveKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use serde::Serialize;
 use serde_json::Value;
 use walkdir::WalkDir;
 
 use crate::alias::{
-    expand_search_terms_with_aliases, load_alias_map_from_roots, normalize_search_terms,
+    expand_search_terms_with_aliases, load_alias_map_from_roots, normalize_search_terms, AliasMap,
     AliasWarning, ALIAS_FILE_NAME,
 };
-use crate::config::BooruConfig;
+use crate::config::{BooruConfig, DEFAULT_VIDEO_EXTENSIONS};
+use crate::cooccurrence::{compute_tag_cooccurrence, related_tags};
+use crate::date::parse_date;
 use crate::error::BooruError;
+use crate::media::MediaKind;
 use crate::metadata::{
     extract_bool_field, extract_nested_scalar_field, extract_scalar_field, extract_string_field,
-    extract_tags, BooruEdits,
+    extract_tags, BooruEdits, Rating,
 };
 use crate::path::{booru_path_for_image, metadata_path_for_image, resolve_image_path};
+use crate::progress::ProgressObserver;
 
 #[derive(Clone, Debug)]
 pub struct ImageItem {
@@ -24,9 +45,45 @@ pub struct ImageItem {
     pub booru_path: PathBuf,
     pub original: Value,
     pub edits: BooruEdits,
+    media_kind: MediaKind,
+    /// Header read cached by [`ImageItem::image_dimensions`].
+    probed_dimensions: OnceLock<Option<(u32, u32)>>,
 }
 
 impl ImageItem {
+    pub fn new(
+        image_path: PathBuf,
+        meta_path: PathBuf,
+        booru_path: PathBuf,
+        original: Value,
+        edits: BooruEdits,
+    ) -> Self {
+        let media_kind = MediaKind::from_path(&image_path, DEFAULT_VIDEO_EXTENSIONS);
+        Self {
+            image_path,
+            meta_path,
+            booru_path,
+            original,
+            edits,
+            media_kind,
+            probed_dimensions: OnceLock::new(),
+        }
+    }
+
+    /// Classifies the image against a library's
+    /// [`BooruConfig::video_extensions`] instead of the defaults.
+    pub fn with_video_extensions(mut self, video_extensions: &[String]) -> Self {
+        self.media_kind = MediaKind::from_path(&self.image_path, video_extensions);
+        self
+    }
+
+    /// Replaces the gallery-dl metadata, forgetting the cached header read
+    /// since a new download may come with a new image.
+    pub fn set_original(&mut self, original: Value) {
+        self.original = original;
+        self.probed_dimensions = OnceLock::new();
+    }
+
     pub fn merged_tags(&self) -> Vec<String> {
         let original_tags = extract_tags(&self.original);
         self.edits.merged_tags(&original_tags)
@@ -131,31 +188,86 @@ impl ImageItem {
         })
     }
 
+    pub fn media_kind(&self) -> MediaKind {
+        self.media_kind
+    }
+
+    /// Width and height as recorded by the downloader, without opening the
+    /// image. Danbooru calls them `image_width`/`image_height`.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        [("width", "height"), ("image_width", "image_height")]
+            .iter()
+            .find_map(|(width, height)| {
+                let width = u32::try_from(self.original.get(*width)?.as_u64()?).ok()?;
+                let height = u32::try_from(self.original.get(*height)?.as_u64()?).ok()?;
+                (width > 0 && height > 0).then_some((width, height))
+            })
+    }
+
+    /// Like [`ImageItem::dimensions`], but falls back to reading the image
+    /// header. The read happens at most once per item, even if it fails.
+    pub fn image_dimensions(&self) -> Option<(u32, u32)> {
+        self.dimensions().or_else(|| {
+            *self
+                .probed_dimensions
+                .get_or_init(|| image::image_dimensions(&self.image_path).ok())
+        })
+    }
+
+    /// Size of the image file in bytes; not cached.
+    pub fn file_size(&self) -> Option<u64> {
+        fs::metadata(&self.image_path).ok().map(|meta| meta.len())
+    }
+
+    /// An explicit sensitive override in the edits wins over the rating.
     pub fn merged_sensitive(&self) -> bool {
         if let Some(sensitive) = self.edits.sensitive {
             return sensitive;
         }
+        self.merged_rating().is_some_and(Rating::is_sensitive)
+    }
+
+    /// Plain sensitive/nsfw flags map to `Questionable` (or `Explicit` for
+    /// keywords like "r18"), since they carry no finer grading. A source
+    /// rating wins over them, except that a true flag lifts it to at least
+    /// `Questionable`.
+    pub fn merged_rating(&self) -> Option<Rating> {
+        if let Some(rating) = self.edits.rating {
+            return Some(rating);
+        }
 
         if pixiv_has_nonempty_restriction_attributes(&self.original) {
-            return true;
+            return Some(Rating::Explicit);
         }
 
-        if let Some(flag) = extract_bool_field(
+        let flag = extract_bool_field(
             &self.original,
             &["sensitive", "nsfw", "is_sensitive", "is_nsfw"],
-        ) {
-            return flag;
+        );
+        let category = extract_string_field(&self.original, &["category"]);
+        if let Some(rating) = extract_scalar_field(&self.original, &["rating"])
+            .and_then(|value| rating_value_to_rating(&value, category.as_deref()))
+        {
+            return Some(if flag == Some(true) {
+                rating.max(Rating::Questionable)
+            } else {
+                rating
+            });
+        }
+
+        if let Some(flag) = flag {
+            return Some(if flag {
+                Rating::Questionable
+            } else {
+                Rating::General
+            });
         }
 
-        if let Some(value) = extract_scalar_field(
+        extract_scalar_field(
             &self.original,
             &["sensitive", "nsfw", "is_sensitive", "is_nsfw"],
-        ) {
-            if let Some(flag) = sensitive_value_to_bool(&value) {
-                return flag;
-            }
-        }
-        false
+        )
+        .and_then(|value| sensitive_value_to_rating(&value))
     }
 
     pub fn platform_url(&self) -> Option<String> {
@@ -166,6 +278,10 @@ impl ImageItem {
             "pixiv" => pixiv_artwork_url(&self.original),
             "danbooru" => danbooru_post_url(&self.original),
             "yandere" => yandere_post_url(&self.original),
+            "gelbooru" => gelbooru_post_url(&self.original),
+            "e621" | "e926" => e621_post_url(&self.original),
+            "deviantart" => deviantart_deviation_url(&self.original),
+            "fanbox" => fanbox_post_url(&self.original),
             "tumblr" => extract_string_field(&self.original, &["post_url", "short_url"]),
             "mastodon" => extract_string_field(&self.original, &["uri", "url"]),
             "bilibili" => bilibili_space_url(&self.original),
@@ -175,14 +291,59 @@ impl ImageItem {
             ),
         }
     }
+
+    /// Profile page of the artist, as opposed to [`Self::platform_url`]
+    /// which points at the post itself.
+    pub fn author_url(&self) -> Option<String> {
+        let category = extract_string_field(&self.original, &["category"])?;
+        let value = &self.original;
+        match category.as_str() {
+            "twitter" => twitter_handle(value).map(|handle| format!("https://x.com/{handle}")),
+            "pixiv" => extract_nested_scalar_field(value, &[&["user", "id"]])
+                .map(|id| format!("https://www.pixiv.net/users/{id}")),
+            "danbooru" => {
+                let artists = extract_string_field(value, &["tag_string_artist"])?;
+                let artist = artists.split_whitespace().next()?;
+                Some(format!(
+                    "https://danbooru.donmai.us/artists?search[any_name_matches]={}",
+                    urlencoding::encode(artist)
+                ))
+            }
+            "weibo" => extract_nested_scalar_field(
+                value,
+                &[&["status", "user", "idstr"], &["status", "user", "id"]],
+            )
+            .map(|uid| format!("https://weibo.com/u/{uid}")),
+            "fanbox" => extract_string_field(value, &["creatorId"])
+                .map(|creator| format!("https://{creator}.fanbox.cc")),
+            "deviantart" => extract_nested_scalar_field(value, &[&["author", "username"]])
+                .map(|user| format!("https://www.deviantart.com/{user}")),
+            "tumblr" => extract_string_field(value, &["blog_name"])
+                .map(|blog| format!("https://{blog}.tumblr.com")),
+            "mastodon" => extract_nested_scalar_field(value, &[&["account", "url"]]),
+            "bilibili" => extract_nested_scalar_field(
+                value,
+                &[&["detail", "modules", "module_author", "mid"]],
+            )
+            .map(|mid| format!("https://space.bilibili.com/{mid}")),
+            _ => None,
+        }
+    }
 }
 
-fn sensitive_value_to_bool(value: &str) -> Option<bool> {
+fn rating_value_to_rating(value: &str, category: Option<&str>) -> Option<Rating> {
+    // Moebooru sites still use `s` for "safe"; danbooru reassigned it to "sensitive".
+    if value.trim().eq_ignore_ascii_case("s") && matches!(category, Some("yandere" | "konachan")) {
+        return Some(Rating::General);
+    }
+    value.parse().ok()
+}
+
+fn sensitive_value_to_rating(value: &str) -> Option<Rating> {
     match value.trim().to_ascii_lowercase().as_str() {
-        "sensitive" | "nsfw" | "adult" | "explicit" | "questionable" | "r18" | "mature" => {
-            Some(true)
-        }
-        "safe" | "sfw" | "general" => Some(false),
+        "adult" | "explicit" | "r18" => Some(Rating::Explicit),
+        "sensitive" | "nsfw" | "questionable" | "mature" => Some(Rating::Questionable),
+        "safe" | "sfw" | "general" => Some(Rating::General),
         _ => None,
     }
 }
@@ -251,7 +412,14 @@ fn extract_first_array_string_field(value: &Value, keys: &[&str]) -> Option<Stri
 
 fn twitter_status_url(value: &Value) -> Option<String> {
     let tweet_id = extract_scalar_field(value, &["tweet_id", "id"])?;
-    if let Some(author) = extract_string_field(value, &["author"]).or_else(|| {
+    if let Some(handle) = twitter_handle(value) {
+        return Some(format!("https://x.com/{handle}/status/{tweet_id}"));
+    }
+    Some(format!("https://x.com/i/status/{tweet_id}"))
+}
+
+fn twitter_handle(value: &Value) -> Option<String> {
+    let author = extract_string_field(value, &["author"]).or_else(|| {
         extract_nested_scalar_field(
             value,
             &[
@@ -263,13 +431,9 @@ fn twitter_status_url(value: &Value) -> Option<String> {
                 &["user", "screen_name"],
             ],
         )
-    }) {
-        let handle = author.trim_start_matches('@');
-        if !handle.is_empty() {
-            return Some(format!("https://x.com/{handle}/status/{tweet_id}"));
-        }
-    }
-    Some(format!("https://x.com/i/status/{tweet_id}"))
+    })?;
+    let handle = author.trim_start_matches('@');
+    (!handle.is_empty()).then(|| handle.to_string())
 }
 
 fn weibo_status_url(value: &Value) -> Option<String> {
@@ -284,9 +448,12 @@ fn weibo_status_url(value: &Value) -> Option<String> {
     Some(format!("https://weibo.com/n/{mblogid}"))
 }
 
+/// Pages of a work, ugoira included, share the work's `id`. Older dumps only
+/// carry `illust_id` or `pixiv_id`, or just a ready-made `pixiv_url`.
 fn pixiv_artwork_url(value: &Value) -> Option<String> {
-    let id = extract_scalar_field(value, &["id"])?;
-    Some(format!("https://www.pixiv.net/artworks/{id}"))
+    extract_scalar_field(value, &["id", "illust_id", "pixiv_id"])
+        .map(|id| format!("https://www.pixiv.net/artworks/{id}"))
+        .or_else(|| extract_string_field(value, &["pixiv_url"]))
 }
 
 fn danbooru_post_url(value: &Value) -> Option<String> {
@@ -299,6 +466,34 @@ fn yandere_post_url(value: &Value) -> Option<String> {
     Some(format!("https://yande.re/post/show/{id}"))
 }
 
+fn gelbooru_post_url(value: &Value) -> Option<String> {
+    let id = extract_scalar_field(value, &["id"])?;
+    Some(format!(
+        "https://gelbooru.com/index.php?page=post&s=view&id={id}"
+    ))
+}
+
+/// e926 is the SFW mirror of e621 and shares its post ids.
+fn e621_post_url(value: &Value) -> Option<String> {
+    let id = extract_scalar_field(value, &["id"])?;
+    Some(format!("https://e621.net/posts/{id}"))
+}
+
+/// gallery-dl records the deviation page as `url`; `index` is the numeric id
+/// that `/deviation/` redirects from.
+fn deviantart_deviation_url(value: &Value) -> Option<String> {
+    extract_string_field(value, &["url"]).or_else(|| {
+        let id = extract_scalar_field(value, &["index", "deviationid"])?;
+        Some(format!("https://www.deviantart.com/deviation/{id}"))
+    })
+}
+
+fn fanbox_post_url(value: &Value) -> Option<String> {
+    let creator = extract_string_field(value, &["creatorId"])?;
+    let id = extract_scalar_field(value, &["id"])?;
+    Some(format!("https://{creator}.fanbox.cc/posts/{id}"))
+}
+
 fn bilibili_space_url(value: &Value) -> Option<String> {
     let opus_id = extract_nested_scalar_field(value, &[&["detail", "id_str"], &["id"]])?;
     Some(format!("https://www.bilibili.com/opus/{opus_id}"))
@@ -444,7 +639,7 @@ fn push_line_break(out: &mut String) {
     out.push('\n');
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ScanWarning {
     pub path: PathBuf,
     pub message: String,
@@ -456,13 +651,153 @@ pub struct ScanReport {
     pub warnings: Vec<ScanWarning>,
 }
 
+/// What [`Library::rescan_incremental`] changed. `updated` counts items whose
+/// metadata or edits were reloaded.
+#[derive(Debug, Default)]
+pub struct RescanReport {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub warnings: Vec<ScanWarning>,
+}
+
+/// Summary counts from [`Library::statistics`]. Dates use the local
+/// timezone, formatted as `YYYY-MM-DD HH:MM:SS +HH:MM`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct LibraryStats {
+    pub item_count: usize,
+    pub tagged_count: usize,
+    pub untagged_count: usize,
+    pub unique_tags: usize,
+    pub unique_authors: usize,
+    pub earliest_date: Option<String>,
+    pub latest_date: Option<String>,
+    pub sensitive_count: usize,
+    /// Items per gallery-dl `category`; items without one count as `(none)`.
+    pub categories: BTreeMap<String, usize>,
+}
+
 #[derive(Debug, Default)]
 pub struct Index {
     pub items: Vec<ImageItem>,
     by_path: HashMap<PathBuf, usize>,
+    /// Metadata and edit file mtimes from the last (re)scan, keyed by the
+    /// paths stored on each item. Missing files have no entry.
+    pub last_seen_mtime: HashMap<PathBuf, i64>,
+    lookups: OnceLock<ItemLookups>,
+    cooccurrence: OnceLock<HashMap<(String, String), usize>>,
+}
+
+/// Item indices keyed by merged tag and by merged author, in index order.
+#[derive(Debug, Default)]
+struct ItemLookups {
+    by_tag: HashMap<String, Vec<usize>>,
+    by_author: HashMap<String, Vec<usize>>,
+    vocabulary: Vec<(String, usize)>,
+    completions: TagCompletionIndex,
+}
+
+/// Tags with their item counts, sorted by lowercased name so the tags
+/// sharing a prefix can be found with a binary search.
+#[derive(Clone, Debug, Default)]
+pub struct TagCompletionIndex {
+    entries: Vec<(String, String, usize)>,
+}
+
+impl TagCompletionIndex {
+    pub fn new(items: &[ImageItem]) -> Self {
+        Self::from_vocabulary(&count_tags(items.iter()))
+    }
+
+    /// Builds the index from tag counts such as [`Index::tag_vocabulary`].
+    pub fn from_vocabulary(vocabulary: &[(String, usize)]) -> Self {
+        let mut entries = vocabulary
+            .iter()
+            .map(|(tag, count)| (tag.to_lowercase(), tag.clone(), *count))
+            .collect::<Vec<_>>();
+        entries.sort();
+        Self { entries }
+    }
+
+    /// Up to `limit` tags starting with `prefix`, ignoring case, most used
+    /// first.
+    pub fn complete(&self, prefix: &str, limit: usize) -> Vec<(String, usize)> {
+        let prefix = prefix.trim().to_lowercase();
+        let start = self
+            .entries
+            .partition_point(|(key, _, _)| key.as_str() < prefix.as_str());
+        let mut matches = self.entries[start..]
+            .iter()
+            .take_while(|(key, _, _)| key.starts_with(&prefix))
+            .map(|(_, tag, count)| (tag.clone(), *count))
+            .collect::<Vec<_>>();
+        matches.sort_by(|(lhs_tag, lhs_count), (rhs_tag, rhs_count)| {
+            rhs_count.cmp(lhs_count).then_with(|| lhs_tag.cmp(rhs_tag))
+        });
+        matches.truncate(limit);
+        matches
+    }
 }
 
 impl Index {
+    pub fn push(&mut self, item: ImageItem) {
+        self.by_path
+            .insert(item.image_path.clone(), self.items.len());
+        self.items.push(item);
+        self.invalidate_lookups();
+    }
+
+    /// Removes the item at `idx`, shifting later items down by one like
+    /// [`Vec::remove`], and forgets the mtimes recorded for its files.
+    pub fn remove(&mut self, idx: usize) -> Option<ImageItem> {
+        if idx >= self.items.len() {
+            return None;
+        }
+        let item = self.items.remove(idx);
+        self.by_path.remove(&item.image_path);
+        for slot in self.by_path.values_mut() {
+            if *slot > idx {
+                *slot -= 1;
+            }
+        }
+        self.last_seen_mtime.remove(&item.meta_path);
+        self.last_seen_mtime.remove(&item.booru_path);
+        self.invalidate_lookups();
+        Some(item)
+    }
+
+    /// Drops the tag and author lookups behind [`Library::items_by_tag`] and
+    /// [`Library::items_by_author`]. Call after changing `items` directly.
+    pub fn invalidate_lookups(&mut self) {
+        self.lookups.take();
+        self.cooccurrence.take();
+    }
+
+    fn lookups(&self) -> &ItemLookups {
+        self.lookups.get_or_init(|| {
+            let mut lookups = ItemLookups::default();
+            for (idx, item) in self.items.iter().enumerate() {
+                for tag in item.merged_tags() {
+                    lookups.by_tag.entry(tag).or_default().push(idx);
+                }
+                if let Some(author) = item.merged_author() {
+                    lookups.by_author.entry(author).or_default().push(idx);
+                }
+            }
+            // `merged_tags` lists each tag once per item, so an index list's
+            // length is the tag's item count.
+            lookups.vocabulary = sort_counts(
+                lookups
+                    .by_tag
+                    .iter()
+                    .map(|(tag, indices)| (tag.clone(), indices.len()))
+                    .collect(),
+            );
+            lookups.completions = TagCompletionIndex::from_vocabulary(&lookups.vocabulary);
+            lookups
+        })
+    }
+
     pub fn get_by_path(&self, path: &Path) -> Option<&ImageItem> {
         self.by_path.get(path).and_then(|idx| self.items.get(*idx))
     }
@@ -471,6 +806,22 @@ impl Index {
         self.items.iter()
     }
 
+    /// Like [`tag_frequency`], optionally leaving out sensitive items.
+    pub fn tag_counts(&self, include_sensitive: bool) -> Vec<(String, usize)> {
+        count_tags(
+            self.items
+                .iter()
+                .filter(|item| include_sensitive || !item.merged_sensitive()),
+        )
+    }
+
+    /// Every merged tag with its item count, most used first, ties by name.
+    /// Served from the cached tag lookup, so it is cheap until the next
+    /// change to `items`.
+    pub fn tag_vocabulary(&self) -> Vec<(String, usize)> {
+        self.lookups().vocabulary.clone()
+    }
+
     pub fn search_by_tags_all(&self, tags: &[String]) -> Vec<&ImageItem> {
         let mut results = Vec::new();
         for item in &self.items {
@@ -499,9 +850,67 @@ pub struct Library {
 #[derive(Clone, Debug, Default)]
 pub struct SearchQuery {
     pub terms: Vec<String>,
+    pub exclude_terms: Vec<String>,
     pub use_aliases: bool,
     pub source_url: Option<String>,
     pub sort: SearchSort,
+    pub mode: SearchMode,
+    /// Unix seconds. Items dated at or after this are kept.
+    pub date_after: Option<i64>,
+    /// Unix seconds. Items dated strictly before this are kept.
+    pub date_before: Option<i64>,
+    pub sensitive: SensitiveFilter,
+    /// Keep only items with exactly this merged tag, looked up in
+    /// [`Library::items_by_tag`] instead of matched like a term.
+    pub exact_tag: Option<String>,
+    /// Keep only items by exactly this merged author.
+    pub exact_author: Option<String>,
+    /// Keep only items at least this wide/high, per
+    /// [`ImageItem::image_dimensions`]. Items of unknown size are dropped.
+    pub min_width: Option<u32>,
+    pub min_height: Option<u32>,
+    /// Keep only image files of at least this many bytes.
+    pub min_file_size: Option<u64>,
+    /// Whether bare terms also match the notes and custom fields of the
+    /// edits. On by default.
+    pub edit_fields: bool,
+}
+
+/// Which items [`Library::search`] keeps by [`ImageItem::merged_sensitive`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SensitiveFilter {
+    #[default]
+    ShowAll,
+    ShowOnly,
+    HideAll,
+}
+
+impl SensitiveFilter {
+    /// `ShowAll` or `HideAll`, for the frontends' show-sensitive toggles.
+    pub fn from_show(show_sensitive: bool) -> Self {
+        if show_sensitive {
+            Self::ShowAll
+        } else {
+            Self::HideAll
+        }
+    }
+
+    pub fn allows(self, sensitive: bool) -> bool {
+        match self {
+            Self::ShowAll => true,
+            Self::ShowOnly => sensitive,
+            Self::HideAll => !sensitive,
+        }
+    }
+}
+
+/// How multiple search terms combine. With aliases enabled, `All` requires
+/// each original term's alias group to match, not every expanded alias.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SearchMode {
+    #[default]
+    Any,
+    All,
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
@@ -509,15 +918,83 @@ pub enum SearchSort {
     #[default]
     IndexOrder,
     FileNameAsc,
+    /// Newest first. For keyed sorts, items without a usable key (no date,
+    /// no author, unreadable file) follow in file name order.
+    DateDesc,
+    DateAsc,
+    AuthorAsc,
+    AuthorDesc,
+    FileSizeAsc,
+    FileSizeDesc,
+    TagCountDesc,
+    /// Most pixels first. Items whose metadata lacks dimensions have their
+    /// image header read.
+    ResolutionDesc,
+    /// Shuffled with the given seed, so the order is reproducible.
+    Random(u64),
+}
+
+impl SearchSort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::IndexOrder => "index",
+            Self::FileNameAsc => "name",
+            Self::DateDesc => "date_desc",
+            Self::DateAsc => "date_asc",
+            Self::AuthorAsc => "author_asc",
+            Self::AuthorDesc => "author_desc",
+            Self::FileSizeAsc => "file_size_asc",
+            Self::FileSizeDesc => "file_size_desc",
+            Self::TagCountDesc => "tag_count_desc",
+            Self::ResolutionDesc => "resolution",
+            Self::Random(_) => "random",
+        }
+    }
+}
+
+/// Parses the names from [`SearchSort::as_str`]. `random` parses with seed 0;
+/// use [`SearchSort::Random`] directly to pick a seed.
+impl FromStr for SearchSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "index" => Ok(Self::IndexOrder),
+            "name" => Ok(Self::FileNameAsc),
+            "date_desc" => Ok(Self::DateDesc),
+            "date_asc" => Ok(Self::DateAsc),
+            "author_asc" => Ok(Self::AuthorAsc),
+            "author_desc" => Ok(Self::AuthorDesc),
+            "file_size_asc" => Ok(Self::FileSizeAsc),
+            "file_size_desc" => Ok(Self::FileSizeDesc),
+            "tag_count_desc" => Ok(Self::TagCountDesc),
+            "resolution" => Ok(Self::ResolutionDesc),
+            "random" => Ok(Self::Random(0)),
+            other => Err(format!("unknown sort `{other}`")),
+        }
+    }
 }
 
 impl SearchQuery {
+    /// Terms prefixed with `-` become exclusions; see [`split_excluded_terms`].
     pub fn new(terms: Vec<String>) -> Self {
+        let (terms, exclude_terms) = split_excluded_terms(terms);
         Self {
             terms,
+            exclude_terms,
             use_aliases: false,
             source_url: None,
             sort: SearchSort::IndexOrder,
+            mode: SearchMode::Any,
+            date_after: None,
+            date_before: None,
+            sensitive: SensitiveFilter::ShowAll,
+            exact_tag: None,
+            exact_author: None,
+            min_width: None,
+            min_height: None,
+            min_file_size: None,
+            edit_fields: true,
         }
     }
 
@@ -537,19 +1014,112 @@ impl SearchQuery {
         self.sort = sort;
         self
     }
+
+    pub fn with_mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_match_all(self, match_all: bool) -> Self {
+        self.with_mode(if match_all {
+            SearchMode::All
+        } else {
+            SearchMode::Any
+        })
+    }
+
+    pub fn with_date_after(mut self, date_after: Option<i64>) -> Self {
+        self.date_after = date_after;
+        self
+    }
+
+    pub fn with_date_before(mut self, date_before: Option<i64>) -> Self {
+        self.date_before = date_before;
+        self
+    }
+
+    pub fn with_sensitive(mut self, filter: SensitiveFilter) -> Self {
+        self.sensitive = filter;
+        self
+    }
+
+    pub fn with_exact_tag(mut self, tag: Option<String>) -> Self {
+        self.exact_tag = tag;
+        self
+    }
+
+    pub fn with_exact_author(mut self, author: Option<String>) -> Self {
+        self.exact_author = author;
+        self
+    }
+
+    pub fn with_min_dimensions(mut self, width: Option<u32>, height: Option<u32>) -> Self {
+        self.min_width = width;
+        self.min_height = height;
+        self
+    }
+
+    pub fn with_min_file_size(mut self, bytes: Option<u64>) -> Self {
+        self.min_file_size = bytes;
+        self
+    }
+
+    pub fn with_edit_fields(mut self, edit_fields: bool) -> Self {
+        self.edit_fields = edit_fields;
+        self
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct SearchResult {
     pub normalized_terms: Vec<String>,
     pub expanded_terms: Vec<String>,
+    pub excluded_terms: Vec<String>,
     pub indices: Vec<usize>,
     pub alias_warnings: Vec<AliasWarning>,
+    /// Set when the sort had to read many files, see [`RESOLUTION_PROBE_WARN_LIMIT`].
+    pub sort_warning: Option<String>,
 }
 
+/// How many image headers [`SearchSort::ResolutionDesc`] may read before
+/// the search reports that it was slow. Headers are read through
+/// [`ImageItem::image_dimensions`], so later searches reuse them.
+pub const RESOLUTION_PROBE_WARN_LIMIT: usize = 1000;
+
 impl Library {
     pub fn scan(config: BooruConfig) -> Result<Self, BooruError> {
-        let report = scan_roots(&config.roots)?;
+        Self::scan_inner(config, None, None)
+    }
+
+    /// Like [`Library::scan`], calling `progress.inc(1)` for every metadata
+    /// file read, whether or not it parses.
+    pub fn scan_with_progress(
+        config: BooruConfig,
+        progress: &dyn ProgressObserver,
+    ) -> Result<Self, BooruError> {
+        Self::scan_inner(config, Some(progress), None)
+    }
+
+    /// Like [`Library::scan`], but also sends a copy of each item to `items`
+    /// as soon as it is loaded, so a frontend can fill its view while the
+    /// scan runs on another thread. The channel closes when the scan ends;
+    /// items arrive in the same order as in the returned library.
+    pub fn scan_streaming(
+        config: BooruConfig,
+        items: Sender<ImageItem>,
+    ) -> Result<Self, BooruError> {
+        Self::scan_inner(config, None, Some(&items))
+    }
+
+    fn scan_inner(
+        config: BooruConfig,
+        progress: Option<&dyn ProgressObserver>,
+        items: Option<&Sender<ImageItem>>,
+    ) -> Result<Self, BooruError> {
+        let excludes = build_exclude_set(&config.exclude_globs)?;
+        let mut warnings = Vec::new();
+        let files = find_metadata_files(&config, &excludes, &mut warnings);
+        let report = load_items(files, &config.video_extensions, warnings, progress, items);
         Ok(Self {
             config,
             index: report.index,
@@ -557,90 +1127,637 @@ impl Library {
         })
     }
 
+    /// Rescans the roots, reusing already loaded items whose metadata and
+    /// edit files still have the mtimes seen last time. Items keep the order
+    /// a full scan would give them. Replaces `self.warnings`.
+    pub fn rescan_incremental(&mut self) -> Result<RescanReport, BooruError> {
+        let excludes = build_exclude_set(&self.config.exclude_globs)?;
+        let mut warnings = Vec::new();
+        let files = find_metadata_files(&self.config, &excludes, &mut warnings);
+
+        let previous_mtime = std::mem::take(&mut self.index.last_seen_mtime);
+        let mut previous = std::mem::take(&mut self.index.items)
+            .into_iter()
+            .map(|item| (item.image_path.clone(), item))
+            .collect::<HashMap<_, _>>();
+        let mut index = Index::default();
+        let mut report = RescanReport::default();
+
+        for (meta_path, image_path) in files {
+            let canonical = fs::canonicalize(&image_path).unwrap_or_else(|_| image_path.clone());
+            if index.by_path.contains_key(&canonical) {
+                continue;
+            }
+            let Some(mut item) = previous.remove(&canonical) else {
+                if let Some(item) = load_item(
+                    &meta_path,
+                    image_path,
+                    &self.config.video_extensions,
+                    &mut index.last_seen_mtime,
+                    &mut warnings,
+                ) {
+                    report.added += 1;
+                    index.push(item);
+                }
+                continue;
+            };
+
+            let meta_mtime = file_mtime(&item.meta_path);
+            let booru_mtime = file_mtime(&item.booru_path);
+            let mut changed = false;
+            if meta_mtime != previous_mtime.get(&item.meta_path).copied() {
+                let Some(original) =
+                    read_original(&item.meta_path, &item.image_path, &mut warnings)
+                else {
+                    report.removed += 1;
+                    continue;
+                };
+                item.set_original(original);
+                changed = true;
+            }
+            if booru_mtime != previous_mtime.get(&item.booru_path).copied() {
+                item.edits = read_edits(&item.booru_path, &mut warnings);
+                changed = true;
+            }
+            if changed {
+                report.updated += 1;
+            }
+            record_mtime(&mut index.last_seen_mtime, &item.meta_path, meta_mtime);
+            record_mtime(&mut index.last_seen_mtime, &item.booru_path, booru_mtime);
+            index.push(item);
+        }
+
+        report.removed += previous.len();
+        self.index = index;
+        self.warnings = warnings.clone();
+        report.warnings = warnings;
+        Ok(report)
+    }
+
+    pub fn statistics(&self) -> LibraryStats {
+        let mut stats = LibraryStats {
+            item_count: self.index.items.len(),
+            ..LibraryStats::default()
+        };
+        let mut tags = HashSet::new();
+        let mut authors = HashSet::new();
+        let mut earliest: Option<DateTime<Local>> = None;
+        let mut latest: Option<DateTime<Local>> = None;
+
+        for item in &self.index.items {
+            if item.merged_sensitive() {
+                stats.sensitive_count += 1;
+            }
+            let item_tags = item.merged_tags();
+            if item_tags.is_empty() {
+                stats.untagged_count += 1;
+            } else {
+                stats.tagged_count += 1;
+            }
+            tags.extend(item_tags);
+            if let Some(author) = item
+                .merged_author()
+                .map(|author| author.trim().to_string())
+                .filter(|author| !author.is_empty())
+            {
+                authors.insert(author);
+            }
+            let category = extract_string_field(&item.original, &["category"])
+                .unwrap_or_else(|| "(none)".to_string());
+            *stats.categories.entry(category).or_default() += 1;
+
+            if let Some(date) = item.merged_date().as_deref().and_then(parse_date) {
+                earliest = Some(earliest.map_or(date, |current| current.min(date)));
+                latest = Some(latest.map_or(date, |current| current.max(date)));
+            }
+        }
+
+        let format_date = |date: DateTime<Local>| date.format("%Y-%m-%d %H:%M:%S %:z").to_string();
+        stats.unique_tags = tags.len();
+        stats.unique_authors = authors.len();
+        stats.earliest_date = earliest.map(format_date);
+        stats.latest_date = latest.map(format_date);
+        stats
+    }
+
     pub fn resolve_image_path(&self, input: &Path) -> PathBuf {
         resolve_image_path(input, &self.config.roots)
     }
 
+    /// Indices of the items carrying each merged tag. Built on first use and
+    /// rebuilt after a rescan.
+    pub fn items_by_tag(&self) -> &HashMap<String, Vec<usize>> {
+        &self.index.lookups().by_tag
+    }
+
+    /// Indices of the items by each merged author, like [`Self::items_by_tag`].
+    pub fn items_by_author(&self) -> &HashMap<String, Vec<usize>> {
+        &self.index.lookups().by_author
+    }
+
+    /// [`tag_completions`] over the library, served from a
+    /// [`TagCompletionIndex`] built from [`Index::tag_vocabulary`] and kept
+    /// until the next change to the items.
+    pub fn tag_completions(&self, prefix: &str, limit: usize) -> Vec<(String, usize)> {
+        self.index.lookups().completions.complete(prefix, limit)
+    }
+
+    /// [`related_tags`] for `tags`, from a co-occurrence matrix of tags used
+    /// at least twice that is kept until the next rescan.
+    pub fn related_tags(&self, tags: &[String], limit: usize) -> Vec<(String, usize)> {
+        let cooccurrence = self
+            .index
+            .cooccurrence
+            .get_or_init(|| compute_tag_cooccurrence(&self.index.items, 2));
+        related_tags(cooccurrence, tags, limit)
+    }
+
     pub fn search(&self, query: SearchQuery) -> SearchResult {
         let normalized_terms = normalize_search_terms(query.terms);
+        let normalized_excludes = normalize_search_terms(query.exclude_terms);
 
-        let (expanded_terms, alias_warnings) = if query.use_aliases {
+        let (alias_map, alias_warnings) = if query.use_aliases {
             let (alias_map, warnings) = load_alias_map_from_roots(&self.config.roots);
-            (
-                expand_search_terms_with_aliases(normalized_terms.clone(), &alias_map),
-                warnings,
-            )
+            (Some(alias_map), warnings)
         } else {
-            (normalized_terms.clone(), Vec::new())
+            (None, Vec::new())
         };
+        let term_groups = normalized_terms
+            .iter()
+            .map(|term| expand_search_term(term, alias_map.as_ref()))
+            .collect::<Vec<_>>();
+        let expanded_terms = flatten_term_groups(&term_groups);
+        let excluded_terms = flatten_term_groups(
+            &normalized_excludes
+                .iter()
+                .map(|term| expand_search_term(term, alias_map.as_ref()))
+                .collect::<Vec<_>>(),
+        );
 
-        let source_url = query.source_url.as_deref();
-        let mut indices = self
-            .index
-            .items
+        let any_matcher = TermMatcher::new(&expanded_terms, query.edit_fields);
+        let all_matchers = term_groups
             .iter()
-            .enumerate()
-            .filter_map(|(idx, item)| {
-                (item_matches_search_terms(item, &expanded_terms)
-                    && item_matches_source_url(item, source_url))
-                .then_some(idx)
+            .map(|group| TermMatcher::new(group, query.edit_fields))
+            .collect::<Vec<_>>();
+        let exclude_matcher = TermMatcher::new(&excluded_terms, query.edit_fields);
+
+        let source_url = query.source_url.as_deref();
+        let candidates = match (
+            exact_matches(self.items_by_tag(), query.exact_tag.as_deref()),
+            exact_matches(self.items_by_author(), query.exact_author.as_deref()),
+        ) {
+            (None, None) => (0..self.index.items.len()).collect::<Vec<_>>(),
+            (Some(indices), None) | (None, Some(indices)) => indices.to_vec(),
+            (Some(by_tag), Some(by_author)) => by_tag
+                .iter()
+                .copied()
+                .filter(|idx| by_author.binary_search(idx).is_ok())
+                .collect(),
+        };
+        let mut indices = candidates
+            .into_iter()
+            .filter(|idx| {
+                let item = &self.index.items[*idx];
+                let terms_match = match query.mode {
+                    SearchMode::Any => any_matcher.matches(item),
+                    SearchMode::All => all_matchers.iter().all(|matcher| matcher.matches(item)),
+                };
+                let excluded = !exclude_matcher.is_empty() && exclude_matcher.matches(item);
+                terms_match
+                    && !excluded
+                    && query.sensitive.allows(item.merged_sensitive())
+                    && item_matches_source_url(item, source_url)
+                    && item_matches_date_range(item, query.date_after, query.date_before)
+                    && item_matches_min_size(
+                        item,
+                        (query.min_width, query.min_height),
+                        query.min_file_size,
+                    )
             })
             .collect::<Vec<_>>();
 
-        if query.sort == SearchSort::FileNameAsc {
-            indices.sort_by(|lhs, rhs| {
-                let left_item = &self.index.items[*lhs];
-                let right_item = &self.index.items[*rhs];
-                let left_name = left_item
-                    .image_path
-                    .file_name()
-                    .and_then(|name| name.to_str())
-                    .unwrap_or("");
-                let right_name = right_item
-                    .image_path
-                    .file_name()
-                    .and_then(|name| name.to_str())
-                    .unwrap_or("");
-
-                left_name
-                    .cmp(right_name)
-                    .then_with(|| left_item.image_path.cmp(&right_item.image_path))
-            });
+        let mut sort_warning = None;
+        match query.sort {
+            SearchSort::IndexOrder => {}
+            SearchSort::FileNameAsc => indices.sort_by(|lhs, rhs| {
+                compare_file_names(&self.index.items[*lhs], &self.index.items[*rhs])
+            }),
+            SearchSort::DateDesc | SearchSort::DateAsc => {
+                let descending = query.sort == SearchSort::DateDesc;
+                sort_indices_by_key(&self.index.items, &mut indices, descending, |item| {
+                    item.merged_date().as_deref().and_then(parse_date)
+                });
+            }
+            SearchSort::AuthorAsc | SearchSort::AuthorDesc => {
+                let descending = query.sort == SearchSort::AuthorDesc;
+                sort_indices_by_key(&self.index.items, &mut indices, descending, |item| {
+                    item.merged_author()
+                        .map(|author| author.trim().to_lowercase())
+                        .filter(|author| !author.is_empty())
+                });
+            }
+            SearchSort::FileSizeAsc | SearchSort::FileSizeDesc => {
+                let descending = query.sort == SearchSort::FileSizeDesc;
+                sort_indices_by_key(&self.index.items, &mut indices, descending, |item| {
+                    fs::metadata(&item.image_path).ok().map(|meta| meta.len())
+                });
+            }
+            SearchSort::TagCountDesc => {
+                sort_indices_by_key(&self.index.items, &mut indices, true, |item| {
+                    Some(item.merged_tags().len())
+                });
+            }
+            SearchSort::ResolutionDesc => {
+                let probes = Cell::new(0);
+                sort_indices_by_key(&self.index.items, &mut indices, true, |item| {
+                    if item.dimensions().is_none() && item.probed_dimensions.get().is_none() {
+                        probes.set(probes.get() + 1);
+                    }
+                    item.image_dimensions()
+                        .map(|(width, height)| u64::from(width) * u64::from(height))
+                });
+                if probes.get() > RESOLUTION_PROBE_WARN_LIMIT {
+                    sort_warning = Some(format!(
+                        "sorting by resolution read {} image headers; metadata had no dimensions",
+                        probes.get()
+                    ));
+                }
+            }
+            SearchSort::Random(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                indices.shuffle(&mut rng);
+            }
         }
 
         SearchResult {
             normalized_terms,
             expanded_terms,
+            excluded_terms,
             indices,
             alias_warnings,
+            sort_warning,
         }
     }
-}
 
-pub fn item_matches_search_terms(item: &ImageItem, terms: &[String]) -> bool {
-    if terms.is_empty() {
-        return true;
+    /// Returns the indices of the items just before and after `current_path`
+    /// in the results of `query`, or `(None, None)` if it is not among them.
+    pub fn adjacent_items(
+        &self,
+        query: &SearchQuery,
+        current_path: &Path,
+    ) -> (Option<usize>, Option<usize>) {
+        let indices = self.search(query.clone()).indices;
+        let Some(pos) = indices
+            .iter()
+            .position(|idx| self.index.items[*idx].image_path == current_path)
+        else {
+            return (None, None);
+        };
+        let previous = pos.checked_sub(1).map(|prev| indices[prev]);
+        (previous, indices.get(pos + 1).copied())
     }
+}
 
-    let tags = item
-        .merged_tags()
-        .into_iter()
-        .map(|tag| tag.to_lowercase())
-        .collect::<Vec<_>>();
-    let author = item.merged_author().map(|author| author.to_lowercase());
-    let detail = item.merged_detail().map(|detail| detail.to_lowercase());
+/// A search term, optionally restricted to one field with a `field:` prefix
+/// (`author:`, `tag:`, `note:` or `detail:`). Unknown prefixes are kept as
+/// plain `Any` terms so values like URLs still match as substrings.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SearchTerm {
+    Any(String),
+    Author(String),
+    Tag(String),
+    Notes(String),
+    Detail(String),
+}
 
-    terms.iter().any(|term| {
-        let needle = term.to_lowercase();
-        tags.iter().any(|tag| tag.contains(&needle))
-            || author
-                .as_ref()
-                .map(|author| author.contains(&needle))
-                .unwrap_or(false)
-            || detail
+/// Formats the term back into query syntax, so generated links parse to the
+/// same term. Bare terms starting with `-` are escaped.
+impl fmt::Display for SearchTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any(value) if value.starts_with('-') => write!(f, "\\{value}"),
+            Self::Any(value) => write!(f, "{value}"),
+            Self::Author(value) => write!(f, "author:{value}"),
+            Self::Tag(value) => write!(f, "tag:{value}"),
+            Self::Notes(value) => write!(f, "note:{value}"),
+            Self::Detail(value) => write!(f, "detail:{value}"),
+        }
+    }
+}
+
+pub fn parse_search_term(s: &str) -> SearchTerm {
+    let trimmed = s.trim();
+    let Some((field, value)) = trimmed.split_once(':') else {
+        return SearchTerm::Any(trimmed.to_string());
+    };
+    if value.is_empty() {
+        return SearchTerm::Any(trimmed.to_string());
+    }
+    let value = value.to_string();
+    match field.to_ascii_lowercase().as_str() {
+        "author" => SearchTerm::Author(value),
+        "tag" => SearchTerm::Tag(value),
+        "note" | "notes" => SearchTerm::Notes(value),
+        "detail" => SearchTerm::Detail(value),
+        _ => SearchTerm::Any(trimmed.to_string()),
+    }
+}
+
+/// How a bare or `tag:` term matches tags. A trailing `*` matches the start of
+/// a whole tag and a leading `*` its end; without either (or with both) the
+/// value matches anywhere inside a tag, as it always has.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TagPattern {
+    Substring(String),
+    Prefix(String),
+    Suffix(String),
+}
+
+impl TagPattern {
+    /// Lowercases `value`, since tags are compared lowercased.
+    pub fn parse(value: &str) -> Self {
+        let value = value.to_lowercase();
+        let (leading, rest) = match value.strip_prefix('*') {
+            Some(rest) => (true, rest),
+            None => (false, value.as_str()),
+        };
+        let (trailing, core) = match rest.strip_suffix('*') {
+            Some(core) => (true, core),
+            None => (false, rest),
+        };
+        let core = core.to_string();
+        match (leading, trailing) {
+            (false, true) => Self::Prefix(core),
+            (true, false) => Self::Suffix(core),
+            _ => Self::Substring(core),
+        }
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        !matches!(self, Self::Substring(_))
+    }
+
+    pub fn matches(&self, tag: &str) -> bool {
+        match self {
+            Self::Substring(needle) => tag.contains(needle.as_str()),
+            Self::Prefix(prefix) => tag.starts_with(prefix.as_str()),
+            Self::Suffix(suffix) => tag.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+// Only bare and `tag:` terms name tags, so only those pick up aliases, and
+// not when they are wildcard patterns.
+fn expand_search_term(term: &str, alias_map: Option<&AliasMap>) -> Vec<String> {
+    let Some(alias_map) = alias_map else {
+        return vec![term.to_string()];
+    };
+    match parse_search_term(term) {
+        SearchTerm::Any(value) | SearchTerm::Tag(value)
+            if TagPattern::parse(&value).is_wildcard() =>
+        {
+            vec![term.to_string()]
+        }
+        SearchTerm::Any(value) => expand_search_terms_with_aliases(vec![value], alias_map),
+        SearchTerm::Tag(value) => expand_search_terms_with_aliases(vec![value], alias_map)
+            .into_iter()
+            .map(|alias| format!("tag:{alias}"))
+            .collect(),
+        _ => vec![term.to_string()],
+    }
+}
+
+fn flatten_term_groups(groups: &[Vec<String>]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    groups
+        .iter()
+        .flatten()
+        .filter(|term| seen.insert(term.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Counts how many items carry each merged tag, most common first, ties by
+/// name.
+pub fn tag_frequency(items: &[ImageItem]) -> Vec<(String, usize)> {
+    count_tags(items.iter())
+}
+
+/// Up to `limit` tags starting with `prefix` (case-insensitively) with their
+/// item counts, most used first. Builds a [`TagCompletionIndex`] each call;
+/// keep one around, or use [`Library::tag_completions`], for repeated lookups.
+pub fn tag_completions(items: &[ImageItem], prefix: &str, limit: usize) -> Vec<(String, usize)> {
+    TagCompletionIndex::new(items).complete(prefix, limit)
+}
+
+/// Counts items per [`ImageItem::merged_author`], most common first, ties
+/// by name. Items without an author are counted under `(none)`.
+pub fn author_frequency(items: &[ImageItem]) -> Vec<(String, usize)> {
+    let mut counts = HashMap::<String, usize>::new();
+    for item in items {
+        let author = item
+            .merged_author()
+            .map(|author| author.trim().to_string())
+            .filter(|author| !author.is_empty())
+            .unwrap_or_else(|| "(none)".to_string());
+        *counts.entry(author).or_default() += 1;
+    }
+    sort_counts(counts)
+}
+
+fn count_tags<'a>(items: impl Iterator<Item = &'a ImageItem>) -> Vec<(String, usize)> {
+    let mut counts = HashMap::<String, usize>::new();
+    for item in items {
+        let tags = item.merged_tags().into_iter().collect::<HashSet<_>>();
+        for tag in tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    sort_counts(counts)
+}
+
+fn sort_counts(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(lhs_name, lhs_count), (rhs_name, rhs_count)| {
+        rhs_count
+            .cmp(lhs_count)
+            .then_with(|| lhs_name.cmp(rhs_name))
+    });
+    counts
+}
+
+/// Splits raw search terms into `(include, exclude)`. A leading `-` marks an
+/// exclusion; write `\-term` to search for a term that really starts with `-`.
+pub fn split_excluded_terms(terms: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+    for term in terms {
+        let trimmed = term.trim();
+        if let Some(literal) = trimmed.strip_prefix('\\') {
+            if literal.starts_with('-') {
+                include.push(literal.to_string());
+                continue;
+            }
+        }
+        match trimmed.strip_prefix('-') {
+            Some(excluded) if !excluded.is_empty() => exclude.push(excluded.to_string()),
+            _ => include.push(term),
+        }
+    }
+    (include, exclude)
+}
+
+// Keys are computed once per item since dates need parsing and sizes need a
+// stat call. Ties fall back to image path order.
+fn sort_indices_by_key<K: Ord>(
+    items: &[ImageItem],
+    indices: &mut Vec<usize>,
+    descending: bool,
+    key: impl Fn(&ImageItem) -> Option<K>,
+) {
+    let mut keyed = indices
+        .iter()
+        .map(|idx| (key(&items[*idx]), *idx))
+        .collect::<Vec<_>>();
+    keyed.sort_by(|(lhs_key, lhs), (rhs_key, rhs)| {
+        let left_item = &items[*lhs];
+        let right_item = &items[*rhs];
+        match (lhs_key, rhs_key) {
+            (Some(left), Some(right)) => {
+                let order = if descending {
+                    right.cmp(left)
+                } else {
+                    left.cmp(right)
+                };
+                order.then_with(|| left_item.image_path.cmp(&right_item.image_path))
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => compare_file_names(left_item, right_item),
+        }
+    });
+    *indices = keyed.into_iter().map(|(_, idx)| idx).collect();
+}
+
+fn compare_file_names(lhs: &ImageItem, rhs: &ImageItem) -> Ordering {
+    let left_name = lhs
+        .image_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    let right_name = rhs
+        .image_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+
+    left_name
+        .cmp(right_name)
+        .then_with(|| lhs.image_path.cmp(&rhs.image_path))
+}
+
+/// The lookup entry for `key`, empty when nothing matches; `None` when there
+/// is no key to look up.
+fn exact_matches<'a>(
+    lookup: &'a HashMap<String, Vec<usize>>,
+    key: Option<&str>,
+) -> Option<&'a [usize]> {
+    key.map(|key| lookup.get(key).map(Vec::as_slice).unwrap_or_default())
+}
+
+/// Whether any of `terms` matches `item`. Parses the terms on every call;
+/// [`Library::search`] prepares them once for the whole library instead.
+pub fn item_matches_search_terms(item: &ImageItem, terms: &[String]) -> bool {
+    TermMatcher::new(terms, true).matches(item)
+}
+
+/// Search terms parsed and lowercased once, to be tested against many items.
+/// Without `edit_fields`, bare terms skip the user's notes and custom
+/// fields; `note:` terms still look at the notes.
+struct TermMatcher {
+    terms: Vec<MatchTerm>,
+    edit_fields: bool,
+}
+
+enum MatchTerm {
+    Any(TagPattern),
+    Author(String),
+    Tag(TagPattern),
+    Notes(String),
+    Detail(String),
+}
+
+impl TermMatcher {
+    fn new(terms: &[String], edit_fields: bool) -> Self {
+        let terms = terms
+            .iter()
+            .map(|term| match parse_search_term(term) {
+                SearchTerm::Any(value) => MatchTerm::Any(TagPattern::parse(&value)),
+                SearchTerm::Author(value) => MatchTerm::Author(value.to_lowercase()),
+                SearchTerm::Tag(value) => MatchTerm::Tag(TagPattern::parse(&value)),
+                SearchTerm::Notes(value) => MatchTerm::Notes(value.to_lowercase()),
+                SearchTerm::Detail(value) => MatchTerm::Detail(value.to_lowercase()),
+            })
+            .collect();
+        Self { terms, edit_fields }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// True for an empty matcher, as for a search without terms.
+    fn matches(&self, item: &ImageItem) -> bool {
+        if self.terms.is_empty() {
+            return true;
+        }
+
+        let tags = item
+            .merged_tags()
+            .into_iter()
+            .map(|tag| tag.to_lowercase())
+            .collect::<Vec<_>>();
+        let author = item.merged_author().map(|author| author.to_lowercase());
+        let detail = item.merged_detail().map(|detail| detail.to_lowercase());
+        let notes = item.edits.notes.as_ref().map(|notes| notes.to_lowercase());
+
+        let tags_match = |pattern: &TagPattern| tags.iter().any(|tag| pattern.matches(tag));
+        let field_contains = |field: &Option<String>, needle: &str| {
+            field
                 .as_ref()
-                .map(|detail| detail.contains(&needle))
+                .map(|value| value.contains(needle))
                 .unwrap_or(false)
+        };
+
+        // Wildcard patterns only ever look at tags.
+        self.terms.iter().any(|term| match term {
+            MatchTerm::Any(pattern) => match pattern {
+                TagPattern::Substring(needle) => {
+                    tags_match(pattern)
+                        || field_contains(&author, needle)
+                        || field_contains(&detail, needle)
+                        || (self.edit_fields
+                            && (field_contains(&notes, needle)
+                                || extra_fields_contain(&item.edits, needle)))
+                }
+                _ => tags_match(pattern),
+            },
+            MatchTerm::Author(needle) => field_contains(&author, needle),
+            MatchTerm::Tag(pattern) => tags_match(pattern),
+            MatchTerm::Notes(needle) => field_contains(&notes, needle),
+            MatchTerm::Detail(needle) => field_contains(&detail, needle),
+        })
+    }
+}
+
+/// Whether a top-level string or number value of the custom edit fields
+/// contains `needle`, ignoring case; nested objects and arrays are not
+/// searched.
+fn extra_fields_contain(edits: &BooruEdits, needle: &str) -> bool {
+    edits.extra.values().any(|value| match value {
+        Value::String(text) => text.to_lowercase().contains(needle),
+        Value::Number(number) => number.to_string().contains(needle),
+        _ => false,
     })
 }
 
@@ -655,11 +1772,177 @@ fn item_matches_source_url(item: &ImageItem, source_url: Option<&str>) -> bool {
     }
 }
 
+fn item_matches_min_size(
+    item: &ImageItem,
+    (min_width, min_height): (Option<u32>, Option<u32>),
+    min_file_size: Option<u64>,
+) -> bool {
+    if min_width.is_some() || min_height.is_some() {
+        let Some((width, height)) = item.image_dimensions() else {
+            return false;
+        };
+        if width < min_width.unwrap_or(0) || height < min_height.unwrap_or(0) {
+            return false;
+        }
+    }
+    match min_file_size {
+        Some(min) => item.file_size().is_some_and(|size| size >= min),
+        None => true,
+    }
+}
+
+/// Items without a parseable date never match once either bound is set.
+/// Millisecond metadata timestamps are compared at millisecond precision.
+fn item_matches_date_range(item: &ImageItem, after: Option<i64>, before: Option<i64>) -> bool {
+    if after.is_none() && before.is_none() {
+        return true;
+    }
+    let Some(date) = item.merged_date().as_deref().and_then(parse_date) else {
+        return false;
+    };
+    let millis = date.timestamp_millis();
+    after.is_none_or(|after| millis >= after.saturating_mul(1000))
+        && before.is_none_or(|before| millis < before.saturating_mul(1000))
+}
+
 pub fn scan_roots(roots: &[PathBuf]) -> Result<ScanReport, BooruError> {
-    let mut index = Index::default();
+    scan_roots_excluding(roots, &[])
+}
+
+/// Like [`scan_roots`], but skips images whose path relative to their root
+/// matches one of `exclude_globs`. Excluded files produce no warnings.
+pub fn scan_roots_excluding(
+    roots: &[PathBuf],
+    exclude_globs: &[String],
+) -> Result<ScanReport, BooruError> {
+    let mut config = BooruConfig::with_roots(Vec::new());
+    config.roots = roots.to_vec();
+    let excludes = build_exclude_set(exclude_globs)?;
     let mut warnings = Vec::new();
+    let files = find_metadata_files(&config, &excludes, &mut warnings);
+    Ok(load_items(
+        files,
+        &config.video_extensions,
+        warnings,
+        None,
+        None,
+    ))
+}
+
+/// Metadata files parsed in parallel before their items are indexed and
+/// streamed, in order, to whoever is watching the scan.
+const LOAD_CHUNK_SIZE: usize = 256;
+
+fn load_items(
+    files: Vec<(PathBuf, PathBuf)>,
+    video_extensions: &[String],
+    mut warnings: Vec<ScanWarning>,
+    progress: Option<&dyn ProgressObserver>,
+    items: Option<&Sender<ImageItem>>,
+) -> ScanReport {
+    let mut index = Index::default();
+    for chunk in files.chunks(LOAD_CHUNK_SIZE) {
+        let loaded = chunk
+            .par_iter()
+            .map(|(meta_path, image_path)| {
+                let mut mtimes = HashMap::new();
+                let mut item_warnings = Vec::new();
+                let item = load_item(
+                    meta_path,
+                    image_path.clone(),
+                    video_extensions,
+                    &mut mtimes,
+                    &mut item_warnings,
+                );
+                if let Some(progress) = progress {
+                    progress.inc(1);
+                }
+                (item, mtimes, item_warnings)
+            })
+            .collect::<Vec<_>>();
+        for (item, mtimes, item_warnings) in loaded {
+            index.last_seen_mtime.extend(mtimes);
+            warnings.extend(item_warnings);
+            if let Some(item) = item {
+                if let Some(items) = items {
+                    // A dropped receiver only means nobody is watching anymore.
+                    let _ = items.send(item.clone());
+                }
+                index.push(item);
+            }
+        }
+    }
+    ScanReport { index, warnings }
+}
+
+/// Walks the configured roots for gallery-dl metadata files that have an
+/// image next to them, returning `(metadata path, image path)` pairs in root
+/// order and by image path within a root, whatever order the directories
+/// list them in.
+fn find_metadata_files(
+    config: &BooruConfig,
+    excludes: &GlobSet,
+    warnings: &mut Vec<ScanWarning>,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut files = Vec::new();
+    for file in walk_roots(config, excludes, warnings) {
+        let is_edits = file.meta_path.to_string_lossy().ends_with(".booru.json");
+        if !file.image_path.exists() {
+            warnings.push(if is_edits {
+                ScanWarning {
+                    path: file.meta_path,
+                    message: "edits for a missing image".to_string(),
+                }
+            } else {
+                ScanWarning {
+                    path: file.image_path,
+                    message: "missing image for metadata".to_string(),
+                }
+            });
+            continue;
+        }
+        if is_edits {
+            continue;
+        }
+        files.push((file.meta_path, file.image_path));
+    }
+    files
+}
+
+/// A file a scan walk kept: a sidecar, or with `exif` a bare image.
+struct WalkedFile {
+    meta_path: PathBuf,
+    image_path: PathBuf,
+    /// `meta_path` is a `*.json` or `*.booru.json` file found on disk rather
+    /// than the missing sidecar of an EXIF-only image.
+    is_sidecar: bool,
+}
 
-    for root in roots {
+/// Walks `config.roots` the way a scan does, honouring `.booruignore`,
+/// `exclude_globs`, `exclude_paths`, `max_depth` and `follow_symlinks`.
+/// Within a root, files are sorted by image path; edit files and sidecars
+/// whose image is missing are included.
+fn walk_roots(
+    config: &BooruConfig,
+    excludes: &GlobSet,
+    warnings: &mut Vec<ScanWarning>,
+) -> Vec<WalkedFile> {
+    let exclude_paths = config
+        .exclude_paths
+        .iter()
+        .filter_map(|path| fs::canonicalize(path).ok())
+        .collect::<Vec<_>>();
+    let is_excluded_path = |path: &Path| {
+        !exclude_paths.is_empty()
+            && fs::canonicalize(path).is_ok_and(|path| {
+                exclude_paths
+                    .iter()
+                    .any(|excluded| path.starts_with(excluded))
+            })
+    };
+    let mut files = Vec::new();
+    for root in &config.roots {
+        let root_start = files.len();
         if !root.exists() {
             warnings.push(ScanWarning {
                 path: root.clone(),
@@ -668,73 +1951,297 @@ pub fn scan_roots(roots: &[PathBuf]) -> Result<ScanReport, BooruError> {
             continue;
         }
 
-        for entry in WalkDir::new(root).into_iter().filter_map(Result::ok) {
+        let mut walker = WalkDir::new(root).follow_links(config.follow_symlinks);
+        if let Some(max_depth) = config.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+        let mut ignores = IgnoreStack::default();
+        let mut entries = walker.into_iter();
+        while let Some(entry) = entries.next() {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let path = entry.path();
+            ignores.leave_to(path);
+            if entry.file_type().is_dir() {
+                if entry.depth() > 0 && (ignores.is_ignored(path) || is_excluded_path(path)) {
+                    entries.skip_current_dir();
+                } else {
+                    ignores.enter(path, warnings);
+                }
+                continue;
+            }
             if !entry.file_type().is_file() {
                 continue;
             }
-            let path = entry.path();
             let Some(file_name) = path.file_name().and_then(|s| s.to_str()) else {
                 continue;
             };
             if file_name == ALIAS_FILE_NAME {
                 continue;
             }
-            if !file_name.ends_with(".json") || file_name.ends_with(".booru.json") {
+            let (meta_path, image_path, is_sidecar) = if file_name.ends_with(".json") {
+                let Some(image_path) = image_path_for_sidecar(path) else {
+                    continue;
+                };
+                (path.to_path_buf(), image_path, true)
+            } else if let Some(meta_path) = exif_only_metadata_path(path) {
+                (meta_path, path.to_path_buf(), false)
+            } else {
+                continue;
+            };
+            if ignores.is_ignored(path) || ignores.is_ignored(&image_path) {
+                continue;
+            }
+            if is_excluded_path(path) || is_excluded_path(&image_path) {
                 continue;
             }
+            if !excludes.is_empty() {
+                let relative = image_path.strip_prefix(root).unwrap_or(&image_path);
+                if excludes.is_match(relative) {
+                    continue;
+                }
+            }
+            files.push(WalkedFile {
+                meta_path,
+                image_path,
+                is_sidecar,
+            });
+        }
+        files[root_start..].sort_by(|lhs, rhs| lhs.image_path.cmp(&rhs.image_path));
+    }
+    files
+}
+
+/// With the `exif` feature, an image that has no gallery-dl `.json` next to
+/// it is still indexed from its embedded EXIF; the returned metadata path
+/// does not exist.
+#[cfg(feature = "exif")]
+fn exif_only_metadata_path(path: &Path) -> Option<PathBuf> {
+    if !crate::exif::has_exif_extension(path) {
+        return None;
+    }
+    let meta_path = metadata_path_for_image(path);
+    (!meta_path.exists()).then_some(meta_path)
+}
+
+#[cfg(not(feature = "exif"))]
+fn exif_only_metadata_path(_path: &Path) -> Option<PathBuf> {
+    None
+}
+
+/// Sidecar files (`*.json` and `*.booru.json`) a scan of `config` would
+/// read whose image no longer exists, sorted by path. `alias.json` is never
+/// reported.
+pub fn find_orphan_metadata(config: &BooruConfig) -> Result<Vec<PathBuf>, BooruError> {
+    Ok(sidecar_files(config)?
+        .into_iter()
+        .filter(|path| image_path_for_sidecar(path).is_some_and(|image| !image.exists()))
+        .collect())
+}
+
+/// The `*.booru.json` files among [`find_orphan_metadata`]. They hold edits
+/// made by hand, so scans warn about each of them as well.
+pub fn scan_orphans(config: &BooruConfig) -> Result<Vec<PathBuf>, BooruError> {
+    Ok(find_orphan_metadata(config)?
+        .into_iter()
+        .filter(|path| path.to_string_lossy().ends_with(".booru.json"))
+        .collect())
+}
+
+/// Every `*.json` and `*.booru.json` file a scan of `config` would read
+/// except `alias.json`, sorted by path.
+pub(crate) fn sidecar_files(config: &BooruConfig) -> Result<Vec<PathBuf>, BooruError> {
+    let excludes = build_exclude_set(&config.exclude_globs)?;
+    let mut files = walk_roots(config, &excludes, &mut Vec::new())
+        .into_iter()
+        .filter(|file| file.is_sidecar)
+        .map(|file| file.meta_path)
+        .collect::<Vec<_>>();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// The image a `*.json` or `*.booru.json` file describes.
+pub(crate) fn image_path_for_sidecar(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_str()?;
+    if file_name == ALIAS_FILE_NAME {
+        return None;
+    }
+    let image_name = file_name
+        .strip_suffix(".booru.json")
+        .or_else(|| file_name.strip_suffix(".json"))?;
+    (!image_name.is_empty()).then(|| path.with_file_name(image_name))
+}
+
+pub const IGNORE_FILE_NAME: &str = ".booruignore";
+
+/// The `.booruignore` files of the directories enclosing the current walk
+/// entry, innermost last. Each file holds one glob per line, matched against
+/// paths relative to the directory containing it; blank lines and `#`
+/// comments are skipped. `!` negation is not supported.
+#[derive(Default)]
+struct IgnoreStack {
+    levels: Vec<(PathBuf, GlobSet)>,
+}
+
+impl IgnoreStack {
+    /// Drops the levels of directories the walk has left.
+    fn leave_to(&mut self, path: &Path) {
+        while self
+            .levels
+            .last()
+            .is_some_and(|(dir, _)| !path.starts_with(dir))
+        {
+            self.levels.pop();
+        }
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        self.levels.iter().any(|(dir, globs)| {
+            path.strip_prefix(dir)
+                .is_ok_and(|relative| globs.is_match(relative))
+        })
+    }
+
+    fn enter(&mut self, dir: &Path, warnings: &mut Vec<ScanWarning>) {
+        let path = dir.join(IGNORE_FILE_NAME);
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+            Err(err) => {
+                warnings.push(ScanWarning {
+                    path,
+                    message: format!("failed to read ignore file: {err}"),
+                });
+                return;
+            }
+        };
 
-            let image_path = path.with_extension("");
-            if !image_path.exists() {
+        let mut builder = GlobSetBuilder::new();
+        for line in data.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('!') {
                 warnings.push(ScanWarning {
-                    path: image_path.clone(),
-                    message: "missing image for metadata".to_string(),
+                    path: path.clone(),
+                    message: format!("negated pattern {line:?} is not supported"),
                 });
                 continue;
             }
-
-            let original = match read_json(path) {
-                Ok(value) => value,
-                Err(err) => {
-                    warnings.push(ScanWarning {
-                        path: path.to_path_buf(),
-                        message: format!("{err}"),
-                    });
-                    continue;
+            match Glob::new(line.trim_matches('/')) {
+                Ok(glob) => {
+                    builder.add(glob);
                 }
-            };
+                Err(err) => warnings.push(ScanWarning {
+                    path: path.clone(),
+                    message: format!("invalid pattern {line:?}: {err}"),
+                }),
+            }
+        }
+        match builder.build() {
+            Ok(globs) if !globs.is_empty() => self.levels.push((dir.to_path_buf(), globs)),
+            Ok(_) => {}
+            Err(err) => warnings.push(ScanWarning {
+                path,
+                message: format!("invalid ignore file: {err}"),
+            }),
+        }
+    }
+}
 
-            let booru_path = booru_path_for_image(&image_path);
-            let edits = match BooruEdits::load(&booru_path) {
-                Ok(Some(edits)) => edits,
-                Ok(None) => BooruEdits::default(),
-                Err(err) => {
-                    warnings.push(ScanWarning {
-                        path: booru_path.clone(),
-                        message: format!("failed to parse booru edits: {err}"),
-                    });
-                    BooruEdits::default()
-                }
-            };
+fn build_exclude_set(exclude_globs: &[String]) -> Result<GlobSet, BooruError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in exclude_globs {
+        let glob = Glob::new(pattern).map_err(|source| BooruError::Glob {
+            pattern: pattern.clone(),
+            source,
+        })?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|source| BooruError::Glob {
+        pattern: exclude_globs.join(", "),
+        source,
+    })
+}
 
-            let image_path = fs::canonicalize(&image_path).unwrap_or(image_path);
-            let meta_path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
-            let booru_path = fs::canonicalize(&booru_path).unwrap_or(booru_path);
+/// Mtimes are taken before reading, so a write that races the read is seen
+/// as a change on the next incremental rescan.
+fn load_item(
+    meta_path: &Path,
+    image_path: PathBuf,
+    video_extensions: &[String],
+    last_seen_mtime: &mut HashMap<PathBuf, i64>,
+    warnings: &mut Vec<ScanWarning>,
+) -> Option<ImageItem> {
+    let booru_path = booru_path_for_image(&image_path);
+    let meta_mtime = file_mtime(meta_path);
+    let booru_mtime = file_mtime(&booru_path);
+    let original = read_original(meta_path, &image_path, warnings)?;
+    let edits = read_edits(&booru_path, warnings);
+
+    let image_path = fs::canonicalize(&image_path).unwrap_or(image_path);
+    let meta_path = fs::canonicalize(meta_path).unwrap_or_else(|_| meta_path.to_path_buf());
+    let booru_path = fs::canonicalize(&booru_path).unwrap_or(booru_path);
+    record_mtime(last_seen_mtime, &meta_path, meta_mtime);
+    record_mtime(last_seen_mtime, &booru_path, booru_mtime);
+
+    Some(
+        ImageItem::new(image_path, meta_path, booru_path, original, edits)
+            .with_video_extensions(video_extensions),
+    )
+}
 
-            let item = ImageItem {
-                image_path: image_path.clone(),
-                meta_path,
-                booru_path,
-                original,
-                edits,
-            };
+#[cfg_attr(not(feature = "exif"), allow(unused_variables))]
+fn read_original(
+    meta_path: &Path,
+    image_path: &Path,
+    warnings: &mut Vec<ScanWarning>,
+) -> Option<Value> {
+    match read_json(meta_path) {
+        Ok(value) => Some(value),
+        // Images without EXIF are not library items, so no warning either.
+        #[cfg(feature = "exif")]
+        Err(BooruError::Io { source, .. }) if source.kind() == std::io::ErrorKind::NotFound => {
+            crate::exif::extract_exif_metadata(image_path).ok()
+        }
+        Err(err) => {
+            warnings.push(ScanWarning {
+                path: meta_path.to_path_buf(),
+                message: format!("{err}"),
+            });
+            None
+        }
+    }
+}
 
-            let idx = index.items.len();
-            index.by_path.insert(image_path, idx);
-            index.items.push(item);
+fn read_edits(booru_path: &Path, warnings: &mut Vec<ScanWarning>) -> BooruEdits {
+    match BooruEdits::load(booru_path) {
+        Ok(Some(edits)) => edits,
+        Ok(None) => BooruEdits::default(),
+        Err(err) => {
+            warnings.push(ScanWarning {
+                path: booru_path.to_path_buf(),
+                message: format!("failed to parse booru edits: {err}"),
+            });
+            BooruEdits::default()
         }
     }
+}
+
+/// Modification time in nanoseconds since the Unix epoch.
+fn file_mtime(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    i64::try_from(since_epoch.as_nanos()).ok()
+}
 
-    Ok(ScanReport { index, warnings })
+fn record_mtime(last_seen_mtime: &mut HashMap<PathBuf, i64>, path: &Path, mtime: Option<i64>) {
+    if let Some(mtime) = mtime {
+        last_seen_mtime.insert(path.to_path_buf(), mtime);
+    }
 }
 
 pub fn load_item_for_image(image_path: &Path) -> Result<ImageItem, BooruError> {
@@ -747,13 +2254,13 @@ pub fn load_item_for_image(image_path: &Path) -> Result<ImageItem, BooruError> {
         None => BooruEdits::default(),
     };
 
-    Ok(ImageItem {
-        image_path: image_path.to_path_buf(),
+    Ok(ImageItem::new(
+        image_path.to_path_buf(),
         meta_path,
         booru_path,
         original,
         edits,
-    })
+    ))
 }
 
 fn read_json(path: &Path) -> Result<Value, BooruError> {
@@ -767,49 +2274,217 @@ fn read_json(path: &Path) -> Result<Value, BooruError> {
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
+/// How long [`LibraryWatcher`] keeps collecting file events after the first
+/// one before it reports a change.
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
 
-    use serde_json::json;
+/// Watches the library roots and reports metadata, edit and image changes
+/// once per [`WATCH_DEBOUNCE`] window. Dropping it stops the watch and joins
+/// the debounce thread.
+pub struct LibraryWatcher {
+    watcher: Option<RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
 
-    use super::{scan_roots, ImageItem, Index, Library, SearchQuery, SearchSort};
-    use crate::config::BooruConfig;
-    use crate::metadata::BooruEdits;
+impl LibraryWatcher {
+    /// Calls `on_change` on a background thread after each burst of changes.
+    /// Roots that do not exist are skipped.
+    pub fn new<F>(config: &BooruConfig, mut on_change: F) -> Result<Self, BooruError>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).map_err(|source| BooruError::Watch { source })?;
+        for root in config.roots.iter().filter(|root| root.exists()) {
+            watcher
+                .watch(root, RecursiveMode::Recursive)
+                .map_err(|source| BooruError::Watch { source })?;
+        }
 
-    fn make_item(original: serde_json::Value) -> ImageItem {
-        ImageItem {
-            image_path: PathBuf::new(),
-            meta_path: PathBuf::new(),
-            booru_path: PathBuf::new(),
-            original,
-            edits: BooruEdits::default(),
+        let thread = thread::spawn(move || {
+            while wait_for_change(&rx) {
+                on_change();
+            }
+        });
+
+        Ok(Self {
+            watcher: Some(watcher),
+            thread: Some(thread),
+        })
+    }
+
+    /// Runs [`Library::rescan_incremental`] on the shared library after each
+    /// burst of changes and hands the outcome to `on_rescan`.
+    pub fn for_library<F>(
+        library: Arc<RwLock<Library>>,
+        mut on_rescan: F,
+    ) -> Result<Self, BooruError>
+    where
+        F: FnMut(Result<RescanReport, BooruError>) + Send + 'static,
+    {
+        let config = library
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .config
+            .clone();
+        Self::new(&config, move || {
+            let result = library
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .rescan_incremental();
+            on_rescan(result);
+        })
+    }
+}
+
+impl Drop for LibraryWatcher {
+    fn drop(&mut self) {
+        // Dropping the watcher closes the event channel, which ends the
+        // debounce loop.
+        self.watcher.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
         }
     }
+}
 
-    fn make_item_with_path(path: &str, original: serde_json::Value) -> ImageItem {
-        ImageItem {
-            image_path: PathBuf::from(path),
-            meta_path: PathBuf::new(),
-            booru_path: PathBuf::new(),
-            original,
-            edits: BooruEdits::default(),
+/// Blocks until a relevant event arrives, then drains events until the
+/// debounce window closes. Returns `false` once the watcher is gone.
+fn wait_for_change(rx: &Receiver<notify::Result<Event>>) -> bool {
+    loop {
+        match rx.recv() {
+            Ok(Ok(event)) if is_library_event(&event) => break,
+            Ok(_) => continue,
+            Err(_) => return false,
         }
     }
 
-    #[test]
-    fn merged_sensitive_parses_sensitive_keywords() {
-        let nsfw_item = make_item(json!({ "sensitive": "nsfw" }));
-        let sfw_item = make_item(json!({ "sensitive": "sfw" }));
-        assert!(nsfw_item.merged_sensitive());
-        assert!(!sfw_item.merged_sensitive());
+    let deadline = Instant::now() + WATCH_DEBOUNCE;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(timeout) {
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return false,
+        }
     }
+}
 
-    #[test]
-    fn merged_sensitive_ignores_unrelated_field() {
-        let item = make_item(json!({ "score": "explicit" }));
-        assert!(!item.merged_sensitive());
+/// Reads are ignored, otherwise a rescan would trigger the next one.
+fn is_library_event(event: &Event) -> bool {
+    match event.kind {
+        EventKind::Access(_) => false,
+        EventKind::Remove(RemoveKind::Folder) => true,
+        _ => event.paths.iter().any(|path| is_library_path(path)),
+    }
+}
+
+fn is_library_path(path: &Path) -> bool {
+    let Some(file_name) = path.file_name().and_then(|s| s.to_str()) else {
+        return false;
+    };
+    if file_name == ALIAS_FILE_NAME {
+        return false;
+    }
+    file_name == IGNORE_FILE_NAME
+        || file_name.ends_with(".json")
+        || path.is_dir()
+        || metadata_path_for_image(path).is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{mpsc, Arc, RwLock};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    use super::{
+        author_frequency, expand_search_term, find_orphan_metadata, item_matches_search_terms,
+        parse_search_term, scan_orphans, scan_roots, scan_roots_excluding, split_excluded_terms,
+        tag_completions, tag_frequency, ImageItem, Index, Library, LibraryStats, LibraryWatcher,
+        SearchMode, SearchQuery, SearchSort, SearchTerm, SensitiveFilter, TagPattern,
+        IGNORE_FILE_NAME, WATCH_DEBOUNCE,
+    };
+    use crate::alias::{alias_map_from_groups, ALIAS_FILE_NAME};
+    use crate::config::BooruConfig;
+    use crate::error::BooruError;
+    use crate::metadata::{BooruEdits, Rating};
+    use crate::progress::ProgressObserver;
+
+    fn make_item(original: serde_json::Value) -> ImageItem {
+        ImageItem::new(
+            PathBuf::new(),
+            PathBuf::new(),
+            PathBuf::new(),
+            original,
+            BooruEdits::default(),
+        )
+    }
+
+    fn make_item_with_path(path: &str, original: serde_json::Value) -> ImageItem {
+        ImageItem::new(
+            PathBuf::from(path),
+            PathBuf::new(),
+            PathBuf::new(),
+            original,
+            BooruEdits::default(),
+        )
+    }
+
+    #[test]
+    fn merged_sensitive_parses_sensitive_keywords() {
+        let nsfw_item = make_item(json!({ "sensitive": "nsfw" }));
+        let sfw_item = make_item(json!({ "sensitive": "sfw" }));
+        assert!(nsfw_item.merged_sensitive());
+        assert!(!sfw_item.merged_sensitive());
+    }
+
+    #[test]
+    fn merged_rating_maps_danbooru_codes() {
+        let item = make_item(json!({ "category": "danbooru", "rating": "q" }));
+        assert_eq!(item.merged_rating(), Some(Rating::Questionable));
+        assert!(item.merged_sensitive());
+
+        let item = make_item(json!({ "category": "danbooru", "rating": "s" }));
+        assert_eq!(item.merged_rating(), Some(Rating::Sensitive));
+        assert!(!item.merged_sensitive());
+
+        let item = make_item(json!({ "category": "yandere", "rating": "s" }));
+        assert_eq!(item.merged_rating(), Some(Rating::General));
+    }
+
+    #[test]
+    fn merged_rating_lets_a_sensitive_flag_raise_the_source_rating() {
+        let item = make_item(json!({ "rating": "g", "sensitive": true }));
+        assert_eq!(item.merged_rating(), Some(Rating::Questionable));
+        assert!(item.merged_sensitive());
+
+        let item = make_item(json!({ "rating": "e", "nsfw": "yes" }));
+        assert_eq!(item.merged_rating(), Some(Rating::Explicit));
+
+        let item = make_item(json!({ "rating": "q", "sensitive": false }));
+        assert_eq!(item.merged_rating(), Some(Rating::Questionable));
+    }
+
+    #[test]
+    fn merged_rating_respects_edits_and_sensitive_override() {
+        let mut item = make_item(json!({ "rating": "e" }));
+        item.edits.rating = Some(Rating::General);
+        assert_eq!(item.merged_rating(), Some(Rating::General));
+        assert!(!item.merged_sensitive());
+
+        item.edits.sensitive = Some(true);
+        assert!(item.merged_sensitive());
+    }
+
+    #[test]
+    fn merged_sensitive_ignores_unrelated_field() {
+        let item = make_item(json!({ "score": "explicit" }));
+        assert!(!item.merged_sensitive());
     }
 
     #[test]
@@ -897,6 +2572,149 @@ mod tests {
         );
     }
 
+    #[test]
+    fn author_url_twitter_from_handle() {
+        let item = make_item(json!({
+            "category": "twitter",
+            "tweet_id": "12345",
+            "author": { "name": "@alice", "nick": "Alice" },
+        }));
+        assert_eq!(item.author_url().as_deref(), Some("https://x.com/alice"));
+    }
+
+    #[test]
+    fn author_url_pixiv_from_user_id() {
+        let item = make_item(json!({
+            "category": "pixiv",
+            "id": 118_000_001,
+            "user": { "id": 1_234_567, "name": "bob" },
+        }));
+        assert_eq!(
+            item.author_url().as_deref(),
+            Some("https://www.pixiv.net/users/1234567")
+        );
+
+        let item = make_item(json!({
+            "category": "pixiv",
+            "id": 118_000_001,
+        }));
+        assert_eq!(item.author_url(), None);
+    }
+
+    #[test]
+    fn platform_url_pixiv_from_id_including_ugoira() {
+        let item = make_item(json!({
+            "category": "pixiv",
+            "id": 118_000_001,
+            "num": 2,
+            "suffix": "_p2",
+        }));
+        assert_eq!(
+            item.platform_url().as_deref(),
+            Some("https://www.pixiv.net/artworks/118000001")
+        );
+
+        let item = make_item(json!({
+            "category": "pixiv",
+            "id": 118_000_002,
+            "type": "ugoira",
+            "frames": [{"file": "000000.jpg", "delay": 60}],
+        }));
+        assert_eq!(
+            item.platform_url().as_deref(),
+            Some("https://www.pixiv.net/artworks/118000002")
+        );
+    }
+
+    #[test]
+    fn platform_url_pixiv_falls_back_to_illust_id() {
+        let item = make_item(json!({
+            "category": "pixiv",
+            "illust_id": "99887766",
+        }));
+        assert_eq!(
+            item.platform_url().as_deref(),
+            Some("https://www.pixiv.net/artworks/99887766")
+        );
+    }
+
+    #[test]
+    fn platform_url_pixiv_uses_recorded_pixiv_url() {
+        let item = make_item(json!({
+            "category": "pixiv",
+            "pixiv_url": "https://www.pixiv.net/en/artworks/12345",
+        }));
+        assert_eq!(
+            item.platform_url().as_deref(),
+            Some("https://www.pixiv.net/en/artworks/12345")
+        );
+    }
+
+    #[test]
+    fn platform_url_gelbooru_from_id() {
+        let item = make_item(json!({
+            "category": "gelbooru",
+            "id": 9_876_543,
+            "md5": "0123456789abcdef0123456789abcdef",
+        }));
+        assert_eq!(
+            item.platform_url().as_deref(),
+            Some("https://gelbooru.com/index.php?page=post&s=view&id=9876543")
+        );
+    }
+
+    #[test]
+    fn platform_url_e621_and_e926_share_posts() {
+        for category in ["e621", "e926"] {
+            let item = make_item(json!({
+                "category": category,
+                "id": 4_242_424,
+                "file": {"ext": "png", "md5": "abc"},
+            }));
+            assert_eq!(
+                item.platform_url().as_deref(),
+                Some("https://e621.net/posts/4242424")
+            );
+        }
+    }
+
+    #[test]
+    fn platform_url_deviantart_prefers_recorded_url() {
+        let item = make_item(json!({
+            "category": "deviantart",
+            "index": 1_012_345_678,
+            "deviationid": "6A1B2C3D-0000-1111-2222-333344445555",
+            "url": "https://www.deviantart.com/someartist/art/Sunset-1012345678",
+        }));
+        assert_eq!(
+            item.platform_url().as_deref(),
+            Some("https://www.deviantart.com/someartist/art/Sunset-1012345678")
+        );
+
+        let item = make_item(json!({
+            "category": "deviantart",
+            "index": 1_012_345_678,
+        }));
+        assert_eq!(
+            item.platform_url().as_deref(),
+            Some("https://www.deviantart.com/deviation/1012345678")
+        );
+    }
+
+    #[test]
+    fn platform_url_fanbox_from_creator_and_id() {
+        let item = make_item(json!({
+            "category": "fanbox",
+            "id": "7654321",
+            "creatorId": "someartist",
+            "title": "Monthly sketches",
+        }));
+        assert_eq!(
+            item.platform_url().as_deref(),
+            Some("https://someartist.fanbox.cc/posts/7654321")
+        );
+    }
+
     #[test]
     fn platform_url_weibo_from_mblogid() {
         let item = make_item(json!({
@@ -986,6 +2804,59 @@ mod tests {
         assert_eq!(item.merged_author().as_deref(), Some("myowa"));
     }
 
+    #[test]
+    fn tag_counts_sort_by_count_then_name_and_skip_sensitive() {
+        let mut index = Index::default();
+        index
+            .items
+            .push(make_item(json!({ "tags": ["sky", "sea", "sky"] })));
+        index
+            .items
+            .push(make_item(json!({ "tags": ["sea", "cloud"] })));
+        index.items.push(make_item(
+            json!({ "tags": ["sea", "sky"], "sensitive": "nsfw" }),
+        ));
+
+        let pairs = |counts: Vec<(String, usize)>| {
+            counts
+                .into_iter()
+                .map(|(tag, count)| format!("{tag}={count}"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            pairs(index.tag_counts(false)),
+            vec!["sea=2", "cloud=1", "sky=1"]
+        );
+        assert_eq!(
+            pairs(index.tag_counts(true)),
+            vec!["sea=3", "sky=2", "cloud=1"]
+        );
+    }
+
+    #[test]
+    fn tag_vocabulary_counts_items_and_follows_edits() {
+        let mut index = Index::default();
+        index.push(make_item(json!({ "tags": ["sky", "sea", "sky"] })));
+        index.push(make_item(json!({ "tags": ["sea", "cloud"] })));
+        index.push(make_item(
+            json!({ "tags": ["sea", "sky"], "sensitive": "nsfw" }),
+        ));
+        assert_eq!(
+            index.tag_vocabulary(),
+            vec![
+                ("sea".to_string(), 3),
+                ("sky".to_string(), 2),
+                ("cloud".to_string(), 1),
+            ]
+        );
+
+        index.remove(1);
+        assert_eq!(
+            index.tag_vocabulary(),
+            vec![("sea".to_string(), 2), ("sky".to_string(), 2)]
+        );
+    }
+
     #[test]
     fn library_search_expands_aliases_when_enabled() {
         let unique = SystemTime::now()
@@ -1006,9 +2877,7 @@ mod tests {
         })));
 
         let library = Library {
-            config: BooruConfig {
-                roots: vec![root.clone()],
-            },
+            config: BooruConfig::with_roots(vec![root.clone()]),
             index,
             warnings: Vec::new(),
         };
@@ -1042,9 +2911,7 @@ mod tests {
         })));
 
         let library = Library {
-            config: BooruConfig {
-                roots: vec![root.clone()],
-            },
+            config: BooruConfig::with_roots(vec![root.clone()]),
             index,
             warnings: Vec::new(),
         };
@@ -1059,79 +2926,1504 @@ mod tests {
     }
 
     #[test]
-    fn library_search_filters_by_source_url() {
+    fn library_search_all_mode_requires_every_term() {
+        let mut index = Index::default();
+        index.items.push(make_item(json!({ "tags": ["sky"] })));
+        index
+            .items
+            .push(make_item(json!({ "tags": ["sky", "sea"] })));
+        index.items.push(make_item(json!({ "tags": ["sea"] })));
+
+        let library = Library {
+            config: BooruConfig::with_roots(Vec::new()),
+            index,
+            warnings: Vec::new(),
+        };
+        let terms = vec!["sky".to_string(), "sea".to_string()];
+
+        let any = library.search(SearchQuery::new(terms.clone()));
+        assert_eq!(any.indices, vec![0, 1, 2]);
+        let all = library.search(SearchQuery::new(terms).with_mode(SearchMode::All));
+        assert_eq!(all.indices, vec![1]);
+    }
+
+    #[test]
+    fn library_search_match_all_combines_author_and_tag_terms() {
         let mut index = Index::default();
         index.items.push(make_item(json!({
-            "category": "misc",
-            "source_url": "https://example.com/a"
+            "author": "AlicePainter",
+            "tags": ["flower_garden"],
         })));
         index.items.push(make_item(json!({
-            "category": "misc",
-            "source_url": "https://example.com/b"
+            "author": "AlicePainter",
+            "tags": ["night_sky"],
         })));
         index.items.push(make_item(json!({
-            "category": "misc"
+            "author": "Bob",
+            "tags": ["flower_garden"],
         })));
 
         let library = Library {
-            config: BooruConfig::default(),
+            config: BooruConfig::with_roots(Vec::new()),
             index,
             warnings: Vec::new(),
         };
+        let terms = vec!["alice".to_string(), "garden".to_string()];
 
-        let result = library.search(
-            SearchQuery::new(Vec::new()).with_source_url(Some("https://example.com/b".to_string())),
-        );
-        assert_eq!(result.indices, vec![1]);
+        let result = library.search(SearchQuery::new(terms.clone()).with_match_all(true));
+        assert_eq!(result.indices, vec![0]);
+        let result = library.search(SearchQuery::new(terms).with_match_all(false));
+        assert_eq!(result.indices, vec![0, 1, 2]);
     }
 
     #[test]
-    fn library_search_can_sort_by_file_name() {
+    fn library_search_all_mode_matches_alias_groups_per_term() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-search-mode-all-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("alias.json"), "[[\"yurucamp\", \"ゆるキャン\"]]").unwrap();
+
         let mut index = Index::default();
-        index.items.push(make_item_with_path(
-            "/tmp/zeta.jpg",
-            json!({
-                "category": "misc",
-            }),
-        ));
-        index.items.push(make_item_with_path(
-            "/tmp/alpha.jpg",
-            json!({
-                "category": "misc",
-            }),
-        ));
-        index.items.push(make_item_with_path(
-            "/tmp/alpha.png",
-            json!({
-                "category": "misc",
-            }),
-        ));
+        index
+            .items
+            .push(make_item(json!({ "tags": ["ゆるキャン", "tent"] })));
+        index.items.push(make_item(json!({ "tags": ["yurucamp"] })));
 
         let library = Library {
-            config: BooruConfig::default(),
+            config: BooruConfig::with_roots(vec![root.clone()]),
             index,
             warnings: Vec::new(),
         };
 
-        let result =
-            library.search(SearchQuery::new(Vec::new()).with_sort(SearchSort::FileNameAsc));
-        assert_eq!(result.indices, vec![1, 2, 0]);
+        let result = library.search(
+            SearchQuery::new(vec!["yurucamp".to_string(), "tent".to_string()])
+                .with_aliases(true)
+                .with_mode(SearchMode::All),
+        );
+        assert_eq!(result.indices, vec![0]);
+
+        std::fs::remove_dir_all(root).unwrap();
     }
 
     #[test]
-    fn scan_roots_ignores_alias_json() {
+    fn split_excluded_terms_handles_dash_prefix_and_escape() {
+        let (include, exclude) = split_excluded_terms(vec![
+            "landscape".to_string(),
+            "-sensitive".to_string(),
+            "\\-dash_tag".to_string(),
+            "-".to_string(),
+        ]);
+        assert_eq!(
+            include,
+            vec![
+                "landscape".to_string(),
+                "-dash_tag".to_string(),
+                "-".to_string()
+            ]
+        );
+        assert_eq!(exclude, vec!["sensitive".to_string()]);
+    }
+
+    #[test]
+    fn library_search_excludes_negated_terms_and_their_aliases() {
         let unique = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_nanos();
-        let root = std::env::temp_dir().join(format!("lightbooru-scan-alias-{unique}"));
+        let root = std::env::temp_dir().join(format!("lightbooru-search-exclude-{unique}"));
         std::fs::create_dir_all(&root).unwrap();
-        std::fs::write(root.join("alias.json"), "[[\"a\", \"b\"]]").unwrap();
+        std::fs::write(root.join("alias.json"), "[[\"nsfw\", \"sensitive\"]]").unwrap();
 
-        let report = scan_roots(std::slice::from_ref(&root)).expect("scan should succeed");
-        assert!(report.index.items.is_empty());
-        assert!(report.warnings.is_empty());
+        let mut index = Index::default();
+        index
+            .items
+            .push(make_item(json!({ "tags": ["landscape"] })));
+        index
+            .items
+            .push(make_item(json!({ "tags": ["landscape", "nsfw"] })));
+        index.items.push(make_item(json!({ "tags": ["portrait"] })));
+
+        let library = Library {
+            config: BooruConfig::with_roots(vec![root.clone()]),
+            index,
+            warnings: Vec::new(),
+        };
+        let terms = vec!["landscape".to_string(), "-sensitive".to_string()];
+
+        let result = library.search(SearchQuery::new(terms.clone()).with_aliases(true));
+        assert_eq!(result.indices, vec![0]);
+        assert!(result.excluded_terms.contains(&"nsfw".to_string()));
+
+        let result = library.search(SearchQuery::new(vec!["-landscape".to_string()]));
+        assert_eq!(result.indices, vec![2]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn library_search_exclusion_wins_over_positive_match_in_any_field() {
+        let mut index = Index::default();
+        index.items.push(make_item(json!({
+            "tags": ["sky"],
+            "author": "bob",
+        })));
+        index.items.push(make_item(json!({
+            "tags": ["sky", "cloud"],
+            "detail": "drawn by alice",
+        })));
+        index.items.push(make_item(json!({
+            "tags": ["sea"],
+            "author": "alice",
+        })));
+
+        let library = Library {
+            config: BooruConfig::with_roots(Vec::new()),
+            index,
+            warnings: Vec::new(),
+        };
+        let query =
+            |terms: &[&str]| SearchQuery::new(terms.iter().map(|term| term.to_string()).collect());
+
+        let result = library.search(query(&["sky", "-bob"]));
+        assert_eq!(result.indices, vec![1]);
+        let result = library.search(query(&["sky", "sea", "-alice"]));
+        assert_eq!(result.indices, vec![0]);
+        let result = library.search(query(&["sky", "-sky"]));
+        assert!(result.indices.is_empty());
+        let result = library.search(query(&["sky", "cloud", "-bob"]).with_match_all(true));
+        assert_eq!(result.indices, vec![1]);
+    }
+
+    #[test]
+    fn tag_patterns_anchor_on_wildcards() {
+        let item = make_item(json!({
+            "tags": ["flower_garden", "sunflower", "yurucamp"],
+            "detail": "flowers at camp",
+        }));
+        let matches = |term: &str| item_matches_search_terms(&item, &[term.to_string()]);
+
+        assert!(TagPattern::parse("flower*").matches("flower_garden"));
+        assert!(!TagPattern::parse("flower*").matches("sunflower"));
+        assert!(TagPattern::parse("*camp").matches("yurucamp"));
+        assert_eq!(
+            TagPattern::parse("*Flower*"),
+            TagPattern::Substring("flower".to_string())
+        );
+
+        assert!(matches("flower*"));
+        assert!(matches("tag:*camp"));
+        assert!(!matches("*garden_party"));
+        assert!(!matches("tag:sun*flower_"));
+        // Patterns only look at tags; plain terms still search everything.
+        assert!(!matches("flowers*"));
+        assert!(matches("flowers"));
+
+        let sunflower = make_item(json!({ "tags": ["sunflower"] }));
+        assert!(!item_matches_search_terms(
+            &sunflower,
+            &["flower*".to_string()]
+        ));
+        assert!(item_matches_search_terms(
+            &sunflower,
+            &["flower".to_string()]
+        ));
+    }
+
+    #[test]
+    fn wildcard_terms_are_not_alias_expanded() {
+        let alias_map = alias_map_from_groups(&vec![vec![
+            "yurucamp".to_string(),
+            "ゆるキャン".to_string(),
+        ]]);
+        assert_eq!(
+            expand_search_term("yuru*", Some(&alias_map)),
+            vec!["yuru*".to_string()]
+        );
+        assert_eq!(
+            expand_search_term("tag:*camp", Some(&alias_map)),
+            vec!["tag:*camp".to_string()]
+        );
+        assert_eq!(expand_search_term("yurucamp", Some(&alias_map)).len(), 2);
+    }
+
+    #[test]
+    fn parse_search_term_recognizes_field_prefixes() {
+        assert_eq!(
+            parse_search_term("author:alice"),
+            SearchTerm::Author("alice".to_string())
+        );
+        assert_eq!(
+            parse_search_term("TAG:flower"),
+            SearchTerm::Tag("flower".to_string())
+        );
+        assert_eq!(
+            parse_search_term("note:todo"),
+            SearchTerm::Notes("todo".to_string())
+        );
+        assert_eq!(
+            parse_search_term("notes:todo"),
+            SearchTerm::Notes("todo".to_string())
+        );
+        assert_eq!(
+            parse_search_term("detail:sunset"),
+            SearchTerm::Detail("sunset".to_string())
+        );
+        assert_eq!(parse_search_term("sky"), SearchTerm::Any("sky".to_string()));
+        assert_eq!(
+            parse_search_term("https://example.com/1"),
+            SearchTerm::Any("https://example.com/1".to_string())
+        );
+        assert_eq!(
+            parse_search_term("author:"),
+            SearchTerm::Any("author:".to_string())
+        );
+    }
+
+    #[test]
+    fn search_term_display_round_trips_through_query_parsing() {
+        for term in [
+            SearchTerm::Any("sky".to_string()),
+            SearchTerm::Author("alice".to_string()),
+            SearchTerm::Tag("genre:fantasy".to_string()),
+            SearchTerm::Notes("todo".to_string()),
+            SearchTerm::Detail("sunset".to_string()),
+        ] {
+            assert_eq!(parse_search_term(&term.to_string()), term);
+        }
+
+        let query = SearchQuery::new(vec![SearchTerm::Any("-dash".to_string()).to_string()]);
+        assert_eq!(query.terms, vec!["-dash".to_string()]);
+        assert!(query.exclude_terms.is_empty());
+    }
+
+    #[test]
+    fn field_terms_only_match_their_field() {
+        let mut item = make_item(json!({
+            "tags": ["sunset_beach"],
+            "author": "alice",
+            "detail": "drawn at sunset by bob",
+        }));
+        item.edits.notes = Some("Print this".to_string());
+
+        let matches = |term: &str| item_matches_search_terms(&item, &[term.to_string()]);
+        assert!(matches("author:alice"));
+        assert!(!matches("author:bob"));
+        assert!(matches("tag:beach"));
+        assert!(!matches("tag:alice"));
+        assert!(matches("detail:bob"));
+        assert!(!matches("detail:beach"));
+        assert!(matches("note:print"));
+        assert!(!matches("note:beach"));
+        assert!(matches("bob"));
+    }
+
+    #[test]
+    fn bare_terms_also_match_user_notes() {
+        let mut item = make_item(json!({
+            "tags": ["landscape"],
+            "author": "alice",
+        }));
+        assert!(!item_matches_search_terms(&item, &["reprint".to_string()]));
+
+        item.edits.notes = Some("Ask about a Reprint".to_string());
+        assert!(item_matches_search_terms(&item, &["reprint".to_string()]));
+        assert!(item_matches_search_terms(
+            &item,
+            &["note:reprint".to_string()]
+        ));
+        assert!(!item_matches_search_terms(
+            &item,
+            &["tag:reprint".to_string()]
+        ));
+    }
+
+    #[test]
+    fn bare_terms_match_custom_field_values_unless_opted_out() {
+        let mut item = make_item(json!({ "tags": ["landscape"] }));
+        item.edits
+            .extra
+            .insert("collection".to_string(), json!("Summer Trip"));
+        item.edits.extra.insert("score".to_string(), json!(42));
+        item.edits
+            .extra
+            .insert("nested".to_string(), json!({ "place": "harbor" }));
+        item.edits.notes = Some("framed".to_string());
+        assert!(item_matches_search_terms(&item, &["summer".to_string()]));
+        assert!(item_matches_search_terms(&item, &["42".to_string()]));
+        assert!(!item_matches_search_terms(&item, &["harbor".to_string()]));
+
+        let mut index = Index::default();
+        index.push(item);
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        let search = |term: &str, edit_fields: bool| {
+            library
+                .search(SearchQuery::new(vec![term.to_string()]).with_edit_fields(edit_fields))
+                .indices
+        };
+        assert_eq!(search("trip", true), vec![0]);
+        assert_eq!(search("framed", true), vec![0]);
+        assert!(search("trip", false).is_empty());
+        assert!(search("framed", false).is_empty());
+        assert_eq!(search("note:framed", false), vec![0]);
+        assert_eq!(search("landscape", false), vec![0]);
+    }
+
+    #[test]
+    fn library_search_mixes_field_and_bare_terms() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-search-fields-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("alias.json"), "[[\"alice\", \"ありす\"]]").unwrap();
+
+        let mut index = Index::default();
+        index
+            .items
+            .push(make_item(json!({ "tags": ["ありす"], "author": "bob" })));
+        index
+            .items
+            .push(make_item(json!({ "tags": ["cat"], "author": "ありす" })));
+        index
+            .items
+            .push(make_item(json!({ "tags": ["cat"], "author": "alice" })));
+
+        let library = Library {
+            config: BooruConfig::with_roots(vec![root.clone()]),
+            index,
+            warnings: Vec::new(),
+        };
+        let search = |terms: &[&str]| {
+            library
+                .search(
+                    SearchQuery::new(terms.iter().map(|term| term.to_string()).collect())
+                        .with_aliases(true)
+                        .with_match_all(true),
+                )
+                .indices
+        };
+
+        assert_eq!(search(&["tag:alice"]), vec![0]);
+        assert_eq!(search(&["author:alice"]), vec![2]);
+        assert_eq!(search(&["alice"]), vec![0, 1, 2]);
+        assert_eq!(search(&["cat", "-author:alice"]), vec![1]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn library_search_filters_by_source_url() {
+        let mut index = Index::default();
+        index.items.push(make_item(json!({
+            "category": "misc",
+            "source_url": "https://example.com/a"
+        })));
+        index.items.push(make_item(json!({
+            "category": "misc",
+            "source_url": "https://example.com/b"
+        })));
+        index.items.push(make_item(json!({
+            "category": "misc"
+        })));
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+
+        let result = library.search(
+            SearchQuery::new(Vec::new()).with_source_url(Some("https://example.com/b".to_string())),
+        );
+        assert_eq!(result.indices, vec![1]);
+    }
+
+    #[test]
+    fn library_search_can_sort_by_file_name() {
+        let mut index = Index::default();
+        index.items.push(make_item_with_path(
+            "/tmp/zeta.jpg",
+            json!({
+                "category": "misc",
+            }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/alpha.jpg",
+            json!({
+                "category": "misc",
+            }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/alpha.png",
+            json!({
+                "category": "misc",
+            }),
+        ));
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+
+        let result =
+            library.search(SearchQuery::new(Vec::new()).with_sort(SearchSort::FileNameAsc));
+        assert_eq!(result.indices, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn library_search_can_sort_by_date() {
+        let mut index = Index::default();
+        index.items.push(make_item_with_path(
+            "/tmp/b.jpg",
+            json!({ "date": "2024-01-02 00:00:00" }),
+        ));
+        index
+            .items
+            .push(make_item_with_path("/tmp/no-date-z.jpg", json!({})));
+        index.items.push(make_item_with_path(
+            "/tmp/c.jpg",
+            json!({ "date": "2025-06-01T00:00:00Z" }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/a.jpg",
+            json!({ "date": "2024-01-02 00:00:00" }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/no-date-a.jpg",
+            json!({ "date": "not a date" }),
+        ));
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+
+        let result = library.search(SearchQuery::new(Vec::new()).with_sort(SearchSort::DateDesc));
+        assert_eq!(result.indices, vec![2, 3, 0, 4, 1]);
+        let result = library.search(SearchQuery::new(Vec::new()).with_sort(SearchSort::DateAsc));
+        assert_eq!(result.indices, vec![3, 0, 2, 4, 1]);
+    }
+
+    #[test]
+    fn adjacent_items_follow_filtered_sorted_results() {
+        let mut index = Index::default();
+        index.items.push(make_item_with_path(
+            "/tmp/c.jpg",
+            json!({ "tags": ["sky"] }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/a.jpg",
+            json!({ "tags": ["sky"] }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/b.jpg",
+            json!({ "tags": ["sky"], "sensitive": true }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/d.jpg",
+            json!({ "tags": ["sea"] }),
+        ));
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+
+        let query = SearchQuery::new(vec!["sky".to_string()]).with_sort(SearchSort::FileNameAsc);
+        assert_eq!(
+            library.adjacent_items(&query, Path::new("/tmp/b.jpg")),
+            (Some(1), Some(0))
+        );
+        assert_eq!(
+            library.adjacent_items(&query, Path::new("/tmp/a.jpg")),
+            (None, Some(2))
+        );
+
+        let query = query.with_sensitive(SensitiveFilter::HideAll);
+        assert_eq!(
+            library.adjacent_items(&query, Path::new("/tmp/a.jpg")),
+            (None, Some(0))
+        );
+        assert_eq!(
+            library.adjacent_items(&query, Path::new("/tmp/b.jpg")),
+            (None, None)
+        );
+        assert_eq!(
+            library.adjacent_items(&query, Path::new("/tmp/d.jpg")),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn library_search_applies_sensitive_filter() {
+        let mut index = Index::default();
+        for (path, sensitive) in [
+            ("/tmp/a.jpg", false),
+            ("/tmp/b.jpg", true),
+            ("/tmp/c.jpg", false),
+        ] {
+            index.items.push(make_item_with_path(
+                path,
+                json!({ "tags": ["sky"], "sensitive": sensitive }),
+            ));
+        }
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        let search = |filter| {
+            library
+                .search(SearchQuery::new(vec!["sky".to_string()]).with_sensitive(filter))
+                .indices
+        };
+
+        assert_eq!(search(SensitiveFilter::ShowAll), vec![0, 1, 2]);
+        assert_eq!(search(SensitiveFilter::ShowOnly), vec![1]);
+        assert_eq!(search(SensitiveFilter::HideAll), vec![0, 2]);
+        assert_eq!(search(SensitiveFilter::from_show(false)), vec![0, 2]);
+    }
+
+    #[test]
+    fn exact_tag_and_author_use_the_inverted_indices() {
+        let mut index = Index::default();
+        for (path, author, tags) in [
+            ("/tmp/a.jpg", "alice", json!(["sky", "skyline"])),
+            ("/tmp/b.jpg", "bob", json!(["sky"])),
+            ("/tmp/c.jpg", "alice", json!(["skyline"])),
+        ] {
+            index.push(make_item_with_path(
+                path,
+                json!({ "author": author, "tags": tags }),
+            ));
+        }
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        assert_eq!(library.items_by_tag()["sky"], vec![0, 1]);
+        assert_eq!(library.items_by_author()["alice"], vec![0, 2]);
+
+        let search = |query: SearchQuery| library.search(query).indices;
+        assert_eq!(
+            search(SearchQuery::new(Vec::new()).with_exact_tag(Some("sky".to_string()))),
+            vec![0, 1]
+        );
+        assert_eq!(
+            search(
+                SearchQuery::new(Vec::new())
+                    .with_exact_tag(Some("sky".to_string()))
+                    .with_exact_author(Some("alice".to_string()))
+            ),
+            vec![0]
+        );
+        assert!(
+            search(SearchQuery::new(Vec::new()).with_exact_tag(Some("sk".to_string()))).is_empty()
+        );
+    }
+
+    #[test]
+    fn min_size_filters_read_image_headers_once() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-min-size-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        let big = root.join("big.png");
+        let small = root.join("small.png");
+        image::RgbImage::new(120, 80).save(&big).unwrap();
+        image::RgbImage::new(40, 40).save(&small).unwrap();
+
+        let mut index = Index::default();
+        index.push(make_item_with_path(big.to_str().unwrap(), json!({})));
+        index.push(make_item_with_path(small.to_str().unwrap(), json!({})));
+        index.push(make_item_with_path(
+            "/nonexistent/ghost.png",
+            json!({ "width": 500, "height": 500 }),
+        ));
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+
+        let search = |query: SearchQuery| library.search(query).indices;
+        let query = || SearchQuery::new(Vec::new());
+        assert_eq!(
+            search(query().with_min_dimensions(Some(100), None)),
+            vec![0, 2]
+        );
+        assert_eq!(
+            search(query().with_min_dimensions(None, Some(100))),
+            vec![2]
+        );
+        assert_eq!(
+            library.index.items[0].probed_dimensions.get(),
+            Some(&Some((120, 80)))
+        );
+        assert_eq!(library.index.items[2].probed_dimensions.get(), None);
+        assert_eq!(search(query().with_min_file_size(Some(1))), vec![0, 1]);
+
+        let mut item = library.index.items[0].clone();
+        item.set_original(json!({}));
+        assert_eq!(item.probed_dimensions.get(), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn tag_completions_match_prefixes_case_insensitively() {
+        let items = vec![
+            make_item(json!({ "tags": ["Sky", "skyline", "sea"] })),
+            make_item(json!({ "tags": ["skyline", "SKATE"] })),
+            make_item(json!({ "tags": ["skyline", "sky"] })),
+        ];
+
+        assert_eq!(
+            tag_completions(&items, "SK", 10),
+            vec![
+                ("skyline".to_string(), 3),
+                ("SKATE".to_string(), 1),
+                ("Sky".to_string(), 1),
+                ("sky".to_string(), 1),
+            ]
+        );
+        assert_eq!(
+            tag_completions(&items, "sky", 2),
+            vec![("skyline".to_string(), 3), ("Sky".to_string(), 1)]
+        );
+        assert!(tag_completions(&items, "z", 10).is_empty());
+        assert_eq!(
+            tag_completions(&items, "", 1),
+            vec![("skyline".to_string(), 3)]
+        );
+
+        let mut index = Index::default();
+        for item in items {
+            index.push(item);
+        }
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        assert_eq!(
+            library.tag_completions("Se", 10),
+            vec![("sea".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn item_lookups_are_rebuilt_after_rescan() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-lookups-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.jpg"), b"img").unwrap();
+        std::fs::write(root.join("a.jpg.json"), r#"{"tags": ["sky"]}"#).unwrap();
+
+        let mut library = Library::scan(BooruConfig::with_roots(vec![root.clone()])).unwrap();
+        assert_eq!(library.items_by_tag()["sky"].len(), 1);
+        assert!(!library.items_by_tag().contains_key("sea"));
+
+        std::fs::write(root.join("b.jpg"), b"img").unwrap();
+        std::fs::write(root.join("b.jpg.json"), r#"{"tags": ["sea"]}"#).unwrap();
+        library.rescan_incremental().unwrap();
+        assert_eq!(library.items_by_tag()["sea"].len(), 1);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn library_search_filters_by_date_range_in_seconds_and_millis() {
+        let mut index = Index::default();
+        index.items.push(make_item_with_path(
+            "/tmp/a.jpg",
+            json!({ "date": 1700000000 }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/b.jpg",
+            json!({ "timestamp": 1700000000500_i64 }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/c.jpg",
+            json!({ "date": 1700000001 }),
+        ));
+        index
+            .items
+            .push(make_item_with_path("/tmp/no-date.jpg", json!({})));
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        let search = |after: Option<i64>, before: Option<i64>| {
+            library
+                .search(
+                    SearchQuery::new(Vec::new())
+                        .with_date_after(after)
+                        .with_date_before(before),
+                )
+                .indices
+        };
+
+        assert_eq!(search(None, None), vec![0, 1, 2, 3]);
+        assert_eq!(search(Some(1700000000), None), vec![0, 1, 2]);
+        assert_eq!(search(Some(1700000001), None), vec![2]);
+        assert_eq!(search(None, Some(1700000000)), Vec::<usize>::new());
+        assert_eq!(search(None, Some(1700000001)), vec![0, 1]);
+        assert_eq!(search(Some(1700000000), Some(1700000001)), vec![0, 1]);
+    }
+
+    #[test]
+    fn library_search_can_sort_by_author_and_tag_count() {
+        let mut index = Index::default();
+        index.items.push(make_item_with_path(
+            "/tmp/a.jpg",
+            json!({ "author": "bob", "tags": ["x"] }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/b.jpg",
+            json!({ "tags": ["x", "y", "z"] }),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/c.jpg",
+            json!({ "author": "Alice", "tags": ["x", "y"] }),
+        ));
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        let sorted = |sort| {
+            library
+                .search(SearchQuery::new(Vec::new()).with_sort(sort))
+                .indices
+        };
+
+        assert_eq!(sorted(SearchSort::AuthorAsc), vec![2, 0, 1]);
+        assert_eq!(sorted(SearchSort::AuthorDesc), vec![0, 2, 1]);
+        assert_eq!(sorted(SearchSort::TagCountDesc), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn index_remove_shifts_path_lookups() {
+        let mut index = Index::default();
+        for name in ["a", "b", "c"] {
+            let mut item =
+                make_item_with_path(&format!("/tmp/{name}.jpg"), json!({ "tags": [name] }));
+            item.meta_path = PathBuf::from(format!("/tmp/{name}.jpg.json"));
+            index.last_seen_mtime.insert(item.meta_path.clone(), 1);
+            index.push(item);
+        }
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        assert_eq!(library.items_by_tag()["c"], vec![2]);
+        let mut index = library.index;
+
+        let removed = index.remove(1).unwrap();
+        assert_eq!(removed.image_path, Path::new("/tmp/b.jpg"));
+        assert!(index.remove(2).is_none());
+        assert!(index.get_by_path(Path::new("/tmp/b.jpg")).is_none());
+        assert_eq!(
+            index
+                .get_by_path(Path::new("/tmp/c.jpg"))
+                .unwrap()
+                .image_path,
+            Path::new("/tmp/c.jpg")
+        );
+        assert!(!index
+            .last_seen_mtime
+            .contains_key(Path::new("/tmp/b.jpg.json")));
+        assert_eq!(index.last_seen_mtime.len(), 2);
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        assert_eq!(library.items_by_tag()["c"], vec![1]);
+        assert!(!library.items_by_tag().contains_key("b"));
+    }
+
+    #[test]
+    fn library_search_can_sort_by_file_size() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-search-size-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("big.jpg"), [0u8; 64]).unwrap();
+        std::fs::write(root.join("small.jpg"), [0u8; 8]).unwrap();
+
+        let mut index = Index::default();
+        for name in ["big.jpg", "missing.jpg", "small.jpg"] {
+            index.items.push(make_item_with_path(
+                root.join(name).to_str().unwrap(),
+                json!({}),
+            ));
+        }
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        let sorted = |sort| {
+            library
+                .search(SearchQuery::new(Vec::new()).with_sort(sort))
+                .indices
+        };
+
+        assert_eq!(sorted(SearchSort::FileSizeAsc), vec![2, 0, 1]);
+        assert_eq!(sorted(SearchSort::FileSizeDesc), vec![0, 2, 1]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn library_search_sorts_by_recorded_resolution() {
+        let mut index = Index::default();
+        index.items.push(make_item_with_path(
+            "/tmp/small.jpg",
+            json!({"width": 640, "height": 480}),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/unknown.jpg",
+            json!({"width": 0, "height": 0}),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/large.jpg",
+            json!({"image_width": 4000, "image_height": 3000}),
+        ));
+        index.items.push(make_item_with_path(
+            "/tmp/tall.jpg",
+            json!({"width": 480, "height": 640}),
+        ));
+        assert_eq!(index.items[2].dimensions(), Some((4000, 3000)));
+        assert_eq!(index.items[1].dimensions(), None);
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        let result =
+            library.search(SearchQuery::new(Vec::new()).with_sort(SearchSort::ResolutionDesc));
+        // Equal pixel counts fall back to the path.
+        assert_eq!(result.indices, vec![2, 0, 3, 1]);
+        assert_eq!(result.sort_warning, None);
+        // The failed header read is remembered rather than retried.
+        assert_eq!(library.index.items[1].probed_dimensions.get(), Some(&None));
+        assert_eq!(
+            "resolution".parse::<SearchSort>(),
+            Ok(SearchSort::ResolutionDesc)
+        );
+    }
+
+    #[test]
+    fn library_search_random_sort_is_seeded() {
+        let mut index = Index::default();
+        for idx in 0..16 {
+            index
+                .items
+                .push(make_item_with_path(&format!("/tmp/{idx}.jpg"), json!({})));
+        }
+
+        let library = Library {
+            config: BooruConfig::default(),
+            index,
+            warnings: Vec::new(),
+        };
+        let sorted = |sort| {
+            library
+                .search(SearchQuery::new(Vec::new()).with_sort(sort))
+                .indices
+        };
+
+        let first = sorted(SearchSort::Random(7));
+        assert_eq!(first, sorted(SearchSort::Random(7)));
+        assert_ne!(first, (0..16).collect::<Vec<_>>());
+        let mut restored = first.clone();
+        restored.sort_unstable();
+        assert_eq!(restored, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scan_roots_ignores_alias_json() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-alias-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("alias.json"), "[[\"a\", \"b\"]]").unwrap();
+
+        let report = scan_roots(std::slice::from_ref(&root)).expect("scan should succeed");
+        assert!(report.index.items.is_empty());
+        assert!(report.warnings.is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(feature = "exif")]
+    #[test]
+    fn scan_roots_falls_back_to_exif_for_bare_images() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-exif-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/exif.jpg");
+        std::fs::copy(&fixture, root.join("bare.jpg")).unwrap();
+        std::fs::copy(&fixture, root.join("sidecar.jpg")).unwrap();
+        std::fs::write(root.join("sidecar.jpg.json"), r#"{"tags": ["from-json"]}"#).unwrap();
+        image::RgbImage::new(2, 2)
+            .save(root.join("plain.png"))
+            .unwrap();
+
+        let report = scan_roots(std::slice::from_ref(&root)).unwrap();
+        assert!(report.warnings.is_empty());
+        let tags = |name: &str| {
+            let item = report
+                .index
+                .items
+                .iter()
+                .find(|item| item.image_path.ends_with(name))
+                .unwrap();
+            item.merged_tags()
+        };
+        assert_eq!(report.index.items.len(), 2);
+        assert_eq!(tags("bare.jpg"), vec!["sunset", "bay"]);
+        assert_eq!(tags("sidecar.jpg"), vec!["from-json"]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[derive(Default)]
+    struct CountingObserver(AtomicU64);
+
+    impl ProgressObserver for CountingObserver {
+        fn inc(&self, delta: u64) {
+            self.0.fetch_add(delta, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn scan_reports_progress_and_streams_items() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-progress-{unique}"));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("alias.json"), "[[\"a\", \"b\"]]").unwrap();
+        std::fs::write(root.join("sub/alias.json"), "[]").unwrap();
+        for image in ["a.jpg", "sub/b.jpg", "sub/c.jpg"] {
+            std::fs::write(root.join(image), b"img").unwrap();
+            std::fs::write(root.join(format!("{image}.json")), "{}").unwrap();
+        }
+        let config = BooruConfig::with_roots(vec![root.clone()]);
+
+        let observer = CountingObserver::default();
+        let library = Library::scan_with_progress(config.clone(), &observer).unwrap();
+        assert_eq!(library.index.items.len(), 3);
+        assert_eq!(observer.0.load(Ordering::Relaxed), 3);
+
+        let (tx, rx) = mpsc::channel();
+        let library = Library::scan_streaming(config, tx).unwrap();
+        let streamed = rx.iter().map(|item| item.image_path).collect::<Vec<_>>();
+        let scanned = library
+            .index
+            .items
+            .iter()
+            .map(|item| item.image_path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(streamed, scanned);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn scan_order_is_stable_and_sorted_by_path() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-order-{unique}"));
+        std::fs::create_dir_all(root.join("m")).unwrap();
+        let mut images = (0..40)
+            .map(|idx| format!("{}/{idx:02}.jpg", if idx % 3 == 0 { "m" } else { "." }))
+            .collect::<Vec<_>>();
+        images.reverse();
+        for image in &images {
+            std::fs::write(root.join(image), b"img").unwrap();
+            std::fs::write(root.join(format!("{image}.json")), "{}").unwrap();
+        }
+        std::fs::write(root.join("broken.jpg"), b"img").unwrap();
+        std::fs::write(root.join("broken.jpg.json"), "{").unwrap();
+
+        let paths = || {
+            let report = scan_roots(std::slice::from_ref(&root)).unwrap();
+            assert_eq!(report.warnings.len(), 1);
+            report
+                .index
+                .items
+                .iter()
+                .map(|item| item.image_path.clone())
+                .collect::<Vec<_>>()
+        };
+        let first = paths();
+        assert_eq!(first.len(), 40);
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(first, sorted);
+        assert_eq!(first, paths());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn scan_roots_excluding_skips_matching_paths_without_warnings() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-exclude-{unique}"));
+        for dir in ["a/thumbnails", "a/full", "b/c/thumbnails"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for image in ["a/thumbnails/x.jpg", "a/full/x.jpg", "b/c/thumbnails/y.jpg"] {
+            std::fs::write(root.join(image), b"img").unwrap();
+            std::fs::write(root.join(format!("{image}.json")), "{}").unwrap();
+        }
+        // Metadata without an image would warn if it were not excluded.
+        std::fs::write(root.join("a/thumbnails/orphan.jpg.json"), "{}").unwrap();
+
+        let report =
+            scan_roots_excluding(std::slice::from_ref(&root), &["*/thumbnails/*".to_string()])
+                .expect("scan should succeed");
+        let paths = report
+            .index
+            .items
+            .iter()
+            .map(|item| item.image_path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![std::fs::canonicalize(root.join("a/full/x.jpg")).unwrap()]
+        );
+        assert!(report.warnings.is_empty());
+
+        assert!(matches!(
+            scan_roots_excluding(std::slice::from_ref(&root), &["a/{".to_string()]),
+            Err(BooruError::Glob { .. })
+        ));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn scan_roots_skips_paths_matched_by_booruignore() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-ignore-{unique}"));
+        for dir in ["private/deep", "public/drafts", "public/final"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for image in [
+            "private/a.jpg",
+            "private/deep/b.jpg",
+            "public/c.tmp",
+            "public/drafts/d.jpg",
+            "public/final/e.jpg",
+            "f.jpg",
+        ] {
+            std::fs::write(root.join(image), b"img").unwrap();
+            std::fs::write(root.join(format!("{image}.json")), "{}").unwrap();
+        }
+        std::fs::write(root.join(".booruignore"), "# comment\nprivate/**\n*.tmp\n").unwrap();
+        // Relative to public/, and scoped to it: `final` elsewhere is kept.
+        std::fs::write(root.join("public/.booruignore"), "drafts/\n!final\n").unwrap();
+
+        let report = scan_roots(std::slice::from_ref(&root)).expect("scan should succeed");
+        let canonical_root = std::fs::canonicalize(&root).unwrap();
+        let mut paths = report
+            .index
+            .items
+            .iter()
+            .map(|item| {
+                item.image_path
+                    .strip_prefix(&canonical_root)
+                    .unwrap()
+                    .to_path_buf()
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("f.jpg"), PathBuf::from("public/final/e.jpg")]
+        );
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].message.contains("not supported"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn library_scan_skips_exclude_paths() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-exclude-path-{unique}"));
+        for dir in ["keep", "skip/nested"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for image in ["keep/a.jpg", "keep/b.jpg", "skip/nested/c.jpg"] {
+            std::fs::write(root.join(image), b"img").unwrap();
+            std::fs::write(root.join(format!("{image}.json")), "{}").unwrap();
+        }
+
+        let mut config = BooruConfig::with_roots(vec![root.clone()]);
+        // Non-canonical spellings still match.
+        config.exclude_paths = vec![
+            root.join("keep/../skip"),
+            root.join("keep/b.jpg"),
+            root.join("does-not-exist"),
+        ];
+        let library = Library::scan(config).unwrap();
+        let paths = library
+            .index
+            .items
+            .iter()
+            .map(|item| item.image_path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![std::fs::canonicalize(root.join("keep/a.jpg")).unwrap()]
+        );
+        assert!(library.warnings.is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn orphan_metadata_is_sidecars_without_an_image() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-orphans-{unique}"));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("a.jpg"), b"img").unwrap();
+        for file in [
+            "a.jpg.json",
+            "a.jpg.booru.json",
+            "gone.png.json",
+            "sub/gone.png.booru.json",
+            ALIAS_FILE_NAME,
+        ] {
+            std::fs::write(root.join(file), "{}").unwrap();
+        }
+
+        let config = BooruConfig::with_roots(vec![root.clone()]);
+        assert_eq!(
+            find_orphan_metadata(&config).unwrap(),
+            vec![
+                root.join("gone.png.json"),
+                root.join("sub/gone.png.booru.json")
+            ]
+        );
+        assert_eq!(
+            scan_orphans(&config).unwrap(),
+            vec![root.join("sub/gone.png.booru.json")]
+        );
+
+        let report = scan_roots(std::slice::from_ref(&root)).unwrap();
+        assert_eq!(report.index.items.len(), 1);
+        let mut warnings = report
+            .warnings
+            .iter()
+            .map(|warning| (warning.path.clone(), warning.message.as_str()))
+            .collect::<Vec<_>>();
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            vec![
+                (root.join("gone.png"), "missing image for metadata"),
+                (
+                    root.join("sub/gone.png.booru.json"),
+                    "edits for a missing image"
+                ),
+            ]
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn orphan_metadata_skips_what_a_scan_skips() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-orphans-excluded-{unique}"));
+        for dir in ["ignored", "globbed", "excluded"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("gone.jpg.booru.json"), "{}").unwrap();
+        }
+        std::fs::write(root.join("gone.jpg.json"), "{}").unwrap();
+        std::fs::write(root.join(IGNORE_FILE_NAME), "ignored/\n").unwrap();
+
+        let mut config = BooruConfig::with_roots(vec![root.clone()]);
+        config.exclude_globs = vec!["globbed/**".to_string()];
+        config.exclude_paths = vec![root.join("excluded")];
+        assert_eq!(
+            find_orphan_metadata(&config).unwrap(),
+            vec![root.join("gone.jpg.json")]
+        );
+        assert!(scan_orphans(&config).unwrap().is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn library_scan_honours_max_depth() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-scan-depth-{unique}"));
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        for image in ["a/x.jpg", "a/b/y.jpg"] {
+            std::fs::write(root.join(image), b"img").unwrap();
+            std::fs::write(root.join(format!("{image}.json")), "{}").unwrap();
+        }
+
+        let mut config = BooruConfig::with_roots(vec![root.clone()]);
+        assert_eq!(Library::scan(config.clone()).unwrap().index.items.len(), 2);
+        config.set_max_depth(0);
+        assert_eq!(Library::scan(config.clone()).unwrap().index.items.len(), 2);
+        config.set_max_depth(2);
+        let library = Library::scan(config).unwrap();
+        assert_eq!(library.index.items.len(), 1);
+        assert!(library.index.items[0].image_path.ends_with("a/x.jpg"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn rescan_incremental_reloads_only_changed_files() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-rescan-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(root.join(format!("{name}.jpg")), b"img").unwrap();
+            std::fs::write(
+                root.join(format!("{name}.jpg.json")),
+                format!("{{\"title\": \"{name}\"}}"),
+            )
+            .unwrap();
+        }
+
+        let mut library = Library::scan(BooruConfig::with_roots(vec![root.clone()])).unwrap();
+        assert_eq!(library.index.items.len(), 3);
+
+        let set_mtime = |path: &Path, secs: u64| {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(UNIX_EPOCH + std::time::Duration::from_secs(secs))
+                .unwrap();
+        };
+        // Same mtime as recorded: the rewrite must not be picked up.
+        let a_meta = root.join("a.jpg.json");
+        set_mtime(&a_meta, 1_000);
+        library.rescan_incremental().unwrap();
+        std::fs::write(&a_meta, "{\"title\": \"stale\"}").unwrap();
+        set_mtime(&a_meta, 1_000);
+        std::fs::write(root.join("b.jpg.json"), "{\"title\": \"b2\"}").unwrap();
+        set_mtime(&root.join("b.jpg.json"), 2_000);
+        std::fs::remove_file(root.join("c.jpg.json")).unwrap();
+        std::fs::write(root.join("d.jpg"), b"img").unwrap();
+        std::fs::write(root.join("d.jpg.json"), "{\"title\": \"d\"}").unwrap();
+
+        let report = library.rescan_incremental().unwrap();
+        assert_eq!((report.added, report.updated, report.removed), (1, 1, 1));
+        let title = |name: &str| {
+            let path = std::fs::canonicalize(root.join(format!("{name}.jpg"))).unwrap();
+            library
+                .index
+                .get_by_path(&path)
+                .and_then(|item| item.original["title"].as_str().map(str::to_string))
+        };
+        assert_eq!(title("a").as_deref(), Some("a"));
+        assert_eq!(title("b").as_deref(), Some("b2"));
+        assert_eq!(title("c"), None);
+        assert_eq!(title("d").as_deref(), Some("d"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn tag_frequency_counts_each_tag_once_per_item() {
+        let mut edited = make_item(json!({ "tags": ["sky", "sea"] }));
+        edited.edits.tags.remove = vec!["sea".to_string()];
+        let items = vec![
+            edited,
+            make_item(json!({ "tags": ["sky", "sky", "cloud"] })),
+            make_item(json!({ "tags": ["cloud", "sea"] })),
+            make_item(json!({})),
+        ];
+
+        assert_eq!(
+            tag_frequency(&items),
+            vec![
+                ("cloud".to_string(), 2),
+                ("sky".to_string(), 2),
+                ("sea".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn author_frequency_uses_merged_author_and_counts_missing_separately() {
+        let items = vec![
+            make_item(json!({ "author": "alice" })),
+            make_item(json!({ "user": { "name": "alice" } })),
+            make_item(json!({ "tag_string_artist": "bob" })),
+            make_item(json!({ "author": "  " })),
+            make_item(json!({ "title": "untitled" })),
+        ];
+
+        assert_eq!(
+            author_frequency(&items),
+            vec![
+                ("(none)".to_string(), 2),
+                ("alice".to_string(), 2),
+                ("bob".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn statistics_count_items_tags_authors_and_categories() {
+        let mut edited = make_item(json!({
+            "category": "twitter",
+            "author": "alice",
+            "tags": ["sky"],
+            "date": "2024-01-02 00:00:00",
+        }));
+        edited.edits.tags.add = vec!["sea".to_string()];
+        edited.edits.sensitive = Some(true);
+        let mut index = Index::default();
+        for item in [
+            edited,
+            make_item(json!({
+                "category": "twitter",
+                "author": "bob",
+                "tags": ["sky", "cloud"],
+                "date": "2025-03-04 05:06:07",
+            })),
+            make_item(json!({
+                "category": "pixiv",
+                "author": "alice",
+                "tags": [],
+            })),
+            make_item(json!({})),
+        ] {
+            index.items.push(item);
+        }
+        let library = Library {
+            config: BooruConfig::with_roots(Vec::new()),
+            index,
+            warnings: Vec::new(),
+        };
+
+        let stats = library.statistics();
+        assert_eq!(stats.item_count, 4);
+        assert_eq!((stats.tagged_count, stats.untagged_count), (2, 2));
+        assert_eq!(stats.sensitive_count, 1);
+        assert_eq!(stats.unique_tags, 3);
+        assert_eq!(stats.unique_authors, 2);
+        assert_eq!(stats.categories.get("twitter"), Some(&2));
+        assert_eq!(stats.categories.get("pixiv"), Some(&1));
+        assert_eq!(stats.categories.get("(none)"), Some(&1));
+        assert!(stats
+            .earliest_date
+            .as_deref()
+            .unwrap()
+            .starts_with("2024-01-02 00:00:00"));
+        assert!(stats
+            .latest_date
+            .as_deref()
+            .unwrap()
+            .starts_with("2025-03-04 05:06:07"));
+
+        let empty = Library {
+            config: BooruConfig::with_roots(Vec::new()),
+            index: Index::default(),
+            warnings: Vec::new(),
+        };
+        assert_eq!(empty.statistics(), LibraryStats::default());
+    }
+
+    #[test]
+    fn library_watcher_rescans_after_metadata_changes() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-watch-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        let library = Arc::new(RwLock::new(
+            Library::scan(BooruConfig::with_roots(vec![root.clone()])).unwrap(),
+        ));
+
+        let (tx, rx) = mpsc::channel();
+        let watcher = LibraryWatcher::for_library(library.clone(), move |result| {
+            let _ = tx.send(result.unwrap().added);
+        })
+        .unwrap();
+
+        std::fs::write(root.join("notes.txt"), b"unrelated").unwrap();
+        assert!(rx.recv_timeout(WATCH_DEBOUNCE * 3).is_err());
+
+        std::fs::write(root.join("a.jpg"), b"img").unwrap();
+        std::fs::write(root.join("a.jpg.json"), "{\"title\": \"a\"}").unwrap();
+        let added = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(added, 1);
+        assert_eq!(library.read().unwrap().index.items.len(), 1);
 
+        drop(watcher);
         std::fs::remove_dir_all(root).unwrap();
     }
 }
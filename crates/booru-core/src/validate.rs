@@ -0,0 +1,199 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::alias::{alias_path_for_root, normalize_search_terms, parse_alias_groups};
+use crate::config::BooruConfig;
+use crate::date::parse_date;
+use crate::error::BooruError;
+use crate::metadata::BooruEdits;
+use crate::scan::{image_path_for_sidecar, sidecar_files, Library};
+
+/// One problem found by [`validate_library`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Whether the image a `*.json` or `*.booru.json` file describes is gone.
+pub fn image_is_missing(sidecar: &Path) -> bool {
+    image_path_for_sidecar(sidecar).is_some_and(|image| !image.exists())
+}
+
+/// Why `path` does not hold valid JSON, if it doesn't.
+pub fn json_error(path: &Path) -> Option<String> {
+    read_json_value(path).err()
+}
+
+fn read_json_value(path: &Path) -> Result<Value, String> {
+    let data = fs::read(path).map_err(|err| format!("failed to read: {err}"))?;
+    serde_json::from_slice(&data).map_err(|err| format!("malformed JSON: {err}"))
+}
+
+/// A `tags.set` replaces the original tags outright, so `tags.add` or
+/// `tags.remove` next to it means the file was edited by hand or by a tool
+/// that disagrees with [`BooruEdits::apply_update`].
+pub fn edits_are_contradictory(edits: &BooruEdits) -> bool {
+    edits.tags.set.is_some() && (!edits.tags.add.is_empty() || !edits.tags.remove.is_empty())
+}
+
+pub fn date_is_unparseable(raw: &str) -> bool {
+    parse_date(raw).is_none()
+}
+
+/// Groups that alias nothing once terms are trimmed, lowercased and deduped.
+pub fn alias_group_is_undersized(group: &[String]) -> bool {
+    normalize_search_terms(group.to_vec()).len() < 2
+}
+
+/// Runs every check above over the configured roots, sorted by path.
+pub fn validate_library(config: &BooruConfig) -> Result<Vec<ValidationIssue>, BooruError> {
+    let mut issues = Vec::new();
+    let mut report = |path: &Path, message: String| {
+        issues.push(ValidationIssue {
+            path: path.to_path_buf(),
+            message,
+        })
+    };
+
+    for path in sidecar_files(config)? {
+        if image_is_missing(&path) {
+            report(&path, "image does not exist".to_string());
+        }
+        if let Some(err) = json_error(&path) {
+            report(&path, err);
+            continue;
+        }
+        if !path.to_string_lossy().ends_with(".booru.json") {
+            continue;
+        }
+        match BooruEdits::load(&path) {
+            Ok(Some(edits)) if edits_are_contradictory(&edits) => report(
+                &path,
+                "tags.set is combined with tags.add or tags.remove".to_string(),
+            ),
+            Ok(_) => {}
+            Err(err) => report(&path, format!("invalid booru edits: {err}")),
+        }
+    }
+
+    for root in &config.roots {
+        let path = alias_path_for_root(root);
+        if !path.is_file() {
+            continue;
+        }
+        match read_json_value(&path).map(|value| parse_alias_groups(&value)) {
+            Ok(Ok(groups)) => {
+                for (idx, group) in groups.iter().enumerate() {
+                    if alias_group_is_undersized(group) {
+                        report(
+                            &path,
+                            format!("alias group at index {idx} has fewer than two terms"),
+                        );
+                    }
+                }
+            }
+            Ok(Err(err)) => report(&path, format!("invalid alias groups: {err}")),
+            Err(err) => report(&path, err),
+        }
+    }
+
+    let library = Library::scan(config.clone())?;
+    for item in &library.index.items {
+        if let Some(raw) = item.merged_date() {
+            if date_is_unparseable(&raw) {
+                report(&item.meta_path, format!("unparseable date `{raw}`"));
+            }
+        }
+    }
+
+    issues.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::{
+        alias_group_is_undersized, date_is_unparseable, edits_are_contradictory, image_is_missing,
+        json_error, validate_library,
+    };
+    use crate::config::BooruConfig;
+    use crate::metadata::{BooruEdits, TagEdits};
+
+    #[test]
+    fn predicates_flag_only_broken_input() {
+        let mut edits = BooruEdits {
+            tags: TagEdits {
+                set: Some(vec!["sky".to_string()]),
+                ..TagEdits::default()
+            },
+            ..BooruEdits::default()
+        };
+        assert!(!edits_are_contradictory(&edits));
+        edits.tags.remove.push("cloud".to_string());
+        assert!(edits_are_contradictory(&edits));
+        edits.tags.set = None;
+        assert!(!edits_are_contradictory(&edits));
+
+        assert!(!date_is_unparseable("2024-01-02 03:04:05"));
+        assert!(date_is_unparseable("someday"));
+
+        let group = |terms: &[&str]| terms.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert!(!alias_group_is_undersized(&group(&["cat", "neko"])));
+        assert!(alias_group_is_undersized(&group(&["Cat", " cat ", ""])));
+    }
+
+    #[test]
+    fn validate_library_reports_each_problem_file() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("lightbooru-validate-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        for image in ["ok.jpg", "bad-date.jpg", "broken.jpg", "edits.jpg"] {
+            std::fs::write(root.join(image), b"img").unwrap();
+        }
+        let files = [
+            ("ok.jpg.json", r#"{"date": "2024-01-02 03:04:05"}"#),
+            ("bad-date.jpg.json", r#"{"date": "someday"}"#),
+            ("broken.jpg.json", "{"),
+            ("edits.jpg.json", "{}"),
+            (
+                "edits.jpg.booru.json",
+                r#"{"tags": {"set": ["a"], "add": ["b"], "remove": []}}"#,
+            ),
+            ("gone.jpg.json", "{}"),
+            ("alias.json", r#"[["cat", "neko"], ["Dog", "dog"]]"#),
+        ];
+        for (name, contents) in files {
+            std::fs::write(root.join(name), contents).unwrap();
+        }
+        assert!(json_error(&root.join("broken.jpg.json")).is_some());
+        assert!(image_is_missing(&root.join("gone.jpg.json")));
+        assert!(!image_is_missing(&root.join("ok.jpg.json")));
+
+        let issues = validate_library(&BooruConfig::with_roots(vec![root.clone()])).unwrap();
+        let mut flagged = issues
+            .iter()
+            .map(|issue| issue.path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        flagged.sort_unstable();
+        assert_eq!(
+            flagged,
+            vec![
+                "alias.json",
+                "bad-date.jpg.json",
+                "broken.jpg.json",
+                "edits.jpg.booru.json",
+                "gone.jpg.json",
+            ]
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -3,52 +3,81 @@ mod ui;
 use std::cell::RefCell;
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::Duration;
 
 use adw::prelude::*;
 use adw::Application;
 use anyhow::Result;
-use booru_core::BooruConfig;
+use booru_core::{BooruConfig, Index, Library};
 use clap::Parser;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser, Debug)]
 #[command(name = "booru-gtk", version, about = "GTK GUI for LightBooru")]
 struct Cli {
-    /// Base directory for gallery-dl downloads (can be repeated)
+    /// Base directory for gallery-dl downloads (can be repeated; replaces
+    /// the roots from booru.toml)
     #[arg(long, short)]
     base: Vec<PathBuf>,
 
+    /// Skip images whose path under a root matches this glob (can be
+    /// repeated; adds to `exclude_globs` from booru.toml)
+    #[arg(long = "exclude")]
+    exclude: Vec<String>,
+
+    /// Skip this file or directory while scanning (can be repeated; adds to
+    /// `exclude_paths` from booru.toml)
+    #[arg(long = "exclude-path")]
+    exclude_path: Vec<PathBuf>,
+
+    /// Descend at most this many levels below each root (0: unlimited)
+    #[arg(long)]
+    max_depth: Option<usize>,
+
     /// Suppress scan warnings
     #[arg(long)]
     quiet: bool,
 
-    /// Show sensitive images (default: hidden)
+    /// Show sensitive images (default: hidden, or `default_sensitive` in booru.toml)
     #[arg(long)]
     sensitive: bool,
+
+    /// Rescan automatically when files under the roots change
+    #[arg(long)]
+    watch: bool,
+
+    /// Seconds between items in slideshow mode
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    slideshow_interval: u64,
 }
 
 fn main() -> Result<()> {
     init_tracing();
 
     let cli = Cli::parse();
-    let config = if cli.base.is_empty() {
-        BooruConfig::default()
-    } else {
-        BooruConfig::with_roots(cli.base)
-    };
+    let mut config = BooruConfig::resolve(cli.base)?;
+    config.apply_scan_overrides(cli.exclude, cli.exclude_path, cli.max_depth);
 
-    let library = ui::scan_library(&config, cli.quiet)?;
+    let show_sensitive = cli.sensitive || config.default_sensitive;
+    // Filled in by a background scan once the window is up.
+    let library = Library {
+        config,
+        index: Index::default(),
+        warnings: Vec::new(),
+    };
     let state = Rc::new(RefCell::new(ui::AppState::new(
         library,
-        cli.sensitive,
+        show_sensitive,
         cli.quiet,
+        Duration::from_secs(cli.slideshow_interval),
     )));
 
     let app = Application::builder()
         .application_id("moe.taoky.lightbooru.gtk")
         .build();
     let state_for_activate = state.clone();
-    app.connect_activate(move |app| ui::build_ui(app, state_for_activate.clone()));
+    let watch = cli.watch;
+    app.connect_activate(move |app| ui::build_ui(app, state_for_activate.clone(), watch));
     app.run();
 
     Ok(())
@@ -1,26 +1,29 @@
 mod build;
 mod image_loader;
+mod scroll_memory;
 mod view;
+mod viewer;
 
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use std::sync::Once;
+use std::time::Duration;
 
 use adw::{
     ApplicationWindow, Banner, BottomSheet, NavigationSplitView, ToastOverlay, ToggleGroup,
     ViewStack, WrapBox,
 };
-use booru_core::{Library, SearchQuery, SearchSort};
+use booru_core::{Library, LibraryWatcher, SearchQuery, SearchSort, SensitiveFilter};
 use gtk::{
-    self, Button, Entry, GridView, Label, LinkButton, ListBox, Picture, ScrolledWindow,
-    SearchEntry, SingleSelection, TextView,
+    self, Button, Entry, GridView, Label, LinkButton, ListBox, MultiSelection, Picture,
+    ScrolledWindow, SearchEntry, TextView,
 };
 use rand::seq::SliceRandom;
 
 use self::image_loader::ImageLoader;
+use self::scroll_memory::ScrollMemory;
 
 pub(crate) use build::build_ui;
-pub(crate) use view::scan_library;
 
 const APP_CSS: &str = include_str!("style.css");
 const APP_UI: &str = include_str!(concat!(env!("OUT_DIR"), "/main.ui"));
@@ -55,26 +58,46 @@ pub(crate) struct AppState {
     library: Library,
     filtered_indices: Vec<usize>,
     selected_pos: Option<usize>,
+    /// Item indices selected in the grid; batch edits apply to all of them.
+    selected_indices: Vec<usize>,
     filter_version: u64,
     browser_mode: BrowserMode,
     show_sensitive: bool,
     random_sort: bool,
+    match_all: bool,
     query: String,
+    scroll_memory: ScrollMemory,
     quiet: bool,
+    slideshow_interval: Duration,
+    /// The running slideshow timer, if any; see `view::start_slideshow`.
+    slideshow: Option<gtk::glib::SourceId>,
+    /// Held for `--watch`; dropping it stops watching.
+    _watcher: Option<LibraryWatcher>,
 }
 
 impl AppState {
-    pub(crate) fn new(library: Library, show_sensitive: bool, quiet: bool) -> Self {
+    pub(crate) fn new(
+        library: Library,
+        show_sensitive: bool,
+        quiet: bool,
+        slideshow_interval: Duration,
+    ) -> Self {
         let mut state = Self {
             library,
             filtered_indices: Vec::new(),
             selected_pos: None,
+            selected_indices: Vec::new(),
             filter_version: 0,
             browser_mode: BrowserMode::Grid,
             show_sensitive,
             random_sort: true,
+            match_all: false,
             query: String::new(),
+            scroll_memory: ScrollMemory::default(),
             quiet,
+            slideshow_interval,
+            slideshow: None,
+            _watcher: None,
         };
         state.rebuild_filter();
         state
@@ -88,14 +111,12 @@ impl AppState {
             SearchQuery::new(terms)
                 .with_aliases(use_aliases)
                 .with_source_url(source_url)
-                .with_sort(SearchSort::FileNameAsc),
+                .with_sort(SearchSort::FileNameAsc)
+                .with_match_all(self.match_all)
+                .with_sensitive(SensitiveFilter::from_show(self.show_sensitive)),
         );
 
-        self.filtered_indices = result
-            .indices
-            .into_iter()
-            .filter(|idx| self.show_sensitive || !self.library.index.items[*idx].merged_sensitive())
-            .collect();
+        self.filtered_indices = result.indices;
         if self.random_sort && !has_source_url_filter {
             let mut rng = rand::thread_rng();
             self.filtered_indices.shuffle(&mut rng);
@@ -106,6 +127,7 @@ impl AppState {
             (Some(pos), false) => Some(pos.min(self.filtered_indices.len() - 1)),
             (None, false) => Some(0),
         };
+        self.selected_indices.clear();
         self.filter_version = self.filter_version.wrapping_add(1);
     }
 
@@ -122,8 +144,9 @@ struct Ui {
     list: ListBox,
     list_scroll: ScrolledWindow,
     grid: GridView,
+    grid_scroll: ScrolledWindow,
     grid_store: gtk::gio::ListStore,
-    grid_selection: SingleSelection,
+    grid_selection: MultiSelection,
     browser_stack: ViewStack,
     picture: Picture,
     title: Label,
@@ -132,6 +155,8 @@ struct Ui {
     source_url: LinkButton,
     search_same_source_button: Button,
     open_file_button: Button,
+    previous_item_button: Button,
+    next_item_button: Button,
     detail: Label,
     tags_wrap: WrapBox,
     tags_add_button: Button,
@@ -140,6 +165,7 @@ struct Ui {
     notes: TextView,
     item_sensitive: gtk::Switch,
     detail_stack: ViewStack,
+    empty_page: adw::StatusPage,
     edit_sheet: BottomSheet,
     toast_overlay: ToastOverlay,
     banner: Banner,
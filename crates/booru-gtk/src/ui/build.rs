@@ -3,20 +3,24 @@ use std::rc::Rc;
 
 use adw::prelude::*;
 use adw::{Application, BottomSheet, NavigationSplitView, ToggleGroup};
+use booru_core::MediaKind;
 use gtk::{
     self, Box as GtkBox, Button, GridView, Label, Picture, SearchEntry, SelectionMode,
-    SignalListItemFactory, SingleSelection,
+    SignalListItemFactory,
 };
 use tracing::debug;
 
 use super::image_loader::{ImageLoader, ImageRequestKind};
 use super::view::{
     append_pending_tags_input, apply_search, ensure_selected_item_visible, grid_cell_widgets,
-    infer_thumbnail_title, install_tag_editor_css, open_selected_file, open_selected_source_url,
+    infer_thumbnail_title, install_tag_editor_css, open_selected_author_url, open_selected_file,
+    open_selected_source_url, present_batch_edit_dialog, present_delete_item_dialog,
     rebuild_tag_wrap, rebuild_view, refresh_detail, refresh_grid, rescan_library,
-    save_selected_edits, selected_author, selected_source_url, show_error_dialog, show_toast,
-    sync_browser_selection,
+    save_selected_edits, scan_library_in_background, select_adjacent_item, selected_author,
+    selected_source_url, show_error_dialog, show_toast, split_last_tag, start_slideshow,
+    stop_slideshow, sync_browser_selection, sync_list_selection, video_placeholder,
 };
+use super::viewer::open_image_viewer;
 use super::*;
 
 #[derive(Clone)]
@@ -42,6 +46,7 @@ impl Ui {
         let list: ListBox = builder_object(builder, "list");
         let list_scroll: ScrolledWindow = builder_object(builder, "list_scroll");
         let grid: GridView = builder_object(builder, "grid");
+        let grid_scroll: ScrolledWindow = builder_object(builder, "grid_scroll");
         let browser_stack: ViewStack = builder_object(builder, "browser_stack");
         let picture: Picture = builder_object(builder, "picture");
         let title: Label = builder_object(builder, "title");
@@ -51,6 +56,8 @@ impl Ui {
         let search_same_source_button: Button =
             builder_object(builder, "search_same_source_button");
         let open_file_button: Button = builder_object(builder, "open_file_button");
+        let previous_item_button: Button = builder_object(builder, "previous_item_button");
+        let next_item_button: Button = builder_object(builder, "next_item_button");
         let detail: Label = builder_object(builder, "detail");
         let tags_wrap: WrapBox = builder_object(builder, "tags_wrap");
         let tags_add_button: Button = builder_object(builder, "tags_add_button");
@@ -58,6 +65,7 @@ impl Ui {
         let notes: TextView = builder_object(builder, "notes");
         let item_sensitive: gtk::Switch = builder_object(builder, "item_sensitive");
         let detail_stack: ViewStack = builder_object(builder, "detail_stack");
+        let empty_page: adw::StatusPage = builder_object(builder, "empty_page");
         let edit_sheet: BottomSheet = builder_object(builder, "edit_sheet");
         let edit_bar: gtk::CenterBox = builder_object(builder, "edit_bar");
         let save_button: Button = builder_object(builder, "save_button");
@@ -70,6 +78,7 @@ impl Ui {
             list,
             list_scroll,
             grid,
+            grid_scroll,
             grid_store,
             grid_selection,
             browser_stack,
@@ -80,6 +89,8 @@ impl Ui {
             source_url,
             search_same_source_button,
             open_file_button,
+            previous_item_button,
+            next_item_button,
             detail,
             tags_wrap,
             tags_add_button,
@@ -88,6 +99,7 @@ impl Ui {
             notes,
             item_sensitive,
             detail_stack,
+            empty_page,
             edit_sheet,
             toast_overlay,
             banner,
@@ -111,10 +123,11 @@ impl Ui {
     }
 }
 
-pub(crate) fn build_ui(app: &Application, state: Rc<RefCell<AppState>>) {
+pub(crate) fn build_ui(app: &Application, state: Rc<RefCell<AppState>>, watch: bool) {
     install_tag_editor_css();
 
-    let image_loader = Rc::new(ImageLoader::new());
+    let video_extensions = state.borrow().library.config.video_extensions.clone();
+    let image_loader = Rc::new(ImageLoader::new(video_extensions));
     let builder = gtk::Builder::new();
     let scope = gtk::BuilderRustScope::new();
     install_builder_callbacks(&scope, &builder);
@@ -137,9 +150,18 @@ pub(crate) fn build_ui(app: &Application, state: Rc<RefCell<AppState>>) {
     ui.detail_stack.set_visible_child_name("empty");
     install_edit_sheet_open_gesture(&controls.edit_bar, &ui.edit_sheet);
     rebuild_tag_wrap(&ui);
+    {
+        let state = state.clone();
+        let ui = ui.clone();
+        controls.window.connect_close_request(move |_| {
+            stop_slideshow(&state, &ui);
+            gtk::glib::Propagation::Proceed
+        });
+    }
     controls.window.present();
     rebuild_view(&state, &ui);
     connect_ui_signals(&state, &ui, &controls);
+    scan_library_in_background(&state, &ui, watch);
 }
 
 fn install_builder_callbacks(scope: &gtk::BuilderRustScope, builder: &gtk::Builder) {
@@ -185,6 +207,10 @@ fn build_item_context_popover(parent: &impl gtk::prelude::IsA<gtk::Widget>) -> g
     let menu = gtk::gio::Menu::new();
     menu.append(Some("Open file"), Some("win.open-file"));
     menu.append(Some("Open source URL"), Some("win.open-source-url"));
+    menu.append(Some("Open author page"), Some("win.open-author-url"));
+    menu.append(Some("View fullscreen"), Some("win.view-image"));
+    menu.append(Some("Batch edit selection…"), Some("win.batch-edit"));
+    menu.append(Some("Delete item"), Some("win.delete-item"));
     let popover = gtk::PopoverMenu::from_model(Some(&menu));
     popover.set_parent(parent);
     popover
@@ -332,12 +358,13 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
                 show_toast(&ui, "No author available for selected item");
                 return;
             };
+            let query = booru_core::SearchTerm::Author(author_name).to_string();
 
             suppress.set(true);
-            search.set_text(&author_name);
+            search.set_text(&query);
             suppress.set(false);
             search_bar.set_search_mode(true);
-            apply_search(&state_handle, &ui, author_name);
+            apply_search(&state_handle, &ui, query);
         });
     }
     {
@@ -348,6 +375,22 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
             open_selected_file(&state_handle, &ui);
         });
     }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let previous_item_button = ui.previous_item_button.clone();
+        previous_item_button.connect_clicked(move |_| {
+            select_adjacent_item(&state_handle, &ui, -1);
+        });
+    }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let next_item_button = ui.next_item_button.clone();
+        next_item_button.connect_clicked(move |_| {
+            select_adjacent_item(&state_handle, &ui, 1);
+        });
+    }
     {
         let state_handle = state.clone();
         let ui = ui.clone();
@@ -366,6 +409,15 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
         });
         controls.window.add_action(&open_source_action);
     }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let open_author_action = gtk::gio::SimpleAction::new("open-author-url", None);
+        open_author_action.connect_activate(move |_, _| {
+            open_selected_author_url(&state_handle, &ui);
+        });
+        controls.window.add_action(&open_author_action);
+    }
     {
         let state_handle = state.clone();
         let ui = ui.clone();
@@ -491,6 +543,53 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
         });
         controls.window.add_action(&random_sort_action);
     }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let match_all_action = gtk::gio::SimpleAction::new_stateful(
+            "match-all",
+            None,
+            &gtk::glib::Variant::from(state.borrow().match_all),
+        );
+        match_all_action.connect_activate(move |action, _| {
+            let mut state = state_handle.borrow_mut();
+            state.match_all = !state.match_all;
+            state.rebuild_filter();
+            let match_all = state.match_all;
+            drop(state);
+            action.set_state(&gtk::glib::Variant::from(match_all));
+            rebuild_view(&state_handle, &ui);
+            if match_all {
+                show_toast(&ui, "Matching all search terms");
+            } else {
+                show_toast(&ui, "Matching any search term");
+            }
+        });
+        controls.window.add_action(&match_all_action);
+    }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let slideshow_action = gtk::gio::SimpleAction::new_stateful(
+            "slideshow",
+            None,
+            &gtk::glib::Variant::from(false),
+        );
+        slideshow_action.connect_activate(move |_, _| {
+            if state_handle.borrow().slideshow.is_some() {
+                stop_slideshow(&state_handle, &ui);
+                show_toast(&ui, "Slideshow stopped");
+            } else {
+                start_slideshow(&state_handle, &ui);
+                let interval = state_handle.borrow().slideshow_interval;
+                show_toast(
+                    &ui,
+                    &format!("Slideshow started ({}s per item)", interval.as_secs()),
+                );
+            }
+        });
+        controls.window.add_action(&slideshow_action);
+    }
     {
         let state_handle = state.clone();
         let ui = ui.clone();
@@ -515,8 +614,18 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
             let selected_pos = row
                 .and_then(|row| usize::try_from(row.index()).ok())
                 .filter(|pos| *pos < state.filtered_indices.len());
+            // Rows are dropped and re-added on every rebuild; only a newly
+            // picked row counts as the user moving the selection.
+            let picked = selected_pos.is_some() && state.selected_pos != selected_pos;
             state.selected_pos = selected_pos;
+            state.selected_indices = selected_pos
+                .map(|pos| state.filtered_indices[pos])
+                .into_iter()
+                .collect();
             drop(state);
+            if picked {
+                stop_slideshow(&state_handle, &ui);
+            }
             sync_browser_selection(&ui, selected_pos);
             refresh_detail(&state_handle, &ui);
         });
@@ -534,22 +643,43 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
         let state_handle = state.clone();
         let ui = ui.clone();
         let grid_selection_handle = ui.grid_selection.clone();
-        grid_selection_handle.connect_selected_notify(move |selection| {
-            let selected_pos = match selection.selected() {
-                gtk::INVALID_LIST_POSITION => None,
-                pos => usize::try_from(pos).ok(),
-            };
-            let selected_pos = {
+        grid_selection_handle.connect_selection_changed(move |selection, _, _| {
+            let bitset = selection.selection();
+            let positions = (0..bitset.size())
+                .map(|nth| bitset.nth(nth as u32) as usize)
+                .collect::<Vec<_>>();
+            let (selected_pos, manual) = {
                 let mut state = state_handle.borrow_mut();
-                let selected_pos = selected_pos.filter(|pos| *pos < state.filtered_indices.len());
-                if state.selected_pos == selected_pos {
+                let positions = positions
+                    .into_iter()
+                    .filter(|pos| *pos < state.filtered_indices.len())
+                    .collect::<Vec<_>>();
+                let selected_indices = positions
+                    .iter()
+                    .map(|pos| state.filtered_indices[*pos])
+                    .collect::<Vec<_>>();
+                // Ctrl/Shift-clicks extend the selection; keep showing the
+                // item that was already focused while it stays selected.
+                let selected_pos = match state.selected_pos {
+                    Some(pos) if positions.contains(&pos) => Some(pos),
+                    _ => positions.first().copied(),
+                };
+                if state.selected_pos == selected_pos && state.selected_indices == selected_indices
+                {
                     return;
                 }
+                // Rebuilding the grid re-selects the same position; that is
+                // not the user moving on.
+                let manual = state.selected_pos != selected_pos || selected_indices.len() > 1;
                 state.selected_pos = selected_pos;
-                selected_pos
+                state.selected_indices = selected_indices;
+                (selected_pos, manual)
             };
 
-            sync_browser_selection(&ui, selected_pos);
+            if manual {
+                stop_slideshow(&state_handle, &ui);
+            }
+            sync_list_selection(&ui, selected_pos);
             refresh_detail(&state_handle, &ui);
         });
     }
@@ -570,6 +700,7 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
             };
 
             if changed {
+                stop_slideshow(&state_handle, &ui);
                 sync_browser_selection(&ui, selected_pos);
                 refresh_detail(&state_handle, &ui);
             }
@@ -587,6 +718,44 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
             }
         });
     }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let view_image_action = gtk::gio::SimpleAction::new("view-image", None);
+        view_image_action.connect_activate(move |_, _| {
+            open_image_viewer(&state_handle, &ui);
+        });
+        controls.window.add_action(&view_image_action);
+    }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let picture_click = gtk::GestureClick::new();
+        picture_click.connect_pressed(move |_, n_press, _, _| {
+            if n_press == 2 {
+                open_image_viewer(&state_handle, &ui);
+            }
+        });
+        ui.picture.add_controller(picture_click);
+    }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let batch_edit_action = gtk::gio::SimpleAction::new("batch-edit", None);
+        batch_edit_action.connect_activate(move |_, _| {
+            present_batch_edit_dialog(&state_handle, &ui);
+        });
+        controls.window.add_action(&batch_edit_action);
+    }
+    {
+        let state_handle = state.clone();
+        let ui = ui.clone();
+        let delete_item_action = gtk::gio::SimpleAction::new("delete-item", None);
+        delete_item_action.connect_activate(move |_, _| {
+            present_delete_item_dialog(&state_handle, &ui);
+        });
+        controls.window.add_action(&delete_item_action);
+    }
     {
         let state_handle = state.clone();
         let ui = ui.clone();
@@ -605,17 +774,238 @@ fn connect_ui_signals(state: &Rc<RefCell<AppState>>, ui: &Ui, controls: &UiContr
             append_pending_tags_input(&ui);
         });
     }
+    install_tag_suggestions(state, ui);
+    install_tag_completion(state, ui);
+}
+
+const TAG_SUGGESTION_LIMIT: usize = 5;
+
+/// While `tags_input` has focus and is empty, lists tags that often appear
+/// alongside the item's current ones below it. Clicking one adds it.
+fn install_tag_suggestions(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let list = gtk::ListBox::new();
+    list.set_selection_mode(SelectionMode::None);
+    list.set_focusable(false);
+    let popover = gtk::Popover::builder()
+        .child(&list)
+        .autohide(false)
+        .has_arrow(false)
+        .position(gtk::PositionType::Bottom)
+        .build();
+    popover.set_parent(&ui.tags_input);
+    let suggestions = Rc::new(RefCell::new(Vec::<String>::new()));
+    let focused = Rc::new(Cell::new(false));
+
+    let refresh = {
+        let state = state.clone();
+        let ui = ui.clone();
+        let list = list.clone();
+        let popover = popover.clone();
+        let suggestions = suggestions.clone();
+        let focused = focused.clone();
+        Rc::new(move || {
+            let related = if focused.get() && ui.tags_input.text().trim().is_empty() {
+                let tags = ui.tag_values.borrow();
+                state
+                    .borrow()
+                    .library
+                    .related_tags(&tags, TAG_SUGGESTION_LIMIT)
+            } else {
+                Vec::new()
+            };
+
+            list.remove_all();
+            if related.is_empty() {
+                suggestions.borrow_mut().clear();
+                popover.popdown();
+                return;
+            }
+            for (tag, count) in &related {
+                let label = Label::builder()
+                    .label(format!("{tag}  ({count})"))
+                    .xalign(0.0)
+                    .build();
+                let row = gtk::ListBoxRow::builder()
+                    .child(&label)
+                    .focusable(false)
+                    .build();
+                list.append(&row);
+            }
+            *suggestions.borrow_mut() = related.into_iter().map(|(tag, _)| tag).collect();
+            popover.popup();
+        })
+    };
+
+    {
+        let ui = ui.clone();
+        let refresh = refresh.clone();
+        list.connect_row_activated(move |_, row| {
+            let Some(tag) = usize::try_from(row.index())
+                .ok()
+                .and_then(|row| suggestions.borrow().get(row).cloned())
+            else {
+                return;
+            };
+            ui.tag_values.borrow_mut().push(tag);
+            rebuild_tag_wrap(&ui);
+            refresh();
+        });
+    }
+    {
+        let refresh = refresh.clone();
+        ui.tags_input.connect_changed(move |_| refresh());
+    }
+    {
+        let focus_controller = gtk::EventControllerFocus::new();
+        {
+            let focused = focused.clone();
+            let refresh = refresh.clone();
+            focus_controller.connect_enter(move |_| {
+                focused.set(true);
+                refresh();
+            });
+        }
+        focus_controller.connect_leave(move |_| {
+            focused.set(false);
+            popover.popdown();
+        });
+        ui.tags_input.add_controller(focus_controller);
+    }
+}
+
+const TAG_COMPLETION_LIMIT: usize = 8;
+
+/// Suggests tags from the library's `Index::tag_vocabulary` for the word
+/// being typed in `tags_input`; the vocabulary follows rescans and edits. Up
+/// and Down move through the suggestions, Tab or a click inserts one; Enter
+/// still adds exactly what was typed, so brand-new tags keep working.
+fn install_tag_completion(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let list = gtk::ListBox::new();
+    list.set_selection_mode(SelectionMode::Single);
+    list.set_focusable(false);
+    let popover = gtk::Popover::builder()
+        .child(&list)
+        .autohide(false)
+        .has_arrow(false)
+        .position(gtk::PositionType::Bottom)
+        .build();
+    popover.set_parent(&ui.tags_input);
+    let suggestions = Rc::new(RefCell::new(Vec::<String>::new()));
+
+    let accept = {
+        let entry = ui.tags_input.clone();
+        let suggestions = suggestions.clone();
+        move |row: i32| {
+            let Some(tag) = usize::try_from(row)
+                .ok()
+                .and_then(|row| suggestions.borrow().get(row).cloned())
+            else {
+                return;
+            };
+            let text = entry.text();
+            let (head, _) = split_last_tag(&text);
+            entry.set_text(&format!("{head}{tag} "));
+            entry.set_position(-1);
+        }
+    };
+
+    {
+        let state = state.clone();
+        let tag_values = ui.tag_values.clone();
+        let list = list.clone();
+        let popover = popover.clone();
+        let suggestions = suggestions.clone();
+        ui.tags_input.connect_changed(move |entry| {
+            let text = entry.text();
+            let (_, word) = split_last_tag(&text);
+            let found = if word.is_empty() {
+                Vec::new()
+            } else {
+                let present = tag_values.borrow();
+                state
+                    .borrow()
+                    .library
+                    .tag_completions(word, TAG_COMPLETION_LIMIT + present.len())
+                    .into_iter()
+                    .filter(|(tag, _)| tag != word && !present.contains(tag))
+                    .take(TAG_COMPLETION_LIMIT)
+                    .collect::<Vec<_>>()
+            };
+
+            list.remove_all();
+            if found.is_empty() {
+                suggestions.borrow_mut().clear();
+                popover.popdown();
+                return;
+            }
+            for (tag, count) in &found {
+                let label = Label::builder()
+                    .label(format!("{tag}  ({count})"))
+                    .xalign(0.0)
+                    .build();
+                let row = gtk::ListBoxRow::builder()
+                    .child(&label)
+                    .focusable(false)
+                    .build();
+                list.append(&row);
+            }
+            *suggestions.borrow_mut() = found.into_iter().map(|(tag, _)| tag).collect();
+            list.select_row(list.row_at_index(0).as_ref());
+            popover.popup();
+        });
+    }
+    {
+        let accept = accept.clone();
+        list.connect_row_activated(move |_, row| {
+            accept(row.index());
+        });
+    }
+    {
+        let list = list.clone();
+        let popover = popover.clone();
+        let key_controller = gtk::EventControllerKey::new();
+        key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        key_controller.connect_key_pressed(move |_, key, _, _| {
+            if !popover.is_visible() {
+                return gtk::glib::Propagation::Proceed;
+            }
+            let selected = list.selected_row().map_or(0, |row| row.index());
+            let step = match key {
+                gtk::gdk::Key::Down => 1,
+                gtk::gdk::Key::Up => -1,
+                gtk::gdk::Key::Tab => {
+                    accept(selected);
+                    return gtk::glib::Propagation::Stop;
+                }
+                gtk::gdk::Key::Escape => {
+                    popover.popdown();
+                    return gtk::glib::Propagation::Stop;
+                }
+                _ => return gtk::glib::Propagation::Proceed,
+            };
+            if let Some(row) = list.row_at_index(selected + step) {
+                list.select_row(Some(&row));
+            }
+            gtk::glib::Propagation::Stop
+        });
+        ui.tags_input.add_controller(key_controller);
+    }
+    {
+        let focus_controller = gtk::EventControllerFocus::new();
+        focus_controller.connect_leave(move |_| {
+            popover.popdown();
+        });
+        ui.tags_input.add_controller(focus_controller);
+    }
 }
 
 fn setup_grid_factory(
     state: &Rc<RefCell<AppState>>,
     grid: &GridView,
     image_loader: Rc<ImageLoader>,
-) -> (gtk::gio::ListStore, SingleSelection) {
+) -> (gtk::gio::ListStore, MultiSelection) {
     let grid_store = gtk::gio::ListStore::new::<gtk::glib::BoxedAnyObject>();
-    let grid_selection = SingleSelection::new(Some(grid_store.clone()));
-    grid_selection.set_autoselect(false);
-    grid_selection.set_can_unselect(true);
+    let grid_selection = MultiSelection::new(Some(grid_store.clone()));
 
     let grid_factory = SignalListItemFactory::new();
     {
@@ -639,7 +1029,10 @@ fn setup_grid_factory(
                 if pos == gtk::INVALID_LIST_POSITION {
                     return;
                 }
-                selection_handle.set_selected(pos);
+                // Keep a multi-selection so the menu can batch edit it.
+                if !selection_handle.is_selected(pos) {
+                    selection_handle.select_item(pos, true);
+                }
                 popup_context_menu(&popover_handle, x, y);
                 gesture.set_state(gtk::EventSequenceState::Claimed);
             });
@@ -710,6 +1103,7 @@ fn setup_grid_factory(
             let card_weak = card.downgrade();
             let pending_request_slot = pending_request_id.clone();
             debug!("Load {}", image_path.display());
+            let is_video = image_loader_handle.media_kind(&image_path) == MediaKind::Video;
             let request_id = image_loader_handle.load(
                 image_path,
                 Some((256, 256)),
@@ -735,6 +1129,9 @@ fn setup_grid_factory(
                             texture_slot.borrow_mut().replace(texture.clone());
                             thumb.set_paintable(Some(&texture));
                         }
+                        Err(_) if is_video => {
+                            thumb.set_paintable(Some(&video_placeholder(&thumb)));
+                        }
                         Err(_) => thumb.set_paintable(None::<&gtk::gdk::Texture>),
                     }
                 },
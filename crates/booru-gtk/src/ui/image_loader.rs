@@ -1,11 +1,12 @@
 use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, VecDeque};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::{mpsc, Arc, Condvar, Mutex};
 use std::thread;
 use std::time::Duration;
 
+use booru_core::{ForeignImageFormat, MediaKind};
 use gtk::glib::prelude::Cast;
 use tracing::{debug, warn};
 
@@ -21,6 +22,7 @@ pub(super) enum ImageRequestKind {
 struct ImageDecodeTask {
     id: u64,
     path: PathBuf,
+    media: MediaKind,
     scale: Option<(i32, i32)>,
     kind: ImageRequestKind,
 }
@@ -56,10 +58,12 @@ pub(super) struct ImageLoader {
     next_id: Rc<Cell<u64>>,
     callbacks: Rc<RefCell<HashMap<u64, ImageLoadCallback>>>,
     queue_state: Arc<(Mutex<ImageTaskQueues>, Condvar)>,
+    /// The library's `video_extensions`; see [`ImageLoader::media_kind`].
+    video_extensions: Vec<String>,
 }
 
 impl ImageLoader {
-    pub(super) fn new() -> Self {
+    pub(super) fn new(video_extensions: Vec<String>) -> Self {
         let (result_tx, result_rx) = mpsc::channel::<ImageDecodeResult>();
         let queue_state = Arc::new((Mutex::new(ImageTaskQueues::default()), Condvar::new()));
 
@@ -119,9 +123,15 @@ impl ImageLoader {
             next_id: Rc::new(Cell::new(1)),
             callbacks,
             queue_state,
+            video_extensions,
         }
     }
 
+    /// Whether `path` is decoded as a video frame or as an image.
+    pub(super) fn media_kind(&self, path: &Path) -> MediaKind {
+        MediaKind::from_path(path, &self.video_extensions)
+    }
+
     pub(super) fn load<F>(
         &self,
         path: PathBuf,
@@ -138,6 +148,7 @@ impl ImageLoader {
 
         let task = ImageDecodeTask {
             id,
+            media: self.media_kind(&path),
             path,
             scale,
             kind,
@@ -192,7 +203,7 @@ fn image_decode_worker(
         };
 
         debug!(lane = ?lane, kind = ?task.kind, path = %task.path.display(), "render");
-        let outcome = decode_image_for_texture(&task.path, task.scale)
+        let outcome = decode_image_for_texture(&task.path, task.media, task.scale)
             .map(|image| ImageDecodeResult::Ok { id: task.id, image })
             .unwrap_or_else(|message| {
                 warn!(
@@ -226,6 +237,65 @@ fn spawn_image_worker(
         .expect("failed to start booru image worker thread");
 }
 
+#[cfg(feature = "ffmpeg")]
+fn load_video_frame(
+    path: &Path,
+    scale: Option<(i32, i32)>,
+) -> Result<gtk::gdk_pixbuf::Pixbuf, String> {
+    let frame = booru_core::extract_video_frame(path).map_err(|err| err.to_string())?;
+    pixbuf_from_bytes(frame, scale)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+fn load_video_frame(
+    _path: &Path,
+    _scale: Option<(i32, i32)>,
+) -> Result<gtk::gdk_pixbuf::Pixbuf, String> {
+    Err("built without the `ffmpeg` feature".to_string())
+}
+
+#[cfg(feature = "heic")]
+fn load_foreign_image(
+    path: &Path,
+    format: ForeignImageFormat,
+    scale: Option<(i32, i32)>,
+) -> Result<gtk::gdk_pixbuf::Pixbuf, String> {
+    let png = booru_core::convert_foreign_image(path, format).map_err(|err| err.to_string())?;
+    pixbuf_from_bytes(png, scale)
+}
+
+#[cfg(not(feature = "heic"))]
+fn load_foreign_image(
+    _path: &Path,
+    format: ForeignImageFormat,
+    _scale: Option<(i32, i32)>,
+) -> Result<gtk::gdk_pixbuf::Pixbuf, String> {
+    Err(format!(
+        "{} images need the `heic` feature and ImageMagick",
+        format.name()
+    ))
+}
+
+/// Decodes an encoded image held in memory, e.g. a converted frame.
+#[cfg(any(feature = "ffmpeg", feature = "heic"))]
+fn pixbuf_from_bytes(
+    bytes: Vec<u8>,
+    scale: Option<(i32, i32)>,
+) -> Result<gtk::gdk_pixbuf::Pixbuf, String> {
+    let stream = gtk::gio::MemoryInputStream::from_bytes(&gtk::glib::Bytes::from_owned(bytes));
+    match scale {
+        Some((width, height)) => gtk::gdk_pixbuf::Pixbuf::from_stream_at_scale(
+            &stream,
+            width,
+            height,
+            true,
+            None::<&gtk::gio::Cancellable>,
+        ),
+        None => gtk::gdk_pixbuf::Pixbuf::from_stream(&stream, None::<&gtk::gio::Cancellable>),
+    }
+    .map_err(|err| err.to_string())
+}
+
 fn queue_is_empty_for_lane(queues: &ImageTaskQueues, lane: ImageWorkerLane) -> bool {
     match lane {
         ImageWorkerLane::Detail => queues.detail.is_empty(),
@@ -253,15 +323,22 @@ fn remove_queued_task(queue: &mut VecDeque<ImageDecodeTask>, id: u64) -> bool {
 
 fn decode_image_for_texture(
     path: &PathBuf,
+    media: MediaKind,
     scale: Option<(i32, i32)>,
 ) -> Result<DecodedImage, String> {
-    let pixbuf = match scale {
-        Some((width, height)) => {
-            gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(path, width, height, true)
-        }
-        None => gtk::gdk_pixbuf::Pixbuf::from_file(path),
-    }
-    .map_err(|err| err.to_string())?;
+    let pixbuf = match media {
+        MediaKind::Image => match ForeignImageFormat::detect(path) {
+            Some(format) => load_foreign_image(path, format, scale)?,
+            None => match scale {
+                Some((width, height)) => {
+                    gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(path, width, height, true)
+                }
+                None => gtk::gdk_pixbuf::Pixbuf::from_file(path),
+            }
+            .map_err(|err| err.to_string())?,
+        },
+        MediaKind::Video => load_video_frame(path, scale)?,
+    };
 
     if pixbuf.colorspace() != gtk::gdk_pixbuf::Colorspace::Rgb {
         return Err("unsupported pixbuf colorspace".to_string());
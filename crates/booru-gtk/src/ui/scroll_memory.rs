@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+
+const DEFAULT_CAPACITY: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(super) struct ScrollPosition {
+    pub(super) list: f64,
+    pub(super) grid: f64,
+}
+
+impl ScrollPosition {
+    fn is_top(&self) -> bool {
+        self.list <= 0.0 && self.grid <= 0.0
+    }
+}
+
+/// Remembers browser scroll offsets per search query, evicting the least
+/// recently used query once `capacity` entries are stored.
+#[derive(Debug)]
+pub(super) struct ScrollMemory {
+    entries: VecDeque<(String, ScrollPosition)>,
+    capacity: usize,
+}
+
+impl Default for ScrollMemory {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl ScrollMemory {
+    pub(super) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub(super) fn remember(&mut self, query: &str, position: ScrollPosition) {
+        let key = normalize_query_key(query);
+        self.forget_key(&key);
+        // Scrolled-to-top is the default state; storing it would only let
+        // intermediate keystrokes evict useful entries.
+        if position.is_top() {
+            return;
+        }
+        self.entries.push_back((key, position));
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub(super) fn recall(&mut self, query: &str) -> Option<ScrollPosition> {
+        let key = normalize_query_key(query);
+        let pos = self.entries.iter().position(|(entry, _)| *entry == key)?;
+        let entry = self.entries.remove(pos)?;
+        let position = entry.1;
+        self.entries.push_back(entry);
+        Some(position)
+    }
+
+    fn forget_key(&mut self, key: &str) {
+        self.entries.retain(|(entry, _)| entry != key);
+    }
+}
+
+fn normalize_query_key(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScrollMemory, ScrollPosition};
+
+    fn pos(list: f64, grid: f64) -> ScrollPosition {
+        ScrollPosition { list, grid }
+    }
+
+    #[test]
+    fn recall_returns_remembered_position() {
+        let mut memory = ScrollMemory::default();
+        memory.remember("cat", pos(120.0, 40.0));
+        assert_eq!(memory.recall("cat"), Some(pos(120.0, 40.0)));
+        assert_eq!(memory.recall("dog"), None);
+    }
+
+    #[test]
+    fn query_key_ignores_surrounding_and_repeated_whitespace() {
+        let mut memory = ScrollMemory::default();
+        memory.remember("  cat   dog ", pos(10.0, 0.0));
+        assert_eq!(memory.recall("cat dog"), Some(pos(10.0, 0.0)));
+    }
+
+    #[test]
+    fn remembering_top_position_forgets_query() {
+        let mut memory = ScrollMemory::default();
+        memory.remember("cat", pos(50.0, 0.0));
+        memory.remember("cat", pos(0.0, 0.0));
+        assert_eq!(memory.recall("cat"), None);
+    }
+
+    #[test]
+    fn capacity_evicts_least_recently_used_query() {
+        let mut memory = ScrollMemory::with_capacity(2);
+        memory.remember("a", pos(1.0, 0.0));
+        memory.remember("b", pos(2.0, 0.0));
+        assert!(memory.recall("a").is_some());
+        memory.remember("c", pos(3.0, 0.0));
+
+        assert_eq!(memory.recall("b"), None);
+        assert_eq!(memory.recall("a"), Some(pos(1.0, 0.0)));
+        assert_eq!(memory.recall("c"), Some(pos(3.0, 0.0)));
+    }
+}
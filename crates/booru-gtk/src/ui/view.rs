@@ -1,14 +1,22 @@
 use std::cell::{Cell, RefCell};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
 
 use adw::prelude::*;
 use adw::{ActionRow, AlertDialog, Toast};
 use anyhow::{anyhow, Result};
-use booru_core::{apply_update_to_image, BooruConfig, EditUpdate, Library};
-use gtk::{self, Box as GtkBox, Button, Label, Picture, TextView};
+use booru_core::{
+    apply_update_to_image, apply_update_to_image_with_previous, restore_edits_for_image,
+    BooruConfig, BooruEdits, BooruError, EditUpdate, Library, LibraryWatcher, MediaKind,
+    RescanReport,
+};
+use gtk::{self, Box as GtkBox, Button, Entry, Label, Picture, TextView};
 
 use super::image_loader::ImageRequestKind;
+use super::scroll_memory::ScrollPosition;
 use super::*;
 
 pub(crate) fn scan_library(config: &BooruConfig, quiet: bool) -> Result<Library> {
@@ -124,12 +132,17 @@ struct DetailSnapshot {
     tags: Vec<String>,
     notes: String,
     sensitive: bool,
+    has_previous: bool,
+    has_next: bool,
 }
 
 pub(super) fn refresh_detail(state: &Rc<RefCell<AppState>>, ui: &Ui) {
     let snapshot = {
         let state = state.borrow();
-        let Some(idx) = state.selected_item_index() else {
+        if state.selected_indices.len() > 1 {
+            return show_multi_selection(ui, state.selected_indices.len());
+        }
+        let (Some(pos), Some(idx)) = (state.selected_pos, state.selected_item_index()) else {
             return clear_detail(ui);
         };
         let item = &state.library.index.items[idx];
@@ -146,6 +159,8 @@ pub(super) fn refresh_detail(state: &Rc<RefCell<AppState>>, ui: &Ui) {
             tags: item.merged_tags(),
             notes: item.edits.notes.clone().unwrap_or_default(),
             sensitive: item.merged_sensitive(),
+            has_previous: pos > 0,
+            has_next: pos + 1 < state.filtered_indices.len(),
         }
     };
 
@@ -178,6 +193,8 @@ pub(super) fn refresh_detail(state: &Rc<RefCell<AppState>>, ui: &Ui) {
         }
     }
     ui.open_file_button.set_sensitive(true);
+    ui.previous_item_button.set_sensitive(snapshot.has_previous);
+    ui.next_item_button.set_sensitive(snapshot.has_next);
     ui.detail.set_text(&snapshot.detail);
     {
         let mut tag_values = ui.tag_values.borrow_mut();
@@ -217,6 +234,11 @@ pub(super) fn refresh_detail(state: &Rc<RefCell<AppState>>, ui: &Ui) {
                 Ok(texture) => {
                     ui_handle.picture.set_paintable(Some(&texture));
                 }
+                Err(_) if ui_handle.image_loader.media_kind(&image_path) == MediaKind::Video => {
+                    ui_handle
+                        .picture
+                        .set_paintable(Some(&video_placeholder(&ui_handle.picture)));
+                }
                 Err(err) => {
                     ui_handle.picture.set_paintable(None::<&gtk::gdk::Texture>);
                     show_error_dialog(
@@ -231,6 +253,14 @@ pub(super) fn refresh_detail(state: &Rc<RefCell<AppState>>, ui: &Ui) {
     ui.detail_pending_request_id.set(Some(request_id));
 }
 
+fn show_multi_selection(ui: &Ui, count: usize) {
+    clear_detail(ui);
+    ui.empty_page.set_title(&format!("{count} items selected"));
+    ui.empty_page.set_description(Some(
+        "Right-click the grid or use the main menu to batch edit the selection.",
+    ));
+}
+
 fn clear_detail(ui: &Ui) {
     if let Some(request_id) = ui.detail_pending_request_id.replace(None) {
         ui.image_loader.cancel_if_queued(request_id);
@@ -240,6 +270,10 @@ fn clear_detail(ui: &Ui) {
     ui.edit_sheet.set_open(false);
     ui.edit_sheet.set_can_open(false);
     ui.detail_stack.set_visible_child_name("empty");
+    ui.empty_page.set_title("No item selected");
+    ui.empty_page.set_description(Some(
+        "Select an item from the left panel to preview and edit metadata.",
+    ));
     ui.title.set_text("(no match)");
     ui.author.set_label("-");
     ui.author.set_sensitive(false);
@@ -250,6 +284,8 @@ fn clear_detail(ui: &Ui) {
     ui.source_url.set_sensitive(false);
     ui.search_same_source_button.set_sensitive(false);
     ui.open_file_button.set_sensitive(false);
+    ui.previous_item_button.set_sensitive(false);
+    ui.next_item_button.set_sensitive(false);
     ui.detail.set_text("");
     ui.tag_values.borrow_mut().clear();
     ui.tags_input.set_text("");
@@ -259,6 +295,84 @@ fn clear_detail(ui: &Ui) {
     ui.picture.set_paintable(None::<&gtk::gdk::Texture>);
 }
 
+/// Steps the selection through the current filtered list, as the browser
+/// pane would, and keeps the new item in view.
+pub(super) fn select_adjacent_item(state: &Rc<RefCell<AppState>>, ui: &Ui, delta: isize) {
+    stop_slideshow(state, ui);
+    let selected_pos = {
+        let mut state = state.borrow_mut();
+        let Some(pos) = state.selected_pos else {
+            return;
+        };
+        let Some(next) = pos
+            .checked_add_signed(delta)
+            .filter(|next| *next < state.filtered_indices.len())
+        else {
+            return;
+        };
+        state.selected_pos = Some(next);
+        Some(next)
+    };
+
+    sync_browser_selection(ui, selected_pos);
+    refresh_detail(state, ui);
+    ensure_selected_item_visible(ui, selected_pos);
+}
+
+/// Advances the selection every `slideshow_interval` until `stop_slideshow`.
+/// It walks `filtered_indices`, so hidden sensitive items stay hidden.
+pub(super) fn start_slideshow(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let interval = state.borrow().slideshow_interval;
+    let state_handle = state.clone();
+    let ui_handle = ui.clone();
+    let source_id = gtk::glib::timeout_add_local(interval, move || {
+        advance_slideshow(&state_handle, &ui_handle);
+        gtk::glib::ControlFlow::Continue
+    });
+    if let Some(previous) = state.borrow_mut().slideshow.replace(source_id) {
+        previous.remove();
+    }
+    set_slideshow_action_state(ui, true);
+}
+
+pub(super) fn stop_slideshow(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let Some(source_id) = state.borrow_mut().slideshow.take() else {
+        return;
+    };
+    source_id.remove();
+    set_slideshow_action_state(ui, false);
+}
+
+fn set_slideshow_action_state(ui: &Ui, running: bool) {
+    if let Some(action) = ui
+        .window
+        .lookup_action("slideshow")
+        .and_then(|action| action.downcast::<gtk::gio::SimpleAction>().ok())
+    {
+        action.set_state(&gtk::glib::Variant::from(running));
+    }
+}
+
+/// Steps to the next item, wrapping to the first. The selection is updated
+/// in the state before the browsers, so their handlers see no manual change.
+fn advance_slideshow(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let selected_pos = {
+        let mut state = state.borrow_mut();
+        let len = state.filtered_indices.len();
+        if len == 0 {
+            return;
+        }
+        let next = state.selected_pos.map_or(0, |pos| (pos + 1) % len);
+        state.selected_pos = Some(next);
+        state.selected_indices = vec![state.filtered_indices[next]];
+        Some(next)
+    };
+
+    sync_browser_selection(ui, selected_pos);
+    refresh_detail(state, ui);
+    ensure_selected_item_visible(ui, selected_pos);
+}
+
 pub(super) fn open_selected_file(state: &Rc<RefCell<AppState>>, ui: &Ui) {
     let Some(image_path) = ({
         let state = state.borrow();
@@ -302,15 +416,70 @@ pub(super) fn open_selected_source_url(state: &Rc<RefCell<AppState>>, ui: &Ui) {
     }
 }
 
+pub(super) fn open_selected_author_url(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let author_url = {
+        let state = state.borrow();
+        state
+            .selected_item_index()
+            .and_then(|idx| state.library.index.items.get(idx))
+            .and_then(|item| item.author_url())
+    };
+    let Some(author_url) = author_url else {
+        show_error_dialog(
+            ui,
+            "Open author page failed",
+            "No author page for selected item.",
+        );
+        return;
+    };
+
+    match launch_uri(&author_url) {
+        Ok(()) => {
+            hide_banner(ui);
+        }
+        Err(err) => {
+            show_error_dialog(ui, "Failed to open author page", &format!("{err}"));
+        }
+    }
+}
+
 pub(super) fn apply_search(state: &Rc<RefCell<AppState>>, ui: &Ui, query: String) {
-    {
+    let restored = {
         let mut state = state.borrow_mut();
-        state.query = query;
+        let previous_query = std::mem::replace(&mut state.query, query);
+        state
+            .scroll_memory
+            .remember(&previous_query, current_scroll_position(ui));
         state.rebuild_filter();
         // Keep search passive: changing the filter should not implicitly open a detail item.
         state.selected_pos = None;
-    }
+        let query = state.query.clone();
+        state.scroll_memory.recall(&query)
+    };
     rebuild_view(state, ui);
+    restore_scroll_position(ui, restored.unwrap_or_default());
+}
+
+fn current_scroll_position(ui: &Ui) -> ScrollPosition {
+    ScrollPosition {
+        list: ui.list_scroll.vadjustment().value(),
+        grid: ui.grid_scroll.vadjustment().value(),
+    }
+}
+
+fn restore_scroll_position(ui: &Ui, position: ScrollPosition) {
+    // Adjustment bounds are only updated after the rebuilt rows are laid out.
+    let ui_handle = ui.clone();
+    gtk::glib::idle_add_local_once(move || {
+        for (scroll, value) in [
+            (&ui_handle.list_scroll, position.list),
+            (&ui_handle.grid_scroll, position.grid),
+        ] {
+            let adjustment = scroll.vadjustment();
+            let max = (adjustment.upper() - adjustment.page_size()).max(adjustment.lower());
+            adjustment.set_value(value.clamp(adjustment.lower(), max));
+        }
+    });
 }
 
 pub(super) fn selected_author(state: &Rc<RefCell<AppState>>) -> Option<String> {
@@ -353,7 +522,7 @@ pub(super) fn save_selected_edits(state: &Rc<RefCell<AppState>>, ui: &Ui) -> Res
     let tags = ui.tag_values.borrow().clone();
     let notes = get_notes_text(&ui.notes);
     let sensitive = ui.item_sensitive.is_active();
-    let edits = apply_update_to_image(
+    let (previous, edits) = apply_update_to_image_with_previous(
         &image_path,
         EditUpdate {
             set_tags: Some(tags),
@@ -361,7 +530,11 @@ pub(super) fn save_selected_edits(state: &Rc<RefCell<AppState>>, ui: &Ui) -> Res
             remove_tags: Vec::new(),
             clear_tags: false,
             notes: Some(notes),
+            clear_notes: false,
             sensitive: Some(sensitive),
+            rating: None,
+            set_fields: Vec::new(),
+            remove_fields: Vec::new(),
         },
     )?;
 
@@ -370,35 +543,418 @@ pub(super) fn save_selected_edits(state: &Rc<RefCell<AppState>>, ui: &Ui) -> Res
         if let Some(item) = state.library.index.items.get_mut(item_idx) {
             item.edits = edits;
         }
+        state.library.index.invalidate_lookups();
         state.rebuild_filter();
     }
 
     rebuild_view(state, ui);
-    show_toast(ui, "Edits saved");
+    let toast = Toast::new("Edits saved");
+    toast.set_timeout(5);
+    toast.set_button_label(Some("Undo"));
+    {
+        let state = state.clone();
+        let ui_handle = ui.clone();
+        toast.connect_button_clicked(move |_| {
+            if let Err(err) = undo_edits(&state, &ui_handle, &image_path, previous.as_ref()) {
+                show_error_dialog(&ui_handle, "Undo failed", &format!("{err}"));
+            }
+        });
+    }
+    ui.toast_overlay.add_toast(toast);
     hide_banner(ui);
     Ok(())
 }
 
-pub(super) fn rescan_library(state: &Rc<RefCell<AppState>>, ui: &Ui) -> Result<()> {
-    let (config, quiet) = {
+/// Puts back the edits `image_path` had before the last save.
+fn undo_edits(
+    state: &Rc<RefCell<AppState>>,
+    ui: &Ui,
+    image_path: &Path,
+    previous: Option<&BooruEdits>,
+) -> Result<()> {
+    restore_edits_for_image(image_path, previous)?;
+    {
+        let mut state = state.borrow_mut();
+        // Look the item up again; a rescan may have moved it since the save.
+        if let Some(item) = state
+            .library
+            .index
+            .items
+            .iter_mut()
+            .find(|item| item.image_path == image_path)
+        {
+            item.edits = previous.cloned().unwrap_or_default();
+        }
+        state.library.index.invalidate_lookups();
+        state.rebuild_filter();
+    }
+    rebuild_view(state, ui);
+    show_toast(ui, "Edits undone");
+    Ok(())
+}
+
+/// Changes applied by the batch-edit dialog to every selected item.
+struct BatchEdit {
+    add_tags: Vec<String>,
+    sensitive: Option<bool>,
+}
+
+pub(super) fn present_batch_edit_dialog(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let count = state.borrow().selected_indices.len();
+    if count == 0 {
+        show_toast(ui, "No items selected");
+        return;
+    }
+
+    let tags_input = Entry::builder().placeholder_text("Tags to add").build();
+    let sensitive = gtk::DropDown::from_strings(&[
+        "Keep sensitive flag",
+        "Mark sensitive",
+        "Mark not sensitive",
+    ]);
+    let content = GtkBox::new(gtk::Orientation::Vertical, 12);
+    content.append(&tags_input);
+    content.append(&sensitive);
+
+    let dialog = AlertDialog::new(Some(&format!("Edit {count} items")), None);
+    dialog.set_extra_child(Some(&content));
+    dialog.add_responses(&[("cancel", "Cancel"), ("apply", "Apply")]);
+    dialog.set_response_appearance("apply", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("apply"));
+    dialog.set_close_response("cancel");
+
+    let state_handle = state.clone();
+    let ui_handle = ui.clone();
+    dialog.connect_response(None, move |_, response| {
+        if response != "apply" {
+            return;
+        }
+        let edit = BatchEdit {
+            add_tags: parse_tags_input(&tags_input.text()),
+            sensitive: match sensitive.selected() {
+                1 => Some(true),
+                2 => Some(false),
+                _ => None,
+            },
+        };
+        if edit.add_tags.is_empty() && edit.sensitive.is_none() {
+            show_toast(&ui_handle, "Nothing to apply");
+            return;
+        }
+        apply_batch_edit(&state_handle, &ui_handle, edit);
+    });
+    dialog.present(Some(&ui.window));
+}
+
+/// Applies `edit` to each selected item, then rebuilds the filter once.
+/// Items that fail are skipped and listed together in the banner.
+fn apply_batch_edit(state: &Rc<RefCell<AppState>>, ui: &Ui, edit: BatchEdit) {
+    let targets = {
+        let state = state.borrow();
+        state
+            .selected_indices
+            .iter()
+            .filter_map(|idx| {
+                let item = state.library.index.items.get(*idx)?;
+                Some((*idx, item.image_path.clone()))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut updated = Vec::new();
+    let mut failures = Vec::new();
+    for (item_idx, image_path) in &targets {
+        let update = EditUpdate {
+            add_tags: edit.add_tags.clone(),
+            sensitive: edit.sensitive,
+            ..EditUpdate::default()
+        };
+        match apply_update_to_image(image_path, update) {
+            Ok(edits) => updated.push((*item_idx, edits)),
+            Err(err) => failures.push(format!("{}: {err}", image_path.display())),
+        }
+    }
+
+    {
+        let mut state = state.borrow_mut();
+        for (item_idx, edits) in updated {
+            if let Some(item) = state.library.index.items.get_mut(item_idx) {
+                item.edits = edits;
+            }
+        }
+        state.library.index.invalidate_lookups();
+        state.rebuild_filter();
+    }
+
+    rebuild_view(state, ui);
+    if failures.is_empty() {
+        show_toast(ui, &format!("Updated {} items", targets.len()));
+        hide_banner(ui);
+    } else {
+        show_banner(
+            ui,
+            &format!(
+                "{} of {} items were not updated: {}",
+                failures.len(),
+                targets.len(),
+                failures.join("; ")
+            ),
+        );
+    }
+}
+
+pub(super) fn present_delete_item_dialog(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let Some(image_path) = ({
+        let state = state.borrow();
+        state
+            .selected_item_index()
+            .and_then(|idx| state.library.index.items.get(idx))
+            .map(|item| item.image_path.clone())
+    }) else {
+        show_toast(ui, "No selected item");
+        return;
+    };
+
+    let name = image_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| image_path.display().to_string());
+    let dialog = AlertDialog::new(
+        Some("Delete item?"),
+        Some(&format!(
+            "{name} and its metadata files will be moved to the trash."
+        )),
+    );
+    dialog.add_responses(&[("cancel", "Cancel"), ("delete", "Delete")]);
+    dialog.set_response_appearance("delete", adw::ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("cancel"));
+    dialog.set_close_response("cancel");
+
+    let state_handle = state.clone();
+    let ui_handle = ui.clone();
+    dialog.connect_response(None, move |_, response| {
+        if response != "delete" {
+            return;
+        }
+        if let Err(err) = delete_item(&state_handle, &ui_handle, &image_path) {
+            show_error_dialog(&ui_handle, "Failed to delete item", &format!("{err}"));
+        }
+    });
+    dialog.present(Some(&ui.window));
+}
+
+/// Trashes the image and whichever sidecars exist, then drops the item from
+/// the index. The selection stays at the same position, which now holds the
+/// next item.
+fn delete_item(state: &Rc<RefCell<AppState>>, ui: &Ui, image_path: &Path) -> Result<()> {
+    stop_slideshow(state, ui);
+    let files = {
         let state = state.borrow();
-        (state.library.config.clone(), state.quiet)
+        let item = state
+            .library
+            .index
+            .get_by_path(image_path)
+            .ok_or_else(|| anyhow!("{} is no longer indexed", image_path.display()))?;
+        [
+            item.image_path.clone(),
+            item.meta_path.clone(),
+            item.booru_path.clone(),
+        ]
     };
-    let library = scan_library(&config, quiet)?;
+    for path in files {
+        if std::fs::symlink_metadata(&path).is_err() {
+            continue;
+        }
+        trash::delete(&path).map_err(|err| anyhow!("failed to trash {}: {err}", path.display()))?;
+    }
+
     {
         let mut state = state.borrow_mut();
-        state.library = library;
+        if let Some(idx) = state
+            .library
+            .index
+            .items
+            .iter()
+            .position(|item| item.image_path == image_path)
+        {
+            state.library.index.remove(idx);
+        }
         state.rebuild_filter();
     }
+
     rebuild_view(state, ui);
-    show_toast(ui, "Rescan complete");
+    let selected_pos = state.borrow().selected_pos;
+    ensure_selected_item_visible(ui, selected_pos);
+    show_toast(ui, "Item moved to trash");
     hide_banner(ui);
     Ok(())
 }
 
+pub(super) fn rescan_library(state: &Rc<RefCell<AppState>>, ui: &Ui) -> Result<()> {
+    let message = match rescan_library_incremental(state) {
+        Ok(report) => format!(
+            "Rescan complete: {} added, {} updated, {} removed",
+            report.added, report.updated, report.removed
+        ),
+        Err(err) => {
+            if !state.borrow().quiet {
+                eprintln!("warning: incremental rescan failed, doing a full scan: {err}");
+            }
+            let (config, quiet) = {
+                let state = state.borrow();
+                (state.library.config.clone(), state.quiet)
+            };
+            let library = scan_library(&config, quiet)?;
+            let mut state = state.borrow_mut();
+            state.library = library;
+            state.rebuild_filter();
+            "Rescan complete".to_string()
+        }
+    };
+    rebuild_view(state, ui);
+    show_toast(ui, &message);
+    hide_banner(ui);
+    Ok(())
+}
+
+fn rescan_library_incremental(state: &Rc<RefCell<AppState>>) -> Result<RescanReport, BooruError> {
+    let mut state = state.borrow_mut();
+    let report = state.library.rescan_incremental()?;
+    if !state.quiet {
+        for warning in &report.warnings {
+            eprintln!("warning: {}: {}", warning.path.display(), warning.message);
+        }
+    }
+    state.rebuild_filter();
+    Ok(report)
+}
+
+/// Scans the library on a worker thread, appending items to the view as they
+/// load. The finished library then replaces the partial one, which brings in
+/// the scan warnings and mtimes, and `--watch` starts only after that.
+pub(super) fn scan_library_in_background(state: &Rc<RefCell<AppState>>, ui: &Ui, watch: bool) {
+    let (item_tx, item_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+    let config = state.borrow().library.config.clone();
+    thread::spawn(move || {
+        let _ = done_tx.send(Library::scan_streaming(config, item_tx));
+    });
+    show_banner(ui, "Scanning library…");
+
+    let state = state.clone();
+    let ui = ui.clone();
+    gtk::glib::timeout_add_local(Duration::from_millis(250), move || {
+        let mut received = false;
+        {
+            let mut state = state.borrow_mut();
+            for item in item_rx.try_iter() {
+                state.library.index.push(item);
+                received = true;
+            }
+        }
+
+        let result = match done_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => {
+                if received {
+                    state.borrow_mut().rebuild_filter();
+                    rebuild_view(&state, &ui);
+                    let count = state.borrow().library.index.items.len();
+                    show_banner(&ui, &format!("Scanning library… {count} items"));
+                }
+                return gtk::glib::ControlFlow::Continue;
+            }
+            Err(TryRecvError::Disconnected) => {
+                hide_banner(&ui);
+                show_error_dialog(
+                    &ui,
+                    "Failed to scan library",
+                    "The scan stopped unexpectedly.",
+                );
+                return gtk::glib::ControlFlow::Break;
+            }
+        };
+
+        match result {
+            Ok(library) => {
+                let mut state = state.borrow_mut();
+                if !state.quiet {
+                    for warning in &library.warnings {
+                        eprintln!("warning: {}: {}", warning.path.display(), warning.message);
+                    }
+                }
+                state.library = library;
+                state.rebuild_filter();
+            }
+            Err(err) => show_error_dialog(&ui, "Failed to scan library", &format!("{err}")),
+        }
+        rebuild_view(&state, &ui);
+        hide_banner(&ui);
+        if watch {
+            if let Err(err) = watch_library(&state, &ui) {
+                show_error_dialog(&ui, "Failed to watch library", &format!("{err}"));
+            }
+        }
+        gtk::glib::ControlFlow::Break
+    });
+}
+
+/// Starts the `--watch` file watcher. The watcher thread only signals; the
+/// rescan itself runs here on the main thread, which owns the library.
+pub(super) fn watch_library(state: &Rc<RefCell<AppState>>, ui: &Ui) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let config = state.borrow().library.config.clone();
+    let watcher = LibraryWatcher::new(&config, move || {
+        let _ = tx.send(());
+    })?;
+    state.borrow_mut()._watcher = Some(watcher);
+
+    let state = state.clone();
+    let ui = ui.clone();
+    gtk::glib::timeout_add_local(Duration::from_millis(250), move || {
+        match rx.try_recv() {
+            Ok(()) => {}
+            Err(TryRecvError::Empty) => return gtk::glib::ControlFlow::Continue,
+            Err(TryRecvError::Disconnected) => return gtk::glib::ControlFlow::Break,
+        }
+        while rx.try_recv().is_ok() {}
+
+        match rescan_library_incremental(&state) {
+            Ok(report) if report.added + report.updated + report.removed > 0 => {
+                rebuild_view(&state, &ui);
+                if report.added > 0 {
+                    show_toast(&ui, &format!("{} new items", report.added));
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                if !state.borrow().quiet {
+                    eprintln!("warning: automatic rescan failed: {err}");
+                }
+            }
+        }
+        gtk::glib::ControlFlow::Continue
+    });
+    Ok(())
+}
+
+fn is_tag_separator(ch: char) -> bool {
+    ch.is_whitespace() || ch == ',' || ch == ';'
+}
+
+/// Splits `input` right before the tag still being typed.
+pub(super) fn split_last_tag(input: &str) -> (&str, &str) {
+    let start = input
+        .char_indices()
+        .rev()
+        .find(|(_, ch)| is_tag_separator(*ch))
+        .map_or(0, |(idx, ch)| idx + ch.len_utf8());
+    input.split_at(start)
+}
+
 fn parse_tags_input(input: &str) -> Vec<String> {
     input
-        .split(|ch: char| ch.is_whitespace() || ch == ',' || ch == ';')
+        .split(is_tag_separator)
         .map(str::trim)
         .filter(|tag| !tag.is_empty())
         .map(ToString::to_string)
@@ -490,41 +1046,40 @@ pub(super) fn grid_cell_widgets(list_item: &gtk::ListItem) -> Option<(GtkBox, Pi
     Some((card, thumb, caption))
 }
 
+/// Selects `selected_pos` alone in both browsers, collapsing any grid
+/// multi-selection.
 pub(super) fn sync_browser_selection(ui: &Ui, selected_pos: Option<usize>) {
-    let current_list_pos = ui
-        .list
-        .selected_row()
-        .and_then(|row| usize::try_from(row.index()).ok());
-    let current_grid_pos = match ui.grid_selection.selected() {
-        gtk::INVALID_LIST_POSITION => None,
-        pos => usize::try_from(pos).ok(),
-    };
+    sync_list_selection(ui, selected_pos);
 
+    let bitset = ui.grid_selection.selection();
+    let current_grid_pos = (bitset.size() == 1).then(|| bitset.nth(0) as usize);
+    if current_grid_pos == selected_pos && (selected_pos.is_some() || bitset.is_empty()) {
+        return;
+    }
     match selected_pos {
         Some(pos) => {
-            if current_list_pos != Some(pos) {
-                if let Some(row) = ui.list.row_at_index(pos as i32) {
-                    ui.list.select_row(Some(&row));
-                } else {
-                    ui.list.unselect_all();
-                }
-            }
-
-            if current_grid_pos != Some(pos) {
-                ui.grid_selection.set_selected(pos as u32);
-            }
+            ui.grid_selection.select_item(pos as u32, true);
         }
         None => {
-            if current_list_pos.is_some() {
-                ui.list.unselect_all();
-            }
-            if current_grid_pos.is_some() {
-                ui.grid_selection.set_selected(gtk::INVALID_LIST_POSITION);
-            }
+            ui.grid_selection.unselect_all();
         }
     }
 }
 
+pub(super) fn sync_list_selection(ui: &Ui, selected_pos: Option<usize>) {
+    let current_list_pos = ui
+        .list
+        .selected_row()
+        .and_then(|row| usize::try_from(row.index()).ok());
+    if current_list_pos == selected_pos {
+        return;
+    }
+    match selected_pos.and_then(|pos| ui.list.row_at_index(pos as i32)) {
+        Some(row) => ui.list.select_row(Some(&row)),
+        None => ui.list.unselect_all(),
+    }
+}
+
 pub(super) fn ensure_selected_item_visible(ui: &Ui, selected_pos: Option<usize>) {
     let Some(pos) = selected_pos else {
         return;
@@ -562,6 +1117,19 @@ pub(super) fn ensure_selected_item_visible(ui: &Ui, selected_pos: Option<usize>)
     }
 }
 
+/// Shown instead of a frame when a video has no thumbnail, e.g. without
+/// the `ffmpeg` feature or binary.
+pub(super) fn video_placeholder(widget: &impl IsA<gtk::Widget>) -> gtk::IconPaintable {
+    gtk::IconTheme::for_display(&widget.display()).lookup_icon(
+        "video-x-generic-symbolic",
+        &["video-x-generic"],
+        128,
+        widget.scale_factor(),
+        gtk::TextDirection::None,
+        gtk::IconLookupFlags::empty(),
+    )
+}
+
 pub(super) fn show_toast(ui: &Ui, message: &str) {
     let toast = Toast::new(message);
     toast.set_timeout(2);
@@ -576,6 +1144,11 @@ pub(super) fn show_error_dialog(ui: &Ui, heading: &str, message: &str) {
     dialog.present(Some(&ui.window));
 }
 
+fn show_banner(ui: &Ui, title: &str) {
+    ui.banner.set_title(title);
+    ui.banner.set_revealed(true);
+}
+
 pub(super) fn hide_banner(ui: &Ui) {
     ui.banner.set_revealed(false);
 }
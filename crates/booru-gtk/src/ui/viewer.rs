@@ -0,0 +1,258 @@
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use adw::prelude::*;
+use booru_core::MediaKind;
+use gtk::{self, Label, Picture, ScrolledWindow};
+
+use super::image_loader::ImageRequestKind;
+use super::view::{ensure_selected_item_visible, video_placeholder};
+use super::{AppState, Ui};
+
+const MAX_ZOOM: f64 = 16.0;
+const ZOOM_STEP: f64 = 1.25;
+
+/// A fullscreen window over the main one. It walks a snapshot of the filtered
+/// items taken when it opened and never moves the main selection.
+struct ImageViewer {
+    state: Rc<RefCell<AppState>>,
+    ui: Ui,
+    window: gtk::Window,
+    scroll: ScrolledWindow,
+    picture: Picture,
+    caption: Label,
+    items: Vec<PathBuf>,
+    position: Cell<usize>,
+    /// `None` fits the image to the window.
+    zoom: Cell<Option<f64>>,
+    drag_origin: Cell<(f64, f64)>,
+    load_seq: Cell<u64>,
+    pending_request_id: Cell<Option<u64>>,
+}
+
+/// Opens the selected item fullscreen. Scrolling zooms, dragging pans,
+/// Left/Right step through the filtered items, `0` fits the image again and
+/// Escape closes.
+pub(super) fn open_image_viewer(state: &Rc<RefCell<AppState>>, ui: &Ui) {
+    let (items, position) = {
+        let state = state.borrow();
+        let Some(position) = state
+            .selected_pos
+            .filter(|pos| *pos < state.filtered_indices.len())
+        else {
+            return;
+        };
+        let items = state
+            .filtered_indices
+            .iter()
+            .map(|idx| state.library.index.items[*idx].image_path.clone())
+            .collect::<Vec<_>>();
+        (items, position)
+    };
+
+    let picture = Picture::builder()
+        .can_shrink(true)
+        .content_fit(gtk::ContentFit::Contain)
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let scroll = ScrolledWindow::builder().child(&picture).build();
+    let caption = Label::builder()
+        .halign(gtk::Align::Center)
+        .valign(gtk::Align::End)
+        .margin_bottom(12)
+        .css_classes(["osd"])
+        .build();
+    let overlay = gtk::Overlay::builder().child(&scroll).build();
+    overlay.add_overlay(&caption);
+    let window = gtk::Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .decorated(false)
+        .child(&overlay)
+        .build();
+
+    let viewer = Rc::new(ImageViewer {
+        state: state.clone(),
+        ui: ui.clone(),
+        window,
+        scroll,
+        picture,
+        caption,
+        items,
+        position: Cell::new(position),
+        zoom: Cell::new(None),
+        drag_origin: Cell::new((0.0, 0.0)),
+        load_seq: Cell::new(0),
+        pending_request_id: Cell::new(None),
+    });
+    viewer.install_controllers();
+    viewer.window.fullscreen();
+    viewer.window.present();
+    viewer.show(position);
+}
+
+impl ImageViewer {
+    fn install_controllers(self: &Rc<Self>) {
+        let keys = gtk::EventControllerKey::new();
+        // Capture, so the scrolled window doesn't eat the arrow keys.
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let viewer = self.clone();
+        keys.connect_key_pressed(move |_, key, _, _| {
+            match key {
+                gtk::gdk::Key::Escape => viewer.window.close(),
+                gtk::gdk::Key::Left => viewer.step(-1),
+                gtk::gdk::Key::Right => viewer.step(1),
+                gtk::gdk::Key::_0 | gtk::gdk::Key::KP_0 => viewer.set_zoom(None),
+                _ => return gtk::glib::Propagation::Proceed,
+            }
+            gtk::glib::Propagation::Stop
+        });
+        self.window.add_controller(keys);
+
+        let wheel = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+        wheel.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let viewer = self.clone();
+        wheel.connect_scroll(move |_, _, dy| {
+            viewer.zoom_by(dy);
+            gtk::glib::Propagation::Stop
+        });
+        self.scroll.add_controller(wheel);
+
+        let drag = gtk::GestureDrag::new();
+        let viewer = self.clone();
+        drag.connect_drag_begin(move |_, _, _| {
+            viewer.drag_origin.set((
+                viewer.scroll.hadjustment().value(),
+                viewer.scroll.vadjustment().value(),
+            ));
+        });
+        let viewer = self.clone();
+        drag.connect_drag_update(move |_, offset_x, offset_y| {
+            let (x, y) = viewer.drag_origin.get();
+            viewer.scroll.hadjustment().set_value(x - offset_x);
+            viewer.scroll.vadjustment().set_value(y - offset_y);
+        });
+        self.scroll.add_controller(drag);
+
+        let viewer = self.clone();
+        self.window.connect_close_request(move |_| {
+            viewer.close();
+            gtk::glib::Propagation::Proceed
+        });
+    }
+
+    fn step(self: &Rc<Self>, delta: isize) {
+        if let Some(next) = self
+            .position
+            .get()
+            .checked_add_signed(delta)
+            .filter(|next| *next < self.items.len())
+        {
+            self.show(next);
+        }
+    }
+
+    fn show(self: &Rc<Self>, position: usize) {
+        let Some(image_path) = self.items.get(position).cloned() else {
+            return;
+        };
+        self.position.set(position);
+        self.picture.set_paintable(None::<&gtk::gdk::Texture>);
+        self.set_zoom(None);
+        self.caption.set_text(&self.caption_text(&image_path));
+
+        if let Some(previous_request_id) = self.pending_request_id.replace(None) {
+            self.ui.image_loader.cancel_if_queued(previous_request_id);
+        }
+        let load_seq = self.load_seq.get().wrapping_add(1);
+        self.load_seq.set(load_seq);
+
+        let viewer = Rc::downgrade(self);
+        let request_id = self.ui.image_loader.load(
+            image_path.clone(),
+            None,
+            ImageRequestKind::Detail,
+            move |finished_id, result| {
+                let Some(viewer) = viewer.upgrade() else {
+                    return;
+                };
+                if viewer.pending_request_id.get() == Some(finished_id) {
+                    viewer.pending_request_id.set(None);
+                }
+                if viewer.load_seq.get() != load_seq {
+                    return;
+                }
+
+                match result {
+                    Ok(texture) => viewer.picture.set_paintable(Some(&texture)),
+                    Err(_)
+                        if viewer.ui.image_loader.media_kind(&image_path) == MediaKind::Video =>
+                    {
+                        viewer
+                            .picture
+                            .set_paintable(Some(&video_placeholder(&viewer.picture)))
+                    }
+                    Err(err) => viewer.caption.set_text(&format!(
+                        "{} (failed to load: {err})",
+                        viewer.caption_text(&image_path)
+                    )),
+                }
+            },
+        );
+        self.pending_request_id.set(Some(request_id));
+    }
+
+    fn caption_text(&self, image_path: &std::path::Path) -> String {
+        let name = image_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| image_path.display().to_string());
+        format!("{}/{}  {name}", self.position.get() + 1, self.items.len())
+    }
+
+    /// Zooms relative to the current scale; zooming out past the fitted size
+    /// goes back to fitting.
+    fn zoom_by(&self, dy: f64) {
+        let Some(fit) = self.fit_scale() else {
+            return;
+        };
+        let current = self.zoom.get().unwrap_or(fit);
+        let next = (current * ZOOM_STEP.powf(-dy)).min(MAX_ZOOM);
+        self.set_zoom((next > fit).then_some(next));
+    }
+
+    fn fit_scale(&self) -> Option<f64> {
+        let paintable = self.picture.paintable()?;
+        let (width, height) = (paintable.intrinsic_width(), paintable.intrinsic_height());
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        let fit_width = f64::from(self.scroll.width()) / f64::from(width);
+        let fit_height = f64::from(self.scroll.height()) / f64::from(height);
+        Some(fit_width.min(fit_height))
+    }
+
+    fn set_zoom(&self, zoom: Option<f64>) {
+        self.zoom.set(zoom);
+        match (zoom, self.picture.paintable()) {
+            (Some(zoom), Some(paintable)) => self.picture.set_size_request(
+                (f64::from(paintable.intrinsic_width()) * zoom).round() as i32,
+                (f64::from(paintable.intrinsic_height()) * zoom).round() as i32,
+            ),
+            _ => self.picture.set_size_request(-1, -1),
+        }
+    }
+
+    /// Drops any queued decode and scrolls the untouched main selection back
+    /// into view, in case the browser moved underneath (e.g. with `--watch`).
+    fn close(&self) {
+        self.load_seq.set(self.load_seq.get().wrapping_add(1));
+        if let Some(request_id) = self.pending_request_id.replace(None) {
+            self.ui.image_loader.cancel_if_queued(request_id);
+        }
+        let selected_pos = self.state.borrow().selected_pos;
+        ensure_selected_item_visible(&self.ui, selected_pos);
+    }
+}
@@ -4,8 +4,13 @@ use std::process::Command;
 use std::time::Duration;
 
 use anyhow::{Context, Result};
+#[cfg(feature = "heic")]
+use booru_core::convert_foreign_image;
+#[cfg(feature = "ffmpeg")]
+use booru_core::extract_video_frame;
 use booru_core::{
-    apply_update_to_image, BooruConfig, EditUpdate, Library, SearchQuery, SearchSort,
+    apply_update_to_image_with_previous, restore_edits_for_image, BooruConfig, BooruEdits,
+    EditUpdate, ForeignImageFormat, Library, MediaKind, SearchQuery, SearchSort, SensitiveFilter,
 };
 use clap::Parser;
 use crossterm::event::{
@@ -29,15 +34,30 @@ const TICK_RATE: Duration = Duration::from_millis(150);
 #[derive(Parser)]
 #[command(name = "booru-tui", version, about = "TUI browser for LightBooru")]
 struct Cli {
-    /// Base directory for gallery-dl downloads (can be repeated)
+    /// Base directory for gallery-dl downloads (can be repeated; replaces
+    /// the roots from booru.toml)
     #[arg(long, short)]
     base: Vec<PathBuf>,
 
+    /// Skip images whose path under a root matches this glob (can be
+    /// repeated; adds to `exclude_globs` from booru.toml)
+    #[arg(long = "exclude")]
+    exclude: Vec<String>,
+
+    /// Skip this file or directory while scanning (can be repeated; adds to
+    /// `exclude_paths` from booru.toml)
+    #[arg(long = "exclude-path")]
+    exclude_path: Vec<PathBuf>,
+
+    /// Descend at most this many levels below each root (0: unlimited)
+    #[arg(long)]
+    max_depth: Option<usize>,
+
     /// Suppress scan warnings
     #[arg(long)]
     quiet: bool,
 
-    /// Show sensitive images (default: hidden)
+    /// Show sensitive images (default: hidden, or `default_sensitive` in booru.toml)
     #[arg(long)]
     sensitive: bool,
 }
@@ -47,6 +67,10 @@ enum InputMode {
     Normal,
     Search,
     Tag,
+    /// Editing the whole tag list, which replaces the merged tags on save.
+    EditTags,
+    /// Multi-line notes editing; Enter inserts a newline and Ctrl-S saves.
+    Notes,
     ConfirmSensitive,
 }
 
@@ -67,15 +91,18 @@ struct LayoutInfo {
 
 struct Preview {
     picker: Picker,
+    /// The library's `video_extensions`, to tell videos from images.
+    video_extensions: Vec<String>,
     current_path: Option<PathBuf>,
     protocol: Option<StatefulProtocol>,
     last_error: Option<String>,
 }
 
 impl Preview {
-    fn new(picker: Picker) -> Self {
+    fn new(picker: Picker, video_extensions: Vec<String>) -> Self {
         Self {
             picker,
+            video_extensions,
             current_path: None,
             protocol: None,
             last_error: None,
@@ -88,14 +115,18 @@ impl Preview {
         }
         self.current_path = Some(path.to_path_buf());
 
-        match load_image(path) {
+        let kind = MediaKind::from_path(path, &self.video_extensions);
+        match load_image(path, kind) {
             Ok(image) => {
                 self.protocol = Some(self.picker.new_resize_protocol(image));
                 self.last_error = None;
             }
             Err(err) => {
                 self.protocol = None;
-                self.last_error = Some(format!("failed to load image: {err}"));
+                self.last_error = Some(match kind {
+                    MediaKind::Video => format!("video file, no thumbnail ({err})"),
+                    MediaKind::Image => format!("failed to load image: {err}"),
+                });
             }
         }
     }
@@ -111,8 +142,11 @@ struct App {
     mode: InputMode,
     focus: FocusPane,
     search_input: String,
+    match_all: bool,
     source_filter: Option<String>,
     input_buffer: String,
+    /// Byte offset of the cursor in `input_buffer`, used by notes mode.
+    input_cursor: usize,
     list_offset: usize,
     detail_scroll: u16,
     detail_split_percent: u16,
@@ -121,6 +155,8 @@ struct App {
     status: String,
     preview: Option<Preview>,
     pending_sensitive_index: Option<usize>,
+    /// The image last edited here and the edits it had before, for `z`.
+    last_edit: Option<(PathBuf, Option<BooruEdits>)>,
 }
 
 impl App {
@@ -135,8 +171,10 @@ impl App {
             mode: InputMode::Normal,
             focus: FocusPane::Images,
             search_input: String::new(),
+            match_all: false,
             source_filter: None,
             input_buffer: String::new(),
+            input_cursor: 0,
             list_offset: 0,
             detail_scroll: 0,
             detail_split_percent: 50,
@@ -145,6 +183,7 @@ impl App {
             status: String::from("Press ? for help. / search, t edit tags, u same-source, q quit."),
             preview: None,
             pending_sensitive_index: None,
+            last_edit: None,
         };
         app.rebuild_filter();
         app
@@ -167,7 +206,7 @@ impl App {
     }
 
     fn set_preview_picker(&mut self, picker: Picker) {
-        let mut preview = Preview::new(picker);
+        let mut preview = Preview::new(picker, self.library.config.video_extensions.clone());
         if let Some(idx) = self.selected_item_index() {
             let path = self.library.index.items[idx].image_path.clone();
             preview.load_for_path(&path);
@@ -175,20 +214,24 @@ impl App {
         self.preview = Some(preview);
     }
 
+    /// Re-runs the search, keeping the selected image selected if it still
+    /// matches.
     fn rebuild_filter(&mut self) {
-        let search = self.library.search(
-            SearchQuery::new(split_search_terms(&self.search_input))
-                .with_aliases(true)
-                .with_source_url(self.source_filter.clone())
-                .with_sort(SearchSort::FileNameAsc),
-        );
-        self.filtered_indices = search
-            .indices
-            .into_iter()
-            .filter(|idx| self.show_sensitive || !self.library.index.items[*idx].merged_sensitive())
-            .collect();
-
-        if self.filtered_indices.is_empty() {
+        let previous = self
+            .selected_item_index()
+            .and_then(|idx| self.library.index.items.get(idx))
+            .map(|item| item.image_path.clone());
+        let search = self.library.search(self.search_query());
+        self.filtered_indices = search.indices;
+
+        let kept = previous.and_then(|path| {
+            self.filtered_indices
+                .iter()
+                .position(|idx| self.library.index.items[*idx].image_path == path)
+        });
+        if let Some(position) = kept {
+            self.selected = position;
+        } else if self.filtered_indices.is_empty() {
             self.selected = 0;
             self.list_offset = 0;
         } else if self.selected >= self.filtered_indices.len() {
@@ -197,10 +240,35 @@ impl App {
         self.list_offset = self.list_offset.min(self.selected);
     }
 
+    fn search_query(&self) -> SearchQuery {
+        SearchQuery::new(split_search_terms(&self.search_input))
+            .with_aliases(true)
+            .with_source_url(self.source_filter.clone())
+            .with_sort(SearchSort::FileNameAsc)
+            .with_match_all(self.match_all)
+            .with_sensitive(SensitiveFilter::from_show(self.show_sensitive))
+    }
+
     fn selected_item_index(&self) -> Option<usize> {
         self.filtered_indices.get(self.selected).copied()
     }
 
+    /// Steps to the previous or next item of the current search, as the
+    /// `[` and `]` keys do in the detail pane.
+    fn select_adjacent_item(&mut self, forward: bool) {
+        let Some(idx) = self.selected_item_index() else {
+            return;
+        };
+        let path = self.library.index.items[idx].image_path.clone();
+        let (previous, next) = self.library.adjacent_items(&self.search_query(), &path);
+        let Some(target) = (if forward { next } else { previous }) else {
+            return;
+        };
+        if let Some(position) = self.filtered_indices.iter().position(|idx| *idx == target) {
+            self.move_selection(position as isize - self.selected as isize);
+        }
+    }
+
     fn move_selection(&mut self, delta: isize) {
         if self.filtered_indices.is_empty() {
             self.selected = 0;
@@ -253,7 +321,7 @@ impl App {
 
     fn set_sensitive(&mut self, idx: usize, new_value: bool) -> Result<()> {
         let image_path = self.library.index.items[idx].image_path.clone();
-        let edits = apply_update_to_image(
+        let (previous, edits) = apply_update_to_image_with_previous(
             &image_path,
             EditUpdate {
                 set_tags: None,
@@ -261,12 +329,18 @@ impl App {
                 remove_tags: Vec::new(),
                 clear_tags: false,
                 notes: None,
+                clear_notes: false,
                 sensitive: Some(new_value),
+                rating: None,
+                set_fields: Vec::new(),
+                remove_fields: Vec::new(),
             },
         )
         .with_context(|| format!("failed to update {}", image_path.display()))?;
 
         self.library.index.items[idx].edits = edits;
+        self.last_edit = Some((image_path.clone(), previous));
+        self.library.index.invalidate_lookups();
         self.rebuild_filter();
         self.status = format!(
             "Sensitive set to {} for {}",
@@ -353,6 +427,16 @@ impl App {
         );
     }
 
+    fn toggle_match_all(&mut self) {
+        self.match_all = !self.match_all;
+        self.rebuild_filter();
+        self.status = format!(
+            "Search now matches {} term(s) ({} result(s)).",
+            if self.match_all { "all" } else { "any" },
+            self.filtered_indices.len()
+        );
+    }
+
     fn clear_source_filter(&mut self) {
         if self.source_filter.take().is_some() {
             self.rebuild_filter();
@@ -429,7 +513,7 @@ impl App {
         };
         let image_path = self.library.index.items[idx].image_path.clone();
 
-        let edits = apply_update_to_image(
+        let (previous, edits) = apply_update_to_image_with_previous(
             &image_path,
             EditUpdate {
                 set_tags: None,
@@ -437,16 +521,129 @@ impl App {
                 remove_tags: changes.remove.clone(),
                 clear_tags: false,
                 notes: None,
+                clear_notes: false,
                 sensitive: None,
+                rating: None,
+                set_fields: Vec::new(),
+                remove_fields: Vec::new(),
             },
         )
         .with_context(|| format!("failed to update {}", image_path.display()))?;
 
         self.library.index.items[idx].edits = edits;
+        self.last_edit = Some((image_path.clone(), previous));
+        self.library.index.invalidate_lookups();
         self.rebuild_filter();
         self.status = format_tag_edit_summary(&changes);
         Ok(())
     }
+
+    /// Known tags completing the one being typed in tag mode.
+    fn tag_suggestions(&self) -> Vec<(String, usize)> {
+        let (_, partial) = split_partial_tag(&self.input_buffer);
+        if partial.is_empty() {
+            return Vec::new();
+        }
+        self.library.tag_completions(partial, 5)
+    }
+
+    fn accept_tag_suggestion(&mut self) {
+        let Some((tag, _)) = self.tag_suggestions().into_iter().next() else {
+            return;
+        };
+        let (head, _) = split_partial_tag(&self.input_buffer);
+        self.input_buffer = format!("{head}{tag} ");
+    }
+
+    /// Saves the edit buffer as the item's complete tag list, so tags missing
+    /// from it are removed.
+    fn set_tags_from_input(&mut self) -> Result<()> {
+        let Some(idx) = self.selected_item_index() else {
+            self.status = "No selected item.".to_string();
+            return Ok(());
+        };
+        let tags = parse_tag_list(&self.input_buffer);
+        if tags == self.library.index.items[idx].merged_tags() {
+            self.status = "Tags unchanged.".to_string();
+            return Ok(());
+        }
+        let image_path = self.library.index.items[idx].image_path.clone();
+
+        let (previous, edits) = apply_update_to_image_with_previous(
+            &image_path,
+            EditUpdate {
+                set_tags: Some(tags.clone()),
+                ..EditUpdate::default()
+            },
+        )
+        .with_context(|| format!("failed to update {}", image_path.display()))?;
+
+        self.library.index.items[idx].edits = edits;
+        self.last_edit = Some((image_path.clone(), previous));
+        self.library.index.invalidate_lookups();
+        self.rebuild_filter();
+        self.status = format!("Tags set: {}", format_tag_list(&tags));
+        Ok(())
+    }
+
+    fn start_notes_edit(&mut self) {
+        let Some(idx) = self.selected_item_index() else {
+            self.status = "No selected item.".to_string();
+            return;
+        };
+        self.mode = InputMode::Notes;
+        self.input_buffer = self.library.index.items[idx]
+            .edits
+            .notes
+            .clone()
+            .unwrap_or_default();
+        self.input_cursor = self.input_buffer.len();
+        self.status = "Edit notes: Enter newline, Ctrl-S save, Esc cancel".to_string();
+    }
+
+    fn undo_last_edit(&mut self) -> Result<()> {
+        let Some((image_path, previous)) = self.last_edit.take() else {
+            self.status = "Nothing to undo.".to_string();
+            return Ok(());
+        };
+        restore_edits_for_image(&image_path, previous.as_ref())
+            .with_context(|| format!("failed to restore {}", image_path.display()))?;
+        if let Some(item) = self
+            .library
+            .index
+            .items
+            .iter_mut()
+            .find(|item| item.image_path == image_path)
+        {
+            item.edits = previous.unwrap_or_default();
+        }
+        self.library.index.invalidate_lookups();
+        self.rebuild_filter();
+        self.status = format!("Undid last edit to {}", image_path.display());
+        Ok(())
+    }
+
+    fn save_notes_from_input(&mut self) -> Result<()> {
+        let Some(idx) = self.selected_item_index() else {
+            self.status = "No selected item.".to_string();
+            return Ok(());
+        };
+        let image_path = self.library.index.items[idx].image_path.clone();
+
+        let (previous, edits) = apply_update_to_image_with_previous(
+            &image_path,
+            EditUpdate {
+                notes: Some(self.input_buffer.clone()),
+                ..EditUpdate::default()
+            },
+        )
+        .with_context(|| format!("failed to update {}", image_path.display()))?;
+
+        self.library.index.items[idx].edits = edits;
+        self.last_edit = Some((image_path.clone(), previous));
+        self.status = format!("Notes saved for {}", image_path.display());
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
@@ -492,6 +689,31 @@ fn parse_tag_changes(input: &str) -> TagChanges {
     TagChanges { add, remove }
 }
 
+/// Splits a tag-mode input into everything before the tag being typed
+/// (including its `+`/`-` marker) and the partial tag itself.
+fn split_partial_tag(input: &str) -> (&str, &str) {
+    let start = input
+        .char_indices()
+        .rev()
+        .find(|(_, ch)| *ch == ',' || ch.is_whitespace())
+        .map_or(0, |(pos, ch)| pos + ch.len_utf8());
+    let token = &input[start..];
+    let marker = token.len() - token.trim_start_matches(['+', '-']).len();
+    input.split_at(start + marker)
+}
+
+/// Splits a full tag list on whitespace and commas, dropping duplicates.
+/// Unlike [`parse_tag_changes`], a leading `+` or `-` is part of the tag.
+fn parse_tag_list(input: &str) -> Vec<String> {
+    let mut tags = Vec::<String>::new();
+    for tag in input.split(|ch: char| ch == ',' || ch.is_whitespace()) {
+        if !tag.is_empty() && !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
 fn append_unique_with_last_wins(primary: &mut Vec<String>, opposite: &mut Vec<String>, tag: &str) {
     opposite.retain(|existing| existing != tag);
     if let Some(pos) = primary.iter().position(|existing| existing == tag) {
@@ -556,18 +778,51 @@ fn truncate_middle(input: &str, max_chars: usize) -> String {
     format!("{head}...{tail}")
 }
 
-fn load_image(path: &Path) -> Result<DynamicImage> {
-    image::open(path).with_context(|| format!("unable to decode {}", path.display()))
+fn load_image(path: &Path, kind: MediaKind) -> Result<DynamicImage> {
+    match kind {
+        MediaKind::Image => match ForeignImageFormat::detect(path) {
+            Some(format) => load_foreign_image(path, format),
+            None => {
+                image::open(path).with_context(|| format!("unable to decode {}", path.display()))
+            }
+        },
+        MediaKind::Video => load_video_frame(path),
+    }
+}
+
+#[cfg(feature = "heic")]
+fn load_foreign_image(path: &Path, format: ForeignImageFormat) -> Result<DynamicImage> {
+    let png = convert_foreign_image(path, format)?;
+    image::load_from_memory(&png)
+        .with_context(|| format!("unable to decode converted {}", path.display()))
+}
+
+#[cfg(not(feature = "heic"))]
+fn load_foreign_image(_path: &Path, format: ForeignImageFormat) -> Result<DynamicImage> {
+    anyhow::bail!(
+        "{} images need the `heic` feature and ImageMagick",
+        format.name()
+    )
+}
+
+#[cfg(feature = "ffmpeg")]
+fn load_video_frame(path: &Path) -> Result<DynamicImage> {
+    let frame = extract_video_frame(path)?;
+    image::load_from_memory(&frame)
+        .with_context(|| format!("unable to decode first frame of {}", path.display()))
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+fn load_video_frame(_path: &Path) -> Result<DynamicImage> {
+    anyhow::bail!("built without the `ffmpeg` feature")
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = if cli.base.is_empty() {
-        BooruConfig::default()
-    } else {
-        BooruConfig::with_roots(cli.base)
-    };
+    let mut config = BooruConfig::resolve(cli.base)?;
+    config.apply_scan_overrides(cli.exclude, cli.exclude_path, cli.max_depth);
 
+    let show_sensitive = cli.sensitive || config.default_sensitive;
     let library = Library::scan(config)?;
     if !cli.quiet {
         for warning in &library.warnings {
@@ -575,7 +830,7 @@ fn main() -> Result<()> {
         }
     }
 
-    run_tui(App::new(library, cli.sensitive))
+    run_tui(App::new(library, show_sensitive))
 }
 
 fn run_tui(mut app: App) -> Result<()> {
@@ -657,6 +912,8 @@ fn handle_key_event(app: &mut App, key: KeyEvent) -> Result<bool> {
         InputMode::Normal => handle_normal_mode(app, key),
         InputMode::Search => Ok(handle_text_mode(app, key, InputMode::Search)?),
         InputMode::Tag => Ok(handle_text_mode(app, key, InputMode::Tag)?),
+        InputMode::EditTags => Ok(handle_text_mode(app, key, InputMode::EditTags)?),
+        InputMode::Notes => Ok(handle_notes_mode(app, key)?),
         InputMode::ConfirmSensitive => handle_confirm_sensitive_mode(app, key),
     }
 }
@@ -692,10 +949,12 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<bool> {
             FocusPane::Images => app.move_selection(-10),
             FocusPane::Detail => app.scroll_detail(-10),
         },
+        KeyCode::Char('[') => app.select_adjacent_item(false),
+        KeyCode::Char(']') => app.select_adjacent_item(true),
         KeyCode::Char('/') => {
             app.mode = InputMode::Search;
             app.input_buffer = app.search_input.clone();
-            app.status = "Search mode: type query and press Enter".to_string();
+            app.status = "Search mode: type query (-term excludes) and press Enter".to_string();
         }
         KeyCode::Char('t') => {
             app.mode = InputMode::Tag;
@@ -703,8 +962,25 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<bool> {
             app.status =
                 "Tag mode: +tag add, -tag remove (space/comma separated), Enter apply".to_string();
         }
+        KeyCode::Char('e') => {
+            if let Some(idx) = app.selected_item_index() {
+                app.mode = InputMode::EditTags;
+                app.input_buffer = app.library.index.items[idx].merged_tags().join(" ");
+                app.status =
+                    "Edit tags: space/comma separated list, Enter save, Esc cancel".to_string();
+            } else {
+                app.status = "No selected item.".to_string();
+            }
+        }
+        KeyCode::Char('n') => app.start_notes_edit(),
+        KeyCode::Char('a') => app.toggle_match_all(),
         KeyCode::Char('u') => app.filter_by_selected_source(),
         KeyCode::Char('U') => app.clear_source_filter(),
+        KeyCode::Char('z') => {
+            if let Err(err) = app.undo_last_edit() {
+                app.status = err.to_string();
+            }
+        }
         KeyCode::Char('s') | KeyCode::Char('S') => {
             if let Err(err) = app.toggle_sensitive() {
                 app.status = err.to_string();
@@ -824,18 +1100,24 @@ fn handle_text_mode(app: &mut App, key: KeyEvent, mode: InputMode) -> Result<boo
             app.status = "Canceled.".to_string();
         }
         KeyCode::Enter => {
-            if mode == InputMode::Search {
-                app.search_input = app.input_buffer.trim().to_string();
-                app.rebuild_filter();
-                app.status = format!("Filter updated: {} result(s)", app.filtered_indices.len());
-            } else {
-                if let Err(err) = app.apply_tag_edits_from_input() {
-                    app.status = err.to_string();
+            let result = match mode {
+                InputMode::Search => {
+                    app.search_input = app.input_buffer.trim().to_string();
+                    app.rebuild_filter();
+                    app.status =
+                        format!("Filter updated: {} result(s)", app.filtered_indices.len());
+                    Ok(())
                 }
+                InputMode::EditTags => app.set_tags_from_input(),
+                _ => app.apply_tag_edits_from_input(),
+            };
+            if let Err(err) = result {
+                app.status = err.to_string();
             }
             app.mode = InputMode::Normal;
             app.input_buffer.clear();
         }
+        KeyCode::Tab if mode == InputMode::Tag => app.accept_tag_suggestion(),
         KeyCode::Backspace => {
             app.input_buffer.pop();
         }
@@ -850,6 +1132,65 @@ fn handle_text_mode(app: &mut App, key: KeyEvent, mode: InputMode) -> Result<boo
     Ok(false)
 }
 
+fn handle_notes_mode(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let buffer = &mut app.input_buffer;
+    let cursor = &mut app.input_cursor;
+    match key.code {
+        KeyCode::Esc => {
+            app.mode = InputMode::Normal;
+            buffer.clear();
+            app.status = "Notes edit canceled.".to_string();
+        }
+        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            if let Err(err) = app.save_notes_from_input() {
+                app.status = err.to_string();
+            }
+            app.mode = InputMode::Normal;
+            app.input_buffer.clear();
+        }
+        KeyCode::Enter => insert_at_cursor(buffer, cursor, '\n'),
+        KeyCode::Backspace => {
+            if let Some(prev) = prev_char_boundary(buffer, *cursor) {
+                buffer.remove(prev);
+                *cursor = prev;
+            }
+        }
+        KeyCode::Delete if *cursor < buffer.len() => {
+            buffer.remove(*cursor);
+        }
+        KeyCode::Left => *cursor = prev_char_boundary(buffer, *cursor).unwrap_or(0),
+        KeyCode::Right => {
+            if let Some(ch) = buffer[*cursor..].chars().next() {
+                *cursor += ch.len_utf8();
+            }
+        }
+        KeyCode::Home => *cursor = buffer[..*cursor].rfind('\n').map_or(0, |pos| pos + 1),
+        KeyCode::End => {
+            *cursor += buffer[*cursor..]
+                .find('\n')
+                .unwrap_or(buffer.len() - *cursor);
+        }
+        KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            insert_at_cursor(buffer, cursor, ch);
+        }
+        _ => {}
+    }
+
+    Ok(false)
+}
+
+fn insert_at_cursor(buffer: &mut String, cursor: &mut usize, ch: char) {
+    buffer.insert(*cursor, ch);
+    *cursor += ch.len_utf8();
+}
+
+fn prev_char_boundary(buffer: &str, cursor: usize) -> Option<usize> {
+    buffer[..cursor]
+        .char_indices()
+        .next_back()
+        .map(|(pos, _)| pos)
+}
+
 fn render_ui(frame: &mut Frame, app: &mut App) {
     let areas = Layout::default()
         .direction(Direction::Vertical)
@@ -867,15 +1208,33 @@ fn render_ui(frame: &mut Frame, app: &mut App) {
         render_help_dialog(frame);
     } else if app.mode == InputMode::ConfirmSensitive {
         render_sensitive_confirm_dialog(frame, app);
+    } else if app.mode == InputMode::Notes {
+        render_notes_editor(frame, app);
     }
 }
 
 fn render_search_panel(frame: &mut Frame, area: Rect, app: &App) {
     let mut label = match app.mode {
         InputMode::Search => format!("Search: {}_", app.input_buffer),
-        InputMode::Tag => format!("Tag edit (+tag/-tag): {}_", app.input_buffer),
-        InputMode::Normal | InputMode::ConfirmSensitive => format!("Search: {}", app.search_input),
+        InputMode::Tag => {
+            let mut label = format!("Tag edit (+tag/-tag): {}_", app.input_buffer);
+            let suggestions = app.tag_suggestions();
+            if !suggestions.is_empty() {
+                label.push_str(" | Tab:");
+                for (tag, count) in suggestions {
+                    label.push_str(&format!(" {tag}({count})"));
+                }
+            }
+            label
+        }
+        InputMode::EditTags => format!("Tags: {}_", app.input_buffer),
+        InputMode::Normal | InputMode::Notes | InputMode::ConfirmSensitive => {
+            format!("Search: {}", app.search_input)
+        }
     };
+    if app.match_all {
+        label.push_str(" | Match: all");
+    }
     if let Some(source_url) = app.source_filter.as_deref() {
         label.push_str(" | Source: ");
         label.push_str(&truncate_middle(source_url, 60));
@@ -906,6 +1265,22 @@ fn render_list_panel(frame: &mut Frame, area: Rect, app: &mut App) {
 
     if total == 0 {
         app.list_offset = 0;
+        if !app.search_input.is_empty() {
+            let banner = Paragraph::new(format!(
+                "No matches for '{}'.\nPress / to change the search.",
+                app.search_input
+            ))
+            .block(Block::default().borders(Borders::ALL).title(
+                if app.focus == FocusPane::Images {
+                    "Images (0/0) [Focus]"
+                } else {
+                    "Images (0/0)"
+                },
+            ))
+            .wrap(Wrap { trim: false });
+            frame.render_widget(banner, area);
+            return;
+        }
     } else {
         if app.selected < app.list_offset {
             app.list_offset = app.selected;
@@ -1169,16 +1544,21 @@ fn render_help_dialog(frame: &mut Frame) {
         "  j/k, Up/Down          Move selection or detail scroll",
         "  Tab, h/l, Left/Right  Switch focus pane",
         "  PageUp/PageDown       Fast scroll/move",
+        "  [ / ]                 Previous/next image from either pane",
         "",
         "Actions:",
         "  Enter                 Open selected image",
         "  Space                 Jump to random image",
         "  b                     Jump back from random history",
-        "  /                     Search",
+        "  /                     Search (prefix -term to exclude)",
+        "  a                     Toggle matching any/all search terms",
         "  t                     Edit tags (+tag / -tag)",
+        "  e                     Rewrite the full tag list",
+        "  n                     Edit notes (Enter newline, Ctrl-S save)",
         "  u                     Filter to same source URL",
         "  U                     Clear source URL filter",
         "  s / S                 Toggle sensitive (mark-as-sensitive asks confirm)",
+        "  z                     Undo the last edit",
         "",
         "Sensitive filter:",
         "  Hidden by default, use --sensitive to include.",
@@ -1195,6 +1575,25 @@ fn render_help_dialog(frame: &mut Frame) {
     frame.render_widget(dialog, area);
 }
 
+/// Draws the notes buffer over the detail pane, with a block cursor, and
+/// scrolls so the cursor's line stays visible.
+fn render_notes_editor(frame: &mut Frame, app: &App) {
+    let area = app.layout.detail_area;
+    frame.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Notes (Ctrl-S save, Esc cancel)");
+    let inner = block.inner(area);
+    let (before, after) = app.input_buffer.split_at(app.input_cursor);
+    let cursor_line = estimate_wrapped_lines(&format!("{before}\u{2588}"), inner.width);
+    let scroll = cursor_line.saturating_sub(inner.height);
+    let editor = Paragraph::new(format!("{before}\u{2588}{after}"))
+        .block(block)
+        .scroll((scroll, 0))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(editor, area);
+}
+
 fn render_sensitive_confirm_dialog(frame: &mut Frame, app: &App) {
     let area = centered_rect(70, 26, frame.area());
     frame.render_widget(Clear, area);
@@ -1220,6 +1619,8 @@ fn render_status(frame: &mut Frame, area: Rect, app: &App) {
         InputMode::Normal => "NORMAL",
         InputMode::Search => "SEARCH",
         InputMode::Tag => "TAG",
+        InputMode::EditTags => "TAGS",
+        InputMode::Notes => "NOTES",
         InputMode::ConfirmSensitive => "CONFIRM",
     };
     let focus = match app.focus {
@@ -1233,7 +1634,58 @@ fn render_status(frame: &mut Frame, area: Rect, app: &App) {
 
 #[cfg(test)]
 mod tests {
-    use super::{apply_tag_changes, parse_tag_changes, TagChanges};
+    use booru_core::{BooruConfig, BooruEdits, ImageItem, Index, Library};
+    use serde_json::json;
+
+    use super::{
+        apply_tag_changes, insert_at_cursor, parse_tag_changes, parse_tag_list, prev_char_boundary,
+        split_partial_tag, App, TagChanges,
+    };
+
+    fn make_app(tags: &[(&str, &[&str])]) -> App {
+        let mut index = Index::default();
+        for (name, item_tags) in tags {
+            index.push(ImageItem::new(
+                format!("/tmp/{name}").into(),
+                format!("/tmp/{name}.json").into(),
+                format!("/tmp/{name}.booru.json").into(),
+                json!({ "tags": item_tags }),
+                BooruEdits::default(),
+            ));
+        }
+        App::new(
+            Library {
+                config: BooruConfig::with_roots(Vec::new()),
+                index,
+                warnings: Vec::new(),
+            },
+            false,
+        )
+    }
+
+    #[test]
+    fn rebuild_filter_keeps_the_selected_item_when_it_still_matches() {
+        let mut app = make_app(&[
+            ("a.jpg", &["sky"]),
+            ("b.jpg", &["sea"]),
+            ("c.jpg", &["sky"]),
+        ]);
+        app.selected = 2;
+
+        app.search_input = "sky".to_string();
+        app.rebuild_filter();
+        assert_eq!(app.filtered_indices, vec![0, 2]);
+        assert_eq!(app.selected_item_index(), Some(2));
+
+        app.search_input = "sea".to_string();
+        app.rebuild_filter();
+        assert_eq!(app.selected_item_index(), Some(1));
+
+        app.search_input = "nothing".to_string();
+        app.rebuild_filter();
+        assert_eq!(app.selected_item_index(), None);
+        assert_eq!(app.selected, 0);
+    }
 
     #[test]
     fn parse_tag_changes_supports_add_and_remove() {
@@ -1259,6 +1711,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_tag_list_splits_on_spaces_and_commas() {
+        assert_eq!(
+            parse_tag_list("  cat,dog ,, bird\tcat  -x "),
+            vec!["cat", "dog", "bird", "-x"]
+        );
+        assert!(parse_tag_list(" , ").is_empty());
+    }
+
+    #[test]
+    fn split_partial_tag_keeps_the_change_marker_in_the_head() {
+        assert_eq!(split_partial_tag("cat -sk"), ("cat -", "sk"));
+        assert_eq!(split_partial_tag("cat,+Do"), ("cat,+", "Do"));
+        assert_eq!(split_partial_tag("猫\u{3000}ね"), ("猫\u{3000}", "ね"));
+        assert_eq!(split_partial_tag("cat "), ("cat ", ""));
+    }
+
+    #[test]
+    fn notes_cursor_moves_by_whole_characters() {
+        let mut buffer = String::from("猫");
+        let mut cursor = buffer.len();
+        insert_at_cursor(&mut buffer, &mut cursor, '\n');
+        insert_at_cursor(&mut buffer, &mut cursor, 'ね');
+        assert_eq!(buffer, "猫\nね");
+        assert_eq!(cursor, buffer.len());
+        assert_eq!(prev_char_boundary(&buffer, cursor), Some(4));
+        assert_eq!(prev_char_boundary(&buffer, 3), Some(0));
+        assert_eq!(prev_char_boundary(&buffer, 0), None);
+    }
+
     #[test]
     fn apply_tag_changes_adds_and_removes_tags() {
         let current = vec!["cat".to_string(), "bird".to_string()];
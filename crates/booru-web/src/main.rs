@@ -1,23 +1,39 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::fs;
+use std::io::SeekFrom;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::ops::Range;
 use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
 use askama::Template;
-use axum::body::Body;
-use axum::extract::{Path, Query, State};
-use axum::http::{header, HeaderValue, StatusCode};
+use axum::body::{Body, Bytes};
+use axum::extract::{MatchedPath, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
 use axum::response::{Html, IntoResponse, Response};
 use axum::routing::get;
-use axum::Router;
-use booru_core::{BooruConfig, Library, SearchQuery, SearchSort};
+use axum::{Json, Router};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use booru_core::{
+    average_color, compute_hashes_with_cache, parse_date, BooruConfig, FileFingerprint,
+    FuzzyHashAlgorithm, HashCache, HashComputation, Library, LibraryWatcher, SearchQuery,
+    SearchSort, SensitiveFilter,
+};
+use chrono::{DateTime, Local};
 use clap::Parser;
-use rand::rngs::StdRng;
-use rand::seq::SliceRandom;
-use rand::SeedableRng;
-use serde::Deserialize;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio::signal;
+use tokio_util::io::ReaderStream;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -26,10 +42,25 @@ use tokio::signal;
     about = "Read-only web browser for LightBooru"
 )]
 struct Cli {
-    /// Base directory for gallery-dl downloads (can be repeated)
+    /// Base directory for gallery-dl downloads (can be repeated; replaces
+    /// the roots from booru.toml)
     #[arg(long, short)]
     base: Vec<PathBuf>,
 
+    /// Skip images whose path under a root matches this glob (can be
+    /// repeated; adds to `exclude_globs` from booru.toml)
+    #[arg(long = "exclude")]
+    exclude: Vec<String>,
+
+    /// Skip this file or directory while scanning (can be repeated; adds to
+    /// `exclude_paths` from booru.toml)
+    #[arg(long = "exclude-path")]
+    exclude_path: Vec<PathBuf>,
+
+    /// Descend at most this many levels below each root (0: unlimited)
+    #[arg(long)]
+    max_depth: Option<usize>,
+
     /// Suppress scan warnings
     #[arg(long)]
     quiet: bool,
@@ -38,37 +69,255 @@ struct Cli {
     #[arg(long, default_value = "127.0.0.1")]
     host: String,
 
+    /// Require this password (HTTP Basic auth) on every route
+    #[arg(long = "auth-password", visible_alias = "password")]
+    password: Option<String>,
+
+    /// With --auth-password, also require this user name (default: any)
+    #[arg(long, requires = "password")]
+    auth_user: Option<String>,
+
     /// Bind port
     #[arg(long, default_value_t = 8080)]
     port: u16,
 
-    /// Show sensitive images by default
+    /// Show sensitive images by default (also `default_sensitive` in booru.toml)
     #[arg(long)]
     sensitive: bool,
 
     /// Maximum items shown in one page
     #[arg(long, default_value_t = 120)]
     limit: usize,
+
+    /// Rescan automatically when files under the roots change
+    #[arg(long)]
+    watch: bool,
+
+    /// Serve `/health` and Prometheus `/metrics`
+    #[arg(long)]
+    metrics: bool,
+
+    /// Directory for generated thumbnails (default:
+    /// `$XDG_CACHE_HOME/lightbooru/thumbnails`)
+    #[arg(long)]
+    thumb_cache: Option<PathBuf>,
+
+    /// Thumbnails kept in memory (0 disables the in-memory cache)
+    #[arg(long, default_value_t = 512)]
+    thumb_cache_size: usize,
+
+    /// Skip decoding every image at startup for the grid's colour and
+    /// blurhash placeholders
+    #[arg(long)]
+    no_placeholders: bool,
 }
 
+const DEFAULT_THUMB_WIDTH: u32 = 256;
+const MAX_THUMB_SIZE: u32 = 1024;
+
+/// Rendered thumbnails keyed by their ETag, which covers the image path,
+/// its size and mtime, and the box, so entries never go stale on a rescan.
+type ThumbMemory = Arc<Mutex<LruCache<String, Bytes>>>;
+
+// Ids may point at another file after a rescan, so media always revalidates
+// and thumbnails only briefly go without.
+const MEDIA_CACHE_CONTROL: &str = "no-cache";
+const THUMB_CACHE_CONTROL: &str = "public, max-age=300";
+
+const GRID_NAV_JS: &str = include_str!("../static/grid-nav.js");
+
+/// A 1x1 transparent GIF, served when an image cannot be thumbnailed.
+const PLACEHOLDER_GIF: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x01, 0x44, 0x00, 0x3b,
+];
+
 #[derive(Clone)]
 struct AppState {
-    library: Arc<Library>,
+    library: Arc<RwLock<Library>>,
     default_show_sensitive: bool,
     default_limit: usize,
+    /// `None` renders thumbnails on every request.
+    thumb_dir: Option<PathBuf>,
+    /// Checked before `thumb_dir`; cleared when a rescan changes items.
+    thumb_memory: Option<ThumbMemory>,
+    /// Perceptual hashes of the whole library, computed on the first
+    /// `/items/:id/similar` request and dropped when a rescan changes items.
+    hashes: Arc<RwLock<Option<LibraryHashes>>>,
+    /// Held while hashing, so concurrent first requests hash only once.
+    hashing: Arc<Mutex<()>>,
+    /// Bumped by the watcher whenever a rescan changes items, which may
+    /// shift their indices.
+    generation: Arc<AtomicU64>,
+    /// Reuse hashes from the same cache as `booructl dupes`.
+    use_hash_cache: bool,
+    /// Set with `--metrics`.
+    metrics: Option<Arc<Metrics>>,
+    /// Filled in the background after startup; grid cards without an entry
+    /// just have no placeholder.
+    placeholders: Placeholders,
+}
+
+/// Hashes keyed by item index, valid for one library generation.
+struct LibraryHashes {
+    generation: u64,
+    computation: HashComputation,
+}
+
+/// Hashes the library unless hashes for the current generation exist. Items
+/// are cloned out of the library lock first, so rescans and other requests
+/// are not held up while hashing; the result is dropped if a rescan landed
+/// in the meantime.
+fn ensure_library_hashes(state: &AppState) {
+    let _hashing = state.hashing.lock().unwrap_or_else(PoisonError::into_inner);
+    let generation = state.generation.load(Ordering::Acquire);
+    let current = state
+        .hashes
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .is_some_and(|hashes| hashes.generation == generation);
+    if current {
+        return;
+    }
+    let items = state.library().index.items.clone();
+    let mut cache = if state.use_hash_cache {
+        HashCache::open_default().ok()
+    } else {
+        None
+    };
+    let computation =
+        compute_hashes_with_cache(&items, FuzzyHashAlgorithm::PHASH, cache.as_mut(), None);
+    if state.generation.load(Ordering::Acquire) == generation {
+        *state.hashes.write().unwrap_or_else(PoisonError::into_inner) = Some(LibraryHashes {
+            generation,
+            computation,
+        });
+    }
+}
+
+/// Shown in a grid card until its thumbnail has loaded.
+#[derive(Clone, Debug)]
+struct Placeholder {
+    color: [u8; 3],
+    blurhash: String,
+}
+
+/// Keyed by image path, so rescans that shift indices keep them valid.
+type Placeholders = Arc<RwLock<HashMap<PathBuf, Placeholder>>>;
+
+/// Decodes the image once for both the 4×4 average colour and a 4×3
+/// blurhash of a small copy. `None` for videos and undecodable files.
+fn compute_placeholder(image_path: &std::path::Path) -> Option<Placeholder> {
+    let image = image::open(image_path).ok()?;
+    let small = image::DynamicImage::ImageRgba8(image.thumbnail(32, 32).to_rgba8());
+    let blurhash = blurhash::encode(4, 3, small.width(), small.height(), small.as_bytes()).ok()?;
+    Some(Placeholder {
+        color: average_color(&small),
+        blurhash,
+    })
+}
+
+fn fill_placeholders(paths: Vec<PathBuf>, placeholders: &Placeholders) {
+    for path in paths {
+        if let Some(placeholder) = compute_placeholder(&path) {
+            placeholders
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(path, placeholder);
+        }
+    }
+}
+
+/// Counters behind `/metrics`. The item count is read from the library at
+/// scrape time.
+#[derive(Debug, Default)]
+struct Metrics {
+    /// Responses by matched route and status code.
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    /// Bits of the `f64` seconds the startup scan took.
+    scan_duration_seconds: AtomicU64,
+}
+
+impl Metrics {
+    fn with_scan_duration(duration: Duration) -> Self {
+        let metrics = Self::default();
+        metrics
+            .scan_duration_seconds
+            .store(duration.as_secs_f64().to_bits(), Ordering::Relaxed);
+        metrics
+    }
+}
+
+impl AppState {
+    fn library(&self) -> RwLockReadGuard<'_, Library> {
+        self.library.read().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
 #[derive(Debug, Default, Deserialize)]
 struct IndexParams {
     q: Option<String>,
+    mode: Option<String>,
     source: Option<String>,
     show_sensitive: Option<String>,
     limit: Option<usize>,
     page: Option<usize>,
+    exact_tag: Option<String>,
+    exact_author: Option<String>,
     from: Option<usize>,
     sy: Option<u32>,
     randomize: Option<String>,
     seed: Option<u64>,
+    sort: Option<String>,
+    after: Option<String>,
+    before: Option<String>,
+    view: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FeedParams {
+    q: Option<String>,
+    format: Option<String>,
+    show_sensitive: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SimilarParams {
+    threshold: Option<u32>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ThumbParams {
+    w: Option<u32>,
+    h: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TagParams {
+    q: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum IndexView {
+    #[default]
+    Grid,
+    List,
+}
+
+impl IndexView {
+    fn from_param(value: Option<&str>) -> Self {
+        match value
+            .map(|value| value.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("list") => Self::List,
+            _ => Self::Grid,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -81,7 +330,11 @@ struct GridItem {
     date: String,
     detail: String,
     tags: Vec<TagLink>,
+    tag_count: usize,
     sensitive: bool,
+    /// `#rrggbb` background shown before the thumbnail loads.
+    placeholder_color: Option<String>,
+    blurhash: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -90,14 +343,69 @@ struct TagLink {
     href: String,
 }
 
+/// One item as returned by the `/api` routes.
+#[derive(Clone, Debug, Serialize)]
+struct ApiItem {
+    id: usize,
+    title: String,
+    author: Option<String>,
+    date: Option<String>,
+    tags: Vec<String>,
+    sensitive: bool,
+    platform_url: Option<String>,
+    media_href: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiItemPage<T = ApiItem> {
+    total: usize,
+    page: usize,
+    total_pages: usize,
+    limit: usize,
+    items: Vec<T>,
+}
+
+/// The `/api/search` flavour of [`ApiItem`], for scripts that want the
+/// file on disk rather than a media link.
+#[derive(Clone, Debug, Serialize)]
+struct ApiSearchItem {
+    id: usize,
+    path: PathBuf,
+    title: String,
+    author: Option<String>,
+    date: Option<String>,
+    tags: Vec<String>,
+    sensitive: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiSearchPage {
+    total: usize,
+    page: usize,
+    items: Vec<ApiSearchItem>,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiTag {
+    tag: String,
+    count: usize,
+}
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
     query: String,
+    match_all: bool,
     source_filter: Option<String>,
+    exact_tag: Option<String>,
+    exact_author: Option<String>,
     show_sensitive: bool,
     randomize: bool,
     seed: Option<u64>,
+    sort: &'static str,
+    after: Option<String>,
+    before: Option<String>,
+    list_view: bool,
     reshuffle_href: Option<String>,
     total_matches: usize,
     shown_count: usize,
@@ -108,6 +416,12 @@ struct IndexTemplate {
     end_item: usize,
     prev_page: Option<usize>,
     next_page: Option<usize>,
+    /// Followed by the keyboard navigation script past either end of the page.
+    prev_page_href: Option<String>,
+    next_page_href: Option<String>,
+    feed_href: String,
+    /// Shown above the results when they aren't a plain search.
+    banner: Option<String>,
     items: Vec<GridItem>,
 }
 
@@ -116,9 +430,12 @@ struct IndexTemplate {
 struct ItemTemplate {
     id: usize,
     back_href: String,
+    prev_href: Option<String>,
+    next_href: Option<String>,
     title: String,
     author: String,
     author_href: Option<String>,
+    author_url: Option<String>,
     date: String,
     detail: String,
     sensitive: bool,
@@ -131,6 +448,16 @@ struct ItemTemplate {
 
 struct HtmlTemplate<T>(T);
 
+#[derive(Template)]
+#[template(path = "opensearch.xml")]
+struct OpenSearchTemplate {
+    base: String,
+    icon: &'static str,
+}
+
+const OPENSEARCH_ICON: &str = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' \
+    viewBox='0 0 16 16'%3E%3Crect width='16' height='16' rx='3' fill='%230c5d66'/%3E%3C/svg%3E";
+
 impl<T> IntoResponse for HtmlTemplate<T>
 where
     T: Template,
@@ -138,40 +465,100 @@ where
     fn into_response(self) -> Response {
         match self.0.render() {
             Ok(content) => Html(content).into_response(),
-            Err(err) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("failed to render template: {err}"),
-            )
-                .into_response(),
+            Err(err) => internal_error(format!("failed to render template: {err}")),
         }
     }
 }
 
+/// A plain-text 500 carrying `err`, which should say what failed.
+fn internal_error(err: impl Display) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = if cli.base.is_empty() {
-        BooruConfig::default()
-    } else {
-        BooruConfig::with_roots(cli.base)
-    };
+    let mut config = BooruConfig::resolve(cli.base)?;
+    config.apply_scan_overrides(cli.exclude, cli.exclude_path, cli.max_depth);
+    let scan_started = Instant::now();
     let library = scan_library(&config, cli.quiet)?;
+    let scan_duration = scan_started.elapsed();
 
     let state = AppState {
-        library: Arc::new(library),
-        default_show_sensitive: cli.sensitive,
+        library: Arc::new(RwLock::new(library)),
+        default_show_sensitive: cli.sensitive || config.default_sensitive,
         default_limit: cli.limit.clamp(1, 1000),
+        thumb_dir: cli
+            .thumb_cache
+            .or_else(|| dirs::cache_dir().map(|dir| dir.join("lightbooru").join("thumbnails"))),
+        thumb_memory: NonZeroUsize::new(cli.thumb_cache_size)
+            .map(|size| Arc::new(Mutex::new(LruCache::new(size)))),
+        hashes: Arc::default(),
+        hashing: Arc::default(),
+        generation: Arc::default(),
+        use_hash_cache: true,
+        metrics: cli
+            .metrics
+            .then(|| Arc::new(Metrics::with_scan_duration(scan_duration))),
+        placeholders: Placeholders::default(),
+    };
+    if !cli.no_placeholders {
+        let paths = state
+            .library()
+            .index
+            .items
+            .iter()
+            .map(|item| item.image_path.clone())
+            .collect::<Vec<_>>();
+        let placeholders = state.placeholders.clone();
+        tokio::task::spawn_blocking(move || fill_placeholders(paths, &placeholders));
+    }
+    let _watcher = if cli.watch {
+        let quiet = cli.quiet;
+        let hashes = state.hashes.clone();
+        let generation = state.generation.clone();
+        let watcher =
+            LibraryWatcher::for_library(state.library.clone(), move |result| match result {
+                Ok(report) => {
+                    if !quiet {
+                        for warning in &report.warnings {
+                            eprintln!("warning: {}: {}", warning.path.display(), warning.message);
+                        }
+                    }
+                    if report.added + report.updated + report.removed > 0 {
+                        // Indices may have shifted.
+                        generation.fetch_add(1, Ordering::AcqRel);
+                        hashes
+                            .write()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .take();
+                        eprintln!(
+                            "rescanned: {} added, {} updated, {} removed",
+                            report.added, report.updated, report.removed
+                        );
+                    }
+                }
+                Err(err) => eprintln!("error: rescan failed: {err}"),
+            })?;
+        Some(watcher)
+    } else {
+        None
     };
-
-    let app = Router::new()
-        .route("/", get(index_handler))
-        .route("/items/:id", get(item_handler))
-        .route("/media/:id", get(media_handler))
-        .with_state(state);
 
     let addr: SocketAddr = format!("{}:{}", cli.host, cli.port)
         .parse()
         .context("invalid bind host/port")?;
+    let auth = cli.password.map(|password| BasicAuth {
+        user: cli.auth_user,
+        password,
+    });
+    if auth.is_none() && !addr.ip().is_loopback() {
+        eprintln!(
+            "warning: listening on {} without --auth-password; anyone who can reach it can browse the library",
+            addr.ip()
+        );
+    }
+    let app = router(state, auth);
     let listener = tokio::net::TcpListener::bind(addr)
         .await
         .context("failed to bind TCP listener")?;
@@ -187,6 +574,177 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Credentials every request except `/favicon.ico` must carry.
+#[derive(Clone)]
+struct BasicAuth {
+    /// `None` accepts any user name.
+    user: Option<String>,
+    password: String,
+}
+
+fn router(state: AppState, auth: Option<BasicAuth>) -> Router {
+    let metrics = state.metrics.clone();
+    let mut app = Router::new()
+        .route("/", get(index_handler))
+        .route("/items/:id", get(item_handler))
+        .route("/items/:id/similar", get(similar_handler))
+        .route("/media/:id", get(media_handler))
+        .route("/thumb/:id", get(thumb_handler))
+        .route("/static/grid-nav.js", get(grid_nav_js_handler))
+        .route("/feed", get(feed_handler))
+        .route("/opensearch.xml", get(opensearch_handler))
+        .route("/api/items", get(api_items_handler))
+        .route("/api/search", get(api_search_handler))
+        .route("/api/items/:id", get(api_item_handler))
+        .route("/api/tags", get(api_tags_handler));
+    if metrics.is_some() {
+        app = app
+            .route("/health", get(health_handler))
+            .route("/metrics", get(metrics_handler));
+    }
+    let mut app = app.with_state(state);
+    if let Some(auth) = auth {
+        app = app.layer(middleware::from_fn_with_state(
+            Arc::new(auth),
+            require_basic_auth,
+        ));
+    }
+    // Outside the auth layer, so rejected requests are counted too.
+    if let Some(metrics) = metrics {
+        app = app.layer(middleware::from_fn_with_state(metrics, count_requests));
+    }
+    app
+}
+
+async fn count_requests(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let response = next.run(request).await;
+    *metrics
+        .requests
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry((route, response.status().as_u16()))
+        .or_default() += 1;
+    response
+}
+
+#[derive(Debug, Serialize)]
+struct Health {
+    status: &'static str,
+    items: usize,
+}
+
+async fn health_handler(State(state): State<AppState>) -> Json<Health> {
+    Json(Health {
+        status: "ok",
+        items: state.library().index.items.len(),
+    })
+}
+
+/// Prometheus text exposition format.
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+    let Some(metrics) = &state.metrics else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let items = state.library().index.items.len();
+    let scan_seconds = f64::from_bits(metrics.scan_duration_seconds.load(Ordering::Relaxed));
+    let mut body = format!(
+        "# HELP lightbooru_items_total Items in the library.\n\
+         # TYPE lightbooru_items_total gauge\n\
+         lightbooru_items_total {items}\n\
+         # HELP lightbooru_library_scan_duration_seconds Time the startup scan took.\n\
+         # TYPE lightbooru_library_scan_duration_seconds gauge\n\
+         lightbooru_library_scan_duration_seconds {scan_seconds}\n\
+         # HELP lightbooru_requests_total HTTP responses by route and status.\n\
+         # TYPE lightbooru_requests_total counter\n"
+    );
+    for ((route, status), count) in metrics
+        .requests
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+    {
+        body.push_str(&format!(
+            "lightbooru_requests_total{{route=\"{route}\",status=\"{status}\"}} {count}\n"
+        ));
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+        .into_response()
+}
+
+async fn require_basic_auth(
+    State(auth): State<Arc<BasicAuth>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Browsers ask for it without credentials.
+    let exempt = request.uri().path() == "/favicon.ico";
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(basic_auth_credentials)
+        .is_some_and(|(user, password)| {
+            let user_ok = auth
+                .user
+                .as_ref()
+                .is_none_or(|expected| constant_time_eq(user.as_bytes(), expected.as_bytes()));
+            // Both are compared, so a wrong user name takes as long as a
+            // wrong password.
+            constant_time_eq(password.as_bytes(), auth.password.as_bytes()) & user_ok
+        });
+    if exempt || authorized {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Basic realm=\"lightbooru\"")],
+        "authentication required",
+    )
+        .into_response()
+}
+
+/// The user name and password of a `Basic` `Authorization` header value.
+fn basic_auth_credentials(value: &str) -> Option<(String, String)> {
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(BASE64_STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+fn constant_time_eq(lhs: &[u8], rhs: &[u8]) -> bool {
+    lhs.len() == rhs.len()
+        && lhs
+            .iter()
+            .zip(rhs)
+            .fold(0, |diff, (lhs, rhs)| diff | (lhs ^ rhs))
+            == 0
+}
+
+async fn grid_nav_js_handler() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        GRID_NAV_JS,
+    )
+}
+
 async fn shutdown_signal() {
     let _ = signal::ctrl_c().await;
 }
@@ -207,21 +765,30 @@ async fn index_handler(
 ) -> impl IntoResponse {
     let query = params.q.unwrap_or_default();
     let query_trimmed = query.trim().to_string();
+    let match_all = parse_match_all(params.mode.as_deref());
     let source_filter = params
         .source
         .map(|source| source.trim().to_string())
         .filter(|source| !source.is_empty());
+    let exact_tag = parse_exact_param(params.exact_tag);
+    let exact_author = parse_exact_param(params.exact_author);
     let show_sensitive = params
         .show_sensitive
         .as_deref()
         .map(parse_truthy)
         .unwrap_or(state.default_show_sensitive);
-    let randomize = params
-        .randomize
-        .as_deref()
-        .map(parse_truthy)
-        .unwrap_or(true);
+    let sort = parse_sort(params.sort.as_deref());
+    let after = parse_date_param(params.after);
+    let before = parse_date_param(params.before);
+    // An explicit order takes precedence over shuffling.
+    let randomize = sort == SearchSort::FileNameAsc
+        && params
+            .randomize
+            .as_deref()
+            .map(parse_truthy)
+            .unwrap_or(true);
     let limit = params.limit.unwrap_or(state.default_limit).clamp(1, 1000);
+    let view = IndexView::from_param(params.view.as_deref());
     let requested_page = params.page.unwrap_or(1).max(1);
     let seed = if randomize {
         Some(params.seed.unwrap_or_else(generate_seed))
@@ -229,80 +796,95 @@ async fn index_handler(
         None
     };
 
-    let use_aliases = !query_trimmed.is_empty();
-    let mut indices = state
-        .library
-        .search(
-            SearchQuery::new(split_search_terms(&query_trimmed))
-                .with_aliases(use_aliases)
-                .with_source_url(source_filter.clone())
-                .with_sort(SearchSort::FileNameAsc),
-        )
-        .indices;
-
-    if !show_sensitive {
-        indices.retain(|idx| !state.library.index.items[*idx].merged_sensitive());
-    }
-    if let Some(seed) = seed {
-        let mut rng = StdRng::seed_from_u64(seed);
-        indices.shuffle(&mut rng);
-    }
-
-    let total_matches = indices.len();
-    let total_pages = if total_matches == 0 {
-        1
-    } else {
-        (total_matches + limit - 1) / limit
-    };
-    let page = requested_page.min(total_pages);
-    let start = (page - 1) * limit;
-    let end = usize::min(start + limit, total_matches);
-    let (start_item, end_item) = if total_matches == 0 {
-        (0, 0)
-    } else {
-        (start + 1, end)
-    };
-    let nav = IndexNav {
+    let mut nav = IndexNav {
         query: query_trimmed.clone(),
+        match_all,
         source_url: source_filter.clone(),
+        exact_tag: exact_tag.clone(),
+        exact_author: exact_author.clone(),
         show_sensitive,
         randomize,
         seed,
+        sort,
+        after: after.clone(),
+        before: before.clone(),
+        view,
         limit,
-        page,
+        page: requested_page,
     };
+    let library = state.library();
+    let placeholders = state
+        .placeholders
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    let indices = search_indices(&library, &nav);
 
-    let items = indices[start..end]
+    let total_matches = indices.len();
+    let (page, total_pages, range) = paginate(total_matches, limit, requested_page);
+    let (start_item, end_item) = if total_matches == 0 {
+        (0, 0)
+    } else {
+        (range.start + 1, range.end)
+    };
+    nav.page = page;
+
+    let items = indices[range]
         .iter()
         .copied()
         .filter_map(|idx| {
-            state
-                .library
+            library
                 .index
                 .items
                 .get(idx)
-                .map(|item| to_grid_item(idx, item, &nav))
+                .map(|item| to_grid_item(idx, item, &nav, placeholders.get(&item.image_path)))
         })
         .collect::<Vec<_>>();
 
     let reshuffle_href = seed.map(|current_seed| {
         build_index_href(&IndexNav {
             query: query_trimmed.clone(),
+            match_all,
             source_url: source_filter.clone(),
+            exact_tag: exact_tag.clone(),
+            exact_author: exact_author.clone(),
             show_sensitive,
             randomize: true,
             seed: Some(next_seed(current_seed)),
+            sort,
+            after: after.clone(),
+            before: before.clone(),
+            view,
             limit,
             page: 1,
         })
     });
 
+    let prev_page = page.checked_sub(1).filter(|p| *p >= 1);
+    let next_page = if page < total_pages {
+        Some(page + 1)
+    } else {
+        None
+    };
+    let page_href = |page| {
+        build_index_href(&IndexNav {
+            page,
+            ..nav.clone()
+        })
+    };
+
     HtmlTemplate(IndexTemplate {
         query: query_trimmed,
+        match_all,
         source_filter,
+        exact_tag,
+        exact_author,
         show_sensitive,
         randomize,
         seed,
+        sort: sort.as_str(),
+        after,
+        before,
+        list_view: view == IndexView::List,
         reshuffle_href,
         total_matches,
         shown_count: items.len(),
@@ -311,12 +893,12 @@ async fn index_handler(
         total_pages,
         start_item,
         end_item,
-        prev_page: page.checked_sub(1).filter(|p| *p >= 1),
-        next_page: if page < total_pages {
-            Some(page + 1)
-        } else {
-            None
-        },
+        prev_page,
+        next_page,
+        prev_page_href: prev_page.map(page_href),
+        next_page_href: next_page.map(page_href),
+        feed_href: feed_href(&nav.query),
+        banner: None,
         items,
     })
 }
@@ -326,10 +908,12 @@ async fn item_handler(
     Path(id): Path<usize>,
     Query(params): Query<IndexParams>,
 ) -> impl IntoResponse {
-    let Some(item) = state.library.index.items.get(id) else {
+    let library = state.library();
+    let Some(item) = library.index.items.get(id) else {
         return (StatusCode::NOT_FOUND, "item not found").into_response();
     };
     let query_trimmed = params.q.unwrap_or_default().trim().to_string();
+    let match_all = parse_match_all(params.mode.as_deref());
     let source_filter = params
         .source
         .map(|source| source.trim().to_string())
@@ -345,17 +929,46 @@ async fn item_handler(
         .map(parse_truthy)
         .unwrap_or(true);
     let seed = if randomize { params.seed } else { None };
+    let sort = parse_sort(params.sort.as_deref());
+    let after = parse_date_param(params.after);
+    let before = parse_date_param(params.before);
     let limit = params.limit.unwrap_or(state.default_limit).clamp(1, 1000);
+    let view = IndexView::from_param(params.view.as_deref());
     let page = params.page.unwrap_or(1).max(1);
-    let mut back_href = build_index_href(&IndexNav {
+    let back_nav = IndexNav {
         query: query_trimmed,
+        match_all,
         source_url: source_filter,
+        exact_tag: parse_exact_param(params.exact_tag),
+        exact_author: parse_exact_param(params.exact_author),
         show_sensitive,
         randomize,
         seed,
+        sort,
+        after: after.clone(),
+        before: before.clone(),
+        view,
         limit,
         page,
-    });
+    };
+    let query = search_query_for_nav(&back_nav);
+    let (prev_id, next_id) = library.adjacent_items(&query, &item.image_path);
+    // A neighbour across a page boundary links back to its own page.
+    let indices = library.search(query).indices;
+    let neighbour_href = |neighbour_id: usize| {
+        let page = indices
+            .iter()
+            .position(|idx| *idx == neighbour_id)
+            .map_or(back_nav.page, |pos| pos / back_nav.limit + 1);
+        let nav = IndexNav {
+            page,
+            ..back_nav.clone()
+        };
+        build_item_href(neighbour_id, &nav)
+    };
+    let prev_href = prev_id.map(neighbour_href);
+    let next_href = next_id.map(neighbour_href);
+    let mut back_href = build_index_href(&back_nav);
     if let Some(scroll_y) = params.sy {
         if back_href.contains('?') {
             back_href.push('&');
@@ -369,10 +982,17 @@ async fn item_handler(
     }
     let tag_nav = IndexNav {
         query: String::new(),
+        match_all,
         source_url: None,
+        exact_tag: None,
+        exact_author: None,
         show_sensitive,
         randomize,
         seed,
+        sort,
+        after,
+        before,
+        view,
         limit,
         page: 1,
     };
@@ -391,9 +1011,12 @@ async fn item_handler(
     HtmlTemplate(ItemTemplate {
         id,
         back_href,
+        prev_href,
+        next_href,
         title: infer_title(item),
         author: author.clone(),
         author_href: build_author_search_href(&author, &tag_nav),
+        author_url: item.author_url(),
         date: item
             .merged_date()
             .unwrap_or_else(|| "(unknown)".to_string()),
@@ -417,84 +1040,882 @@ async fn item_handler(
     .into_response()
 }
 
-async fn media_handler(State(state): State<AppState>, Path(id): Path<usize>) -> impl IntoResponse {
-    let Some(item) = state.library.index.items.get(id) else {
-        return (StatusCode::NOT_FOUND, "item not found").into_response();
-    };
-
-    match tokio::fs::read(&item.image_path).await {
-        Ok(bytes) => {
-            let mime = mime_guess::from_path(&item.image_path).first_or_octet_stream();
-            let mut response = Response::new(Body::from(bytes));
-            response.headers_mut().insert(
-                header::CONTENT_TYPE,
-                HeaderValue::from_str(mime.as_ref())
-                    .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream")),
-            );
-            response
-        }
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("failed to read image: {err}"),
-        )
-            .into_response(),
-    }
+/// JSON counterpart of the index page. Results are shuffled only when a
+/// `seed` is given, so plain paging stays stable.
+async fn api_items_handler(
+    State(state): State<AppState>,
+    Query(params): Query<IndexParams>,
+) -> impl IntoResponse {
+    Json(api_item_page(&state, params, to_api_item))
 }
 
-fn to_grid_item(id: usize, item: &booru_core::ImageItem, nav: &IndexNav) -> GridItem {
-    let author = item
-        .merged_author()
-        .unwrap_or_else(|| "(unknown)".to_string());
-    GridItem {
+/// Same query parameters as the index page, trimmed down to the matches.
+async fn api_search_handler(
+    State(state): State<AppState>,
+    Query(params): Query<IndexParams>,
+) -> impl IntoResponse {
+    let page = api_item_page(&state, params, |id, item| ApiSearchItem {
         id,
-        detail_href: build_item_href(id, nav),
+        path: item.image_path.clone(),
         title: infer_title(item),
-        author: author.clone(),
-        author_href: build_author_search_href(&author, nav),
-        date: item
-            .merged_date()
-            .unwrap_or_else(|| "(unknown)".to_string()),
-        detail: truncate_for_preview(
-            &item
-                .merged_detail()
-                .unwrap_or_else(|| "(no description)".to_string()),
-            140,
-        ),
-        tags: item
-            .merged_tags()
-            .into_iter()
-            .take(8)
-            .map(|tag| TagLink {
-                href: build_tag_search_href(tag.as_str(), nav),
-                label: tag,
-            })
-            .collect(),
+        author: item.merged_author(),
+        date: item.merged_date(),
+        tags: item.merged_tags(),
         sensitive: item.merged_sensitive(),
-    }
+    });
+    Json(ApiSearchPage {
+        total: page.total,
+        page: page.page,
+        items: page.items,
+    })
 }
 
-fn infer_title(item: &booru_core::ImageItem) -> String {
-    booru_core::extract_string_field(&item.original, &["title", "filename"])
-        .or_else(|| {
-            item.image_path
-                .file_name()
-                .map(|name| name.to_string_lossy().to_string())
+/// Runs the search `params` describe and converts one page of it. Unlike
+/// the index page, results are only shuffled when a `seed` is given.
+fn api_item_page<T>(
+    state: &AppState,
+    params: IndexParams,
+    to_item: impl Fn(usize, &booru_core::ImageItem) -> T,
+) -> ApiItemPage<T> {
+    let limit = params.limit.unwrap_or(state.default_limit).clamp(1, 1000);
+    let nav = IndexNav {
+        query: params.q.unwrap_or_default().trim().to_string(),
+        match_all: parse_match_all(params.mode.as_deref()),
+        source_url: params
+            .source
+            .map(|source| source.trim().to_string())
+            .filter(|source| !source.is_empty()),
+        exact_tag: parse_exact_param(params.exact_tag),
+        exact_author: parse_exact_param(params.exact_author),
+        show_sensitive: params
+            .show_sensitive
+            .as_deref()
+            .map(parse_truthy)
+            .unwrap_or(state.default_show_sensitive),
+        randomize: params.seed.is_some(),
+        seed: params.seed,
+        sort: parse_sort(params.sort.as_deref()),
+        after: parse_date_param(params.after),
+        before: parse_date_param(params.before),
+        view: IndexView::Grid,
+        limit,
+        page: params.page.unwrap_or(1).max(1),
+    };
+    let library = state.library();
+    let indices = search_indices(&library, &nav);
+    let (page, total_pages, range) = paginate(indices.len(), limit, nav.page);
+    let items = indices[range]
+        .iter()
+        .filter_map(|idx| {
+            library
+                .index
+                .items
+                .get(*idx)
+                .map(|item| to_item(*idx, item))
         })
-        .unwrap_or_else(|| "(untitled)".to_string())
-}
+        .collect();
 
-fn truncate_for_preview(input: &str, max_chars: usize) -> String {
-    let mut chars = input.chars();
-    let preview = chars.by_ref().take(max_chars).collect::<String>();
-    if chars.next().is_some() {
-        format!("{preview}...")
-    } else {
-        preview
+    ApiItemPage {
+        total: indices.len(),
+        page,
+        total_pages,
+        limit,
+        items,
     }
 }
 
-fn split_search_terms(query: &str) -> Vec<String> {
-    query
+async fn api_item_handler(State(state): State<AppState>, Path(id): Path<usize>) -> Response {
+    match state.library().index.items.get(id) {
+        Some(item) => Json(to_api_item(id, item)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "item not found" })),
+        )
+            .into_response(),
+    }
+}
+
+/// Tag completions for `q`, most used first.
+async fn api_tags_handler(
+    State(state): State<AppState>,
+    Query(params): Query<TagParams>,
+) -> Json<Vec<ApiTag>> {
+    let limit = params.limit.unwrap_or(20).clamp(1, 200);
+    let tags = state
+        .library()
+        .tag_completions(params.q.as_deref().unwrap_or_default(), limit)
+        .into_iter()
+        .map(|(tag, count)| ApiTag { tag, count })
+        .collect();
+    Json(tags)
+}
+
+const FEED_LENGTH: usize = 50;
+
+/// The newest matches for `q` as RSS, or as Atom with `format=atom`.
+async fn feed_handler(
+    State(state): State<AppState>,
+    Query(params): Query<FeedParams>,
+    headers: HeaderMap,
+) -> Response {
+    let query = params.q.unwrap_or_default().trim().to_string();
+    let show_sensitive = params
+        .show_sensitive
+        .as_deref()
+        .map(parse_truthy)
+        .unwrap_or(state.default_show_sensitive);
+    // Feed readers want absolute links.
+    let base = request_base_url(&headers);
+    let title = if query.is_empty() {
+        "lightbooru".to_string()
+    } else {
+        format!("lightbooru: {query}")
+    };
+    let link = format!("{base}{}", feed_href(&query).replacen("/feed", "/", 1));
+
+    let nav = IndexNav {
+        query,
+        match_all: false,
+        source_url: None,
+        exact_tag: None,
+        exact_author: None,
+        show_sensitive,
+        randomize: false,
+        seed: None,
+        sort: SearchSort::DateDesc,
+        after: None,
+        before: None,
+        view: IndexView::Grid,
+        limit: FEED_LENGTH,
+        page: 1,
+    };
+    let (mut entries, paths): (Vec<_>, Vec<_>) = {
+        let library = state.library();
+        search_indices(&library, &nav)
+            .into_iter()
+            .take(FEED_LENGTH)
+            .filter_map(|idx| {
+                let item = library.index.items.get(idx)?;
+                let entry = FeedEntry {
+                    // Ids shift on rescan; the path does not.
+                    guid: format!("urn:lightbooru:{:016x}", path_hash(&item.image_path)),
+                    link: format!("{base}/items/{idx}"),
+                    media_href: format!("{base}/media/{idx}"),
+                    mime: mime_guess::from_path(&item.image_path)
+                        .first_or_octet_stream()
+                        .to_string(),
+                    length: 0,
+                    title: infer_title(item),
+                    detail: item.merged_detail(),
+                    author: item.merged_author(),
+                    date: item.merged_date().as_deref().and_then(parse_date),
+                };
+                Some((entry, item.image_path.clone()))
+            })
+            .unzip()
+    };
+    let lengths = tokio::task::spawn_blocking(move || {
+        paths
+            .iter()
+            .map(|path| fs::metadata(path).map(|meta| meta.len()).unwrap_or(0))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+    for (entry, length) in entries.iter_mut().zip(lengths) {
+        entry.length = length;
+    }
+
+    let (content_type, body) = match params.format.as_deref() {
+        Some("atom") => ("application/atom+xml", atom_feed(title, link, &entries)),
+        _ => ("application/rss+xml", rss_feed(title, link, &entries)),
+    };
+    ([(header::CONTENT_TYPE, content_type)], body).into_response()
+}
+
+/// Scheme and authority the client used to reach us, so absolute links
+/// still work behind a reverse proxy. Empty without a `Host` header.
+fn request_base_url(headers: &HeaderMap) -> String {
+    let Some(host) = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+    else {
+        return String::new();
+    };
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|proto| proto.to_str().ok())
+        .and_then(|proto| proto.split(',').next())
+        .map(str::trim)
+        .filter(|proto| matches!(*proto, "http" | "https"))
+        .unwrap_or("http");
+    format!("{scheme}://{host}")
+}
+
+async fn opensearch_handler(headers: HeaderMap) -> Response {
+    let template = OpenSearchTemplate {
+        base: request_base_url(&headers),
+        icon: OPENSEARCH_ICON,
+    };
+    match template.render() {
+        Ok(body) => (
+            [(
+                header::CONTENT_TYPE,
+                "application/opensearchdescription+xml",
+            )],
+            body,
+        )
+            .into_response(),
+        Err(err) => internal_error(format!("failed to render template: {err}")),
+    }
+}
+
+struct FeedEntry {
+    /// Stable across rescans, unlike `link`.
+    guid: String,
+    title: String,
+    link: String,
+    media_href: String,
+    mime: String,
+    length: u64,
+    detail: Option<String>,
+    author: Option<String>,
+    date: Option<DateTime<Local>>,
+}
+
+fn feed_href(query: &str) -> String {
+    if query.is_empty() {
+        "/feed".to_string()
+    } else {
+        format!("/feed?q={}", urlencoding::encode(query))
+    }
+}
+
+fn rss_feed(title: String, link: String, entries: &[FeedEntry]) -> String {
+    let items = entries
+        .iter()
+        .map(|entry| rss::Item {
+            title: Some(entry.title.clone()),
+            link: Some(entry.link.clone()),
+            description: entry.detail.clone(),
+            author: entry.author.clone(),
+            guid: Some(rss::Guid {
+                value: entry.guid.clone(),
+                permalink: false,
+            }),
+            pub_date: entry.date.map(|date| date.to_rfc2822()),
+            enclosure: Some(rss::Enclosure {
+                url: entry.media_href.clone(),
+                length: entry.length.to_string(),
+                mime_type: entry.mime.clone(),
+            }),
+            ..rss::Item::default()
+        })
+        .collect();
+    rss::Channel {
+        title: title.clone(),
+        link,
+        description: title,
+        items,
+        ..rss::Channel::default()
+    }
+    .to_string()
+}
+
+fn atom_feed(title: String, link: String, entries: &[FeedEntry]) -> String {
+    let updated = |date: Option<DateTime<Local>>| {
+        date.unwrap_or_else(|| DateTime::<Local>::from(UNIX_EPOCH))
+            .fixed_offset()
+    };
+    let entries = entries
+        .iter()
+        .map(|entry| atom_syndication::Entry {
+            title: entry.title.clone().into(),
+            id: entry.guid.clone(),
+            updated: updated(entry.date),
+            published: entry.date.map(|date| date.fixed_offset()),
+            authors: entry
+                .author
+                .iter()
+                .map(|name| atom_syndication::Person {
+                    name: name.clone(),
+                    ..atom_syndication::Person::default()
+                })
+                .collect(),
+            links: vec![
+                atom_syndication::Link {
+                    href: entry.link.clone(),
+                    ..atom_syndication::Link::default()
+                },
+                atom_syndication::Link {
+                    href: entry.media_href.clone(),
+                    rel: "enclosure".to_string(),
+                    mime_type: Some(entry.mime.clone()),
+                    length: Some(entry.length.to_string()),
+                    ..atom_syndication::Link::default()
+                },
+            ],
+            summary: entry.detail.clone().map(Into::into),
+            ..atom_syndication::Entry::default()
+        })
+        .collect::<Vec<_>>();
+    atom_syndication::Feed {
+        title: title.into(),
+        id: link.clone(),
+        updated: entries
+            .iter()
+            .map(|entry| entry.updated)
+            .max()
+            .unwrap_or_else(|| updated(None)),
+        links: vec![atom_syndication::Link {
+            href: link,
+            ..atom_syndication::Link::default()
+        }],
+        entries,
+        ..atom_syndication::Feed::default()
+    }
+    .to_string()
+}
+
+/// Items whose perceptual hash is within `threshold` bits of item `id`,
+/// closest first, on the index page layout.
+async fn similar_handler(
+    State(state): State<AppState>,
+    Path(id): Path<usize>,
+    Query(params): Query<SimilarParams>,
+) -> Response {
+    let threshold = params.threshold.unwrap_or(8).min(64);
+    let limit = params.limit.unwrap_or(10).clamp(1, 200);
+    if state.library().index.items.get(id).is_none() {
+        return (StatusCode::NOT_FOUND, "item not found").into_response();
+    }
+
+    let generation = state.generation.load(Ordering::Acquire);
+    let computed = state
+        .hashes
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .is_some_and(|hashes| hashes.generation == generation);
+    if !computed {
+        let state = state.clone();
+        let computation = tokio::task::spawn_blocking(move || ensure_library_hashes(&state)).await;
+        if let Err(err) = computation {
+            return internal_error(format!("failed to hash the library: {err}"));
+        }
+    }
+
+    let matches = {
+        let hashes = state.hashes.read().unwrap_or_else(PoisonError::into_inner);
+        let generation = state.generation.load(Ordering::Acquire);
+        let hashes = hashes
+            .as_ref()
+            .filter(|hashes| hashes.generation == generation)
+            .map(|hashes| hashes.computation.hashes.as_slice());
+        let target = hashes
+            .unwrap_or_default()
+            .iter()
+            .find(|(idx, _)| *idx == id)
+            .map(|(_, hash)| hash);
+        target.map(|target| {
+            let mut matches = hashes
+                .unwrap_or_default()
+                .iter()
+                .filter(|(idx, _)| *idx != id)
+                .filter_map(|(idx, hash)| Some((target.distance(hash).ok()?, *idx)))
+                .filter(|(distance, _)| *distance <= threshold)
+                .collect::<Vec<_>>();
+            matches.sort_unstable();
+            matches
+        })
+    };
+
+    let nav = IndexNav {
+        query: String::new(),
+        match_all: false,
+        source_url: None,
+        exact_tag: None,
+        exact_author: None,
+        show_sensitive: state.default_show_sensitive,
+        randomize: false,
+        seed: None,
+        sort: SearchSort::FileNameAsc,
+        after: None,
+        before: None,
+        view: IndexView::Grid,
+        limit,
+        page: 1,
+    };
+    let library = state.library();
+    let placeholders = state
+        .placeholders
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    let (banner, items) = match matches {
+        Some(matches) => {
+            let items = matches
+                .iter()
+                .filter_map(|(_, idx)| Some((*idx, library.index.items.get(*idx)?)))
+                .filter(|(_, item)| nav.show_sensitive || !item.merged_sensitive())
+                .take(limit)
+                .map(|(idx, item)| {
+                    to_grid_item(idx, item, &nav, placeholders.get(&item.image_path))
+                })
+                .collect::<Vec<_>>();
+            let banner =
+                format!("Images similar to #{id} (within {threshold} bits, closest first)");
+            (banner, items)
+        }
+        None => (format!("#{id} could not be hashed"), Vec::new()),
+    };
+
+    HtmlTemplate(IndexTemplate {
+        query: String::new(),
+        match_all: false,
+        source_filter: None,
+        exact_tag: None,
+        exact_author: None,
+        show_sensitive: nav.show_sensitive,
+        randomize: false,
+        seed: None,
+        sort: nav.sort.as_str(),
+        after: None,
+        before: None,
+        list_view: false,
+        reshuffle_href: None,
+        total_matches: items.len(),
+        shown_count: items.len(),
+        limit,
+        page: 1,
+        total_pages: 1,
+        start_item: usize::from(!items.is_empty()),
+        end_item: items.len(),
+        prev_page: None,
+        next_page: None,
+        prev_page_href: None,
+        next_page_href: None,
+        feed_href: feed_href(""),
+        banner: Some(banner),
+        items,
+    })
+    .into_response()
+}
+
+/// Streams the original file. A single `Range` is honoured so browsers can
+/// seek in videos, and the size and mtime make up an `ETag` so unchanged
+/// files are answered with `304 Not Modified`.
+async fn media_handler(
+    State(state): State<AppState>,
+    Path(id): Path<usize>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(image_path) = state
+        .library()
+        .index
+        .items
+        .get(id)
+        .map(|item| item.image_path.clone())
+    else {
+        return (StatusCode::NOT_FOUND, "item not found").into_response();
+    };
+
+    let read_error = |err: &dyn Display| internal_error(format!("failed to read image: {err}"));
+    let fingerprint = match FileFingerprint::from_path(&image_path) {
+        Ok(fingerprint) => fingerprint,
+        Err(err) => return read_error(&err),
+    };
+    let etag = media_etag(&image_path, &fingerprint);
+    let modified = UNIX_EPOCH + Duration::from_secs(fingerprint.mtime.max(0) as u64);
+    if media_not_modified(&headers, &etag, modified) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        insert_media_validators(response.headers_mut(), &etag, modified, MEDIA_CACHE_CONTROL);
+        return response;
+    }
+
+    let mut file = match tokio::fs::File::open(&image_path).await {
+        Ok(file) => file,
+        Err(err) => return read_error(&err),
+    };
+    let len = fingerprint.size as u64;
+    let range = parse_byte_range(
+        headers
+            .get(header::RANGE)
+            .and_then(|value| value.to_str().ok()),
+        len,
+    );
+    let (status, start, count) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, len),
+        ByteRange::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+        ByteRange::Unsatisfiable => {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{len}"))],
+            )
+                .into_response();
+        }
+    };
+    if start > 0 {
+        if let Err(err) = file.seek(SeekFrom::Start(start)).await {
+            return read_error(&err);
+        }
+    }
+
+    let mime = mime_guess::from_path(&image_path).first_or_octet_stream();
+    let mut response = Response::new(Body::from_stream(ReaderStream::new(file.take(count))));
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(mime.as_ref())
+            .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream")),
+    );
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(count));
+    insert_media_validators(headers, &etag, modified, MEDIA_CACHE_CONTROL);
+    if status == StatusCode::PARTIAL_CONTENT {
+        let content_range = format!("bytes {start}-{}/{len}", start + count - 1);
+        if let Ok(value) = HeaderValue::from_str(&content_range) {
+            headers.insert(header::CONTENT_RANGE, value);
+        }
+    }
+    response
+}
+
+/// Ids are positions that shift on rescan, so the tag covers the path too:
+/// another file landing on the same id never revalidates as unchanged.
+fn media_etag(image_path: &std::path::Path, fingerprint: &FileFingerprint) -> String {
+    format!(
+        "\"{:016x}-{:x}-{:x}\"",
+        path_hash(image_path),
+        fingerprint.size,
+        fingerprint.mtime
+    )
+}
+
+/// 64-bit FNV-1a of the path's bytes. Unlike `DefaultHasher` it stays the
+/// same across builds and Rust releases.
+fn path_hash(path: &std::path::Path) -> u64 {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// `If-None-Match` takes precedence over `If-Modified-Since`, which only
+/// has second precision.
+fn media_not_modified(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+    if let Some(value) = headers.get(header::IF_NONE_MATCH) {
+        return value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag.trim_start_matches("W/") == etag)
+        });
+    }
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .is_some_and(|since| modified <= since)
+}
+
+fn insert_media_validators(
+    headers: &mut HeaderMap,
+    etag: &str,
+    modified: SystemTime,
+    cache_control: &'static str,
+) {
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+        headers.insert(header::LAST_MODIFIED, value);
+    }
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control),
+    );
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    /// Inclusive start and end offsets.
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Reads a `Range` header for a body of `len` bytes. Anything other than one
+/// well-formed `bytes=` range is ignored, as RFC 9110 allows, and the whole
+/// body is served.
+fn parse_byte_range(value: Option<&str>, len: u64) -> ByteRange {
+    let Some(spec) = value.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    if start.is_empty() {
+        // `bytes=-N` asks for the last N bytes.
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial(len.saturating_sub(suffix), len - 1),
+            Err(_) => ByteRange::Full,
+        };
+    }
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let end = if end.is_empty() {
+        u64::MAX
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return ByteRange::Full,
+        }
+    };
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start, end.min(len - 1))
+}
+
+async fn thumb_handler(
+    State(state): State<AppState>,
+    Path(id): Path<usize>,
+    Query(params): Query<ThumbParams>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(image_path) = state
+        .library()
+        .index
+        .items
+        .get(id)
+        .map(|item| item.image_path.clone())
+    else {
+        return (StatusCode::NOT_FOUND, "item not found").into_response();
+    };
+    let width = params
+        .w
+        .unwrap_or(DEFAULT_THUMB_WIDTH)
+        .clamp(16, MAX_THUMB_SIZE);
+    // Without `h` only the width is bounded.
+    let height = params
+        .h
+        .map_or(u32::MAX, |height| height.clamp(16, MAX_THUMB_SIZE));
+
+    let stat_path = image_path.clone();
+    let fingerprint = tokio::task::spawn_blocking(move || FileFingerprint::from_path(&stat_path))
+        .await
+        .ok()
+        .and_then(Result::ok);
+    let Some(fingerprint) = fingerprint else {
+        return thumb_placeholder();
+    };
+    let etag = thumb_etag(&image_path, &fingerprint, width, height);
+    let modified = UNIX_EPOCH + Duration::from_secs(fingerprint.mtime.max(0) as u64);
+    if media_not_modified(&headers, &etag, modified) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        insert_media_validators(response.headers_mut(), &etag, modified, THUMB_CACHE_CONTROL);
+        return response;
+    }
+
+    let cached = state.thumb_memory.as_ref().and_then(|thumbs| {
+        thumbs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&etag)
+            .cloned()
+    });
+    let thumbnail = match cached {
+        Some(bytes) => Some(bytes),
+        None => {
+            let thumb_dir = state.thumb_dir.clone();
+            let rendered = tokio::task::spawn_blocking(move || {
+                load_thumbnail(&image_path, width, height, thumb_dir.as_deref())
+            })
+            .await
+            .ok()
+            .flatten()
+            .map(Bytes::from);
+            if let (Some(thumbs), Some(bytes)) = (&state.thumb_memory, &rendered) {
+                thumbs
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .put(etag.clone(), bytes.clone());
+            }
+            rendered
+        }
+    };
+    let Some(bytes) = thumbnail else {
+        return thumb_placeholder();
+    };
+
+    let mut response = Response::new(Body::from(bytes));
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("image/jpeg"));
+    insert_media_validators(headers, &etag, modified, THUMB_CACHE_CONTROL);
+    response
+}
+
+/// Like [`media_etag`], for one thumbnail box of the image.
+fn thumb_etag(
+    image_path: &std::path::Path,
+    fingerprint: &FileFingerprint,
+    width: u32,
+    height: u32,
+) -> String {
+    format!(
+        "\"{:016x}-{:x}-{:x}-{width}x{height}\"",
+        path_hash(image_path),
+        fingerprint.size,
+        fingerprint.mtime
+    )
+}
+
+fn thumb_placeholder() -> Response {
+    let mut response = Response::new(Body::from(Bytes::from_static(PLACEHOLDER_GIF)));
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("image/gif"));
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+/// JPEG bytes of `image_path` scaled down to fit `width` x `height`, taken
+/// from `cache_dir` when an entry for the current mtime exists there. `None`
+/// if the file cannot be read or decoded.
+fn load_thumbnail(
+    image_path: &std::path::Path,
+    width: u32,
+    height: u32,
+    cache_dir: Option<&std::path::Path>,
+) -> Option<Vec<u8>> {
+    let mtime = fs::metadata(image_path)
+        .and_then(|meta| meta.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    // Ids shift when the library is rescanned, so the key uses the path.
+    let cache_path = cache_dir.map(|dir| {
+        dir.join(format!(
+            "{:016x}-{width}x{height}-{mtime:x}.jpg",
+            path_hash(image_path)
+        ))
+    });
+    if let Some(bytes) = cache_path.as_ref().and_then(|path| fs::read(path).ok()) {
+        return Some(bytes);
+    }
+
+    let image = image::open(image_path).ok()?;
+    let image = if image.width() > width || image.height() > height {
+        image.resize(width, height, FilterType::Triangle)
+    } else {
+        image
+    };
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, 80)
+        .encode_image(&image.to_rgb8())
+        .ok()?;
+
+    if let (Some(dir), Some(path)) = (cache_dir, cache_path) {
+        // A failed cache write only costs a re-render next time.
+        let _ = fs::create_dir_all(dir).and_then(|()| fs::write(path, &bytes));
+    }
+    Some(bytes)
+}
+
+fn to_grid_item(
+    id: usize,
+    item: &booru_core::ImageItem,
+    nav: &IndexNav,
+    placeholder: Option<&Placeholder>,
+) -> GridItem {
+    let author = item
+        .merged_author()
+        .unwrap_or_else(|| "(unknown)".to_string());
+    let tags = item.merged_tags();
+    GridItem {
+        id,
+        detail_href: build_item_href(id, nav),
+        title: infer_title(item),
+        author: author.clone(),
+        author_href: build_author_search_href(&author, nav),
+        date: item
+            .merged_date()
+            .unwrap_or_else(|| "(unknown)".to_string()),
+        detail: truncate_for_preview(
+            &item
+                .merged_detail()
+                .unwrap_or_else(|| "(no description)".to_string()),
+            140,
+        ),
+        tag_count: tags.len(),
+        tags: tags
+            .into_iter()
+            .take(8)
+            .map(|tag| TagLink {
+                href: build_tag_search_href(tag.as_str(), nav),
+                label: tag,
+            })
+            .collect(),
+        sensitive: item.merged_sensitive(),
+        placeholder_color: placeholder.map(|placeholder| {
+            let [r, g, b] = placeholder.color;
+            format!("#{r:02x}{g:02x}{b:02x}")
+        }),
+        blurhash: placeholder.map(|placeholder| placeholder.blurhash.clone()),
+    }
+}
+
+fn to_api_item(id: usize, item: &booru_core::ImageItem) -> ApiItem {
+    ApiItem {
+        id,
+        title: infer_title(item),
+        author: item.merged_author(),
+        date: item.merged_date(),
+        tags: item.merged_tags(),
+        sensitive: item.merged_sensitive(),
+        platform_url: item.platform_url(),
+        media_href: format!("/media/{id}"),
+    }
+}
+
+/// Clamps `requested_page` to the last page and returns it together with
+/// the page count and the slice of `total` results the page covers.
+fn paginate(total: usize, limit: usize, requested_page: usize) -> (usize, usize, Range<usize>) {
+    let total_pages = total.div_ceil(limit).max(1);
+    let page = requested_page.min(total_pages);
+    let start = (page - 1) * limit;
+    (page, total_pages, start..usize::min(start + limit, total))
+}
+
+fn infer_title(item: &booru_core::ImageItem) -> String {
+    booru_core::extract_string_field(&item.original, &["title", "filename"])
+        .or_else(|| {
+            item.image_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "(untitled)".to_string())
+}
+
+fn truncate_for_preview(input: &str, max_chars: usize) -> String {
+    let mut chars = input.chars();
+    let preview = chars.by_ref().take(max_chars).collect::<String>();
+    if chars.next().is_some() {
+        format!("{preview}...")
+    } else {
+        preview
+    }
+}
+
+fn split_search_terms(query: &str) -> Vec<String> {
+    query
         .split_whitespace()
         .filter(|part| !part.trim().is_empty())
         .map(|part| part.to_string())
@@ -508,13 +1929,69 @@ fn parse_truthy(value: &str) -> bool {
     )
 }
 
+fn parse_sort(value: Option<&str>) -> SearchSort {
+    // Shuffling is handled by `randomize`/`seed`, so only deterministic orders apply.
+    match value.and_then(|value| value.parse::<SearchSort>().ok()) {
+        Some(SearchSort::IndexOrder | SearchSort::Random(_)) | None => SearchSort::FileNameAsc,
+        Some(sort) => sort,
+    }
+}
+
+fn parse_match_all(value: Option<&str>) -> bool {
+    matches!(
+        value
+            .map(|value| value.trim().to_ascii_lowercase())
+            .as_deref(),
+        Some("and" | "all")
+    )
+}
+
+/// Keeps the date as typed so links and the date inputs round-trip it;
+/// values `parse_date` does not understand are dropped.
+fn parse_date_param(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| parse_date(value).is_some())
+}
+
+fn date_param_timestamp(value: Option<&str>) -> Option<i64> {
+    value.and_then(parse_date).map(|date| date.timestamp())
+}
+
+/// The search behind an index page. Item pages rebuild it from the same
+/// parameters to find their neighbours.
+fn search_query_for_nav(nav: &IndexNav) -> SearchQuery {
+    SearchQuery::new(split_search_terms(&nav.query))
+        .with_aliases(!nav.query.is_empty())
+        .with_source_url(nav.source_url.clone())
+        .with_exact_tag(nav.exact_tag.clone())
+        .with_exact_author(nav.exact_author.clone())
+        .with_sort(nav.seed.map(SearchSort::Random).unwrap_or(nav.sort))
+        .with_match_all(nav.match_all)
+        .with_sensitive(SensitiveFilter::from_show(nav.show_sensitive))
+        .with_date_after(date_param_timestamp(nav.after.as_deref()))
+        .with_date_before(date_param_timestamp(nav.before.as_deref()))
+}
+
+fn search_indices(library: &Library, nav: &IndexNav) -> Vec<usize> {
+    library.search(search_query_for_nav(nav)).indices
+}
+
 #[derive(Clone, Debug)]
 struct IndexNav {
     query: String,
+    match_all: bool,
     source_url: Option<String>,
+    /// Set by tag and author links; see [`SearchQuery::exact_tag`].
+    exact_tag: Option<String>,
+    exact_author: Option<String>,
     show_sensitive: bool,
     randomize: bool,
     seed: Option<u64>,
+    sort: SearchSort,
+    after: Option<String>,
+    before: Option<String>,
+    view: IndexView,
     limit: usize,
     page: usize,
 }
@@ -542,11 +2019,20 @@ fn build_index_query_string(nav: &IndexNav) -> String {
     if !nav.query.is_empty() {
         pairs.push(format!("q={}", urlencoding::encode(&nav.query)));
     }
+    if nav.match_all {
+        pairs.push("mode=and".to_string());
+    }
     if let Some(source) = nav.source_url.as_deref() {
         if !source.is_empty() {
             pairs.push(format!("source={}", urlencoding::encode(source)));
         }
     }
+    if let Some(tag) = nav.exact_tag.as_deref() {
+        pairs.push(format!("exact_tag={}", urlencoding::encode(tag)));
+    }
+    if let Some(author) = nav.exact_author.as_deref() {
+        pairs.push(format!("exact_author={}", urlencoding::encode(author)));
+    }
     if nav.show_sensitive {
         pairs.push("show_sensitive=1".to_string());
     }
@@ -560,13 +2046,25 @@ fn build_index_query_string(nav: &IndexNav) -> String {
             pairs.push(format!("seed={seed}"));
         }
     }
+    if nav.sort != SearchSort::FileNameAsc {
+        pairs.push(format!("sort={}", nav.sort.as_str()));
+    }
+    if let Some(after) = nav.after.as_deref() {
+        pairs.push(format!("after={}", urlencoding::encode(after)));
+    }
+    if let Some(before) = nav.before.as_deref() {
+        pairs.push(format!("before={}", urlencoding::encode(before)));
+    }
+    if nav.view == IndexView::List {
+        pairs.push("view=list".to_string());
+    }
     pairs.push(format!("limit={}", nav.limit));
     pairs.push(format!("page={}", nav.page));
     pairs.join("&")
 }
 
 fn build_tag_search_href(tag: &str, nav: &IndexNav) -> String {
-    build_term_search_href(tag, nav)
+    build_exact_search_href(Some(tag.to_string()), None, nav)
 }
 
 fn build_author_search_href(author: &str, nav: &IndexNav) -> Option<String> {
@@ -574,16 +2072,27 @@ fn build_author_search_href(author: &str, nav: &IndexNav) -> Option<String> {
     if trimmed.is_empty() || trimmed == "(unknown)" {
         return None;
     }
-    Some(build_term_search_href(trimmed, nav))
+    Some(build_exact_search_href(None, Some(author.to_string()), nav))
 }
 
-fn build_term_search_href(term: &str, nav: &IndexNav) -> String {
+fn build_exact_search_href(
+    exact_tag: Option<String>,
+    exact_author: Option<String>,
+    nav: &IndexNav,
+) -> String {
     let tag_nav = IndexNav {
-        query: term.to_string(),
+        query: String::new(),
+        match_all: nav.match_all,
         source_url: None,
+        exact_tag,
+        exact_author,
         show_sensitive: nav.show_sensitive,
         randomize: nav.randomize,
         seed: nav.seed,
+        sort: nav.sort,
+        after: nav.after.clone(),
+        before: nav.before.clone(),
+        view: nav.view,
         limit: nav.limit,
         page: 1,
     };
@@ -597,16 +2106,29 @@ fn build_source_search_href(source: &str, nav: &IndexNav) -> Option<String> {
     }
     let source_nav = IndexNav {
         query: String::new(),
+        match_all: nav.match_all,
         source_url: Some(trimmed.to_string()),
+        exact_tag: None,
+        exact_author: None,
         show_sensitive: nav.show_sensitive,
         randomize: false,
         seed: None,
+        sort: nav.sort,
+        after: nav.after.clone(),
+        before: nav.before.clone(),
+        view: nav.view,
         limit: nav.limit,
         page: 1,
     };
     Some(build_index_href(&source_nav))
 }
 
+/// Exact tags and authors are matched verbatim, so only blank values are
+/// dropped.
+fn parse_exact_param(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
 fn generate_seed() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -618,3 +2140,1192 @@ fn generate_seed() -> u64 {
 fn next_seed(seed: u64) -> u64 {
     seed.wrapping_mul(6364136223846793005).wrapping_add(1)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::path::PathBuf;
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex, RwLock};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use axum::body::to_bytes;
+    use axum::extract::{Path, Query, State};
+    use axum::response::IntoResponse;
+    use booru_core::{BooruConfig, BooruEdits, FileFingerprint, ImageItem, Index, Library};
+    use lru::LruCache;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::{
+        api_item_handler, api_items_handler, api_search_handler, api_tags_handler,
+        compute_placeholder, feed_handler, index_handler, item_handler, load_thumbnail, media_etag,
+        media_handler, parse_byte_range, path_hash, router, thumb_handler, AppState, BasicAuth,
+        ByteRange, FeedParams, IndexParams, Metrics, Placeholder, Placeholders, TagParams,
+        ThumbParams, PLACEHOLDER_GIF,
+    };
+
+    fn make_state() -> AppState {
+        let mut index = Index::default();
+        index.items.push(ImageItem::new(
+            "/tmp/a.jpg".into(),
+            "/tmp/a.jpg.json".into(),
+            "/tmp/a.jpg.booru.json".into(),
+            json!({
+                "title": "first",
+                "author": "alice",
+                "tags": ["sky", "cloud"],
+            }),
+            BooruEdits::default(),
+        ));
+        AppState {
+            library: Arc::new(RwLock::new(Library {
+                config: BooruConfig::with_roots(Vec::new()),
+                index,
+                warnings: Vec::new(),
+            })),
+            default_show_sensitive: false,
+            default_limit: 120,
+            thumb_dir: None,
+            thumb_memory: None,
+            hashes: Arc::default(),
+            hashing: Arc::default(),
+            generation: Arc::default(),
+            use_hash_cache: false,
+            metrics: None,
+            placeholders: Placeholders::default(),
+        }
+    }
+
+    async fn body_text(response: axum::response::Response) -> String {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn list_view_round_trips_through_navigation_links() {
+        let params = IndexParams {
+            view: Some("list".to_string()),
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains("class=\"list\""));
+        assert!(body.contains("/items/0?randomize=0&amp;view=list&amp;limit=120&amp;page=1"));
+        assert!(body.contains("/?exact_author=alice&amp;randomize=0&amp;view=list"));
+
+        let params = IndexParams {
+            view: Some("list".to_string()),
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            item_handler(State(make_state()), Path(0), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains("href=\"/?randomize=0&amp;view=list&amp;limit=120&amp;page=1\""));
+    }
+
+    #[tokio::test]
+    async fn item_page_links_to_neighbours_in_result_order() {
+        let mut index = Index::default();
+        for (name, sensitive) in [("c", false), ("a", false), ("b", true)] {
+            index.items.push(ImageItem::new(
+                format!("/tmp/{name}.jpg").into(),
+                format!("/tmp/{name}.jpg.json").into(),
+                format!("/tmp/{name}.jpg.booru.json").into(),
+                json!({ "tags": ["sky"], "sensitive": sensitive }),
+                BooruEdits::default(),
+            ));
+        }
+        let state = AppState {
+            library: Arc::new(RwLock::new(Library {
+                config: BooruConfig::with_roots(Vec::new()),
+                index,
+                warnings: Vec::new(),
+            })),
+            default_show_sensitive: false,
+            default_limit: 120,
+            thumb_dir: None,
+            thumb_memory: None,
+            hashes: Arc::default(),
+            hashing: Arc::default(),
+            generation: Arc::default(),
+            use_hash_cache: false,
+            metrics: None,
+            placeholders: Placeholders::default(),
+        };
+
+        let params = IndexParams {
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            item_handler(State(state.clone()), Path(1), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(!body.contains("rel=\"prev\""));
+        assert!(body.contains(
+            "rel=\"next\" href=\"/items/0?randomize=0&amp;limit=120&amp;page=1&amp;from=0\""
+        ));
+
+        let params = IndexParams {
+            randomize: Some("0".to_string()),
+            show_sensitive: Some("1".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            item_handler(State(state), Path(1), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains("rel=\"next\" href=\"/items/2?"));
+    }
+
+    #[tokio::test]
+    async fn middle_item_links_to_neighbours_on_their_pages() {
+        let mut index = Index::default();
+        for name in ["a", "b", "c"] {
+            index.items.push(ImageItem::new(
+                format!("/tmp/{name}.jpg").into(),
+                format!("/tmp/{name}.jpg.json").into(),
+                format!("/tmp/{name}.jpg.booru.json").into(),
+                json!({ "tags": ["sky"] }),
+                BooruEdits::default(),
+            ));
+        }
+        let state = make_state();
+        state.library.write().unwrap().index = index;
+        let item_page = |limit: usize, page: usize| {
+            let params = IndexParams {
+                randomize: Some("0".to_string()),
+                limit: Some(limit),
+                page: Some(page),
+                ..IndexParams::default()
+            };
+            let state = state.clone();
+            async move {
+                body_text(
+                    item_handler(State(state), Path(1), Query(params))
+                        .await
+                        .into_response(),
+                )
+                .await
+            }
+        };
+
+        let body = item_page(120, 1).await;
+        assert!(body.contains(
+            "rel=\"prev\" href=\"/items/0?randomize=0&amp;limit=120&amp;page=1&amp;from=0\""
+        ));
+        assert!(body.contains(
+            "rel=\"next\" href=\"/items/2?randomize=0&amp;limit=120&amp;page=1&amp;from=2\""
+        ));
+
+        // One item per page: each neighbour sits on its own page.
+        let body = item_page(1, 2).await;
+        assert!(body.contains(
+            "rel=\"prev\" href=\"/items/0?randomize=0&amp;limit=1&amp;page=1&amp;from=0\""
+        ));
+        assert!(body.contains(
+            "rel=\"next\" href=\"/items/2?randomize=0&amp;limit=1&amp;page=3&amp;from=2\""
+        ));
+    }
+
+    #[tokio::test]
+    async fn api_returns_items_as_json_and_json_404s() {
+        let response = api_items_handler(State(make_state()), Query(IndexParams::default()))
+            .await
+            .into_response();
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "total": 1,
+                "page": 1,
+                "total_pages": 1,
+                "limit": 120,
+                "items": [{
+                    "id": 0,
+                    "title": "first",
+                    "author": "alice",
+                    "date": null,
+                    "tags": ["sky", "cloud"],
+                    "sensitive": false,
+                    "platform_url": null,
+                    "media_href": "/media/0",
+                }],
+            })
+        );
+
+        let params = IndexParams {
+            q: Some("sea".to_string()),
+            ..IndexParams::default()
+        };
+        let response = api_items_handler(State(make_state()), Query(params))
+            .await
+            .into_response();
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body["total"], 0);
+        assert_eq!(body["items"], json!([]));
+
+        let response = api_item_handler(State(make_state()), Path(0)).await;
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body["title"], "first");
+
+        let response = api_item_handler(State(make_state()), Path(7)).await;
+        assert_eq!(response.status(), axum::http::StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body, json!({ "error": "item not found" }));
+    }
+
+    #[tokio::test]
+    async fn feeds_list_dated_matches_newest_first() {
+        use axum::http::{header, HeaderMap};
+
+        let state = make_state();
+        {
+            let mut library = state.library.write().unwrap();
+            library.index.items[0].original["date"] = json!("2024-01-02 03:04:05");
+            library.index.push(ImageItem::new(
+                "/tmp/b.png".into(),
+                "/tmp/b.png.json".into(),
+                "/tmp/b.png.booru.json".into(),
+                json!({
+                    "title": "second <&>",
+                    "tags": ["sky"],
+                    "date": "2024-03-04 05:06:07",
+                    "content": "a & b",
+                }),
+                BooruEdits::default(),
+            ));
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "booru.local:8080".parse().unwrap());
+        let feed = |format: Option<&str>| {
+            feed_handler(
+                State(state.clone()),
+                Query(FeedParams {
+                    q: Some("sky".to_string()),
+                    format: format.map(ToString::to_string),
+                    show_sensitive: None,
+                }),
+                headers.clone(),
+            )
+        };
+
+        let response = feed(None).await;
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/rss+xml"
+        );
+        let channel: rss::Channel = body_text(response).await.parse().unwrap();
+        assert_eq!(channel.link, "http://booru.local:8080/?q=sky");
+        let titles = channel
+            .items
+            .iter()
+            .map(|item| item.title.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["second <&>", "first"]);
+        let newest = &channel.items[0];
+        assert_eq!(
+            newest.link.as_deref(),
+            Some("http://booru.local:8080/items/1")
+        );
+        assert_eq!(newest.author, None);
+        let enclosure = newest.enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "http://booru.local:8080/media/1");
+        assert_eq!(enclosure.mime_type, "image/png");
+        let guid = newest.guid.as_ref().unwrap();
+        assert!(!guid.is_permalink());
+        assert_eq!(
+            guid.value(),
+            format!("urn:lightbooru:{:016x}", path_hash("/tmp/b.png".as_ref()))
+        );
+        let date = newest.pub_date.as_deref().unwrap();
+        assert!(date.contains("4 Mar 2024"), "{date}");
+        assert_eq!(channel.items[1].author.as_deref(), Some("alice"));
+
+        let response = feed(Some("atom")).await;
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/atom+xml"
+        );
+        let atom: atom_syndication::Feed = body_text(response).await.parse().unwrap();
+        assert_eq!(atom.entries.len(), 2);
+        assert_eq!(atom.entries[0].title.as_str(), "second <&>");
+        assert_eq!(atom.entries[0].id, guid.value());
+        assert_eq!(atom.updated, atom.entries[0].updated);
+
+        let page = body_text(
+            index_handler(State(make_state()), Query(IndexParams::default()))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(page.contains(r#"<link rel="alternate" type="application/rss+xml""#));
+    }
+
+    #[tokio::test]
+    async fn similar_page_lists_close_images_and_hashes_once() {
+        use axum::http::StatusCode;
+
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("booru-web-similar-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gradient = image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, 128])
+        });
+        let checker = image::RgbImage::from_fn(64, 64, |x, y| {
+            let on = (x / 8 + y / 8) % 2 == 0;
+            image::Rgb(if on { [255, 255, 255] } else { [0, 0, 0] })
+        });
+        let state = make_state();
+        {
+            let mut library = state.library.write().unwrap();
+            let mut add = |name: &str, image: &image::RgbImage| {
+                let path = dir.join(name);
+                image.save(&path).unwrap();
+                library.index.push(ImageItem::new(
+                    path,
+                    std::path::PathBuf::new(),
+                    std::path::PathBuf::new(),
+                    json!({ "title": name }),
+                    BooruEdits::default(),
+                ));
+            };
+            add("gradient.png", &gradient);
+            add("copy.png", &gradient);
+            add("checker.png", &checker);
+        }
+        let request = |uri: &str| {
+            axum::http::Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+        let app = router(state.clone(), None);
+
+        let response = app
+            .clone()
+            .oneshot(request("/items/1/similar?threshold=8"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_text(response).await;
+        assert!(body.contains("Images similar to #1"));
+        assert!(body.contains("copy.png"));
+        assert!(!body.contains("checker.png"));
+        assert_eq!(state.hashes.read().unwrap().as_ref().unwrap().generation, 0);
+
+        // A rescan bumps the generation; stale hashes are not reused.
+        state.generation.fetch_add(1, Ordering::AcqRel);
+        let response = app
+            .clone()
+            .oneshot(request("/items/1/similar?threshold=8"))
+            .await
+            .unwrap();
+        assert!(body_text(response).await.contains("copy.png"));
+        assert_eq!(state.hashes.read().unwrap().as_ref().unwrap().generation, 1);
+
+        // Item 0 does not exist on disk, so it has no hash.
+        let response = app
+            .clone()
+            .oneshot(request("/items/0/similar"))
+            .await
+            .unwrap();
+        assert!(body_text(response).await.contains("could not be hashed"));
+        let response = app.oneshot(request("/items/9/similar")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn api_search_pages_matches_with_their_paths() {
+        let params = IndexParams {
+            q: Some("sky".to_string()),
+            ..IndexParams::default()
+        };
+        let response = api_search_handler(State(make_state()), Query(params))
+            .await
+            .into_response();
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "total": 1,
+                "page": 1,
+                "items": [{
+                    "id": 0,
+                    "path": "/tmp/a.jpg",
+                    "title": "first",
+                    "author": "alice",
+                    "date": null,
+                    "tags": ["sky", "cloud"],
+                    "sensitive": false,
+                }],
+            })
+        );
+
+        let response = router(make_state(), None)
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri("/api/search?q=sea&page=3&limit=5")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body, json!({ "total": 0, "page": 1, "items": [] }));
+    }
+
+    #[test]
+    fn byte_ranges_are_clamped_to_the_body() {
+        assert_eq!(parse_byte_range(None, 10), ByteRange::Full);
+        assert_eq!(
+            parse_byte_range(Some("bytes=2-5"), 10),
+            ByteRange::Partial(2, 5)
+        );
+        assert_eq!(
+            parse_byte_range(Some("bytes=2-99"), 10),
+            ByteRange::Partial(2, 9)
+        );
+        assert_eq!(
+            parse_byte_range(Some("bytes=-3"), 10),
+            ByteRange::Partial(7, 9)
+        );
+        assert_eq!(
+            parse_byte_range(Some("bytes=10-"), 10),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_byte_range(Some("bytes=5-2"), 10), ByteRange::Full);
+        assert_eq!(
+            parse_byte_range(Some("bytes=0-1, 4-5"), 10),
+            ByteRange::Full
+        );
+        assert_eq!(parse_byte_range(Some("items=0-1"), 10), ByteRange::Full);
+    }
+
+    #[tokio::test]
+    async fn media_honours_range_requests() {
+        use axum::http::{header, HeaderMap, StatusCode};
+
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("booru-web-range-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let video = dir.join("clip.mp4");
+        let data = (0..=255u8).cycle().take(300).collect::<Vec<_>>();
+        std::fs::write(&video, &data).unwrap();
+        let state = make_state();
+        state.library.write().unwrap().index.items[0].image_path = video;
+
+        let request = |range: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(range) = range {
+                headers.insert(header::RANGE, range.parse().unwrap());
+            }
+            media_handler(State(state.clone()), Path(0), headers)
+        };
+
+        let response = request(None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "video/mp4");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], &data[..]);
+
+        let response = request(Some("bytes=10-19")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 10-19/300");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "10");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], &data[10..20]);
+
+        let response = request(Some("bytes=100-")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[header::CONTENT_RANGE],
+            "bytes 100-299/300"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], &data[100..]);
+
+        let response = request(Some("bytes=300-400")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */300");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn media_etag_depends_on_the_path() {
+        let fingerprint = FileFingerprint {
+            mtime: 1_700_000_000,
+            size: 1234,
+        };
+        let a = media_etag(std::path::Path::new("/lib/a.png"), &fingerprint);
+        let b = media_etag(std::path::Path::new("/lib/b.png"), &fingerprint);
+        assert_ne!(a, b);
+        assert_eq!(
+            a,
+            media_etag(std::path::Path::new("/lib/a.png"), &fingerprint)
+        );
+    }
+
+    #[tokio::test]
+    async fn media_revalidates_with_etag_and_last_modified() {
+        use axum::http::{header, StatusCode};
+
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("booru-web-etag-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("a.png");
+        std::fs::write(&image, b"not really a png").unwrap();
+        let state = make_state();
+        state.library.write().unwrap().index.items[0].image_path = image;
+        let app = router(state, None);
+        let request = |name: Option<header::HeaderName>, value: &str| {
+            let mut request = axum::http::Request::builder().uri("/media/0");
+            if let Some(name) = name {
+                request = request.header(name, value);
+            }
+            request.body(axum::body::Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(None, "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let last_modified = response.headers()[header::LAST_MODIFIED]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = app
+            .clone()
+            .oneshot(request(Some(header::IF_NONE_MATCH), &etag))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        assert!(body_text(response).await.is_empty());
+
+        let response = app
+            .clone()
+            .oneshot(request(Some(header::IF_NONE_MATCH), "\"stale\""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(request(Some(header::IF_NONE_MATCH), "*"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(request(Some(header::IF_MODIFIED_SINCE), &last_modified))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let response = app
+            .oneshot(request(
+                Some(header::IF_MODIFIED_SINCE),
+                "Thu, 01 Jan 1970 00:00:00 GMT",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn thumbnails_keep_aspect_ratio_and_are_cached() {
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("booru-web-thumb-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image_path = dir.join("wide.png");
+        image::RgbImage::new(64, 32).save(&image_path).unwrap();
+        let cache_dir = dir.join("cache");
+
+        let bytes = load_thumbnail(&image_path, 16, u32::MAX, Some(&cache_dir)).unwrap();
+        let thumb = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (16, 8));
+        let cached = std::fs::read_dir(&cache_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(cached.len(), 1);
+        // Named by a stable hash, so the cache survives a rebuild.
+        assert!(cached[0].starts_with(&format!(
+            "{:016x}-16x{}-",
+            path_hash(&image_path),
+            u32::MAX
+        )));
+        assert_eq!(
+            load_thumbnail(&image_path, 16, u32::MAX, Some(&cache_dir)).unwrap(),
+            bytes
+        );
+
+        std::fs::write(dir.join("broken.png"), b"not a png").unwrap();
+        assert!(load_thumbnail(&dir.join("broken.png"), 16, u32::MAX, None).is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn thumb_route_fits_the_box_and_remembers_renders() {
+        use axum::http::{header, HeaderValue, StatusCode};
+
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("booru-web-thumb-route-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image_path = dir.join("tall.png");
+        image::RgbImage::new(40, 80).save(&image_path).unwrap();
+
+        let state = make_state();
+        state.library.write().unwrap().index.items[0].image_path = image_path.clone();
+        let thumbs = Arc::new(Mutex::new(LruCache::new(NonZeroUsize::new(4).unwrap())));
+        let state = AppState {
+            thumb_memory: Some(thumbs.clone()),
+            ..state
+        };
+        let app = router(state, None);
+        let get = |uri: &'static str, etag: Option<HeaderValue>| {
+            let app = app.clone();
+            async move {
+                let mut request = axum::http::Request::builder().uri(uri);
+                if let Some(etag) = etag {
+                    request = request.header(header::IF_NONE_MATCH, etag);
+                }
+                let request = request.body(axum::body::Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap()
+            }
+        };
+
+        let response = get("/thumb/0?w=4096&h=20", None).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/jpeg");
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=300"
+        );
+        let etag = response.headers()[header::ETAG].clone();
+        assert!(etag.to_str().unwrap().ends_with("-1024x20\""));
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let thumb = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (10, 20));
+        assert!(thumbs.lock().unwrap().contains(etag.to_str().unwrap()));
+
+        let response = get("/thumb/0?w=4096&h=20", Some(etag.clone())).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // A new file at the same path gets a new tag and a fresh render.
+        image::RgbImage::new(200, 100).save(&image_path).unwrap();
+        let response = get("/thumb/0?w=4096&h=20", Some(etag.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let thumb = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (40, 20));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn thumb_of_unreadable_image_is_a_placeholder() {
+        let response = thumb_handler(
+            State(make_state()),
+            Path(0),
+            Query(ThumbParams {
+                w: Some(64),
+                h: None,
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            "image/gif"
+        );
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&bytes[..], PLACEHOLDER_GIF);
+    }
+
+    #[tokio::test]
+    async fn grid_view_is_default_and_omitted_from_links() {
+        let params = IndexParams {
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains("class=\"grid\""));
+        assert!(!body.contains("view=list"));
+    }
+
+    #[tokio::test]
+    async fn index_exposes_keyboard_navigation_targets() {
+        let state = make_state();
+        {
+            let mut library = state.library.write().unwrap();
+            for name in ["b", "c"] {
+                let mut item = library.index.items[0].clone();
+                item.image_path = format!("/tmp/{name}.jpg").into();
+                library.index.push(item);
+            }
+        }
+        let params = IndexParams {
+            randomize: Some("0".to_string()),
+            limit: Some(1),
+            page: Some(2),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(state.clone()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains(
+            "id=\"item-1\" data-detail-href=\"/items/1?randomize=0&amp;limit=1&amp;page=2&amp;from=1\""
+        ));
+        assert!(body.contains("data-prev-page-href=\"/?randomize=0&amp;limit=1&amp;page=1\""));
+        assert!(body.contains("data-next-page-href=\"/?randomize=0&amp;limit=1&amp;page=3\""));
+        assert!(body.contains("<script src=\"/static/grid-nav.js\" defer></script>"));
+
+        let response = router(state, None)
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri("/static/grid-nav.js")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            "text/javascript; charset=utf-8"
+        );
+        assert!(body_text(response).await.contains("data-detail-href"));
+    }
+
+    #[tokio::test]
+    async fn tag_links_filter_by_exact_tag() {
+        let params = IndexParams {
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(
+            body.contains("href=\"/?exact_tag=cloud&amp;randomize=0&amp;limit=120&amp;page=1\"")
+        );
+
+        for (tag, shown) in [("cloud", true), ("clou", false)] {
+            let params = IndexParams {
+                exact_tag: Some(tag.to_string()),
+                randomize: Some("0".to_string()),
+                ..IndexParams::default()
+            };
+            let body = body_text(
+                index_handler(State(make_state()), Query(params))
+                    .await
+                    .into_response(),
+            )
+            .await;
+            assert_eq!(body.contains("id=\"item-0\""), shown, "{tag}");
+            assert!(body.contains(&format!("name=\"exact_tag\" value=\"{tag}\"")));
+        }
+    }
+
+    #[tokio::test]
+    async fn api_tags_completes_prefixes() {
+        let params = TagParams {
+            q: Some("CL".to_string()),
+            limit: None,
+        };
+        let axum::Json(tags) = api_tags_handler(State(make_state()), Query(params)).await;
+        assert_eq!(
+            serde_json::to_value(tags).unwrap(),
+            json!([{ "tag": "cloud", "count": 1 }])
+        );
+    }
+
+    #[tokio::test]
+    async fn api_tags_route_backs_the_search_box() {
+        let state = make_state();
+        {
+            let mut library = state.library.write().unwrap();
+            library.index.push(ImageItem::new(
+                "/tmp/b.jpg".into(),
+                "/tmp/b.jpg.json".into(),
+                "/tmp/b.jpg.booru.json".into(),
+                json!({ "tags": ["flower", "flag", "sky"] }),
+                BooruEdits::default(),
+            ));
+        }
+        let request = |uri: &str| {
+            axum::http::Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+        let app = router(state, None);
+
+        let response = app
+            .clone()
+            .oneshot(request("/api/tags?q=fl"))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(
+            body,
+            json!([{ "tag": "flag", "count": 1 }, { "tag": "flower", "count": 1 }])
+        );
+
+        let response = app.oneshot(request("/")).await.unwrap();
+        let body = body_text(response).await;
+        assert!(body.contains("list=\"tag-completions\""));
+        assert!(body.contains("/api/tags?limit=10&q="));
+    }
+
+    #[tokio::test]
+    async fn negated_query_term_hides_matching_items() {
+        let params = IndexParams {
+            q: Some("-cloud".to_string()),
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(!body.contains("/items/0?"));
+    }
+
+    #[tokio::test]
+    async fn and_mode_requires_every_term_and_is_kept_in_links() {
+        let params = IndexParams {
+            q: Some("alice sea".to_string()),
+            mode: Some("and".to_string()),
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(!body.contains("/items/0?"));
+
+        let params = IndexParams {
+            q: Some("alice cloud".to_string()),
+            mode: Some("and".to_string()),
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains("/items/0?q=alice%20cloud&amp;mode=and&amp;randomize=0"));
+    }
+
+    #[tokio::test]
+    async fn date_sort_disables_shuffle_and_is_kept_in_links() {
+        let params = IndexParams {
+            sort: Some("date_desc".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains("/items/0?randomize=0&amp;sort=date_desc&amp;limit=120&amp;page=1"));
+    }
+
+    #[tokio::test]
+    async fn date_range_filters_undated_items_and_ignores_bad_dates() {
+        let params = IndexParams {
+            after: Some("2024-01-01".to_string()),
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains("No images matched current filters."));
+        assert!(body.contains(r#"value="2024-01-01""#));
+
+        let params = IndexParams {
+            before: Some("someday".to_string()),
+            randomize: Some("0".to_string()),
+            ..IndexParams::default()
+        };
+        let body = body_text(
+            index_handler(State(make_state()), Query(params))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert!(body.contains("/items/0?randomize=0&amp;limit=120&amp;page=1"));
+    }
+
+    #[tokio::test]
+    async fn password_protects_every_route() {
+        use axum::http::StatusCode;
+        use base64::prelude::{Engine as _, BASE64_STANDARD};
+
+        let request = |uri: &str, credentials: Option<&str>| {
+            let mut request = axum::http::Request::builder().uri(uri);
+            if let Some(credentials) = credentials {
+                request = request.header(
+                    axum::http::header::AUTHORIZATION,
+                    format!("Basic {}", BASE64_STANDARD.encode(credentials)),
+                );
+            }
+            request.body(axum::body::Body::empty()).unwrap()
+        };
+        let app = router(
+            make_state(),
+            Some(BasicAuth {
+                user: None,
+                password: "hunter2".to_string(),
+            }),
+        );
+
+        for uri in ["/", "/api/items/0", "/media/0"] {
+            let response = app.clone().oneshot(request(uri, None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{uri}");
+            assert!(response
+                .headers()
+                .contains_key(axum::http::header::WWW_AUTHENTICATE));
+        }
+        let response = app
+            .clone()
+            .oneshot(request("/api/items/0", Some("me:wrong")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(request("/api/items/0", Some("anyone:hunter2")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let open = router(make_state(), None);
+        let response = open.oneshot(request("/api/items/0", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn opensearch_descriptor_uses_request_host() {
+        use axum::http::header;
+
+        let request = axum::http::Request::builder()
+            .uri("/opensearch.xml")
+            .header(header::HOST, "booru.example:8443")
+            .header("x-forwarded-proto", "https")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router(make_state(), None).oneshot(request).await.unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/opensearchdescription+xml"
+        );
+        let body = body_text(response).await;
+        assert!(body.contains("template=\"https://booru.example:8443/?q={searchTerms}\""));
+        assert!(body.contains("<Image width=\"16\" height=\"16\" type=\"image/svg+xml\">data:"));
+    }
+
+    #[test]
+    fn placeholder_averages_colour_and_encodes_a_blurhash() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lightbooru-web-placeholder-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("teal.png");
+        image::RgbImage::from_pixel(64, 48, image::Rgb([0, 128, 128]))
+            .save(&path)
+            .unwrap();
+
+        let placeholder = compute_placeholder(&path).unwrap();
+        assert_eq!(placeholder.color, [0, 128, 128]);
+        // One size flag, one AC scale, four DC and two per AC component.
+        assert_eq!(placeholder.blurhash.len(), 4 + 2 * 4 * 3);
+        assert!(compute_placeholder(&dir.join("missing.png")).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn grid_cards_carry_known_placeholders() {
+        let state = make_state();
+        state.placeholders.write().unwrap().insert(
+            PathBuf::from("/tmp/a.jpg"),
+            Placeholder {
+                color: [10, 20, 30],
+                blurhash: "LEHV6nWB2yk8pyo0adR*.7kCMdnj".to_string(),
+            },
+        );
+        let request = axum::http::Request::builder()
+            .uri("/")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let body = body_text(router(state, None).oneshot(request).await.unwrap()).await;
+        assert!(body.contains(r#"style="background-color: #0a141e""#));
+        assert!(body.contains(r#"data-blurhash="LEHV6nWB2yk8pyo0adR*.7kCMdnj""#));
+    }
+
+    #[tokio::test]
+    async fn health_and_metrics_are_opt_in_and_count_requests() {
+        use axum::http::{header, StatusCode};
+
+        let get = |app: axum::Router, uri: &'static str| async move {
+            let request = axum::http::Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            app.oneshot(request).await.unwrap()
+        };
+
+        let plain = router(make_state(), None);
+        assert_eq!(
+            get(plain.clone(), "/health").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get(plain, "/metrics").await.status(), StatusCode::NOT_FOUND);
+
+        let state = AppState {
+            metrics: Some(Arc::new(Metrics::with_scan_duration(
+                std::time::Duration::from_millis(1500),
+            ))),
+            ..make_state()
+        };
+        let app = router(state, None);
+        let health = body_text(get(app.clone(), "/health").await).await;
+        assert_eq!(health, r#"{"status":"ok","items":1}"#);
+        get(app.clone(), "/api/items/0").await;
+        get(app.clone(), "/api/items/7").await;
+
+        let response = get(app, "/metrics").await;
+        assert!(response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain; version=0.0.4"));
+        let body = body_text(response).await;
+        for line in [
+            "lightbooru_items_total 1",
+            "lightbooru_library_scan_duration_seconds 1.5",
+            r#"lightbooru_requests_total{route="/health",status="200"} 1"#,
+            r#"lightbooru_requests_total{route="/api/items/:id",status="200"} 1"#,
+            r#"lightbooru_requests_total{route="/api/items/:id",status="404"} 1"#,
+        ] {
+            assert!(body.lines().any(|l| l == line), "missing {line} in {body}");
+        }
+    }
+
+    #[tokio::test]
+    async fn auth_user_must_match_when_configured() {
+        use axum::http::StatusCode;
+        use base64::prelude::{Engine as _, BASE64_STANDARD};
+
+        let app = router(
+            make_state(),
+            Some(BasicAuth {
+                user: Some("admin".to_string()),
+                password: "hunter2".to_string(),
+            }),
+        );
+        let status = |uri: &'static str, credentials: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut request = axum::http::Request::builder().uri(uri);
+                if let Some(credentials) = credentials {
+                    request = request.header(
+                        axum::http::header::AUTHORIZATION,
+                        format!("Basic {}", BASE64_STANDARD.encode(credentials)),
+                    );
+                }
+                let request = request.body(axum::body::Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(status("/", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status("/", Some("anyone:hunter2")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status("/", Some("admin:hunter2")).await, StatusCode::OK);
+        // Exempt, so it reaches the router and misses there.
+        assert_eq!(status("/favicon.ico", None).await, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,83 @@
+// Keyboard navigation for the index page: j/k and the arrow keys move a
+// highlight through the results, Enter opens the highlighted item. Moving
+// past either end follows the pager.
+(function () {
+  const items = Array.from(document.querySelectorAll("[data-detail-href]"));
+  if (items.length === 0) return;
+  const results = items[0].parentElement;
+  let current = items.findIndex(function (item) {
+    return "#" + item.id === window.location.hash;
+  });
+
+  function select(index) {
+    if (current >= 0) items[current].classList.remove("nav-current");
+    current = index;
+    items[current].classList.add("nav-current");
+    items[current].scrollIntoView({ block: "nearest" });
+  }
+
+  // Items in the first row; 1 in the list view.
+  function columns() {
+    const top = items[0].offsetTop;
+    let count = 0;
+    while (count < items.length && items[count].offsetTop === top) count++;
+    return Math.max(1, count);
+  }
+
+  function isTyping(target) {
+    return target instanceof HTMLElement &&
+      (target.isContentEditable || /^(INPUT|SELECT|TEXTAREA|BUTTON)$/.test(target.tagName));
+  }
+
+  if (current >= 0) select(current);
+
+  document.addEventListener("keydown", function (event) {
+    if (event.altKey || event.ctrlKey || event.metaKey || isTyping(event.target)) return;
+    let step;
+    switch (event.key) {
+      case "j":
+      case "ArrowRight":
+        step = 1;
+        break;
+      case "k":
+      case "ArrowLeft":
+        step = -1;
+        break;
+      case "ArrowDown":
+        step = columns();
+        break;
+      case "ArrowUp":
+        step = -columns();
+        break;
+      case "Enter":
+        // A focused link opens itself.
+        if (current < 0 || event.target instanceof HTMLAnchorElement) return;
+        event.preventDefault();
+        window.location.href = items[current].dataset.detailHref;
+        return;
+      default:
+        return;
+    }
+    event.preventDefault();
+    if (current < 0) {
+      select(0);
+      return;
+    }
+    const next = current + step;
+    if (next >= items.length) {
+      if (step === 1 && results.dataset.nextPageHref) {
+        window.location.href = results.dataset.nextPageHref;
+      } else {
+        select(items.length - 1);
+      }
+    } else if (next < 0) {
+      if (step === -1 && results.dataset.prevPageHref) {
+        window.location.href = results.dataset.prevPageHref;
+      } else {
+        select(0);
+      }
+    } else {
+      select(next);
+    }
+  });
+})();
@@ -1,33 +1,60 @@
 use std::collections::HashSet;
 use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::io::IsTerminal;
+use std::io::{IsTerminal, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Context, Result};
 use booru_core::{
-    alias_path_for_root, apply_update_to_image, compute_hashes_with_cache, group_duplicates,
-    load_alias_groups_from_root, merge_alias_terms, metadata_path_for_image,
-    normalize_search_terms, remove_alias_terms, resolve_image_path, save_alias_groups_to_root,
-    BooruConfig, EditUpdate, FuzzyHashAlgorithm, HashCache, Library, ProgressObserver, SearchQuery,
+    alias_groups_from_map, alias_path_for_root, apply_update_to_image,
+    apply_update_to_image_dry_run, apply_update_to_image_with_backup, author_frequency,
+    booru_path_for_image, canonicalize_tags, check_integrity, compute_hashes_with_cache,
+    compute_tag_cooccurrence, find_orphan_metadata, find_similar_to, group_duplicates,
+    import_alias_groups, load_alias_groups_as_written, load_alias_groups_from_path,
+    load_alias_groups_from_root, load_alias_map_from_roots, merge_alias_terms,
+    metadata_path_for_image, normalize_search_term, normalize_search_terms, parse_date,
+    related_tags, remove_alias_terms, rename_alias_term, resolve_image_path,
+    save_alias_groups_to_root, scan_orphans, validate_library, AliasGroups, BooruConfig,
+    BooruEdits, DuplicateGroup, DuplicateReport, DuplicateWarning, EditUpdate, FuzzyHashAlgorithm,
+    HashCache, HashComputation, ImageItem, IntegrityCache, Library, MediaKind, ProgressObserver,
+    Rating, SearchMode, SearchQuery, SearchSort, HASH_SIZES,
 };
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, Local};
 use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::engine::{
     ArgValueCompleter, CompletionCandidate, PathCompleter, ValueCompleter,
 };
 use clap_complete::{generate, CompleteEnv, Shell};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Select;
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
 
 const COMPLETE_ENV_VAR: &str = "BOORUCTL_COMPLETE";
 
 #[derive(Parser)]
 #[command(name = "booructl", version, about = "CLI tools for LightBooru")]
 struct Cli {
-    /// Base directory for gallery-dl downloads (can be repeated)
+    /// Base directory for gallery-dl downloads (can be repeated; replaces
+    /// the roots from booru.toml)
     #[arg(long, short)]
     base: Vec<PathBuf>,
 
+    /// Skip images whose path under a root matches this glob (can be
+    /// repeated; adds to `exclude_globs` from booru.toml)
+    #[arg(long = "exclude")]
+    exclude: Vec<String>,
+
+    /// Skip this file or directory while scanning (can be repeated; adds to
+    /// `exclude_paths` from booru.toml)
+    #[arg(long = "exclude-path")]
+    exclude_path: Vec<PathBuf>,
+
+    /// Descend at most this many levels below each root (0: unlimited)
+    #[arg(long)]
+    max_depth: Option<usize>,
+
     /// Suppress scan warnings
     #[arg(long)]
     quiet: bool,
@@ -61,7 +88,40 @@ enum Commands {
         path: PathBuf,
         #[arg(long = "set-tag")]
         set_tags: Vec<String>,
-        #[arg(long = "add-tag")]
+        #[arg(long = "add-tag", add = ArgValueCompleter::new(complete_tag))]
+        add_tags: Vec<String>,
+        #[arg(long = "remove-tag")]
+        remove_tags: Vec<String>,
+        #[arg(long)]
+        clear_tags: bool,
+        #[arg(long)]
+        notes: Option<String>,
+        /// Remove the notes field instead of setting it
+        #[arg(long, conflicts_with = "notes")]
+        clear_notes: bool,
+        /// Override the content rating
+        #[arg(long, value_enum)]
+        rating: Option<RatingArg>,
+        /// Store a custom field as `key=value`; the value is parsed as JSON
+        /// if it can be, else kept as a string
+        #[arg(long = "set-field", value_parser = parse_field_arg)]
+        set_fields: Vec<(String, serde_json::Value)>,
+        /// Drop a custom field
+        #[arg(long = "remove-field")]
+        remove_fields: Vec<String>,
+        /// Keep the previous edits as `<image>.booru.json.bak`
+        #[arg(long)]
+        backup: bool,
+        /// Print what would change instead of writing the edits
+        #[arg(long, conflicts_with = "backup")]
+        dry_run: bool,
+    },
+    /// Apply the same edit to every image matching a search
+    BatchEdit {
+        /// Search terms, as for `search` (e.g. "sky -cloud")
+        #[arg(long)]
+        query: String,
+        #[arg(long = "add-tag", add = ArgValueCompleter::new(complete_tag))]
         add_tags: Vec<String>,
         #[arg(long = "remove-tag")]
         remove_tags: Vec<String>,
@@ -69,12 +129,118 @@ enum Commands {
         clear_tags: bool,
         #[arg(long)]
         notes: Option<String>,
+        /// Override the sensitive flag (true or false)
+        #[arg(long)]
+        sensitive: Option<bool>,
+        /// Print what would change instead of writing the edits
+        #[arg(long)]
+        dry_run: bool,
+        /// Edit at most this many matching images
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Apply without asking first
+        #[arg(long)]
+        confirm: bool,
     },
-    /// Search images by substring in tags/author/detail
+    /// Search images by substring in tags/author/detail/notes
     Search {
+        /// Prefix a term with `-` to exclude it (pass such terms after `--`);
+        /// `flower*` and `*camp` match the start or end of whole tags
         terms: Vec<String>,
         #[arg(long, default_value_t = 100)]
         limit: usize,
+        /// Skip this many sorted results before printing
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Print only the number of matches
+        #[arg(long)]
+        count: bool,
+        /// End each path with a NUL byte instead of a newline, for `xargs -0`
+        #[arg(long, conflicts_with = "count")]
+        print0: bool,
+        /// Match any term (or) or require every term (and)
+        #[arg(long, value_enum, default_value = "or")]
+        mode: MatchMode,
+        /// Result order
+        #[arg(long, value_enum, default_value = "path")]
+        sort: SortOrder,
+        /// Seed for `--sort random` (defaults to the current time)
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Only images dated at or after this (unix seconds or a date)
+        #[arg(long, value_parser = parse_date_arg)]
+        after: Option<i64>,
+        /// Only images dated before this (unix seconds or a date)
+        #[arg(long, value_parser = parse_date_arg)]
+        before: Option<i64>,
+        /// Only images at least this many pixels wide
+        #[arg(long)]
+        min_width: Option<u32>,
+        /// Only images at least this many pixels high
+        #[arg(long)]
+        min_height: Option<u32>,
+        /// Only image files of at least this many KiB
+        #[arg(long)]
+        min_size_kb: Option<u64>,
+        /// Don't match plain terms against notes and custom edit fields
+        #[arg(long)]
+        skip_edit_fields: bool,
+    },
+    /// List tags by how many images use them
+    #[command(visible_alias = "tag-frequency")]
+    Tags {
+        /// Show at most N tags; 0 shows them all
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        /// Hide tags used fewer than N times
+        #[arg(long, default_value_t = 1)]
+        min_count: usize,
+        /// Only list tags starting with this prefix
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Count tags on sensitive images too
+        #[arg(long)]
+        include_sensitive: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value = "table")]
+        format: CountFormat,
+        /// Shorthand for `--format json`
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+    },
+    /// Suggest tags that often appear on the same images as a tag
+    TagSuggest {
+        #[arg(add = ArgValueCompleter::new(complete_tag))]
+        tag: String,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Ignore tags used on fewer than N images
+        #[arg(long, default_value_t = 2)]
+        min_support: usize,
+        /// Count tags on sensitive images too
+        #[arg(long)]
+        include_sensitive: bool,
+    },
+    /// List authors by how many images they have
+    #[command(visible_alias = "author-list")]
+    Authors {
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Hide authors with fewer than N images
+        #[arg(long, default_value_t = 1)]
+        min_count: usize,
+        /// Output format
+        #[arg(long, value_enum, default_value = "table")]
+        format: CountFormat,
+    },
+    /// Summarize the library
+    Stats {
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: StatsFormat,
+        /// Shorthand for `--format json`
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
     },
     /// Show or manage alias groups in alias.json
     Alias {
@@ -83,10 +249,103 @@ enum Commands {
     },
     /// Find perceptual-hash duplicates
     Dupes {
+        #[arg(long, value_enum, default_value = "dhash")]
+        algo: HashAlgo,
+        /// Cells per side the image is reduced to before hashing; the hash
+        /// has size² bits, so scale --threshold along with it
+        #[arg(long, default_value_t = FuzzyHashAlgorithm::DEFAULT_SIZE, value_parser = parse_hash_size)]
+        hash_size: u8,
+        #[arg(long, default_value_t = 8)]
+        threshold: u32,
+        /// Disable sqlite hash cache
+        #[arg(long)]
+        no_cache: bool,
+        /// Override cache path
+        #[arg(long)]
+        cache: Option<PathBuf>,
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: DupesFormat,
+        /// Shorthand for `--format json`
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+        /// Print every member of every group, each ending with a NUL byte,
+        /// for `xargs -0`. Group boundaries are not kept
+        #[arg(long, conflicts_with_all = ["json", "format", "interactive"])]
+        print0: bool,
+        /// For each group, pick one image to keep and drop the edits of the
+        /// others. Only prints what it would remove unless --confirm is given
+        #[arg(long, conflicts_with_all = ["json", "format"])]
+        interactive: bool,
+        /// With --interactive, also remove the other images and their
+        /// metadata files
+        #[arg(long, requires = "interactive")]
+        delete_images: bool,
+        /// With --interactive, delete files permanently instead of trashing them
+        #[arg(long, requires = "interactive")]
+        permanent: bool,
+        /// With --interactive, actually remove files
+        #[arg(long, requires = "interactive")]
+        confirm: bool,
+        /// Drop cache rows for images no longer found under the roots. Rows
+        /// of other libraries sharing the cache file are dropped too
+        #[arg(long, conflicts_with = "no_cache")]
+        prune_cache: bool,
+        /// Like --prune-cache, but before hashing, and also compact the
+        /// cache file
+        #[arg(long, conflicts_with_all = ["no_cache", "prune_cache"])]
+        vacuum_cache: bool,
+        /// Empty the cache before hashing, so every image is hashed again
+        #[arg(long, conflicts_with_all = ["no_cache", "prune_cache", "vacuum_cache"])]
+        clear_cache: bool,
+    },
+    /// List `.json`/`.booru.json` files whose image no longer exists
+    Orphans {
+        /// Only list `.booru.json` files, which hold edits made by hand
+        #[arg(long)]
+        edits: bool,
+        /// Remove the orphaned files (asks first unless --confirm is given)
+        #[arg(long)]
+        delete: bool,
+        /// With --delete, remove without asking
+        #[arg(long, requires = "delete")]
+        confirm: bool,
+        #[arg(long, value_enum, default_value = "text")]
+        format: OrphanFormat,
+    },
+    /// Check metadata, edits and alias files for problems
+    Validate {
+        /// Exit with an error if any problem is found
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Verify image contents against SHA-256 digests from earlier runs
+    CheckIntegrity {
+        /// Record the current digests of changed files and forget missing ones
+        #[arg(long)]
+        repair: bool,
+        /// Hash every file, even those whose size and mtime match the
+        /// recorded ones
+        #[arg(long)]
+        full: bool,
+        /// Override cache path
+        #[arg(long)]
+        cache: Option<PathBuf>,
+    },
+    /// Find images perceptually similar to one image
+    Similar {
+        #[arg(
+            value_hint = clap::ValueHint::AnyPath,
+            add = ArgValueCompleter::new(complete_image_path_with_base)
+        )]
+        path: PathBuf,
         #[arg(long, value_enum, default_value = "dhash")]
         algo: HashAlgo,
         #[arg(long, default_value_t = 8)]
         threshold: u32,
+        /// Show at most this many images
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
         /// Disable sqlite hash cache
         #[arg(long)]
         no_cache: bool,
@@ -109,9 +368,33 @@ enum AliasCommands {
     /// Show alias groups
     List,
     /// Add terms into one alias group (and merge overlapping groups)
-    Add { terms: Vec<String> },
+    Add {
+        terms: Vec<String>,
+        /// Merge even when the terms already belong to different groups
+        #[arg(long)]
+        force: bool,
+    },
     /// Remove terms from all alias groups
     Remove { terms: Vec<String> },
+    /// Rename a term in every alias group it belongs to
+    Rename { from: String, to: String },
+    /// Print the merged alias groups of all roots in alias.json format
+    Export,
+    /// Merge the alias groups of an alias.json-style file into alias.json
+    Import {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: PathBuf,
+        /// Merge even when a group joins different existing groups
+        #[arg(long)]
+        force: bool,
+    },
+    /// Rewrite stored tags so alias members become their group's first term,
+    /// as written in alias.json
+    Canonicalize {
+        /// Print what would change instead of writing the edits
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Clone, Copy, Debug, ValueEnum)]
@@ -121,17 +404,146 @@ enum HashAlgo {
     Phash,
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum RatingArg {
+    General,
+    Sensitive,
+    Questionable,
+    Explicit,
+}
+
+impl From<RatingArg> for Rating {
+    fn from(rating: RatingArg) -> Self {
+        match rating {
+            RatingArg::General => Rating::General,
+            RatingArg::Sensitive => Rating::Sensitive,
+            RatingArg::Questionable => Rating::Questionable,
+            RatingArg::Explicit => Rating::Explicit,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum CountFormat {
+    Table,
+    /// An array of `{"tag": ..., "count": ...}` (or `"author"`) objects
+    Json,
+    /// `tag,count` (or `author,count`) rows after a header line
+    Csv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum DupesFormat {
+    /// Group members one per line
+    Text,
+    /// `{"groups": [...], "warnings": [...]}`, each group with its paths,
+    /// widest distance and per-member details
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum StatsFormat {
+    /// One `name: value` line per figure
+    Text,
+    /// The library statistics as one object
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OrphanFormat {
+    /// One path per line
+    Text,
+    /// An array of paths
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum MatchMode {
+    Or,
+    And,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SortOrder {
+    /// Full image path
+    Path,
+    /// File name, then path
+    Name,
+    /// Newest first
+    DateDesc,
+    /// Oldest first
+    DateAsc,
+    AuthorAsc,
+    AuthorDesc,
+    FileSizeAsc,
+    FileSizeDesc,
+    /// Most tags first
+    TagCountDesc,
+    /// Most pixels first
+    Resolution,
+    Random,
+}
+
+impl SortOrder {
+    fn to_search_sort(self, seed: Option<u64>) -> SearchSort {
+        match self {
+            Self::Path => SearchSort::IndexOrder,
+            Self::Name => SearchSort::FileNameAsc,
+            Self::DateDesc => SearchSort::DateDesc,
+            Self::DateAsc => SearchSort::DateAsc,
+            Self::AuthorAsc => SearchSort::AuthorAsc,
+            Self::AuthorDesc => SearchSort::AuthorDesc,
+            Self::FileSizeAsc => SearchSort::FileSizeAsc,
+            Self::FileSizeDesc => SearchSort::FileSizeDesc,
+            Self::TagCountDesc => SearchSort::TagCountDesc,
+            Self::Resolution => SearchSort::ResolutionDesc,
+            Self::Random => SearchSort::Random(seed.unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_nanos() as u64)
+                    .unwrap_or(0)
+            })),
+        }
+    }
+}
+
+impl From<MatchMode> for SearchMode {
+    fn from(mode: MatchMode) -> Self {
+        match mode {
+            MatchMode::Or => SearchMode::Any,
+            MatchMode::And => SearchMode::All,
+        }
+    }
+}
+
+impl From<HashAlgo> for FuzzyHashAlgorithm {
+    fn from(algo: HashAlgo) -> Self {
+        match algo {
+            HashAlgo::Ahash => FuzzyHashAlgorithm::AHASH,
+            HashAlgo::Dhash => FuzzyHashAlgorithm::DHASH,
+            HashAlgo::Phash => FuzzyHashAlgorithm::PHASH,
+        }
+    }
+}
+
+fn parse_hash_size(raw: &str) -> Result<u8, String> {
+    raw.parse::<u8>()
+        .ok()
+        .filter(|size| HASH_SIZES.contains(size))
+        .ok_or_else(|| {
+            let sizes = HASH_SIZES.map(|size| size.to_string()).join(", ");
+            format!("expected one of {sizes}")
+        })
+}
+
 fn main() -> Result<()> {
     CompleteEnv::with_factory(|| Cli::command())
         .var(COMPLETE_ENV_VAR)
         .complete();
 
     let cli = Cli::parse();
-    let config = if cli.base.is_empty() {
-        BooruConfig::default()
-    } else {
-        BooruConfig::with_roots(cli.base.clone())
-    };
+    let mut config = BooruConfig::resolve(cli.base.clone())?;
+    config.apply_scan_overrides(cli.exclude.clone(), cli.exclude_path.clone(), cli.max_depth);
 
     match cli.command {
         Commands::Info {
@@ -146,23 +558,210 @@ fn main() -> Result<()> {
             remove_tags,
             clear_tags,
             notes,
+            clear_notes,
+            rating,
+            set_fields,
+            remove_fields,
+            backup,
+            dry_run,
         } => edit_command(
             &config,
             &path,
-            set_tags,
+            EditUpdate {
+                set_tags: normalize_tag_args(set_tags),
+                add_tags: flatten_tag_args(add_tags),
+                remove_tags: flatten_tag_args(remove_tags),
+                clear_tags,
+                notes,
+                clear_notes,
+                sensitive: None,
+                rating: rating.map(Rating::from),
+                set_fields,
+                remove_fields,
+            },
+            backup,
+            dry_run,
+        ),
+        Commands::BatchEdit {
+            query,
             add_tags,
             remove_tags,
             clear_tags,
             notes,
+            sensitive,
+            dry_run,
+            limit,
+            confirm,
+        } => batch_edit_command(
+            &config,
+            &query,
+            EditUpdate {
+                add_tags: flatten_tag_args(add_tags),
+                remove_tags: flatten_tag_args(remove_tags),
+                clear_tags,
+                notes,
+                sensitive,
+                ..EditUpdate::default()
+            },
+            limit,
+            dry_run,
+            confirm,
+            cli.quiet,
+        ),
+        Commands::Search {
+            terms,
+            limit,
+            offset,
+            count,
+            print0,
+            mode,
+            sort,
+            seed,
+            after,
+            before,
+            min_width,
+            min_height,
+            min_size_kb,
+            skip_edit_fields,
+        } => search_command(
+            &config,
+            terms,
+            SearchOutput {
+                offset,
+                limit,
+                count_only: count,
+                print0,
+            },
+            mode,
+            sort.to_search_sort(seed),
+            SearchFilters {
+                after,
+                before,
+                min_width,
+                min_height,
+                min_size: min_size_kb.map(|kb| kb.saturating_mul(1024)),
+                edit_fields: !skip_edit_fields,
+            },
+            cli.quiet,
+        ),
+        Commands::Tags {
+            limit,
+            min_count,
+            prefix,
+            include_sensitive,
+            format,
+            json,
+        } => tags_command(
+            &config,
+            TagFilter {
+                limit: (limit > 0).then_some(limit),
+                min_count,
+                prefix,
+                include_sensitive,
+            },
+            if json { CountFormat::Json } else { format },
+            cli.quiet,
+        ),
+        Commands::TagSuggest {
+            tag,
+            limit,
+            min_support,
+            include_sensitive,
+        } => tag_suggest_command(
+            &config,
+            &tag,
+            limit,
+            min_support,
+            include_sensitive,
+            cli.quiet,
+        ),
+        Commands::Authors {
+            limit,
+            min_count,
+            format,
+        } => authors_command(&config, limit, min_count, format, cli.quiet),
+        Commands::Stats { format, json } => stats_command(
+            &config,
+            if json { StatsFormat::Json } else { format },
+            cli.quiet,
         ),
-        Commands::Search { terms, limit } => search_command(&config, terms, limit, cli.quiet),
         Commands::Alias { command } => alias_command(&config, command, cli.quiet),
         Commands::Dupes {
+            algo,
+            hash_size,
+            threshold,
+            no_cache,
+            cache,
+            format,
+            json,
+            print0,
+            interactive,
+            delete_images,
+            permanent,
+            confirm,
+            prune_cache,
+            vacuum_cache,
+            clear_cache,
+        } => {
+            let cache = open_hash_cache(no_cache, cache, cli.quiet)?;
+            let cleanup = if clear_cache {
+                CacheCleanup::Clear
+            } else if vacuum_cache {
+                CacheCleanup::Vacuum
+            } else if prune_cache {
+                CacheCleanup::Prune
+            } else {
+                CacheCleanup::Keep
+            };
+            let output = if interactive {
+                DupesOutput::Interactive(InteractiveDupes {
+                    delete_images,
+                    permanent,
+                    confirm,
+                })
+            } else if json || format == DupesFormat::Json {
+                DupesOutput::Json
+            } else if print0 {
+                DupesOutput::Print0
+            } else {
+                DupesOutput::Text
+            };
+            let algo = FuzzyHashAlgorithm {
+                size: hash_size,
+                ..algo.into()
+            };
+            dupes_command(&config, algo, threshold, cache, cleanup, output, cli.quiet)
+        }
+        Commands::Orphans {
+            edits,
+            delete,
+            confirm,
+            format,
+        } => orphans_command(&config, edits, delete, confirm, format),
+        Commands::Validate { strict } => validate_command(&config, strict),
+        Commands::CheckIntegrity {
+            repair,
+            full,
+            cache,
+        } => {
+            let mut cache = match cache {
+                Some(path) => IntegrityCache::open(&path),
+                None => IntegrityCache::open_default(),
+            }
+            .context("failed to open cache")?;
+            check_integrity_command(&config, &mut cache, repair, full, cli.quiet)
+        }
+        Commands::Similar {
+            path,
             algo,
             threshold,
+            limit,
             no_cache,
             cache,
-        } => dupes_command(&config, algo, threshold, no_cache, cache, cli.quiet),
+        } => {
+            let cache = open_hash_cache(no_cache, cache, cli.quiet)?;
+            similar_command(&config, &path, algo, threshold, limit, cache, cli.quiet)
+        }
         Commands::Completion { shell, aot } => completion_command(shell, aot),
     }
 }
@@ -235,6 +834,29 @@ fn complete_image_path_with_base(current: &OsStr) -> Vec<CompletionCandidate> {
     }
 }
 
+/// Completes the last comma-separated tag with known tags, most used first.
+fn complete_tag(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    let (head, partial) = match current.rfind(',') {
+        Some(pos) => current.split_at(pos + 1),
+        None => ("", current),
+    };
+    let config = BooruConfig::with_roots(completion_roots_from_env());
+    let Ok(library) = Library::scan(config) else {
+        return Vec::new();
+    };
+    library
+        .tag_completions(partial.trim_start(), 50)
+        .into_iter()
+        .map(|(tag, count)| {
+            CompletionCandidate::new(format!("{head}{tag}"))
+                .help(Some(format!("{count} images").into()))
+        })
+        .collect()
+}
+
 fn has_explicit_path_prefix(current: &str) -> bool {
     current.starts_with('/')
         || current.starts_with("./")
@@ -306,11 +928,9 @@ fn completion_roots_from_env() -> Vec<PathBuf> {
         i += 1;
     }
 
-    if bases.is_empty() {
-        BooruConfig::default().roots
-    } else {
-        BooruConfig::with_roots(bases).roots
-    }
+    BooruConfig::resolve(bases)
+        .unwrap_or_else(|_| BooruConfig::default())
+        .roots
 }
 
 fn completion_words_from_env() -> Vec<OsString> {
@@ -414,6 +1034,10 @@ fn info_command(
         Some(detail) => println!("Detail: {detail}"),
         None => println!("Detail: (none)"),
     }
+    println!(
+        "Rating: {}",
+        item.merged_rating().map(Rating::as_str).unwrap_or("(none)")
+    );
     println!(
         "Sensitive (NSFW): {}",
         if item.merged_sensitive() { "yes" } else { "no" }
@@ -438,11 +1062,9 @@ fn info_command(
 fn edit_command(
     config: &BooruConfig,
     path: &Path,
-    set_tags: Vec<String>,
-    add_tags: Vec<String>,
-    remove_tags: Vec<String>,
-    clear_tags: bool,
-    notes: Option<String>,
+    update: EditUpdate,
+    backup: bool,
+    dry_run: bool,
 ) -> Result<()> {
     let image_path = resolve_image_path(path, &config.roots);
     if !image_path.exists() {
@@ -454,38 +1076,223 @@ fn edit_command(
         return Err(anyhow!("metadata not found: {}", meta_path.display()));
     }
 
-    let update = EditUpdate {
-        set_tags: normalize_tag_args(set_tags),
-        add_tags: flatten_tag_args(add_tags),
-        remove_tags: flatten_tag_args(remove_tags),
-        clear_tags,
-        notes,
-        sensitive: None,
-    };
+    if dry_run {
+        let original = fs::read_to_string(&meta_path)
+            .with_context(|| format!("failed to read {}", meta_path.display()))?;
+        let original = serde_json::from_str(&original)
+            .with_context(|| format!("failed to parse {}", meta_path.display()))?;
+        let booru_path = booru_path_for_image(&image_path);
+        let edits = BooruEdits::load(&booru_path)?.unwrap_or_default();
+        let current = ImageItem::new(image_path.clone(), meta_path, booru_path, original, edits);
+        let mut updated = current.clone();
+        updated.edits = apply_update_to_image_dry_run(&image_path, update)?;
+        let diff = edit_diff(&current, &updated);
+        if diff.is_empty() {
+            println!("No changes.");
+        }
+        for line in diff {
+            println!("{line}");
+        }
+        return Ok(());
+    }
 
-    let edits =
-        apply_update_to_image(&image_path, update).context("failed to write booru edits")?;
+    let edits = if backup {
+        apply_update_to_image_with_backup(&image_path, update)
+    } else {
+        apply_update_to_image(&image_path, update)
+    }
+    .context("failed to write booru edits")?;
     println!("Updated: {}", image_path.display());
     println!("Booru edits: {}", serde_json::to_string_pretty(&edits)?);
     Ok(())
 }
 
+fn batch_edit_command(
+    config: &BooruConfig,
+    query: &str,
+    update: EditUpdate,
+    limit: Option<usize>,
+    dry_run: bool,
+    confirm: bool,
+    quiet: bool,
+) -> Result<()> {
+    update.validate()?;
+    let terms = query
+        .split_whitespace()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if terms.is_empty() {
+        return Err(anyhow!("no search terms provided"));
+    }
+    let library = scan_library(config, quiet)?;
+    let search = library.search(
+        SearchQuery::new(terms)
+            .with_aliases(true)
+            .with_sort(SearchSort::FileNameAsc),
+    );
+    if !quiet {
+        for warning in &search.alias_warnings {
+            eprintln!("warning: {}: {}", warning.path.display(), warning.message);
+        }
+    }
+    let matches = search
+        .indices
+        .iter()
+        .filter_map(|idx| library.index.items.get(*idx))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect::<Vec<_>>();
+    let changes = plan_batch_edit(&matches, &update);
+    if changes.is_empty() {
+        println!("No changes for {} matching images.", matches.len());
+        return Ok(());
+    }
+
+    if dry_run {
+        for (item, diff) in &changes {
+            println!("{}", item.image_path.display());
+            for line in diff {
+                println!("  {line}");
+            }
+        }
+        println!(
+            "{} of {} matching images would change.",
+            changes.len(),
+            matches.len()
+        );
+        return Ok(());
+    }
+    if !confirm {
+        print!("Apply edits to {} images? [y/N] ", changes.len());
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    for (item, _) in &changes {
+        apply_update_to_image(&item.image_path, update.clone()).with_context(|| {
+            format!(
+                "failed to write booru edits for {}",
+                item.image_path.display()
+            )
+        })?;
+        println!("{}", item.image_path.display());
+    }
+    println!(
+        "Changed {} of {} matching images.",
+        changes.len(),
+        matches.len()
+    );
+    Ok(())
+}
+
+/// The items `update` would actually change, with their [`edit_diff`].
+fn plan_batch_edit<'a>(
+    items: &[&'a ImageItem],
+    update: &EditUpdate,
+) -> Vec<(&'a ImageItem, Vec<String>)> {
+    items
+        .iter()
+        .filter_map(|item| {
+            let mut updated = (*item).clone();
+            updated.edits.apply_update(update.clone());
+            let diff = edit_diff(item, &updated);
+            (!diff.is_empty()).then_some((*item, diff))
+        })
+        .collect()
+}
+
+/// `-`/`+` lines for the tags, notes, sensitive flag and rating that differ
+/// between two versions of an item.
+fn edit_diff(before: &ImageItem, after: &ImageItem) -> Vec<String> {
+    let mut lines = Vec::new();
+    let before_tags = before.merged_tags();
+    let after_tags = after.merged_tags();
+    for tag in before_tags.iter().filter(|tag| !after_tags.contains(tag)) {
+        lines.push(format!("-tag: {tag}"));
+    }
+    for tag in after_tags.iter().filter(|tag| !before_tags.contains(tag)) {
+        lines.push(format!("+tag: {tag}"));
+    }
+
+    let mut push_change = |field: &str, before: Option<String>, after: Option<String>| {
+        if before != after {
+            lines.extend(before.map(|value| format!("-{field}: {value}")));
+            lines.extend(after.map(|value| format!("+{field}: {value}")));
+        }
+    };
+    push_change(
+        "notes",
+        before.edits.notes.clone(),
+        after.edits.notes.clone(),
+    );
+    push_change(
+        "sensitive",
+        Some(before.merged_sensitive().to_string()),
+        Some(after.merged_sensitive().to_string()),
+    );
+    push_change(
+        "rating",
+        before
+            .merged_rating()
+            .map(|rating| rating.as_str().to_string()),
+        after
+            .merged_rating()
+            .map(|rating| rating.as_str().to_string()),
+    );
+    let mut keys = before
+        .edits
+        .extra
+        .keys()
+        .chain(after.edits.extra.keys())
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        push_change(
+            key,
+            before.edits.extra.get(key).map(|value| value.to_string()),
+            after.edits.extra.get(key).map(|value| value.to_string()),
+        );
+    }
+    lines
+}
+
 fn search_command(
     config: &BooruConfig,
     terms: Vec<String>,
-    limit: usize,
+    output: SearchOutput,
+    mode: MatchMode,
+    sort: SearchSort,
+    filters: SearchFilters,
     quiet: bool,
 ) -> Result<()> {
     let library = scan_library(config, quiet)?;
-    let search = library.search(SearchQuery::new(terms).with_aliases(true));
+    let search = library.search(
+        SearchQuery::new(terms)
+            .with_aliases(true)
+            .with_mode(mode.into())
+            .with_sort(sort)
+            .with_date_after(filters.after)
+            .with_date_before(filters.before)
+            .with_min_dimensions(filters.min_width, filters.min_height)
+            .with_min_file_size(filters.min_size)
+            .with_edit_fields(filters.edit_fields),
+    );
 
-    if search.normalized_terms.is_empty() {
+    if search.normalized_terms.is_empty() && search.excluded_terms.is_empty() && !filters.is_set() {
         return Err(anyhow!("no search terms provided"));
     }
     if !quiet {
         for warning in search.alias_warnings {
             eprintln!("warning: {}: {}", warning.path.display(), warning.message);
         }
+        if let Some(warning) = search.sort_warning {
+            eprintln!("warning: {warning}");
+        }
     }
 
     let mut results = search
@@ -493,72 +1300,443 @@ fn search_command(
         .iter()
         .filter_map(|idx| library.index.items.get(*idx))
         .collect::<Vec<_>>();
-    results.sort_by_key(|item| item.image_path.clone());
-    for item in results.into_iter().take(limit) {
-        println!("{}", item.image_path.display());
+    if output.count_only {
+        println!("{}", results.len());
+        return Ok(());
+    }
+    if sort == SearchSort::IndexOrder {
+        results.sort_by_key(|item| item.image_path.clone());
+    }
+    let total = results.len();
+    let range = page_range(total, output.offset, output.limit);
+    write_paths(
+        &mut std::io::stdout().lock(),
+        results[range.clone()]
+            .iter()
+            .map(|item| item.image_path.as_path()),
+        output.print0,
+    )?;
+    if !quiet {
+        eprintln!("{}", page_summary(&range, total));
     }
     Ok(())
 }
 
-fn alias_command(config: &BooruConfig, command: AliasCommands, quiet: bool) -> Result<()> {
-    match command {
-        AliasCommands::List => alias_list_command(config, quiet),
-        AliasCommands::Add { terms } => alias_add_command(config, terms),
-        AliasCommands::Remove { terms } => alias_remove_command(config, terms),
+struct SearchOutput {
+    offset: usize,
+    limit: usize,
+    count_only: bool,
+    print0: bool,
+}
+
+/// Writes each path's raw bytes followed by a newline, or a NUL with
+/// `print0`, so non-UTF-8 names come out unchanged.
+fn write_paths<'a>(
+    out: &mut impl Write,
+    paths: impl IntoIterator<Item = &'a Path>,
+    print0: bool,
+) -> std::io::Result<()> {
+    let separator = if print0 { b'\0' } else { b'\n' };
+    for path in paths {
+        out.write_all(path.as_os_str().as_encoded_bytes())?;
+        out.write_all(&[separator])?;
     }
+    out.flush()
 }
 
-fn alias_list_command(config: &BooruConfig, quiet: bool) -> Result<()> {
-    let show_root = config.roots.len() > 1;
-    for (idx, root) in config.roots.iter().enumerate() {
-        if show_root {
-            if idx > 0 {
-                println!();
-            }
-            println!("Root: {}", root.display());
-        }
+/// The slice of `total` sorted results to print; empty once `offset` runs
+/// past the end.
+fn page_range(total: usize, offset: usize, limit: usize) -> Range<usize> {
+    let start = offset.min(total);
+    start..start.saturating_add(limit).min(total)
+}
 
-        match load_alias_groups_from_root(root) {
-            Ok(groups) => {
-                if groups.is_empty() {
-                    println!("(none)");
-                } else {
-                    for group in groups {
-                        println!("{}", group.join(" | "));
-                    }
-                }
-            }
-            Err(err) => {
-                let path = alias_path_for_root(root);
-                if !quiet {
-                    eprintln!("warning: {}: {}", path.display(), err);
-                }
-                println!("(invalid alias file)");
-            }
-        }
+/// `showing 100-199 of 4213`, with zero-based inclusive positions.
+fn page_summary(range: &Range<usize>, total: usize) -> String {
+    if range.is_empty() {
+        format!("showing 0 of {total}")
+    } else {
+        format!("showing {}-{} of {total}", range.start, range.end - 1)
     }
-    Ok(())
 }
 
-fn alias_add_command(config: &BooruConfig, terms: Vec<String>) -> Result<()> {
-    let root = alias_edit_root(config)?;
-    let terms = normalize_search_terms(terms);
-    if terms.len() < 2 {
-        return Err(anyhow!("alias add requires at least 2 non-empty terms"));
-    }
+struct SearchFilters {
+    after: Option<i64>,
+    before: Option<i64>,
+    min_width: Option<u32>,
+    min_height: Option<u32>,
+    /// Bytes.
+    min_size: Option<u64>,
+    edit_fields: bool,
+}
 
-    let path = alias_path_for_root(root);
-    let mut groups =
-        load_alias_groups_from_root(root).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
-    let changed = merge_alias_terms(&mut groups, terms);
-    if changed {
-        save_alias_groups_to_root(root, &groups)
-            .map_err(|err| anyhow!("{}: {}", path.display(), err))?;
-        println!("Updated {}", path.display());
-    } else {
-        println!("No changes.");
+impl SearchFilters {
+    fn is_set(&self) -> bool {
+        self.after.is_some()
+            || self.before.is_some()
+            || self.min_width.is_some()
+            || self.min_height.is_some()
+            || self.min_size.is_some()
     }
-    Ok(())
+}
+
+fn parse_field_arg(raw: &str) -> Result<(String, serde_json::Value), String> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value, got `{raw}`"))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("missing field name in `{raw}`"));
+    }
+    let value = serde_json::from_str(value)
+        .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+    Ok((key.to_string(), value))
+}
+
+fn parse_date_arg(raw: &str) -> Result<i64, String> {
+    parse_date(raw)
+        .map(|dt| dt.timestamp())
+        .ok_or_else(|| format!("unrecognized date: {raw}"))
+}
+
+struct TagFilter {
+    limit: Option<usize>,
+    min_count: usize,
+    prefix: Option<String>,
+    include_sensitive: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct TagCount {
+    tag: String,
+    count: usize,
+}
+
+fn tags_command(
+    config: &BooruConfig,
+    filter: TagFilter,
+    format: CountFormat,
+    quiet: bool,
+) -> Result<()> {
+    let library = scan_library(config, quiet)?;
+    let counts = filter_tag_counts(library.index.tag_counts(filter.include_sensitive), &filter);
+
+    match format {
+        CountFormat::Table => {
+            for entry in counts {
+                println!("{:>6}  {}", entry.count, entry.tag);
+            }
+        }
+        CountFormat::Json => println!("{}", serde_json::to_string_pretty(&counts)?),
+        CountFormat::Csv => {
+            println!("tag,count");
+            for entry in counts {
+                println!("{},{}", csv_field(&entry.tag), entry.count);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn tag_suggest_command(
+    config: &BooruConfig,
+    tag: &str,
+    limit: usize,
+    min_support: usize,
+    include_sensitive: bool,
+    quiet: bool,
+) -> Result<()> {
+    let library = scan_library(config, quiet)?;
+    let items = library
+        .index
+        .items
+        .iter()
+        .filter(|item| include_sensitive || !item.merged_sensitive())
+        .cloned()
+        .collect::<Vec<_>>();
+    let cooccurrence = compute_tag_cooccurrence(&items, min_support);
+    let related = related_tags(&cooccurrence, &[tag.to_string()], limit);
+    if related.is_empty() && !quiet {
+        eprintln!("no tags seen together with {tag}");
+    }
+    for (related, count) in related {
+        println!("{count:>6}  {related}");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct AuthorCount {
+    author: String,
+    count: usize,
+}
+
+fn authors_command(
+    config: &BooruConfig,
+    limit: Option<usize>,
+    min_count: usize,
+    format: CountFormat,
+    quiet: bool,
+) -> Result<()> {
+    let library = scan_library(config, quiet)?;
+    let counts = author_frequency(&library.index.items)
+        .into_iter()
+        .filter(|(_, count)| *count >= min_count)
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|(author, count)| AuthorCount { author, count })
+        .collect::<Vec<_>>();
+
+    match format {
+        CountFormat::Table => {
+            for entry in counts {
+                println!("{:>6}  {}", entry.count, entry.author);
+            }
+        }
+        CountFormat::Json => println!("{}", serde_json::to_string_pretty(&counts)?),
+        CountFormat::Csv => {
+            println!("author,count");
+            for entry in counts {
+                println!("{},{}", csv_field(&entry.author), entry.count);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn filter_tag_counts(counts: Vec<(String, usize)>, filter: &TagFilter) -> Vec<TagCount> {
+    let prefix = filter.prefix.as_deref().unwrap_or("");
+    counts
+        .into_iter()
+        .filter(|(tag, count)| *count >= filter.min_count && tag.starts_with(prefix))
+        .take(filter.limit.unwrap_or(usize::MAX))
+        .map(|(tag, count)| TagCount { tag, count })
+        .collect()
+}
+
+fn orphans_command(
+    config: &BooruConfig,
+    edits: bool,
+    delete: bool,
+    confirm: bool,
+    format: OrphanFormat,
+) -> Result<()> {
+    let orphans = if edits {
+        scan_orphans(config)?
+    } else {
+        find_orphan_metadata(config)?
+    };
+    match format {
+        OrphanFormat::Text => {
+            for path in &orphans {
+                println!("{}", path.display());
+            }
+        }
+        OrphanFormat::Json => println!("{}", serde_json::to_string_pretty(&orphans)?),
+    }
+    if !delete || orphans.is_empty() {
+        return Ok(());
+    }
+    if !confirm {
+        // On stderr, so `--format json` output stays parseable.
+        eprint!("Delete {} orphaned metadata files? [y/N] ", orphans.len());
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            eprintln!("Aborted.");
+            return Ok(());
+        }
+    }
+    for path in &orphans {
+        fs::remove_file(path).with_context(|| format!("failed to delete {}", path.display()))?;
+    }
+    if format == OrphanFormat::Text {
+        println!("Deleted {} orphaned metadata files.", orphans.len());
+    }
+    Ok(())
+}
+
+fn validate_command(config: &BooruConfig, strict: bool) -> Result<()> {
+    let issues = validate_library(config)?;
+    for issue in &issues {
+        println!("{}: {}", issue.path.display(), issue.message);
+    }
+    if issues.is_empty() {
+        println!("No problems found.");
+    } else if strict {
+        return Err(anyhow!("{} problems found", issues.len()));
+    }
+    Ok(())
+}
+
+fn check_integrity_command(
+    config: &BooruConfig,
+    cache: &mut IntegrityCache,
+    repair: bool,
+    full: bool,
+    quiet: bool,
+) -> Result<()> {
+    let library = scan_library(config, quiet)?;
+    let items = &library.index.items;
+    let progress = progress_bar(items.len(), "checking", quiet);
+    let observer = progress.as_ref().map(|pb| HashProgress { pb: pb.clone() });
+    let report = check_integrity(
+        items,
+        cache,
+        repair,
+        full,
+        observer.as_ref().map(|o| o as &dyn ProgressObserver),
+    );
+    if let Some(pb) = &progress {
+        pb.finish_and_clear();
+    }
+    let report = report?;
+
+    if !quiet {
+        for warning in &report.warnings {
+            eprintln!("warning: {}: {}", warning.path.display(), warning.message);
+        }
+    }
+    for (label, paths) in [
+        ("changed", &report.changed),
+        ("missing", &report.missing),
+        ("new", &report.new),
+    ] {
+        for path in paths {
+            println!("{label}: {}", path.display());
+        }
+    }
+    println!(
+        "{} unchanged, {} changed, {} missing, {} new",
+        report.unchanged,
+        report.changed.len(),
+        report.missing.len(),
+        report.new.len()
+    );
+    if !repair && (!report.changed.is_empty() || !report.missing.is_empty()) {
+        return Err(anyhow!(
+            "{} files changed or went missing; rerun with --repair to accept them",
+            report.changed.len() + report.missing.len()
+        ));
+    }
+    Ok(())
+}
+
+fn stats_command(config: &BooruConfig, format: StatsFormat, quiet: bool) -> Result<()> {
+    let library = scan_library(config, quiet)?;
+    let stats = library.statistics();
+
+    if format == StatsFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!("Items: {}", stats.item_count);
+    println!("Tagged: {}", stats.tagged_count);
+    println!("Untagged: {}", stats.untagged_count);
+    println!("Sensitive: {}", stats.sensitive_count);
+    println!("Safe: {}", stats.item_count - stats.sensitive_count);
+    println!("Distinct tags: {}", stats.unique_tags);
+    println!("Distinct authors: {}", stats.unique_authors);
+    match (&stats.earliest_date, &stats.latest_date) {
+        (Some(earliest), Some(latest)) => println!("Date range: {earliest} .. {latest}"),
+        _ => println!("Date range: (none)"),
+    }
+    println!("Categories:");
+    let mut categories = stats.categories.iter().collect::<Vec<_>>();
+    categories.sort_by(|(lhs_name, lhs_count), (rhs_name, rhs_count)| {
+        rhs_count
+            .cmp(lhs_count)
+            .then_with(|| lhs_name.cmp(rhs_name))
+    });
+    for (category, count) in categories {
+        println!("  {category}: {count}");
+    }
+    Ok(())
+}
+
+fn alias_command(config: &BooruConfig, command: AliasCommands, quiet: bool) -> Result<()> {
+    match command {
+        AliasCommands::List => alias_list_command(config, quiet),
+        AliasCommands::Add { terms, force } => alias_add_command(config, terms, force),
+        AliasCommands::Remove { terms } => alias_remove_command(config, terms),
+        AliasCommands::Rename { from, to } => alias_rename_command(config, &from, &to),
+        AliasCommands::Export => alias_export_command(config, quiet),
+        AliasCommands::Import { file, force } => alias_import_command(config, &file, force),
+        AliasCommands::Canonicalize { dry_run } => {
+            alias_canonicalize_command(config, dry_run, quiet)
+        }
+    }
+}
+
+fn alias_list_command(config: &BooruConfig, quiet: bool) -> Result<()> {
+    let show_root = config.roots.len() > 1;
+    for (idx, root) in config.roots.iter().enumerate() {
+        if show_root {
+            if idx > 0 {
+                println!();
+            }
+            println!("Root: {}", root.display());
+        }
+
+        match load_alias_groups_from_root(root) {
+            Ok(groups) => {
+                if groups.is_empty() {
+                    println!("(none)");
+                } else {
+                    for group in groups {
+                        println!("{}", group.join(" | "));
+                    }
+                }
+            }
+            Err(err) => {
+                let path = alias_path_for_root(root);
+                if !quiet {
+                    eprintln!("warning: {}: {}", path.display(), err);
+                }
+                println!("(invalid alias file)");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn alias_add_command(config: &BooruConfig, terms: Vec<String>, force: bool) -> Result<()> {
+    let root = alias_edit_root(config)?;
+    let terms = normalize_search_terms(terms);
+    if terms.len() < 2 {
+        return Err(anyhow!("alias add requires at least 2 non-empty terms"));
+    }
+
+    let path = alias_path_for_root(root);
+    let mut groups =
+        load_alias_groups_from_root(root).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+    let (changed, conflicts) = merge_alias_terms(&mut groups, terms);
+    if !conflicts.is_empty() && !force {
+        for conflict in &conflicts {
+            eprintln!("warning: {}: {}", path.display(), conflict.message());
+        }
+        return Err(anyhow!(
+            "terms span {} existing alias groups; pass --force to merge them",
+            conflicts.len()
+        ));
+    }
+    if changed {
+        save_alias_groups_to_root(root, &groups)
+            .map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        println!("Updated {}", path.display());
+    } else {
+        println!("No changes.");
+    }
+    Ok(())
 }
 
 fn alias_remove_command(config: &BooruConfig, terms: Vec<String>) -> Result<()> {
@@ -582,10 +1760,115 @@ fn alias_remove_command(config: &BooruConfig, terms: Vec<String>) -> Result<()>
     Ok(())
 }
 
+fn alias_rename_command(config: &BooruConfig, from: &str, to: &str) -> Result<()> {
+    let root = alias_edit_root(config)?;
+    if normalize_search_term(from).is_none() || normalize_search_term(to).is_none() {
+        return Err(anyhow!("alias rename requires two non-empty terms"));
+    }
+
+    let path = alias_path_for_root(root);
+    let mut groups =
+        load_alias_groups_from_root(root).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+    let changed = rename_alias_term(&mut groups, from, to);
+    if changed {
+        save_alias_groups_to_root(root, &groups)
+            .map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        println!("Updated {}", path.display());
+    } else {
+        println!("No changes.");
+    }
+    Ok(())
+}
+
+fn alias_export_command(config: &BooruConfig, quiet: bool) -> Result<()> {
+    let (map, warnings) = load_alias_map_from_roots(&config.roots);
+    if !quiet {
+        for warning in &warnings {
+            eprintln!("warning: {}: {}", warning.path.display(), warning.message);
+        }
+    }
+    let groups = alias_groups_from_map(&map);
+    println!("{}", serde_json::to_string_pretty(&groups)?);
+    Ok(())
+}
+
+fn alias_import_command(config: &BooruConfig, file: &Path, force: bool) -> Result<()> {
+    let root = alias_edit_root(config)?;
+    let incoming =
+        load_alias_groups_from_path(file).map_err(|err| anyhow!("{}: {}", file.display(), err))?;
+
+    let path = alias_path_for_root(root);
+    let mut groups =
+        load_alias_groups_from_root(root).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+    let (changed, conflicts) = import_alias_groups(&mut groups, incoming);
+    if !conflicts.is_empty() && !force {
+        for conflict in &conflicts {
+            eprintln!("warning: {}: {}", path.display(), conflict.message());
+        }
+        return Err(anyhow!(
+            "imported groups join {} existing alias groups; pass --force to merge them",
+            conflicts.len()
+        ));
+    }
+    if changed {
+        save_alias_groups_to_root(root, &groups)
+            .map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        println!("Updated {}", path.display());
+    } else {
+        println!("No changes.");
+    }
+    Ok(())
+}
+
+/// Uses the groups of every root, unlike the commands that edit alias.json.
+fn alias_canonicalize_command(config: &BooruConfig, dry_run: bool, quiet: bool) -> Result<()> {
+    let mut groups = AliasGroups::new();
+    for root in &config.roots {
+        let loaded = load_alias_groups_as_written(root)
+            .map_err(|err| anyhow!("{}: {}", alias_path_for_root(root).display(), err))?;
+        groups.extend(loaded);
+    }
+    if groups.is_empty() {
+        println!("No alias groups.");
+        return Ok(());
+    }
+
+    let library = scan_library(config, quiet)?;
+    let mut changed = 0;
+    for item in &library.index.items {
+        let tags = item.merged_tags();
+        let canonical = canonicalize_tags(&tags, &groups);
+        if canonical == tags {
+            continue;
+        }
+        println!("{}", item.image_path.display());
+        println!("  {} -> {}", tags.join(", "), canonical.join(", "));
+        if !dry_run {
+            let update = EditUpdate {
+                set_tags: Some(canonical),
+                ..EditUpdate::default()
+            };
+            apply_update_to_image(&item.image_path, update).with_context(|| {
+                format!(
+                    "failed to write booru edits for {}",
+                    item.image_path.display()
+                )
+            })?;
+        }
+        changed += 1;
+    }
+    if dry_run {
+        println!("{changed} images would change.");
+    } else {
+        println!("Changed {changed} images.");
+    }
+    Ok(())
+}
+
 fn alias_edit_root(config: &BooruConfig) -> Result<&PathBuf> {
     if config.roots.len() != 1 {
         return Err(anyhow!(
-            "alias add/remove requires exactly one base root; pass a single --base"
+            "alias add/remove/rename/import requires exactly one base root; pass a single --base"
         ));
     }
     Ok(&config.roots[0])
@@ -593,60 +1876,48 @@ fn alias_edit_root(config: &BooruConfig) -> Result<&PathBuf> {
 
 fn dupes_command(
     config: &BooruConfig,
-    algo: HashAlgo,
+    algo: FuzzyHashAlgorithm,
     threshold: u32,
-    no_cache: bool,
-    cache_path: Option<PathBuf>,
+    mut cache: Option<HashCache>,
+    cleanup: CacheCleanup,
+    output: DupesOutput,
     quiet: bool,
 ) -> Result<()> {
     let library = scan_library(config, quiet)?;
-    let algo = match algo {
-        HashAlgo::Ahash => FuzzyHashAlgorithm::AHash,
-        HashAlgo::Dhash => FuzzyHashAlgorithm::DHash,
-        HashAlgo::Phash => FuzzyHashAlgorithm::PHash,
-    };
-
-    let mut cache = if no_cache {
-        None
-    } else if let Some(path) = cache_path {
-        Some(HashCache::open(&path).context("failed to open cache")?)
-    } else {
-        match HashCache::open_default() {
-            Ok(cache) => Some(cache),
-            Err(err) => {
-                if !quiet {
-                    eprintln!("warning: cache disabled: {err}");
-                }
-                None
+    match (cleanup, cache.as_mut()) {
+        (CacheCleanup::Vacuum, Some(cache)) => {
+            let removed = cache
+                .vacuum(&library.index.items)
+                .context("failed to vacuum cache")?;
+            if !quiet {
+                eprintln!("vacuumed {removed} stale cache rows");
+            }
+        }
+        (CacheCleanup::Clear, Some(cache)) => {
+            let removed = cache.force_clear().context("failed to clear cache")?;
+            if !quiet {
+                eprintln!("cleared {removed} cache rows");
+            }
+        }
+        _ => {}
+    }
+    let computation = hash_items(&library.index.items, algo, cache.as_mut(), quiet);
+    if matches!(cleanup, CacheCleanup::Prune) {
+        if let Some(cache) = cache.as_mut() {
+            let existing = library
+                .index
+                .items
+                .iter()
+                .map(|item| item.image_path.to_string_lossy().into_owned())
+                .collect::<HashSet<_>>();
+            let removed = cache.prune(&existing).context("failed to prune cache")?;
+            if !quiet {
+                eprintln!("pruned {removed} stale cache rows");
             }
         }
-    };
-
-    let show_progress = !quiet && std::io::stderr().is_terminal();
-    let progress = if show_progress {
-        let pb = ProgressBar::new(library.index.items.len() as u64);
-        pb.set_style(
-            ProgressStyle::with_template("{spinner:.green} {msg} [{bar:40.cyan/blue}] {pos}/{len}")
-                .unwrap()
-                .progress_chars("=>-"),
-        );
-        pb.set_message("hashing");
-        Some(pb)
-    } else {
-        None
-    };
-
-    let observer = progress.as_ref().map(|pb| HashProgress { pb: pb.clone() });
-    let computation = compute_hashes_with_cache(
-        &library.index.items,
-        algo,
-        cache.as_mut(),
-        observer.as_ref().map(|o| o as &dyn ProgressObserver),
-    );
-    if let Some(pb) = &progress {
-        pb.finish_and_clear();
     }
 
+    let show_progress = !quiet && std::io::stderr().is_terminal();
     let spinner = if show_progress {
         let sp = ProgressBar::new_spinner();
         sp.set_message("comparing");
@@ -655,7 +1926,13 @@ fn dupes_command(
     } else {
         None
     };
-    let groups = group_duplicates(&library.index.items, &computation.hashes, threshold, true);
+    let groups = group_duplicates(
+        &library.index.items,
+        &computation.hashes,
+        algo,
+        threshold,
+        true,
+    );
     if let Some(sp) = spinner {
         sp.finish_and_clear();
     }
@@ -664,10 +1941,32 @@ fn dupes_command(
         eprintln!("warning: {}: {}", warning.path.display(), warning.message);
     }
 
+    if let DupesOutput::Json = output {
+        let report = DuplicateReport {
+            groups,
+            warnings: computation.warnings,
+        };
+        let json = dupes_json(&library.index.items, &report);
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+    if let DupesOutput::Print0 = output {
+        let items = &library.index.items;
+        let paths = groups
+            .iter()
+            .flat_map(|group| &group.items)
+            .filter_map(|idx| items.get(*idx))
+            .map(|item| item.image_path.as_path());
+        write_paths(&mut std::io::stdout().lock(), paths, true)?;
+        return Ok(());
+    }
     if groups.is_empty() {
         println!("No duplicates found.");
         return Ok(());
     }
+    if let DupesOutput::Interactive(options) = output {
+        return interactive_dupes_resolution(&library.index.items, &groups, options);
+    }
 
     for (idx, group) in groups.iter().enumerate() {
         println!("Group {}:", idx + 1);
@@ -680,6 +1979,320 @@ fn dupes_command(
     Ok(())
 }
 
+/// What `dupes` does with hash cache rows for images that are gone.
+#[derive(Clone, Copy)]
+enum CacheCleanup {
+    Keep,
+    /// Delete them after hashing.
+    Prune,
+    /// Delete them before hashing and compact the file.
+    Vacuum,
+    /// Delete every row before hashing.
+    Clear,
+}
+
+#[derive(Clone, Copy)]
+enum DupesOutput {
+    Text,
+    Json,
+    Print0,
+    Interactive(InteractiveDupes),
+}
+
+#[derive(Clone, Copy)]
+struct InteractiveDupes {
+    delete_images: bool,
+    permanent: bool,
+    confirm: bool,
+}
+
+fn interactive_dupes_resolution(
+    items: &[ImageItem],
+    groups: &[DuplicateGroup],
+    options: InteractiveDupes,
+) -> Result<()> {
+    let action = match (options.confirm, options.permanent) {
+        (false, _) => "would remove",
+        (true, false) => "trashed",
+        (true, true) => "deleted",
+    };
+    let theme = ColorfulTheme::default();
+    let (mut resolved, mut skipped, mut removed) = (0, 0, 0);
+    for (idx, group) in groups.iter().enumerate() {
+        let members = group
+            .items
+            .iter()
+            .filter_map(|item_idx| items.get(*item_idx))
+            .map(|item| item.image_path.as_path())
+            .filter(|path| path.exists())
+            .collect::<Vec<_>>();
+        if members.len() < 2 {
+            println!(
+                "Group {}/{}: fewer than two images left, skipping.",
+                idx + 1,
+                groups.len()
+            );
+            skipped += 1;
+            continue;
+        }
+
+        let mut choices = members
+            .iter()
+            .map(|path| {
+                let size = fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+                format!("{} ({size} bytes)", path.display())
+            })
+            .collect::<Vec<_>>();
+        choices.push("Skip this group".to_string());
+        let choice = Select::with_theme(&theme)
+            .with_prompt(format!(
+                "Group {}/{}: keep which one?",
+                idx + 1,
+                groups.len()
+            ))
+            .items(&choices)
+            .default(0)
+            .interact_opt()?;
+        // Escape or q stops early.
+        let Some(keep) = choice else {
+            break;
+        };
+        if keep == members.len() {
+            skipped += 1;
+            continue;
+        }
+
+        for (number, path) in members.iter().enumerate() {
+            if number == keep {
+                continue;
+            }
+            let files = dupe_files_to_remove(path, options.delete_images);
+            let files = if options.confirm {
+                remove_files(files, options.permanent)?
+            } else {
+                files.into_iter().filter(|file| file.exists()).collect()
+            };
+            for file in files {
+                println!("  {action}: {}", file.display());
+                removed += 1;
+            }
+        }
+        resolved += 1;
+    }
+
+    println!("{resolved} groups resolved, {skipped} skipped, {removed} files {action}.");
+    if !options.confirm && removed > 0 {
+        println!("Nothing was changed; pass --confirm to remove them.");
+    }
+    Ok(())
+}
+
+/// The edit file of a duplicate that isn't kept, or with `delete_images`,
+/// the image and all of its sidecar files.
+fn dupe_files_to_remove(image_path: &Path, delete_images: bool) -> Vec<PathBuf> {
+    if delete_images {
+        image_files(image_path).into()
+    } else {
+        vec![booru_path_for_image(image_path)]
+    }
+}
+
+/// An image followed by its gallery-dl metadata and booru edit files.
+fn image_files(image_path: &Path) -> [PathBuf; 3] {
+    [
+        image_path.to_path_buf(),
+        metadata_path_for_image(image_path),
+        booru_path_for_image(image_path),
+    ]
+}
+
+/// Removes whichever of `paths` still exist and returns them.
+fn remove_files(paths: impl IntoIterator<Item = PathBuf>, permanent: bool) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for path in paths {
+        if fs::symlink_metadata(&path).is_err() {
+            continue;
+        }
+        if permanent {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to delete {}", path.display()))?;
+        } else {
+            trash::delete(&path).with_context(|| format!("failed to trash {}", path.display()))?;
+        }
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
+#[derive(Debug, Serialize)]
+struct DupesJson<'a> {
+    groups: Vec<DupeGroupJson>,
+    warnings: &'a [DuplicateWarning],
+}
+
+#[derive(Debug, Serialize)]
+struct DupeGroupJson {
+    items: Vec<PathBuf>,
+    /// The widest distance between two members.
+    distance: u32,
+    members: Vec<DupeEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct DupeEntry {
+    image_path: PathBuf,
+    meta_path: PathBuf,
+    file_size: Option<u64>,
+    /// Distance to the first entry of the group.
+    distance: u32,
+}
+
+/// [`DuplicateReport`] with item indices resolved to paths.
+fn dupes_json<'a>(items: &[ImageItem], report: &'a DuplicateReport) -> DupesJson<'a> {
+    let groups = report
+        .groups
+        .iter()
+        .map(|group| {
+            let members = group
+                .items
+                .iter()
+                .zip(&group.distances)
+                .filter_map(|(idx, distance)| {
+                    let item = items.get(*idx)?;
+                    Some(DupeEntry {
+                        image_path: item.image_path.clone(),
+                        meta_path: item.meta_path.clone(),
+                        file_size: fs::metadata(&item.image_path).ok().map(|meta| meta.len()),
+                        distance: *distance,
+                    })
+                })
+                .collect::<Vec<_>>();
+            DupeGroupJson {
+                items: members
+                    .iter()
+                    .map(|member| member.image_path.clone())
+                    .collect(),
+                distance: group.distance,
+                members,
+            }
+        })
+        .collect();
+    DupesJson {
+        groups,
+        warnings: &report.warnings,
+    }
+}
+
+/// `path` may be outside the library; it is hashed on its own either way.
+fn similar_command(
+    config: &BooruConfig,
+    path: &Path,
+    algo: HashAlgo,
+    threshold: u32,
+    limit: usize,
+    cache: Option<HashCache>,
+    quiet: bool,
+) -> Result<()> {
+    let library = scan_library(config, quiet)?;
+    let image_path = resolve_image_path(path, &library.config.roots);
+    let mut cache = cache;
+
+    let spinner = (!quiet && std::io::stderr().is_terminal()).then(|| {
+        let sp = ProgressBar::new_spinner();
+        sp.set_message("hashing");
+        sp.enable_steady_tick(std::time::Duration::from_millis(120));
+        sp
+    });
+    let kind = MediaKind::from_path(&image_path, &library.config.video_extensions);
+    let matches = find_similar_to(
+        &image_path,
+        kind,
+        &library.index.items,
+        algo.into(),
+        threshold,
+        limit,
+        cache.as_mut(),
+    );
+    if let Some(sp) = spinner {
+        sp.finish_and_clear();
+    }
+    let report = matches.with_context(|| format!("cannot hash {}", image_path.display()))?;
+    for warning in &report.warnings {
+        eprintln!("warning: {}: {}", warning.path.display(), warning.message);
+    }
+
+    if report.matches.is_empty() {
+        println!("No similar images found.");
+        return Ok(());
+    }
+
+    for (idx, distance) in report.matches {
+        if let Some(item) = library.index.items.get(idx) {
+            println!("{distance:>3}  {}", item.image_path.display());
+        }
+    }
+    Ok(())
+}
+
+fn open_hash_cache(
+    no_cache: bool,
+    cache_path: Option<PathBuf>,
+    quiet: bool,
+) -> Result<Option<HashCache>> {
+    if no_cache {
+        return Ok(None);
+    }
+    if let Some(path) = cache_path {
+        return Ok(Some(
+            HashCache::open(&path).context("failed to open cache")?,
+        ));
+    }
+    match HashCache::open_default() {
+        Ok(cache) => Ok(Some(cache)),
+        Err(err) => {
+            if !quiet {
+                eprintln!("warning: cache disabled: {err}");
+            }
+            Ok(None)
+        }
+    }
+}
+
+fn hash_items(
+    items: &[ImageItem],
+    algo: FuzzyHashAlgorithm,
+    cache: Option<&mut HashCache>,
+    quiet: bool,
+) -> HashComputation {
+    let progress = progress_bar(items.len(), "hashing", quiet);
+    let observer = progress.as_ref().map(|pb| HashProgress { pb: pb.clone() });
+    let computation = compute_hashes_with_cache(
+        items,
+        algo,
+        cache,
+        observer.as_ref().map(|o| o as &dyn ProgressObserver),
+    );
+    if let Some(pb) = &progress {
+        pb.finish_and_clear();
+    }
+    computation
+}
+
+/// Only drawn when stderr is a terminal.
+fn progress_bar(len: usize, message: &'static str, quiet: bool) -> Option<ProgressBar> {
+    if quiet || !std::io::stderr().is_terminal() {
+        return None;
+    }
+    let pb = ProgressBar::new(len as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.green} {msg} [{bar:40.cyan/blue}] {pos}/{len}")
+            .unwrap()
+            .progress_chars("=>-"),
+    );
+    pb.set_message(message);
+    Some(pb)
+}
+
 fn scan_library(config: &BooruConfig, quiet: bool) -> Result<Library> {
     let library = Library::scan(config.clone())?;
     if !quiet {
@@ -741,53 +2354,7 @@ fn format_date_for_display(raw: Option<String>) -> String {
 }
 
 fn format_date_string(raw: &str) -> Option<String> {
-    if let Ok(ts) = raw.parse::<i64>() {
-        return format_unix_timestamp(ts);
-    }
-
-    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
-        return Some(format_local_datetime(dt.with_timezone(&Local)));
-    }
-
-    if let Ok(dt) = DateTime::parse_from_str(raw, "%a %b %d %H:%M:%S %z %Y") {
-        return Some(format_local_datetime(dt.with_timezone(&Local)));
-    }
-
-    for fmt in [
-        "%Y-%m-%d %H:%M:%S",
-        "%Y-%m-%d %H:%M",
-        "%Y/%m/%d %H:%M:%S",
-        "%Y/%m/%d %H:%M",
-    ] {
-        if let Ok(naive) = NaiveDateTime::parse_from_str(raw, fmt) {
-            if let Some(local_dt) = localize_naive_datetime(naive) {
-                return Some(format_local_datetime(local_dt));
-            }
-        }
-    }
-
-    None
-}
-
-fn format_unix_timestamp(ts: i64) -> Option<String> {
-    let (seconds, nanos) = if ts.abs() >= 1_000_000_000_000 {
-        let seconds = ts.div_euclid(1000);
-        let millis = ts.rem_euclid(1000) as u32;
-        (seconds, millis * 1_000_000)
-    } else {
-        (ts, 0)
-    };
-
-    let utc = Utc.timestamp_opt(seconds, nanos).single()?;
-    Some(format_local_datetime(utc.with_timezone(&Local)))
-}
-
-fn localize_naive_datetime(naive: NaiveDateTime) -> Option<DateTime<Local>> {
-    let local = Local.from_local_datetime(&naive);
-    local
-        .single()
-        .or_else(|| local.earliest())
-        .or_else(|| local.latest())
+    parse_date(raw).map(format_local_datetime)
 }
 
 fn format_local_datetime(dt: DateTime<Local>) -> String {
@@ -796,24 +2363,36 @@ fn format_local_datetime(dt: DateTime<Local>) -> String {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
-    use booru_core::BooruEdits;
+    use booru_core::{BooruConfig, BooruEdits, EditUpdate, Library};
     use chrono::{Local, TimeZone, Utc};
     use serde_json::json;
 
     use booru_core::item_matches_search_terms;
 
-    use super::format_date_string;
+    use booru_core::{
+        group_duplicates, DuplicateReport, DuplicateWarning, FuzzyHash, FuzzyHashAlgorithm,
+    };
+
+    use super::{
+        batch_edit_command, csv_field, dupe_files_to_remove, dupes_json, edit_diff,
+        filter_tag_counts, format_date_string, image_files, page_range, page_summary,
+        parse_field_arg, remove_files, write_paths, TagFilter,
+    };
 
     fn make_item(original: serde_json::Value) -> booru_core::ImageItem {
-        booru_core::ImageItem {
-            image_path: PathBuf::new(),
-            meta_path: PathBuf::new(),
-            booru_path: PathBuf::new(),
+        make_item_at(PathBuf::new(), original)
+    }
+
+    fn make_item_at(image_path: PathBuf, original: serde_json::Value) -> booru_core::ImageItem {
+        booru_core::ImageItem::new(
+            image_path,
+            PathBuf::new(),
+            PathBuf::new(),
             original,
-            edits: BooruEdits::default(),
-        }
+            BooruEdits::default(),
+        )
     }
 
     #[test]
@@ -866,4 +2445,297 @@ mod tests {
             &[String::from("nomatch"), String::from("CLOUD")]
         ));
     }
+
+    #[test]
+    fn search_finds_terms_only_present_in_notes() {
+        let mut item = make_item(json!({
+            "tags": ["blue_sky"],
+            "author": "Bob",
+        }));
+        item.edits.notes = Some("favorite wallpaper".to_string());
+
+        assert!(item_matches_search_terms(
+            &item,
+            &[String::from("wallpaper")]
+        ));
+        assert!(item_matches_search_terms(
+            &item,
+            &[String::from("note:favorite")]
+        ));
+    }
+
+    #[test]
+    fn search_author_qualifier_ignores_tag_matches() {
+        let by_author = make_item(json!({
+            "tags": ["landscape"],
+            "author": "myowa",
+        }));
+        let tagged = make_item(json!({
+            "tags": ["myowa_style"],
+            "author": "someone",
+        }));
+
+        let terms = [String::from("author:myowa")];
+        assert!(item_matches_search_terms(&by_author, &terms));
+        assert!(!item_matches_search_terms(&tagged, &terms));
+    }
+
+    #[test]
+    fn tag_count_filters_apply_before_limit() {
+        let counts = vec![
+            (String::from("sky"), 5),
+            (String::from("sea"), 4),
+            (String::from("skyline"), 2),
+            (String::from("sketch"), 1),
+        ];
+        let filter = TagFilter {
+            limit: Some(2),
+            min_count: 2,
+            prefix: Some(String::from("sk")),
+            include_sensitive: false,
+        };
+
+        let tags = filter_tag_counts(counts, &filter)
+            .into_iter()
+            .map(|entry| (entry.tag, entry.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tags,
+            vec![(String::from("sky"), 5), (String::from("skyline"), 2)]
+        );
+    }
+
+    #[test]
+    fn batch_edit_updates_only_matching_images_that_change() {
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("booructl-batch-edit-{unique}"));
+        std::fs::create_dir_all(&root).unwrap();
+        for (name, tags) in [
+            ("a", json!(["sky"])),
+            ("b", json!(["sky", "sea"])),
+            ("c", json!(["forest"])),
+        ] {
+            std::fs::write(root.join(format!("{name}.jpg")), b"img").unwrap();
+            std::fs::write(
+                root.join(format!("{name}.jpg.json")),
+                json!({ "tags": tags }).to_string(),
+            )
+            .unwrap();
+        }
+        let config = BooruConfig::with_roots(vec![root.clone()]);
+        let update = || EditUpdate {
+            add_tags: vec!["sea".to_string()],
+            ..EditUpdate::default()
+        };
+
+        batch_edit_command(&config, "sky", update(), None, true, false, true).unwrap();
+        assert!(!root.join("a.jpg.booru.json").exists());
+
+        batch_edit_command(&config, "sky", update(), None, false, true, true).unwrap();
+        let library = Library::scan(config).unwrap();
+        let tags_of = |name: &str| {
+            library
+                .index
+                .items
+                .iter()
+                .find(|item| item.image_path.ends_with(name))
+                .unwrap()
+                .merged_tags()
+        };
+        assert_eq!(tags_of("a.jpg"), vec!["sky", "sea"]);
+        assert_eq!(tags_of("c.jpg"), vec!["forest"]);
+        // `b` already had the tag, so its edits file is never written.
+        assert!(root.join("a.jpg.booru.json").exists());
+        assert!(!root.join("b.jpg.booru.json").exists());
+        assert!(!root.join("c.jpg.booru.json").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn edit_diff_lists_changed_fields() {
+        let before = make_item(json!({ "tags": ["sky", "cloud"] }));
+        let mut after = before.clone();
+        after.edits.tags.remove = vec!["cloud".to_string()];
+        after.edits.tags.add = vec!["sea".to_string()];
+        after.edits.notes = Some("check".to_string());
+        after.edits.sensitive = Some(true);
+        after
+            .edits
+            .extra
+            .insert("collection".to_string(), json!("favorites"));
+        assert_eq!(
+            edit_diff(&before, &after),
+            vec![
+                "-tag: cloud",
+                "+tag: sea",
+                "+notes: check",
+                "-sensitive: false",
+                "+sensitive: true",
+                "+collection: \"favorites\"",
+            ]
+        );
+        assert!(edit_diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn field_args_parse_json_or_fall_back_to_strings() {
+        assert_eq!(
+            parse_field_arg("collection=favorites"),
+            Ok(("collection".to_string(), json!("favorites")))
+        );
+        assert_eq!(
+            parse_field_arg("score=3"),
+            Ok(("score".to_string(), json!(3)))
+        );
+        assert_eq!(
+            parse_field_arg("pair=[1, \"a=b\"]"),
+            Ok(("pair".to_string(), json!([1, "a=b"])))
+        );
+        assert!(parse_field_arg("novalue").is_err());
+        assert!(parse_field_arg("=x").is_err());
+    }
+
+    #[test]
+    fn image_files_lists_sidecars_next_to_the_image() {
+        assert_eq!(
+            image_files(Path::new("/lib/a.jpg")),
+            [
+                PathBuf::from("/lib/a.jpg"),
+                PathBuf::from("/lib/a.jpg.json"),
+                PathBuf::from("/lib/a.jpg.booru.json"),
+            ]
+        );
+        assert_eq!(
+            image_files(Path::new("pixiv/123_p0.tar.gz")),
+            [
+                PathBuf::from("pixiv/123_p0.tar.gz"),
+                PathBuf::from("pixiv/123_p0.tar.gz.json"),
+                PathBuf::from("pixiv/123_p0.tar.gz.booru.json"),
+            ]
+        );
+        assert_eq!(
+            image_files(Path::new("noext")),
+            [
+                PathBuf::from("noext"),
+                PathBuf::from("noext.json"),
+                PathBuf::from("noext.booru.json"),
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_files_skips_files_already_gone() {
+        let dir = std::env::temp_dir().join(format!("booructl-remove-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("a.png");
+        std::fs::write(&image, b"img").unwrap();
+        std::fs::write(dir.join("a.png.json"), "{}").unwrap();
+
+        let removed = remove_files(image_files(&image), true).unwrap();
+        assert_eq!(removed, vec![image.clone(), dir.join("a.png.json")]);
+        assert!(remove_files(image_files(&image), true).unwrap().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn dupe_resolution_keeps_other_images_unless_asked() {
+        let image = Path::new("/lib/a.jpg");
+        assert_eq!(
+            dupe_files_to_remove(image, false),
+            vec![PathBuf::from("/lib/a.jpg.booru.json")]
+        );
+        assert_eq!(
+            dupe_files_to_remove(image, true),
+            image_files(image).to_vec()
+        );
+    }
+
+    #[test]
+    fn dupes_json_lists_groups_with_paths_distances_and_warnings() {
+        let items = ["a/1.jpg", "b/1.jpg", "c/2.jpg"]
+            .into_iter()
+            .map(|name| make_item_at(PathBuf::from("/lib").join(name), json!({})))
+            .collect::<Vec<_>>();
+        let base = FuzzyHash {
+            algo: FuzzyHashAlgorithm::DHASH,
+            bits: vec![false; 64],
+        };
+        let mut near = base.clone();
+        near.bits[3] = true;
+        near.bits[9] = true;
+        let far = FuzzyHash {
+            algo: FuzzyHashAlgorithm::DHASH,
+            bits: vec![true; 64],
+        };
+        let hashes = vec![(0, base), (1, near), (2, far)];
+        let groups = group_duplicates(&items, &hashes, FuzzyHashAlgorithm::DHASH, 4, true);
+
+        let report = DuplicateReport {
+            groups,
+            warnings: vec![DuplicateWarning {
+                path: PathBuf::from("/lib/broken.jpg"),
+                message: "unsupported image".to_string(),
+            }],
+        };
+        let value = serde_json::to_value(dupes_json(&items, &report)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "groups": [{
+                    "items": ["/lib/a/1.jpg", "/lib/b/1.jpg"],
+                    "distance": 2,
+                    "members": [
+                        {
+                            "image_path": "/lib/a/1.jpg",
+                            "meta_path": "",
+                            "file_size": null,
+                            "distance": 0,
+                        },
+                        {
+                            "image_path": "/lib/b/1.jpg",
+                            "meta_path": "",
+                            "file_size": null,
+                            "distance": 2,
+                        },
+                    ],
+                }],
+                "warnings": [{ "path": "/lib/broken.jpg", "message": "unsupported image" }],
+            })
+        );
+    }
+
+    #[test]
+    fn csv_field_quotes_only_when_needed() {
+        assert_eq!(csv_field("sky"), "sky");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn page_range_applies_offset_then_limit() {
+        assert_eq!(page_range(4213, 100, 100), 100..200);
+        assert_eq!(page_range(150, 100, 100), 100..150);
+        assert_eq!(page_range(50, 100, 100), 50..50);
+        assert_eq!(page_range(10, 0, usize::MAX), 0..10);
+
+        assert_eq!(page_summary(&(100..200), 4213), "showing 100-199 of 4213");
+        assert_eq!(page_summary(&(50..50), 50), "showing 0 of 50");
+    }
+
+    #[test]
+    fn print0_separates_paths_with_nul() {
+        let paths = [Path::new("/lib/a b.jpg"), Path::new("/lib/line\nbreak.png")];
+        let mut out = Vec::new();
+        write_paths(&mut out, paths, true).unwrap();
+        assert_eq!(out, b"/lib/a b.jpg\0/lib/line\nbreak.png\0");
+
+        let mut out = Vec::new();
+        write_paths(&mut out, paths, false).unwrap();
+        assert_eq!(out, b"/lib/a b.jpg\n/lib/line\nbreak.png\n");
+    }
 }